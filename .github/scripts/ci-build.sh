@@ -4,24 +4,20 @@ set -xe
 
 # Execute this script under the root folder of this repo. Otherwise it will fail.
 
-# Build plans
-cargo build --features nogc
-cargo build --features semispace
+# Build (all the plans are included, and the plan is chosen at run time)
+cargo build
 
 # Build features
-cargo build --features nogc,vm_space
-cargo build --features nogc,vm_space,code_space,ro_space
-cargo build --features nogc,nogc_lock_free
-cargo build --features nogc,nogc_lock_free,nogc_no_zeroing
-cargo build --features semispace,vm_space
-cargo build --features semispace,vm_space,code_space,ro_space
-cargo build --features nogc,sanity
-cargo build --features semispace,sanity
+cargo build --features vm_space
+cargo build --features vm_space,code_space,ro_space
+cargo build --features nogc_lock_free
+cargo build --features nogc_lock_free,nogc_no_zeroing
+cargo build --features sanity
 
 # Build different implementations of heap layout
-cargo build --features nogc,force_32bit_heap_layout
+cargo build --features force_32bit_heap_layout
 # For x86_64-linux, also see if we can build for i686
 if [[ $arch == "x86_64" && $os == "linux" ]]; then
-    cargo build --target i686-unknown-linux-gnu --features nogc
-    cargo build --target i686-unknown-linux-gnu --features nogc,force_32bit_heap_layout
-fi
\ No newline at end of file
+    cargo build --target i686-unknown-linux-gnu
+    cargo build --target i686-unknown-linux-gnu --features force_32bit_heap_layout
+fi
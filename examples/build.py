@@ -14,8 +14,7 @@ else:
 
 MMTk_ROOT = os.path.join(__file__, "..", "..")
 
-plan_dir = os.path.abspath(os.path.join(MMTk_ROOT, "src", "plan"))
-PLANS = next(os.walk(plan_dir))[1]
+PLANS = ["NoGC", "SemiSpace", "GenCopy", "MarkSweep", "Immix", "MarkCompact", "ConcurrentMarkSweep", "StickyMarkSweep", "ReferenceCounting"]
 
 os.chdir(os.path.abspath(MMTk_ROOT))
 
@@ -50,55 +49,58 @@ elif system == "Linux":
 
 vmbinding = "vmbindings/dummyvm"
 
-for plan in PLANS:
-    cmd = []
-    cmd.append("cargo")
-    if toolchain:
-        cmd.append(toolchain)
-    cmd.extend([
-        "build",
-        "--manifest-path",
-        "vmbindings/dummyvm/Cargo.toml",
-        "--no-default-features",
-        "--features", " ".join([plan, extra_features])
-    ])
-
-    exec_and_redirect(cmd)
-    exec_and_redirect(cmd + ["--release"])
-    shutil.copyfile("{}/target/release/libmmtk_dummyvm{}".format(vmbinding, SUFFIX),
-                    "./libmmtk{}".format(SUFFIX))
-
-    if system == "Linux":
-        exec_and_redirect(cmd + ["--target=i686-unknown-linux-gnu"])
-        exec_and_redirect(
-            cmd + ["--release", "--target=i686-unknown-linux-gnu"])
-        shutil.copyfile(
-            "{}/target/i686-unknown-linux-gnu/release/libmmtk_dummyvm{}".format(vmbinding, SUFFIX),
-            "./libmmtk_32{}".format(SUFFIX))
-
+cmd = []
+cmd.append("cargo")
+if toolchain:
+    cmd.append(toolchain)
+cmd.extend([
+    "build",
+    "--manifest-path",
+    "vmbindings/dummyvm/Cargo.toml",
+    "--no-default-features",
+    "--features", extra_features
+])
+
+exec_and_redirect(cmd)
+exec_and_redirect(cmd + ["--release"])
+shutil.copyfile("{}/target/release/libmmtk_dummyvm{}".format(vmbinding, SUFFIX),
+                "./libmmtk{}".format(SUFFIX))
+
+if system == "Linux":
+    exec_and_redirect(cmd + ["--target=i686-unknown-linux-gnu"])
+    exec_and_redirect(
+        cmd + ["--release", "--target=i686-unknown-linux-gnu"])
+    shutil.copyfile(
+        "{}/target/i686-unknown-linux-gnu/release/libmmtk_dummyvm{}".format(vmbinding, SUFFIX),
+        "./libmmtk_32{}".format(SUFFIX))
+
+exec_and_redirect([
+    "clang",
+    "-lmmtk",
+    "-L.",
+    "-I{}/api".format(vmbinding),
+    "-O3",
+    "-o",
+    "test_mmtk",
+    "./examples/main.c"])
+
+if system == "Linux":
     exec_and_redirect([
         "clang",
-        "-lmmtk",
+        "-lmmtk_32",
         "-L.",
         "-I{}/api".format(vmbinding),
-        "-O3",
+        "-O3", "-m32",
         "-o",
-        "test_mmtk",
+        "test_mmtk_32",
         "./examples/main.c"])
 
+# The plan is chosen at run time
+for plan in PLANS:
+    exec_and_redirect(["./test_mmtk"], env={LIBRARY_PATH: ".", "MMTK_PLAN": plan})
     if system == "Linux":
-        exec_and_redirect([
-            "clang",
-            "-lmmtk_32",
-            "-L.",
-            "-I{}/api".format(vmbinding),
-            "-O3", "-m32",
-            "-o",
-            "test_mmtk_32",
-            "./examples/main.c"])
-
-    exec_and_redirect(["./test_mmtk"], env={LIBRARY_PATH: "."})
-    os.remove("./test_mmtk")
-    if system == "Linux":
-        exec_and_redirect(["./test_mmtk_32"], env={LIBRARY_PATH: "."})
-        os.remove("./test_mmtk_32")
+        exec_and_redirect(["./test_mmtk_32"], env={LIBRARY_PATH: ".", "MMTK_PLAN": plan})
+
+os.remove("./test_mmtk")
+if system == "Linux":
+    os.remove("./test_mmtk_32")
@@ -26,8 +26,7 @@
 //!      Each space is an instance of a policy, and takes up a unique proportion of the heap.
 //!   * [Work packets](scheduler/work/trait.GCWork.html): units of GC works scheduled by the MMTk's scheduler.
 //! * [GC plans](plan/global/trait.Plan.html): GC algorithms composed from components.
-//!   *Note that currently the choice of plans is made through Rust features, which is a build-time config, so only one plan is present in the generated binary
-//!   and in this documentation. We plan to make this a run-time config so that users can choose GC plans at boot time.*
+//!   *The plan is chosen at run time through the `plan` option (e.g. `MMTK_PLAN=SemiSpace`), and all the plans are present in the generated binary.*
 //! * [Heap implementations](util/heap/index.html): the underlying implementations of memory resources that support spaces.
 //! * [Scheduler](scheduler/scheduler/struct.Scheduler.html): the MMTk scheduler to allow flexible and parallel execution of GC works.
 //! * Interfaces: bi-directional interfaces between MMTk and language implementations
@@ -61,11 +60,9 @@ pub mod policy;
 pub mod scheduler;
 pub mod vm;
 
-#[doc(no_inline)]
-pub use crate::plan::selected_plan::{SelectedConstraints, SelectedPlan};
-
 pub use crate::mm::memory_manager;
 pub use crate::mmtk::MMTK;
 pub use crate::plan::{
-    AllocationSemantics, CopyContext, Mutator, MutatorContext, Plan, TraceLocal, TransitiveClosure,
+    AllocationSemantics, CopyContext, ForwardingReadBarrier, Mutator, MutatorContext, Plan,
+    PlanConstraints, ReadBarrier, ReadBarrierSelector, TraceLocal, TransitiveClosure,
 };
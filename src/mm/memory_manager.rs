@@ -11,19 +11,23 @@
 //! it can turn the `Box` pointer to a native pointer (`*mut Mutator`), and forge a mut reference from the native
 //! pointer. Either way, the VM binding code needs to guarantee the safety.
 
+use std::io;
+use std::path::Path;
 use std::sync::atomic::Ordering;
 
 use crate::plan::mutator_context::{Mutator, MutatorContext};
-use crate::plan::Plan;
+use crate::scheduler::gc_works::ProcessEdgesWork;
 use crate::scheduler::GCWorker;
 
 use crate::vm::Collection;
 
 use crate::util::{Address, ObjectReference};
 
-use self::selected_plan::SelectedPlan;
-use crate::plan::selected_plan;
 use crate::util::alloc::allocators::AllocatorSelector;
+use crate::util::alloc_bit;
+use crate::util::conservative_roots::ProcessConservativeRoots;
+use crate::util::options::OptionNotEnabled;
+use crate::util::pin_bit;
 
 use crate::mmtk::MMTK;
 use crate::plan::AllocationSemantics;
@@ -47,7 +51,8 @@ pub fn start_control_collector<VM: VMBinding>(mmtk: &MMTK<VM>, tls: OpaquePointe
 ///
 /// Arguments:
 /// * `mmtk`: A reference to an MMTk instance to initialize.
-/// * `heap_size`: The heap size for the MMTk instance in bytes.
+/// * `heap_size`: The heap size for the MMTk instance in bytes. If the heap size is variable,
+///   this is the initial heap size.
 pub fn gc_init<VM: VMBinding>(mmtk: &'static mut MMTK<VM>, heap_size: usize) {
     crate::util::logger::init().unwrap();
     mmtk.plan.gc_init(heap_size, &mmtk.vm_map, &mmtk.scheduler);
@@ -62,15 +67,15 @@ pub fn gc_init<VM: VMBinding>(mmtk: &'static mut MMTK<VM>, heap_size: usize) {
 pub fn bind_mutator<VM: VMBinding>(
     mmtk: &'static MMTK<VM>,
     tls: OpaquePointer,
-) -> Box<Mutator<SelectedPlan<VM>>> {
-    SelectedPlan::bind_mutator(&mmtk.plan, tls, mmtk)
+) -> Box<Mutator<VM>> {
+    mmtk.plan.bind_mutator(tls, mmtk)
 }
 
 /// Reclaim a mutator that is no longer needed.
 ///
 /// Arguments:
 /// * `mutator`: A reference to the mutator to be destroyed.
-pub fn destroy_mutator<VM: VMBinding>(mutator: Box<Mutator<SelectedPlan<VM>>>) {
+pub fn destroy_mutator<VM: VMBinding>(mutator: Box<Mutator<VM>>) {
     drop(mutator);
 }
 
@@ -78,7 +83,7 @@ pub fn destroy_mutator<VM: VMBinding>(mutator: Box<Mutator<SelectedPlan<VM>>>) {
 ///
 /// Arguments:
 /// * `mutator`: A reference to the mutator.
-pub fn flush_mutator<VM: VMBinding>(mutator: &mut Mutator<SelectedPlan<VM>>) {
+pub fn flush_mutator<VM: VMBinding>(mutator: &mut Mutator<VM>) {
     mutator.flush()
 }
 
@@ -92,7 +97,7 @@ pub fn flush_mutator<VM: VMBinding>(mutator: &mut Mutator<SelectedPlan<VM>>) {
 /// * `offset`: Offset associated with the alignment.
 /// * `semantics`: The allocation semantic required for the allocation.
 pub fn alloc<VM: VMBinding>(
-    mutator: &mut Mutator<SelectedPlan<VM>>,
+    mutator: &mut Mutator<VM>,
     size: usize,
     align: usize,
     offset: isize,
@@ -103,7 +108,8 @@ pub fn alloc<VM: VMBinding>(
 
 /// Perform post-allocation actions, usually initializing object metadata. For many allocators none are
 /// required. For performance reasons, a VM should implement the post alloc fast-path on their side
-/// rather than just calling this function.
+/// rather than just calling this function. With the `alloc_bit` option, the fast-path also needs to set
+/// the alloc bit of the object (see `util::alloc_bit`), so that `is_mmtk_object()` can find it.
 ///
 /// Arguments:
 /// * `mutator`: The mutator to perform post-alloc actions.
@@ -112,7 +118,7 @@ pub fn alloc<VM: VMBinding>(
 /// * `bytes`: The size of the space allocated for the object (in bytes).
 /// * `semantics`: The allocation semantics used for the allocation.
 pub fn post_alloc<VM: VMBinding>(
-    mutator: &mut Mutator<SelectedPlan<VM>>,
+    mutator: &mut Mutator<VM>,
     refer: ObjectReference,
     type_refer: ObjectReference,
     bytes: usize,
@@ -157,17 +163,34 @@ pub fn start_worker<VM: VMBinding>(
 /// * `tls`: The thread that wants to enable the collection.
 pub fn enable_collection<VM: VMBinding>(mmtk: &'static MMTK<VM>, tls: OpaquePointer) {
     mmtk.scheduler.initialize(mmtk.options.threads, mmtk, tls);
+    if !mmtk.options.work_trace.is_empty() {
+        mmtk.scheduler.enable_trace();
+    }
     VM::VMCollection::spawn_worker_thread(tls, None); // spawn controller thread
     mmtk.plan.base().initialized.store(true, Ordering::SeqCst);
 }
 
-/// Process MMTk run-time options.
+/// Process MMTk run-time options. Returns false if there is no option with the name, or the value
+/// is not valid for the option, in which case the option is not changed. The value also needs to
+/// keep the bounds with the other options, e.g. `min_nursery <= max_nursery`, so raising both
+/// `min_nursery` and `max_nursery` needs `max_nursery` to be set first. The plan and the spaces are
+/// created along with the MMTk instance, so the `plan`, `alloc_bit` and `pin_bit` options cannot
+/// be set here. To set the options before
+/// the MMTk instance is created, and to get the reason of a failure, use `OptionsBuilder` and
+/// `MMTK::with_options()` instead.
 ///
 /// Arguments:
 /// * `mmtk`: A reference to an MMTk instance.
 /// * `name`: The name of the option.
 /// * `value`: The value of the option (as a string).
 pub fn process<VM: VMBinding>(mmtk: &'static MMTK<VM>, name: &str, value: &str) -> bool {
+    if name == "plan" || name == "alloc_bit" || name == "pin_bit" {
+        warn!(
+            "The {} option cannot be changed after the MMTk instance is created",
+            name
+        );
+        return false;
+    }
     unsafe { mmtk.options.process(name, value) }
 }
 
@@ -199,7 +222,9 @@ pub fn last_heap_address() -> Address {
     HEAP_END
 }
 
-/// Return the total memory in bytes.
+/// Return the total memory in bytes. If the heap size is variable (see the `variable_size_heap`,
+/// `min_heap` and `max_heap` options), this is the current heap size, which MMTk adjusts after
+/// full heap GCs.
 ///
 /// Arguments:
 /// * `mmtk`: A reference to an MMTk instance.
@@ -223,6 +248,51 @@ pub fn handle_user_collection_request<VM: VMBinding>(mmtk: &MMTK<VM>, tls: Opaqu
     mmtk.plan.handle_user_collection_request(tls, false);
 }
 
+/// Dump the objects reachable from the roots to the file at `path`, in the format described in
+/// `util::heap_dump`. This triggers a GC (even with `ignore_system_g_c`), and the dump is taken at
+/// the end of the GC, before the mutators resume. Returns the error if the dump could not be
+/// written, or if no GC took the dump (e.g. the plan does not collect).
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `tls`: The thread that requests the dump. It blocks until the dump is written.
+/// * `path`: The file to write the dump to.
+pub fn dump_heap<VM: VMBinding, P: AsRef<Path>>(
+    mmtk: &MMTK<VM>,
+    tls: OpaquePointer,
+    path: P,
+) -> io::Result<()> {
+    mmtk.heap_dumper.request(path.as_ref().to_path_buf());
+    mmtk.plan.handle_user_collection_request(tls, true);
+    mmtk.heap_dumper.cancel_request();
+    mmtk.heap_dumper.take_result().unwrap_or_else(|| {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "no heap dump was taken",
+        ))
+    })
+}
+
+/// Call `f` for each object allocated by MMTk in the heap, e.g. to inspect the heap. The objects
+/// are found in the memory that the spaces have allocated, and they include the dead objects
+/// that have not been reclaimed yet. This can only be called when the mutators are stopped and no
+/// GC is in progress, and `f` must not allocate. Most spaces find their objects by the alloc bits,
+/// so this returns an error without calling `f` if the `alloc_bit` option is not enabled.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `f`: The function to call with each object.
+pub fn enumerate_objects<VM: VMBinding, F: FnMut(ObjectReference)>(
+    mmtk: &MMTK<VM>,
+    mut f: F,
+) -> Result<(), OptionNotEnabled> {
+    if !mmtk.options.alloc_bit {
+        return Err(OptionNotEnabled("alloc_bit"));
+    }
+    mmtk.plan.enumerate_objects(&mut f);
+    Ok(())
+}
+
 /// Is the object alive?
 ///
 /// Arguments:
@@ -247,6 +317,95 @@ pub fn is_mapped_address(address: Address) -> bool {
     address.is_mapped()
 }
 
+/// Is there an object allocated by MMTk at the address? Unlike `is_live_object()` and
+/// `is_mapped_object()`, the address does not need to be a valid object reference, so this can be
+/// used to filter arbitrary words, e.g. in conservative stack scanning. Object references need to
+/// be word-aligned, and the objects in the VM space are not included. The objects are only found
+/// with the `alloc_bit` option.
+///
+/// Arguments:
+/// * `address`: The address to query.
+pub fn is_mmtk_object(address: Address) -> bool {
+    alloc_bit::is_alloced_object(address)
+}
+
+/// Find the object allocated by MMTk that contains the address, i.e. the object with the highest
+/// reference that is not above the address, if the address is before the end of that object.
+/// Returns `None` if there is no such object in the space of the address. The objects are only
+/// found with the `alloc_bit` option. An object outside the large object space is only searched
+/// for up to the largest size of such objects back from the address.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `internal_ptr`: The address to query.
+pub fn find_object_from_internal_pointer<VM: VMBinding>(
+    mmtk: &MMTK<VM>,
+    internal_ptr: Address,
+) -> Option<ObjectReference> {
+    alloc_bit::find_object_from_internal_pointer(
+        internal_ptr,
+        mmtk.plan.constraints().max_non_los_default_alloc_bytes,
+    )
+}
+
+/// Pin the object, so that no GC moves it until it is unpinned. This is for the objects whose
+/// addresses are held outside the heap, e.g. by native code. Pinning does not keep the object
+/// alive. Returns true if the object was not pinned before.
+///
+/// Arguments:
+/// * `object`: The object to pin. It needs to be allocated by MMTk.
+pub fn pin_object(object: ObjectReference) -> bool {
+    pin_bit::pin_object(object)
+}
+
+/// Unpin the object, so that GCs may move it again. Returns true if the object was pinned before.
+///
+/// Arguments:
+/// * `object`: The object to unpin. It needs to be allocated by MMTk.
+pub fn unpin_object(object: ObjectReference) -> bool {
+    pin_bit::unpin_object(object)
+}
+
+/// Is the object pinned?
+///
+/// Arguments:
+/// * `object`: The object to query. It needs to be allocated by MMTk.
+pub fn is_pinned(object: ObjectReference) -> bool {
+    pin_bit::is_pinned(object)
+}
+
+/// Scan the memory from `start` to `end` (e.g. the stack of a mutator) conservatively for roots.
+/// A binding that cannot find the references in the memory precisely can call this while it scans
+/// the roots (e.g. in `Scanning::scan_thread_root()`), instead of creating a `W` with the root
+/// slots. Every word-aligned word that is the reference of an object allocated by MMTk (see
+/// `is_mmtk_object()`) keeps the object alive, and the object is pinned, so that it does not move
+/// in the current GC. The words are never updated. Returns an error without scanning if the
+/// `alloc_bit` or `pin_bit` option is not enabled.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `start`: The start of the memory to scan.
+/// * `end`: The end of the memory to scan (exclusive).
+pub fn scan_roots_conservatively<W: ProcessEdgesWork>(
+    mmtk: &'static MMTK<W::VM>,
+    start: Address,
+    end: Address,
+) -> Result<(), OptionNotEnabled> {
+    if !mmtk.options.alloc_bit {
+        return Err(OptionNotEnabled("alloc_bit"));
+    }
+    if !mmtk.options.pin_bit {
+        return Err(OptionNotEnabled("pin_bit"));
+    }
+    let objects = mmtk.conservative_roots.scan(start, end);
+    if !objects.is_empty() {
+        mmtk.scheduler
+            .closure_stage
+            .add(ProcessConservativeRoots::<W>::new(objects));
+    }
+    Ok(())
+}
+
 /// Check that if a garbage collection is in progress and if the given
 /// object is not movable.  If it is movable error messages are
 /// logged and the system exits.
@@ -303,6 +462,37 @@ pub fn add_phantom_candidate<VM: VMBinding>(
         .add_phantom_candidate::<VM>(reff, referent);
 }
 
+/// Register an object for finalization. When the object becomes unreachable, MMTk keeps it alive,
+/// and the VM can get it with [`get_finalized_object()`](fn.get_finalized_object.html) after the GC.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+/// * `object`: The object that has a finalizer.
+pub fn add_finalizer<VM: VMBinding>(mmtk: &MMTK<VM>, object: ObjectReference) {
+    if mmtk.options.no_finalizer {
+        warn!("add_finalizer() is called when no_finalizer = true");
+    }
+
+    mmtk.finalizable_processor.lock().unwrap().add(object);
+}
+
+/// Get an object that is ready for finalization, or `None` if there isn't any. The object is
+/// no longer kept alive by MMTk after this, so the VM should finalize it (or keep it reachable)
+/// before the next GC.
+///
+/// Arguments:
+/// * `mmtk`: A reference to an MMTk instance.
+pub fn get_finalized_object<VM: VMBinding>(mmtk: &MMTK<VM>) -> Option<ObjectReference> {
+    if mmtk.options.no_finalizer {
+        warn!("get_finalized_object() is called when no_finalizer = true");
+    }
+
+    mmtk.finalizable_processor
+        .lock()
+        .unwrap()
+        .get_ready_object()
+}
+
 /// Generic hook to allow benchmarks to be harnessed. We do a full heap
 /// GC, and then start recording statistics for MMTk.
 ///
@@ -1,20 +1,25 @@
+use crate::plan::global::create_plan;
 use crate::plan::Plan;
-use crate::plan::SelectedPlan;
 use crate::policy::space::SFTMap;
 use crate::scheduler::Scheduler;
+use crate::util::conservative_roots::ConservativeRoots;
+use crate::util::finalizable_processor::FinalizableProcessor;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::layout::map::Map;
+use crate::util::heap_dump::HeapDumper;
 use crate::util::options::{Options, UnsafeOptionsWrapper};
 use crate::util::reference_processor::ReferenceProcessors;
 #[cfg(feature = "sanity")]
 use crate::util::sanity::sanity_checker::SanityChecker;
+use crate::util::statistics::gc_event_log::GCEventLog;
+use crate::util::statistics::work_trace::WorkTrace;
 use crate::util::OpaquePointer;
+use crate::util::{alloc_bit, pin_bit};
 use crate::vm::VMBinding;
 use std::default::Default;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
-#[cfg(feature = "sanity")]
 use std::sync::Mutex;
 
 lazy_static! {
@@ -33,11 +38,16 @@ lazy_static! {
 /// An MMTk instance. MMTk allows mutiple instances to run independently, and each instance gives users a separate heap.
 /// *Note that multi-instances is not fully supported yet*
 pub struct MMTK<VM: VMBinding> {
-    pub plan: SelectedPlan<VM>,
+    pub plan: Box<dyn Plan<VM = VM>>,
     pub vm_map: &'static VMMap,
     pub mmapper: &'static Mmapper,
     pub sftmap: &'static SFTMap,
     pub reference_processors: ReferenceProcessors,
+    pub finalizable_processor: Mutex<FinalizableProcessor>,
+    pub conservative_roots: ConservativeRoots,
+    pub heap_dumper: HeapDumper,
+    pub gc_event_log: GCEventLog,
+    pub work_trace: WorkTrace,
     pub options: Arc<UnsafeOptionsWrapper>,
     pub scheduler: Arc<Scheduler<Self>>,
     #[cfg(feature = "sanity")]
@@ -49,10 +59,25 @@ unsafe impl<VM: VMBinding> Send for MMTK<VM> {}
 unsafe impl<VM: VMBinding> Sync for MMTK<VM> {}
 
 impl<VM: VMBinding> MMTK<VM> {
+    /// Create an instance with the default options, including the ones set by `MMTK_*`
+    /// environment variables.
     pub fn new() -> Self {
+        Self::with_options(Options::default())
+    }
+
+    /// Create an instance with the given options (see `OptionsBuilder`).
+    pub fn with_options(options: Options) -> Self {
         let scheduler = Scheduler::new();
-        let options = Arc::new(UnsafeOptionsWrapper::new(Options::default()));
-        let plan = SelectedPlan::new(&VM_MAP, &MMAPPER, options.clone(), unsafe {
+        let options = Arc::new(UnsafeOptionsWrapper::new(options));
+        // The spaces map the alloc bits and the pin bits if they are enabled when they are created.
+        if options.alloc_bit {
+            alloc_bit::enable();
+        }
+        if options.pin_bit {
+            pin_bit::enable();
+        }
+        // The plan is decided by the `plan` option, so it must be set (e.g. via MMTK_PLAN) before this point.
+        let plan = create_plan(options.plan, &VM_MAP, &MMAPPER, options.clone(), unsafe {
             &*(scheduler.as_ref() as *const Scheduler<MMTK<VM>>)
         });
         MMTK {
@@ -61,6 +86,11 @@ impl<VM: VMBinding> MMTK<VM> {
             mmapper: &MMAPPER,
             sftmap: &SFT_MAP,
             reference_processors: ReferenceProcessors::new(),
+            finalizable_processor: Mutex::new(FinalizableProcessor::new()),
+            conservative_roots: ConservativeRoots::new(),
+            heap_dumper: HeapDumper::new(),
+            gc_event_log: GCEventLog::new(),
+            work_trace: WorkTrace::new(),
             options,
             scheduler,
             #[cfg(feature = "sanity")]
@@ -1,7 +1,15 @@
+use crate::mmtk::SFT_MAP;
+use crate::plan::TransitiveClosure;
 use crate::policy::space::Space;
 use crate::scheduler::gc_works::*;
+use crate::util::card_table::{CardTable, BYTES_IN_CARD};
+use crate::util::constants::{BYTES_IN_ADDRESS, LOG_BYTES_IN_ADDRESS};
 use crate::util::*;
+use crate::vm::{ObjectModel, Scanning, VMBinding};
 use crate::MMTK;
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
 
 /// For field writes in HotSpot, we cannot always get the source object pointer and the field address
 pub enum WriteTarget {
@@ -12,6 +20,38 @@ pub enum WriteTarget {
 pub trait Barrier: 'static + Send + Sync {
     fn flush(&mut self);
     fn post_write_barrier(&mut self, target: WriteTarget);
+    /// Called before the reference in `slot`, a field of `src`, is overwritten by `new_value`.
+    /// `old_value` is the reference in the slot before the write. If the binding does not know which
+    /// field is written, `slot` is zero and `old_value` is null. Only barriers that need the old value
+    /// (e.g. snapshot-at-the-beginning barriers) implement this, and bindings only need to call it
+    /// if `PlanConstraints::needs_pre_write_barrier` is set.
+    #[inline(always)]
+    fn pre_write_barrier(
+        &mut self,
+        _src: ObjectReference,
+        _slot: Address,
+        _old_value: ObjectReference,
+        _new_value: ObjectReference,
+    ) {
+    }
+    /// Called after the reference slots from `start` to `end` are written in bulk. By default,
+    /// each slot goes through `post_write_barrier`. Barriers that can log a range at once override this.
+    fn record_modified_range(&mut self, start: Address, end: Address) {
+        let mut slot = start;
+        while slot < end {
+            self.post_write_barrier(WriteTarget::Slot(slot));
+            slot += BYTES_IN_ADDRESS;
+        }
+    }
+    /// Called before `len` references are copied to the slots starting at `dst`, which are fields
+    /// of `dst_object`. As with `pre_write_barrier`, only barriers that need the old values or the
+    /// object before the write implement this.
+    fn pre_array_copy(&mut self, _dst_object: ObjectReference, _dst: Address, _len: usize) {}
+    /// Called after `len` references are copied from the slots starting at `src` to the slots
+    /// starting at `dst` (e.g. for `System.arraycopy`).
+    fn post_array_copy(&mut self, _src: Address, dst: Address, len: usize) {
+        self.record_modified_range(dst, dst + (len << LOG_BYTES_IN_ADDRESS));
+    }
 }
 
 pub struct NoBarrier;
@@ -25,17 +65,22 @@ impl Barrier for NoBarrier {
 pub struct ModBuffer {
     modified_nodes: Vec<ObjectReference>,
     modified_edges: Vec<Address>,
+    /// The ranges of modified slots, as `(start, end)`.
+    modified_ranges: Vec<(Address, Address)>,
 }
 
+/// A barrier that remembers the modified objects and fields, so nursery GCs can trace from them.
 pub struct FieldRememberingBarrier<E: ProcessEdgesWork, S: Space<E::VM>> {
     mmtk: &'static MMTK<E::VM>,
-    nursery: &'static S,
+    /// Writes to this space are not remembered. Without a nursery space (e.g. with sticky mark
+    /// bits, where young and old objects share the spaces), all the writes are remembered.
+    nursery: Option<&'static S>,
     mod_buffer: ModBuffer,
 }
 
 impl<E: ProcessEdgesWork, S: Space<E::VM>> FieldRememberingBarrier<E, S> {
     #[allow(unused)]
-    pub fn new(mmtk: &'static MMTK<E::VM>, nursery: &'static S) -> Self {
+    pub fn new(mmtk: &'static MMTK<E::VM>, nursery: Option<&'static S>) -> Self {
         Self {
             mmtk,
             nursery,
@@ -52,7 +97,7 @@ impl<E: ProcessEdgesWork, S: Space<E::VM>> FieldRememberingBarrier<E, S> {
 
     fn enqueue_edge(&mut self, slot: Address) {
         self.mod_buffer.modified_edges.push(slot);
-        if self.mod_buffer.modified_edges.len() >= 512 {
+        if self.mod_buffer.modified_edges.len() >= E::CAPACITY {
             self.flush();
         }
     }
@@ -64,6 +109,8 @@ impl<E: ProcessEdgesWork, S: Space<E::VM>> Barrier for FieldRememberingBarrier<E
         std::mem::swap(&mut modified_nodes, &mut self.mod_buffer.modified_nodes);
         let mut modified_edges = vec![];
         std::mem::swap(&mut modified_edges, &mut self.mod_buffer.modified_edges);
+        let mut modified_ranges = vec![];
+        std::mem::swap(&mut modified_ranges, &mut self.mod_buffer.modified_ranges);
         debug_assert!(
             !self.mmtk.scheduler.final_stage.is_activated(),
             "{:?}",
@@ -72,20 +119,529 @@ impl<E: ProcessEdgesWork, S: Space<E::VM>> Barrier for FieldRememberingBarrier<E
         self.mmtk
             .scheduler
             .closure_stage
-            .add(ProcessModBuf::<E>::new(modified_nodes, modified_edges));
+            .add(ProcessModBuf::<E>::new(
+                modified_nodes,
+                modified_edges,
+                modified_ranges,
+            ));
     }
     fn post_write_barrier(&mut self, target: WriteTarget) {
         match target {
             WriteTarget::Object(obj) => {
-                if !self.nursery.in_space(obj) {
+                let in_nursery = match self.nursery {
+                    Some(nursery) => nursery.in_space(obj),
+                    None => false,
+                };
+                if !in_nursery {
                     self.enqueue_node(obj);
                 }
             }
             WriteTarget::Slot(slot) => {
-                if !self.nursery.address_in_space(slot) {
+                let in_nursery = match self.nursery {
+                    Some(nursery) => nursery.address_in_space(slot),
+                    None => false,
+                };
+                if !in_nursery {
                     self.enqueue_edge(slot);
                 }
             }
         }
     }
+    fn record_modified_range(&mut self, start: Address, end: Address) {
+        let in_nursery = match self.nursery {
+            Some(nursery) => nursery.address_in_space(start),
+            None => false,
+        };
+        if in_nursery || start >= end {
+            return;
+        }
+        self.mod_buffer.modified_ranges.push((start, end));
+        if self.mod_buffer.modified_ranges.len() >= E::CAPACITY {
+            self.flush();
+        }
+    }
+}
+
+/// A barrier that remembers each mature object at most once per GC cycle. Mature objects have
+/// the unlogged bit set in their headers (`PlanConstraints::needs_log_bit_in_header`). The first
+/// write to an unlogged object clears the bit and logs the object, and later writes only check
+/// the bit. The logged objects are scanned in nursery GCs, and get the unlogged bit back in the
+/// next GC (see `ProcessObjectModBuf`). Nursery objects are never unlogged, so they are not logged.
+pub struct ObjectRememberingBarrier<E: ProcessEdgesWork> {
+    mmtk: &'static MMTK<E::VM>,
+    mod_buffer: ModBuffer,
+}
+
+impl<E: ProcessEdgesWork> ObjectRememberingBarrier<E> {
+    pub fn new(mmtk: &'static MMTK<E::VM>) -> Self {
+        Self {
+            mmtk,
+            mod_buffer: ModBuffer::default(),
+        }
+    }
+
+    fn enqueue_node(&mut self, obj: ObjectReference) {
+        self.mod_buffer.modified_nodes.push(obj);
+        if self.mod_buffer.modified_nodes.len() >= E::CAPACITY {
+            self.flush();
+        }
+    }
+
+    fn enqueue_edge(&mut self, slot: Address) {
+        self.mod_buffer.modified_edges.push(slot);
+        if self.mod_buffer.modified_edges.len() >= E::CAPACITY {
+            self.flush();
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> Barrier for ObjectRememberingBarrier<E> {
+    fn flush(&mut self) {
+        if self.mod_buffer.modified_nodes.is_empty()
+            && self.mod_buffer.modified_edges.is_empty()
+            && self.mod_buffer.modified_ranges.is_empty()
+        {
+            return;
+        }
+        let mut modified_nodes = vec![];
+        std::mem::swap(&mut modified_nodes, &mut self.mod_buffer.modified_nodes);
+        let mut modified_edges = vec![];
+        std::mem::swap(&mut modified_edges, &mut self.mod_buffer.modified_edges);
+        let mut modified_ranges = vec![];
+        std::mem::swap(&mut modified_ranges, &mut self.mod_buffer.modified_ranges);
+        // The logged objects are unlogged again before any object is copied
+        self.mmtk
+            .scheduler
+            .prepare_stage
+            .add(ProcessObjectModBuf::<E>::new(
+                modified_nodes,
+                modified_edges,
+                modified_ranges,
+            ));
+    }
+    #[inline(always)]
+    fn post_write_barrier(&mut self, target: WriteTarget) {
+        match target {
+            WriteTarget::Object(obj) => {
+                // Fast path: the object is a nursery object, or it is logged already
+                if !header_byte::is_unlogged::<E::VM>(obj) {
+                    return;
+                }
+                if header_byte::attempt_log::<E::VM>(obj) {
+                    self.enqueue_node(obj);
+                }
+            }
+            WriteTarget::Slot(slot) => {
+                // Without the object, we cannot use the unlogged bit. Remember the slot.
+                self.enqueue_edge(slot);
+            }
+        }
+    }
+    fn record_modified_range(&mut self, start: Address, end: Address) {
+        // As with a slot, the range is remembered as a whole rather than slot by slot
+        if start >= end {
+            return;
+        }
+        self.mod_buffer.modified_ranges.push((start, end));
+        if self.mod_buffer.modified_ranges.len() >= E::CAPACITY {
+            self.flush();
+        }
+    }
+}
+
+/// A card-marking barrier. A write dirties the card of the modified field, or the card where the
+/// modified object starts. The barrier is a shift and a byte store (see `CardTable::biased_base()`),
+/// so the binding can inline it. A nursery GC scans the objects on the dirty cards (see `ScanDirtyCards`).
+///
+/// Only the objects copied to the mature space are recorded in the card table, so the writes to
+/// the objects in the non-moving spaces (e.g. large objects) are remembered as with the
+/// `ObjectRememberingBarrier`. These objects have the unlogged bit set when they are allocated,
+/// while the copied objects do not, so an inlined barrier only needs to check the bit.
+pub struct CardMarkingBarrier<E: ProcessEdgesWork> {
+    card_table: &'static CardTable,
+    remembering: ObjectRememberingBarrier<E>,
+}
+
+impl<E: ProcessEdgesWork> CardMarkingBarrier<E> {
+    pub fn new(mmtk: &'static MMTK<E::VM>, card_table: &'static CardTable) -> Self {
+        Self {
+            card_table,
+            remembering: ObjectRememberingBarrier::new(mmtk),
+        }
+    }
+
+    /// Are the objects at the address found by scanning cards?
+    fn in_card_table(addr: Address) -> bool {
+        SFT_MAP.get(addr).is_movable()
+    }
+}
+
+impl<E: ProcessEdgesWork> Barrier for CardMarkingBarrier<E> {
+    fn flush(&mut self) {
+        self.remembering.flush();
+    }
+    #[inline(always)]
+    fn post_write_barrier(&mut self, target: WriteTarget) {
+        match target {
+            WriteTarget::Object(obj) => {
+                if header_byte::is_unlogged::<E::VM>(obj) {
+                    self.remembering.post_write_barrier(target);
+                } else {
+                    self.card_table
+                        .mark_card(<E::VM as VMBinding>::VMObjectModel::object_start_ref(obj));
+                }
+            }
+            WriteTarget::Slot(slot) => {
+                if Self::in_card_table(slot) {
+                    self.card_table.mark_card(slot);
+                } else {
+                    self.remembering.post_write_barrier(target);
+                }
+            }
+        }
+    }
+    fn record_modified_range(&mut self, start: Address, end: Address) {
+        if !Self::in_card_table(start) {
+            self.remembering.record_modified_range(start, end);
+            return;
+        }
+        // Dirty each card in the range once
+        let mut card = start.align_down(BYTES_IN_CARD);
+        while card < end {
+            self.card_table.mark_card(card);
+            card += BYTES_IN_CARD;
+        }
+    }
+}
+
+/// Collects the referents of an object.
+pub struct ObjectReferents(pub Vec<ObjectReference>);
+
+impl TransitiveClosure for ObjectReferents {
+    fn process_edge(&mut self, slot: Address) {
+        self.0.push(unsafe { slot.load::<ObjectReference>() });
+    }
+    fn process_node(&mut self, _object: ObjectReference) {
+        unreachable!();
+    }
+}
+
+/// A snapshot-at-the-beginning (deletion) barrier for concurrent marking. While marking is in
+/// progress, it logs the references that are about to be overwritten, and the logged objects are
+/// marked by `ConcurrentMark` works. Together with marking the objects allocated during marking,
+/// this makes sure that all the objects that are reachable when marking starts get marked.
+pub struct SATBBarrier<E: ProcessEdgesWork> {
+    mmtk: &'static MMTK<E::VM>,
+    /// Whether concurrent marking is in progress. The barrier does nothing otherwise.
+    marking: &'static AtomicBool,
+    tls: OpaquePointer,
+    satb_buffer: Vec<ObjectReference>,
+}
+
+impl<E: ProcessEdgesWork> SATBBarrier<E> {
+    pub fn new(
+        mmtk: &'static MMTK<E::VM>,
+        marking: &'static AtomicBool,
+        tls: OpaquePointer,
+    ) -> Self {
+        Self {
+            mmtk,
+            marking,
+            tls,
+            satb_buffer: vec![],
+        }
+    }
+
+    fn enqueue(&mut self, object: ObjectReference) {
+        if object.is_null() {
+            return;
+        }
+        self.satb_buffer.push(object);
+        if self.satb_buffer.len() >= E::CAPACITY {
+            self.flush();
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> Barrier for SATBBarrier<E> {
+    fn flush(&mut self) {
+        if self.satb_buffer.is_empty() {
+            return;
+        }
+        let mut satb_buffer = vec![];
+        std::mem::swap(&mut satb_buffer, &mut self.satb_buffer);
+        self.mmtk
+            .scheduler
+            .add_concurrent_work(ConcurrentMark::<E>::new(satb_buffer, false));
+    }
+    fn post_write_barrier(&mut self, _target: WriteTarget) {}
+    fn pre_write_barrier(
+        &mut self,
+        src: ObjectReference,
+        slot: Address,
+        old_value: ObjectReference,
+        _new_value: ObjectReference,
+    ) {
+        if !self.marking.load(Ordering::Relaxed) {
+            return;
+        }
+        if slot.is_zero() {
+            // We do not know which field is going to be overwritten, so log all the referents.
+            let mut referents = ObjectReferents(vec![]);
+            <E::VM as VMBinding>::VMScanning::scan_object(&mut referents, src, self.tls);
+            for object in referents.0 {
+                self.enqueue(object);
+            }
+        } else {
+            self.enqueue(old_value);
+        }
+    }
+    fn pre_array_copy(&mut self, _dst_object: ObjectReference, dst: Address, len: usize) {
+        if !self.marking.load(Ordering::Relaxed) {
+            return;
+        }
+        for i in 0..len {
+            let old_value =
+                unsafe { (dst + (i << LOG_BYTES_IN_ADDRESS)).load::<ObjectReference>() };
+            self.enqueue(old_value);
+        }
+    }
+    fn record_modified_range(&mut self, _start: Address, _end: Address) {
+        // The old values are logged before the write
+    }
+}
+
+/// A coalescing barrier for deferred reference counting. The first write to an unlogged object
+/// (see `ObjectRememberingBarrier`) logs the object, and takes a snapshot of its referents before
+/// the write. In the next GC, the referents of the logged objects get increments (with `E`), and
+/// the referents in the snapshots get decrements, so an object that is written many times between
+/// two GCs only costs one increment and one decrement for each of its referents.
+///
+/// The snapshot has to be taken before the object is written, so this is a pre-write barrier,
+/// and the source object needs to be known. The object is in the being-logged state while the
+/// snapshot is taken, and the writes of other mutators to the object wait until it is logged.
+pub struct RCBarrier<E: ProcessEdgesWork> {
+    mmtk: &'static MMTK<E::VM>,
+    tls: OpaquePointer,
+    /// The decrement buffers of all the mutators. They are processed in the next GC.
+    dec_buffers: &'static Mutex<Vec<Vec<ObjectReference>>>,
+    mod_buffer: Vec<ObjectReference>,
+    decs: Vec<ObjectReference>,
+}
+
+impl<E: ProcessEdgesWork> RCBarrier<E> {
+    pub fn new(
+        mmtk: &'static MMTK<E::VM>,
+        dec_buffers: &'static Mutex<Vec<Vec<ObjectReference>>>,
+        tls: OpaquePointer,
+    ) -> Self {
+        Self {
+            mmtk,
+            tls,
+            dec_buffers,
+            mod_buffer: vec![],
+            decs: vec![],
+        }
+    }
+
+    fn log_object(&mut self, obj: ObjectReference) {
+        let mut referents = ObjectReferents(vec![]);
+        <E::VM as VMBinding>::VMScanning::scan_object(&mut referents, obj, self.tls);
+        self.decs
+            .extend(referents.0.into_iter().filter(|object| !object.is_null()));
+        self.mod_buffer.push(obj);
+        if self.mod_buffer.len() >= E::CAPACITY || self.decs.len() >= E::CAPACITY {
+            self.flush();
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> Barrier for RCBarrier<E> {
+    fn flush(&mut self) {
+        if !self.decs.is_empty() {
+            let mut decs = vec![];
+            std::mem::swap(&mut decs, &mut self.decs);
+            self.dec_buffers.lock().unwrap().push(decs);
+        }
+        if !self.mod_buffer.is_empty() {
+            let mut modified_nodes = vec![];
+            std::mem::swap(&mut modified_nodes, &mut self.mod_buffer);
+            // The logged objects are unlogged again, and their referents get increments
+            self.mmtk
+                .scheduler
+                .prepare_stage
+                .add(ProcessObjectModBuf::<E>::new(
+                    modified_nodes,
+                    vec![],
+                    vec![],
+                ));
+        }
+    }
+    fn post_write_barrier(&mut self, _target: WriteTarget) {}
+    #[inline(always)]
+    fn pre_write_barrier(
+        &mut self,
+        src: ObjectReference,
+        _slot: Address,
+        _old_value: ObjectReference,
+        _new_value: ObjectReference,
+    ) {
+        debug_assert!(!src.is_null(), "The RC barrier needs the source object");
+        // Fast path: the object is a new object, or it is logged already. This also waits if
+        // another mutator is logging the object.
+        if header_byte::attempt_log_and_lock::<E::VM>(src) {
+            self.log_object(src);
+            header_byte::finish_logging::<E::VM>(src);
+        }
+    }
+    fn pre_array_copy(&mut self, dst_object: ObjectReference, dst: Address, _len: usize) {
+        // Log the destination object, with the snapshot of its referents before the copy
+        let null = unsafe { Address::ZERO.to_object_reference() };
+        self.pre_write_barrier(dst_object, dst, null, null);
+    }
+    fn record_modified_range(&mut self, _start: Address, _end: Address) {
+        // The object of the slots is logged before the write (see `pre_array_copy`), and its
+        // referents get increments in the next GC
+    }
+}
+
+/// The read barrier that a binding needs to emit for reference loads (see `PlanConstraints::read_barrier`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadBarrierSelector {
+    /// No read barrier. The loaded references can be used as they are.
+    NoBarrier,
+    /// Every loaded reference goes through `MutatorContext::load_reference`, which may return a
+    /// different reference (e.g. the new copy of an object that is moved concurrently).
+    LoadReference,
+}
+
+/// A read barrier, applied to the references loaded by the mutator.
+pub trait ReadBarrier: 'static + Send + Sync {
+    /// Called after `value` is loaded from `slot`. Returns the reference that the mutator should use.
+    fn load_reference(&mut self, slot: Address, value: ObjectReference) -> ObjectReference;
+}
+
+pub struct NoReadBarrier;
+
+impl ReadBarrier for NoReadBarrier {
+    #[inline(always)]
+    fn load_reference(&mut self, _slot: Address, value: ObjectReference) -> ObjectReference {
+        value
+    }
+}
+
+/// A read barrier for plans that copy objects while the mutators are running. While copying is in
+/// progress, a loaded reference to a forwarded object is resolved to the new copy, and the slot is
+/// updated (healed), so later loads from the slot take the fast path.
+pub struct ForwardingReadBarrier<VM: VMBinding> {
+    /// Whether concurrent copying is in progress. The barrier does nothing otherwise.
+    copying: &'static AtomicBool,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ForwardingReadBarrier<VM> {
+    pub fn new(copying: &'static AtomicBool) -> Self {
+        Self {
+            copying,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<VM: VMBinding> ReadBarrier for ForwardingReadBarrier<VM> {
+    #[inline(always)]
+    fn load_reference(&mut self, slot: Address, value: ObjectReference) -> ObjectReference {
+        if value.is_null() || !self.copying.load(Ordering::Relaxed) {
+            return value;
+        }
+        if !forwarding_word::is_forwarded_or_being_forwarded::<VM>(value) {
+            return value;
+        }
+        // Wait for the object to be copied if a GC thread is copying it
+        let forwarding_status = forwarding_word::get_forwarding_status::<VM>(value);
+        let new_value =
+            forwarding_word::spin_and_get_forwarded_object::<VM>(value, forwarding_status);
+        debug_assert!(forwarding_word::is_forwarded::<VM>(value));
+        if !slot.is_zero() {
+            // Heal the slot. Another thread may have written a new reference to it in the meantime.
+            let _ = unsafe {
+                slot.compare_exchange::<AtomicUsize>(
+                    value.to_address().as_usize(),
+                    new_value.to_address().as_usize(),
+                    Ordering::SeqCst,
+                    Ordering::Relaxed,
+                )
+            };
+        }
+        new_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::mock_vm::{self, MockVM};
+
+    fn copying(value: bool) -> &'static AtomicBool {
+        Box::leak(Box::new(AtomicBool::new(value)))
+    }
+
+    fn load_slot(slot: Address) -> ObjectReference {
+        unsafe { slot.load::<ObjectReference>() }
+    }
+
+    #[test]
+    fn forwarding_read_barrier_heals_slot() {
+        let holder = mock_vm::alloc_object(1);
+        let old = mock_vm::alloc_object(0);
+        let new = mock_vm::alloc_object(0);
+        forwarding_word::set_forwarding_pointer::<MockVM>(old, new);
+        let slot = mock_vm::field(holder, 0);
+        unsafe { slot.store(old) };
+
+        let mut barrier = ForwardingReadBarrier::<MockVM>::new(copying(true));
+        assert_eq!(barrier.load_reference(slot, old), new);
+        assert_eq!(load_slot(slot), new);
+        // The slot is healed, so the next load takes the fast path.
+        assert_eq!(barrier.load_reference(slot, new), new);
+    }
+
+    #[test]
+    fn forwarding_read_barrier_without_slot() {
+        let old = mock_vm::alloc_object(0);
+        let new = mock_vm::alloc_object(0);
+        forwarding_word::set_forwarding_pointer::<MockVM>(old, new);
+
+        let mut barrier = ForwardingReadBarrier::<MockVM>::new(copying(true));
+        assert_eq!(barrier.load_reference(Address::ZERO, old), new);
+    }
+
+    #[test]
+    fn forwarding_read_barrier_not_copying() {
+        let holder = mock_vm::alloc_object(1);
+        let old = mock_vm::alloc_object(0);
+        let new = mock_vm::alloc_object(0);
+        forwarding_word::set_forwarding_pointer::<MockVM>(old, new);
+        let slot = mock_vm::field(holder, 0);
+        unsafe { slot.store(old) };
+
+        let mut barrier = ForwardingReadBarrier::<MockVM>::new(copying(false));
+        assert_eq!(barrier.load_reference(slot, old), old);
+        assert_eq!(load_slot(slot), old);
+    }
+
+    #[test]
+    fn forwarding_read_barrier_unforwarded_and_null() {
+        let holder = mock_vm::alloc_object(1);
+        let object = mock_vm::alloc_object(0);
+        let slot = mock_vm::field(holder, 0);
+        unsafe { slot.store(object) };
+
+        let mut barrier = ForwardingReadBarrier::<MockVM>::new(copying(true));
+        assert_eq!(barrier.load_reference(slot, object), object);
+        assert_eq!(load_slot(slot), object);
+        assert!(barrier
+            .load_reference(slot, unsafe { Address::ZERO.to_object_reference() })
+            .is_null());
+    }
 }
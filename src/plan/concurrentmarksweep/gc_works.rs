@@ -0,0 +1,138 @@
+use super::global::ConcurrentMarkSweep;
+use crate::plan::global::GcStatus;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::{CoordinatorWork, GCWork, GCWorker};
+use crate::util::{Address, ObjectReference};
+use crate::vm::{Collection, VMBinding};
+use crate::MMTK;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+/// Marks objects in the stop-the-world pauses (full heap collections and final mark pauses),
+/// and in the concurrent works.
+#[derive(Default)]
+pub struct CMSProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<CMSProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for CMSProcessEdges<VM> {
+    type VM = VM;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<ConcurrentMarkSweep<VM>>();
+        plan.trace_object(self, object)
+    }
+}
+
+impl<VM: VMBinding> Deref for CMSProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for CMSProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// Marks the objects that are directly reachable from the roots in the initial mark pause.
+/// The marked objects are not scanned in the pause, but by concurrent works after the pause.
+#[derive(Default)]
+pub struct InitialMarkProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<InitialMarkProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for InitialMarkProcessEdges<VM> {
+    type VM = VM;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<ConcurrentMarkSweep<VM>>();
+        plan.trace_object(self, object)
+    }
+    fn flush(&mut self) {
+        let mut nodes = vec![];
+        mem::swap(&mut nodes, &mut self.nodes);
+        self.plan_as::<ConcurrentMarkSweep<VM>>()
+            .gray_objects
+            .lock()
+            .unwrap()
+            .push(nodes);
+    }
+}
+
+impl<VM: VMBinding> Deref for InitialMarkProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for InitialMarkProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// The end of the initial mark pause: start concurrent marking from the objects marked
+/// in the pause, and resume the mutators.
+#[derive(Default)]
+pub struct EndOfInitialMark<VM: VMBinding>(PhantomData<VM>);
+
+impl<VM: VMBinding> EndOfInitialMark<VM> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for EndOfInitialMark<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        // The concurrent works request the final mark pause when they are done, which they
+        // only do if they are not in a GC. So the GC status needs to be reset before they start.
+        mmtk.plan.base().set_gc_status(GcStatus::NotInGC);
+        mmtk.conservative_roots.unpin_all();
+        let plan = mmtk.plan.downcast_ref::<ConcurrentMarkSweep<VM>>().unwrap();
+        let mut gray_objects = mem::take(&mut *plan.gray_objects.lock().unwrap());
+        if gray_objects.is_empty() {
+            // We still need a concurrent work to request the final mark pause
+            gray_objects.push(vec![]);
+        }
+        for objects in gray_objects {
+            mmtk.scheduler
+                .add_concurrent_work(ConcurrentMark::<CMSProcessEdges<VM>>::new(objects, true));
+        }
+        mmtk.gc_event_log.gc_end(mmtk, worker, "initial_mark");
+        mmtk.work_trace.flush(mmtk, worker);
+        // The triggers of this pause are consumed. The final mark pause is triggered separately.
+        mmtk.plan.reset_collection_trigger();
+        <VM as VMBinding>::VMCollection::resume_mutators(worker.tls);
+    }
+}
+
+impl<VM: VMBinding> CoordinatorWork<MMTK<VM>> for EndOfInitialMark<VM> {}
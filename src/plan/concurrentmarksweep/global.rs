@@ -0,0 +1,232 @@
+use super::gc_works::{CMSProcessEdges, EndOfInitialMark, InitialMarkProcessEdges};
+use crate::mmtk::MMTK;
+use crate::plan::concurrentmarksweep::mutator::create_cms_mutator;
+use crate::plan::concurrentmarksweep::mutator::ALLOCATOR_MAPPING;
+use crate::plan::global::BasePlan;
+use crate::plan::global::CommonPlan;
+use crate::plan::global::GcStatus;
+use crate::plan::global::NoCopy;
+use crate::plan::mutator_context::Mutator;
+use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
+use crate::plan::Plan;
+use crate::plan::PlanConstraints;
+use crate::plan::TransitiveClosure;
+use crate::policy::marksweepspace::{MarkSweepSpace, MAX_CELL_SIZE};
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::*;
+use crate::util::alloc::allocators::AllocatorSelector;
+use crate::util::heap::layout::heap_layout::Mmapper;
+use crate::util::heap::layout::heap_layout::VMMap;
+use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::VMRequest;
+use crate::util::options::UnsafeOptionsWrapper;
+#[cfg(feature = "sanity")]
+use crate::util::sanity::sanity_checker::*;
+use crate::util::{ObjectReference, OpaquePointer};
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use enum_map::EnumMap;
+
+pub const CMS_CONSTRAINTS: PlanConstraints = PlanConstraints {
+    moves_objects: false,
+    gc_header_bits: 2,
+    gc_header_words: 0,
+    num_specialized_scans: 1,
+    max_non_los_default_alloc_bytes: MAX_CELL_SIZE,
+    needs_concurrent_workers: true,
+    needs_pre_write_barrier: true,
+    ..PlanConstraints::default()
+};
+
+/// A mark-sweep plan that marks concurrently with the mutators.
+///
+/// A concurrent collection starts when the used heap reaches the `concurrent_trigger` percentage.
+/// The initial mark pause marks the objects that are directly reachable from the roots, and
+/// enables the SATB barrier. The objects are then marked by concurrent works while the mutators
+/// are running. When marking is done, the final mark pause marks from the roots again, finishes
+/// marking the objects logged by the barrier, processes the references and sweeps the heap.
+/// If the heap is full, or the user requests a collection, the collection is done in a single
+/// stop-the-world pause (or the final mark pause if marking is in progress).
+pub struct ConcurrentMarkSweep<VM: VMBinding> {
+    pub ms_space: MarkSweepSpace<VM>,
+    pub common: CommonPlan<VM>,
+    /// Whether concurrent marking is in progress, from the initial mark pause to the final mark pause.
+    pub marking: AtomicBool,
+    /// Whether the current pause is an initial mark pause.
+    initial_mark: AtomicBool,
+    /// The objects marked in the initial mark pause. They are scanned by concurrent works.
+    pub gray_objects: Mutex<Vec<Vec<ObjectReference>>>,
+}
+
+unsafe impl<VM: VMBinding> Sync for ConcurrentMarkSweep<VM> {}
+
+impl<VM: VMBinding> Plan for ConcurrentMarkSweep<VM> {
+    type VM = VM;
+
+    fn constraints(&self) -> &'static PlanConstraints {
+        &CMS_CONSTRAINTS
+    }
+
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box NoCopy::new(mmtk)
+    }
+
+    fn gc_init(
+        &mut self,
+        heap_size: usize,
+        vm_map: &'static VMMap,
+        scheduler: &Arc<MMTkScheduler<VM>>,
+    ) {
+        self.common.gc_init(heap_size, vm_map, scheduler);
+
+        self.ms_space.init(&vm_map);
+    }
+
+    fn schedule_collection(&'static self, scheduler: &MMTkScheduler<VM>) {
+        self.base().set_collection_kind();
+        self.base().set_gc_status(GcStatus::GcPrepare);
+        let concurrent = self
+            .base()
+            .internal_triggered_collection
+            .swap(false, Ordering::Relaxed);
+        if concurrent && !self.marking.load(Ordering::SeqCst) {
+            self.initial_mark.store(true, Ordering::SeqCst);
+            // Stop mutators & mark the objects that are directly reachable from the roots
+            scheduler
+                .unconstrained_works
+                .add(StopMutators::<InitialMarkProcessEdges<VM>>::new());
+            // Prepare global/collectors/mutators
+            scheduler.prepare_stage.add(Prepare::new(self));
+            // Start concurrent marking & resume mutators
+            scheduler.set_finalizer(Some(EndOfInitialMark::<VM>::new()));
+            return;
+        }
+        // A full heap collection, or the final mark pause if marking is in progress. The concurrent
+        // works and the objects logged by the barriers (flushed when mutators are prepared) are
+        // marked before the closure stage opens.
+        self.initial_mark.store(false, Ordering::SeqCst);
+        // Stop & scan mutators (mutator scanning can happen before STW)
+        scheduler
+            .unconstrained_works
+            .add(StopMutators::<CMSProcessEdges<VM>>::new());
+        // Process weak references
+        scheduler.schedule_ref_processing::<CMSProcessEdges<VM>>();
+        // Prepare global/collectors/mutators
+        scheduler.prepare_stage.add(Prepare::new(self));
+        // Release global/collectors/mutators
+        scheduler.release_stage.add(Release::new(self));
+        // Resume mutators
+        #[cfg(feature = "sanity")]
+        scheduler.final_stage.add(ScheduleSanityGC);
+        scheduler.set_finalizer(Some(EndOfGC));
+    }
+
+    fn bind_mutator(
+        &'static self,
+        tls: OpaquePointer,
+        mmtk: &'static MMTK<Self::VM>,
+    ) -> Box<Mutator<VM>> {
+        Box::new(create_cms_mutator(tls, mmtk))
+    }
+
+    fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector> {
+        &*ALLOCATOR_MAPPING
+    }
+
+    fn prepare(&self, tls: OpaquePointer) {
+        if self.marking.load(Ordering::SeqCst) {
+            // The final mark pause. The spaces are prepared in the initial mark pause.
+            return;
+        }
+        self.common.prepare(tls, true);
+        self.ms_space.prepare();
+        if self.initial_mark.load(Ordering::SeqCst) {
+            // The objects allocated during marking are live, and the barrier logs the overwritten references
+            self.ms_space.set_alloc_marked(true);
+            self.marking.store(true, Ordering::SeqCst);
+        }
+    }
+
+    fn release(&self, tls: OpaquePointer) {
+        self.marking.store(false, Ordering::SeqCst);
+        self.ms_space.set_alloc_marked(false);
+        self.common.release(tls, true);
+        // sweep the mark-sweep space
+        self.ms_space.release(false);
+    }
+
+    fn concurrent_collection_required(&self) -> bool {
+        !self.marking.load(Ordering::Relaxed)
+            && self.get_pages_reserved() * 100
+                >= self.get_total_pages() * self.options().concurrent_trigger
+    }
+
+    fn get_collection_reserve(&self) -> usize {
+        0
+    }
+
+    fn get_pages_used(&self) -> usize {
+        self.ms_space.reserved_pages() + self.common.get_pages_used()
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        self.ms_space.enumerate_objects(f);
+        self.common.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.ms_space);
+        self.common.for_each_space(f);
+    }
+
+    fn base(&self) -> &BasePlan<VM> {
+        &self.common.base
+    }
+
+    fn common(&self) -> &CommonPlan<VM> {
+        &self.common
+    }
+}
+
+impl<VM: VMBinding> ConcurrentMarkSweep<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        _scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+
+        ConcurrentMarkSweep {
+            ms_space: MarkSweepSpace::new(
+                "ms",
+                false,
+                VMRequest::discontiguous(),
+                vm_map,
+                mmapper,
+                &mut heap,
+            ),
+            common: CommonPlan::new(vm_map, mmapper, options, heap, &CMS_CONSTRAINTS),
+            marking: AtomicBool::new(false),
+            initial_mark: AtomicBool::new(false),
+            gray_objects: Mutex::new(vec![]),
+        }
+    }
+
+    #[inline]
+    pub fn trace_object<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+    ) -> ObjectReference {
+        if self.ms_space.in_space(object) {
+            return self.ms_space.trace_object(trace, object);
+        }
+        self.common.trace_object(trace, object)
+    }
+}
@@ -0,0 +1,64 @@
+use super::gc_works::CMSProcessEdges;
+use super::ConcurrentMarkSweep;
+use crate::plan::barriers::{NoReadBarrier, SATBBarrier};
+use crate::plan::mutator_context::Mutator;
+use crate::plan::mutator_context::MutatorConfig;
+use crate::plan::AllocationSemantics as AllocationType;
+use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
+use crate::util::alloc::FreeListAllocator;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+use crate::MMTK;
+use enum_map::enum_map;
+use enum_map::EnumMap;
+
+pub fn cms_mutator_prepare<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // Flush the references logged by the SATB barrier, so they get marked in the final mark pause
+    mutator.barrier.flush();
+}
+
+pub fn cms_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // The mark-sweep space rebuilds its free lists when it sweeps, so drop the cells held by the allocator
+    let free_list_allocator = unsafe {
+        mutator
+            .allocators
+            .get_allocator_mut(mutator.config.allocator_mapping[AllocationType::Default])
+    }
+    .downcast_mut::<FreeListAllocator<VM>>()
+    .unwrap();
+    free_list_allocator.reset();
+}
+
+lazy_static! {
+    pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationType, AllocatorSelector> = enum_map! {
+        AllocationType::Default => AllocatorSelector::FreeList(0),
+        AllocationType::Immortal | AllocationType::Code | AllocationType::ReadOnly => AllocatorSelector::BumpPointer(0),
+        AllocationType::Los => AllocatorSelector::LargeObject(0),
+    };
+}
+
+pub fn create_cms_mutator<VM: VMBinding>(
+    mutator_tls: OpaquePointer,
+    mmtk: &'static MMTK<VM>,
+) -> Mutator<VM> {
+    let cms = mmtk.plan.downcast_ref::<ConcurrentMarkSweep<VM>>().unwrap();
+    let config = MutatorConfig {
+        allocator_mapping: &*ALLOCATOR_MAPPING,
+        space_mapping: box vec![
+            (AllocatorSelector::FreeList(0), &cms.ms_space),
+            (AllocatorSelector::BumpPointer(0), cms.common.get_immortal()),
+            (AllocatorSelector::LargeObject(0), cms.common.get_los()),
+        ],
+        prepare_func: &cms_mutator_prepare,
+        release_func: &cms_mutator_release,
+    };
+
+    Mutator {
+        allocators: Allocators::<VM>::new(mutator_tls, &*mmtk.plan, &config.space_mapping),
+        barrier: box SATBBarrier::<CMSProcessEdges<VM>>::new(mmtk, &cms.marking, mutator_tls),
+        read_barrier: box NoReadBarrier,
+        mutator_tls,
+        config,
+        plan: cms,
+    }
+}
@@ -1,6 +0,0 @@
-pub use crate::plan::plan_constraints::*;
-
-pub const MOVES_OBJECTS: bool = true;
-pub const GC_HEADER_BITS: usize = 2;
-pub const GC_HEADER_WORDS: usize = 0;
-pub const NUM_SPECIALIZED_SCANS: usize = 1;
@@ -1,10 +1,12 @@
 use super::global::GenCopy;
-use crate::plan::{CopyContext, Plan};
+use crate::plan::CopyContext;
 use crate::policy::space::Space;
 use crate::scheduler::gc_works::*;
 use crate::scheduler::{GCWork, GCWorker};
 use crate::util::alloc::{Allocator, BumpAllocator};
 use crate::util::forwarding_word;
+use crate::util::header_byte;
+use crate::util::pin_bit;
 use crate::util::{Address, ObjectReference, OpaquePointer};
 use crate::vm::*;
 use crate::MMTK;
@@ -20,8 +22,8 @@ impl<VM: VMBinding> CopyContext for GenCopyCopyContext<VM> {
     type VM = VM;
     fn new(mmtk: &'static MMTK<Self::VM>) -> Self {
         Self {
-            plan: unsafe { &*(&mmtk.plan as *const _ as *const GenCopy<VM>) },
-            ss: BumpAllocator::new(OpaquePointer::UNINITIALIZED, None, &mmtk.plan),
+            plan: mmtk.plan.downcast_ref::<GenCopy<VM>>().unwrap(),
+            ss: BumpAllocator::new(OpaquePointer::UNINITIALIZED, None, &*mmtk.plan),
         }
     }
     fn init(&mut self, tls: OpaquePointer) {
@@ -50,10 +52,18 @@ impl<VM: VMBinding> CopyContext for GenCopyCopyContext<VM> {
         &mut self,
         obj: ObjectReference,
         _tib: Address,
-        _bytes: usize,
+        bytes: usize,
         _semantics: crate::AllocationSemantics,
     ) {
         forwarding_word::clear_forwarding_bits::<VM>(obj);
+        // Objects are only copied to the mature space. The object remembering barrier logs them
+        // on the first write, and the card-marking barrier finds them in the card table.
+        match &self.plan.card_table {
+            Some(card_table) => {
+                card_table.record_object(VM::VMObjectModel::object_start_ref(obj), bytes)
+            }
+            None => header_byte::mark_as_unlogged::<VM>(obj),
+        }
     }
 }
 
@@ -76,25 +86,38 @@ impl<VM: VMBinding> ProcessEdgesWork for GenCopyNurseryProcessEdges<VM> {
         if object.is_null() {
             return object;
         }
+        let plan = self.plan_as::<GenCopy<VM>>();
         // Evacuate nursery objects
-        if self.plan().nursery.in_space(object) {
-            return self.plan().nursery.trace_object(
+        if plan.nursery.in_space(object) {
+            return plan.nursery.trace_object(
                 self,
                 object,
                 super::global::ALLOC_SS,
-                self.worker().local(),
+                self.worker().copy_context::<GenCopyCopyContext<VM>>(),
             );
         }
-        debug_assert!(!self.plan().fromspace().in_space(object));
-        debug_assert!(self.plan().tospace().in_space(object));
+        // The large objects allocated since the last GC are traced in a nursery GC
+        let los = plan.common.get_los();
+        if los.in_space(object) {
+            return los.trace_object(self, object);
+        }
+        // Other objects outside the nursery are not traced in a nursery GC. The mature objects
+        // kept in place by pinning may be in the from-space.
+        debug_assert!(
+            !plan.fromspace().in_space(object) || plan.fromspace().has_retained_objects()
+        );
         object
     }
     #[inline]
     fn process_edge(&mut self, slot: Address) {
-        debug_assert!(!self.plan().fromspace().address_in_space(slot));
+        let plan = self.plan_as::<GenCopy<VM>>();
+        debug_assert!(
+            !plan.fromspace().address_in_space(slot) || plan.fromspace().has_retained_objects()
+        );
         let object = unsafe { slot.load::<ObjectReference>() };
         let new_object = self.trace_object(object);
-        debug_assert!(!self.plan().nursery.in_space(new_object));
+        debug_assert!(!plan.nursery.in_space(new_object) || pin_bit::is_pinned(new_object));
+        plan.remember_pinned_nursery_slot(slot, new_object);
         unsafe { slot.store(new_object) };
     }
 }
@@ -131,33 +154,42 @@ impl<VM: VMBinding> ProcessEdgesWork for GenCopyMatureProcessEdges<VM> {
         if object.is_null() {
             return object;
         }
+        let plan = self.plan_as::<GenCopy<VM>>();
         // Evacuate nursery objects
-        if self.plan().nursery.in_space(object) {
-            return self.plan().nursery.trace_object(
+        if plan.nursery.in_space(object) {
+            return plan.nursery.trace_object(
                 self,
                 object,
                 super::global::ALLOC_SS,
-                self.worker().local(),
+                self.worker().copy_context::<GenCopyCopyContext<VM>>(),
             );
         }
         // Evacuate mature objects
-        if self.plan().tospace().in_space(object) {
-            return self.plan().tospace().trace_object(
+        if plan.tospace().in_space(object) {
+            return plan.tospace().trace_object(
                 self,
                 object,
                 super::global::ALLOC_SS,
-                self.worker().local(),
+                self.worker().copy_context::<GenCopyCopyContext<VM>>(),
             );
         }
-        if self.plan().fromspace().in_space(object) {
-            return self.plan().fromspace().trace_object(
+        if plan.fromspace().in_space(object) {
+            return plan.fromspace().trace_object(
                 self,
                 object,
                 super::global::ALLOC_SS,
-                self.worker().local(),
+                self.worker().copy_context::<GenCopyCopyContext<VM>>(),
             );
         }
-        self.plan().common.trace_object(self, object)
+        plan.common.trace_object(self, object)
+    }
+    #[inline]
+    fn process_edge(&mut self, slot: Address) {
+        let object = unsafe { slot.load::<ObjectReference>() };
+        let new_object = self.trace_object(object);
+        self.plan_as::<GenCopy<VM>>()
+            .remember_pinned_nursery_slot(slot, new_object);
+        unsafe { slot.store(new_object) };
     }
 }
 
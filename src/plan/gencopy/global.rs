@@ -2,18 +2,22 @@ use super::gc_works::{GenCopyCopyContext, GenCopyMatureProcessEdges, GenCopyNurs
 use super::mutator::create_gencopy_mutator;
 use super::mutator::ALLOCATOR_MAPPING;
 use crate::mmtk::MMTK;
+use crate::mmtk::SFT_MAP;
 use crate::plan::global::BasePlan;
 use crate::plan::global::CommonPlan;
 use crate::plan::global::GcStatus;
 use crate::plan::mutator_context::Mutator;
 use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
 use crate::plan::Plan;
+use crate::plan::PlanConstraints;
 use crate::policy::copyspace::CopySpace;
 use crate::policy::space::Space;
 use crate::scheduler::gc_works::*;
 use crate::scheduler::*;
 use crate::util::alloc::allocators::AllocatorSelector;
-use crate::util::constants::LOG_BYTES_IN_PAGE;
+use crate::util::card_table::{CardTable, CARD_SIDE_METADATA_SPEC, OBJECT_SIDE_METADATA_SPECS};
+use crate::util::conversions::bytes_to_pages_up;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
@@ -22,16 +26,31 @@ use crate::util::heap::VMRequest;
 use crate::util::options::UnsafeOptionsWrapper;
 #[cfg(feature = "sanity")]
 use crate::util::sanity::sanity_checker::*;
-use crate::util::OpaquePointer;
+use crate::util::{Address, ObjectReference, OpaquePointer};
 use crate::vm::*;
 use enum_map::EnumMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-
-pub type SelectedPlan<VM> = GenCopy<VM>;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 pub const ALLOC_SS: AllocationSemantics = AllocationSemantics::Default;
-pub const NURSERY_SIZE: usize = 16 * 1024 * 1024;
+/// The initial nursery size, bounded by the `min_nursery` and `max_nursery` options.
+pub const DEFAULT_NURSERY_SIZE: usize = 16 * 1024 * 1024;
+/// If more than this percentage of the nursery survives a nursery GC, the nursery grows,
+/// so that the objects have more time to die before the next nursery GC.
+const NURSERY_GROW_SURVIVAL_PERCENT: usize = 20;
+/// If less than this percentage of the nursery survives a nursery GC, the nursery shrinks,
+/// leaving more of the heap to the mature space.
+const NURSERY_SHRINK_SURVIVAL_PERCENT: usize = 5;
+
+pub const GENCOPY_CONSTRAINTS: PlanConstraints = PlanConstraints {
+    moves_objects: true,
+    gc_header_bits: 2,
+    gc_header_words: 0,
+    num_specialized_scans: 1,
+    needs_log_bit_in_header: true,
+    ..PlanConstraints::default()
+};
 
 pub struct GenCopy<VM: VMBinding> {
     pub nursery: CopySpace<VM>,
@@ -40,6 +59,17 @@ pub struct GenCopy<VM: VMBinding> {
     pub copyspace1: CopySpace<VM>,
     pub common: CommonPlan<VM>,
     in_nursery: AtomicBool,
+    /// The current nursery size in pages. It is adjusted after each nursery GC.
+    nursery_pages: AtomicUsize,
+    /// The nursery pages and the mature pages at the start of the current GC, to compute the survival rate.
+    nursery_pages_before_gc: AtomicUsize,
+    mature_pages_before_gc: AtomicUsize,
+    /// The card table for the card-marking barrier, if the `card_marking` option is set. The objects
+    /// copied to the mature space are recorded in it, and the dirty cards are scanned in nursery GCs.
+    pub card_table: Option<CardTable>,
+    /// The slots outside the nursery that refer to nursery objects kept in place by pinning. The
+    /// barrier does not log these slots again, so they are traced in the next nursery GC.
+    pinned_nursery_slots: Mutex<Vec<Address>>,
     pub scheduler: &'static MMTkScheduler<VM>,
 }
 
@@ -47,59 +77,20 @@ unsafe impl<VM: VMBinding> Sync for GenCopy<VM> {}
 
 impl<VM: VMBinding> Plan for GenCopy<VM> {
     type VM = VM;
-    type Mutator = Mutator<Self>;
-    type CopyContext = GenCopyCopyContext<VM>;
-
-    fn collection_required(&self, space_full: bool, _space: &dyn Space<Self::VM>) -> bool
-    where
-        Self: Sized,
-    {
-        let nursery_full = self.nursery.reserved_pages() >= (NURSERY_SIZE >> LOG_BYTES_IN_PAGE);
-        let heap_full = self.get_pages_reserved() > self.get_total_pages();
-        space_full || nursery_full || heap_full
+
+    fn constraints(&self) -> &'static PlanConstraints {
+        &GENCOPY_CONSTRAINTS
     }
 
-    fn new(
-        vm_map: &'static VMMap,
-        mmapper: &'static Mmapper,
-        options: Arc<UnsafeOptionsWrapper>,
-        scheduler: &'static MMTkScheduler<Self::VM>,
-    ) -> Self {
-        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box GenCopyCopyContext::new(mmtk)
+    }
 
-        GenCopy {
-            nursery: CopySpace::new(
-                "nursery",
-                false,
-                true,
-                VMRequest::fixed_extent(NURSERY_SIZE, false),
-                vm_map,
-                mmapper,
-                &mut heap,
-            ),
-            hi: AtomicBool::new(false),
-            copyspace0: CopySpace::new(
-                "copyspace0",
-                false,
-                true,
-                VMRequest::discontiguous(),
-                vm_map,
-                mmapper,
-                &mut heap,
-            ),
-            copyspace1: CopySpace::new(
-                "copyspace1",
-                true,
-                true,
-                VMRequest::discontiguous(),
-                vm_map,
-                mmapper,
-                &mut heap,
-            ),
-            common: CommonPlan::new(vm_map, mmapper, options, heap),
-            in_nursery: AtomicBool::default(),
-            scheduler,
-        }
+    fn collection_required(&self, space_full: bool, _space: &dyn Space<Self::VM>) -> bool {
+        let nursery_full =
+            self.nursery.reserved_pages() >= self.nursery_pages.load(Ordering::Relaxed);
+        let heap_full = self.get_pages_reserved() > self.get_total_pages();
+        space_full || nursery_full || heap_full
     }
 
     fn gc_init(
@@ -119,16 +110,37 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
         self.in_nursery.store(in_nursery, Ordering::SeqCst);
         self.base().set_collection_kind();
         self.base().set_gc_status(GcStatus::GcPrepare);
+        // A full heap GC finds the slots again
+        let pinned_nursery_slots = mem::take(&mut *self.pinned_nursery_slots.lock().unwrap());
 
-        // Stop & scan mutators (mutator scanning can happen before STW)
+        // Stop & scan mutators (mutator scanning can happen before STW),
+        // and process weak references
         if in_nursery {
             scheduler
                 .unconstrained_works
                 .add(StopMutators::<GenCopyNurseryProcessEdges<VM>>::new());
+            scheduler.schedule_ref_processing::<GenCopyNurseryProcessEdges<VM>>();
+            // Scan the mature objects on the cards dirtied by the barrier
+            if let Some(card_table) = &self.card_table {
+                ScanDirtyCards::<GenCopyNurseryProcessEdges<VM>>::schedule_all(
+                    card_table,
+                    &self.tospace().allocated_extents(),
+                    scheduler,
+                );
+            }
+            if !pinned_nursery_slots.is_empty() {
+                scheduler
+                    .closure_stage
+                    .add(GenCopyNurseryProcessEdges::<VM>::new(
+                        pinned_nursery_slots,
+                        false,
+                    ));
+            }
         } else {
             scheduler
                 .unconstrained_works
                 .add(StopMutators::<GenCopyMatureProcessEdges<VM>>::new());
+            scheduler.schedule_ref_processing::<GenCopyMatureProcessEdges<VM>>();
         }
         // Prepare global/collectors/mutators
         scheduler.prepare_stage.add(Prepare::new(self));
@@ -144,7 +156,7 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
         &'static self,
         tls: OpaquePointer,
         mmtk: &'static MMTK<Self::VM>,
-    ) -> Box<Mutator<Self>> {
+    ) -> Box<Mutator<VM>> {
         Box::new(create_gencopy_mutator(tls, mmtk))
     }
 
@@ -153,22 +165,37 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
     }
 
     fn prepare(&self, tls: OpaquePointer) {
-        self.common.prepare(tls, true);
+        // A nursery GC only collects the large objects allocated since the last GC
+        self.common.prepare(tls, !self.in_nursery());
         self.nursery.prepare(true);
         if !self.in_nursery() {
+            // All the mature objects are copied, and recorded again
+            if let Some(card_table) = &self.card_table {
+                for (start, bytes) in self.tospace().allocated_extents() {
+                    card_table.clear(start, bytes);
+                }
+            }
             self.hi
                 .store(!self.hi.load(Ordering::SeqCst), Ordering::SeqCst); // flip the semi-spaces
         }
         let hi = self.hi.load(Ordering::SeqCst);
         self.copyspace0.prepare(hi);
         self.copyspace1.prepare(!hi);
+        self.nursery_pages_before_gc
+            .store(self.nursery.reserved_pages(), Ordering::SeqCst);
+        self.mature_pages_before_gc
+            .store(self.tospace().reserved_pages(), Ordering::SeqCst);
     }
 
     fn release(&self, tls: OpaquePointer) {
-        self.common.release(tls, true);
+        self.common.release(tls, !self.in_nursery());
+        if self.in_nursery() {
+            self.resize_nursery();
+        }
         self.nursery.release();
         if !self.in_nursery() {
             self.fromspace().release();
+            self.tospace().release_retained_objects();
         }
     }
 
@@ -177,11 +204,28 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
     }
 
     fn get_pages_used(&self) -> usize {
+        // The from-space keeps the pages of the objects pinned in the last full heap GC
         self.nursery.reserved_pages()
             + self.tospace().reserved_pages()
+            + self.fromspace().reserved_pages()
             + self.common.get_pages_used()
     }
 
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        // The from-space may still hold the objects that were pinned in the last GC
+        self.nursery.enumerate_objects(f);
+        self.copyspace0.enumerate_objects(f);
+        self.copyspace1.enumerate_objects(f);
+        self.common.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.nursery);
+        f(&self.copyspace0);
+        f(&self.copyspace1);
+        self.common.for_each_space(f);
+    }
+
     fn base(&self) -> &BasePlan<VM> {
         &self.common.base
     }
@@ -196,8 +240,122 @@ impl<VM: VMBinding> Plan for GenCopy<VM> {
 }
 
 impl<VM: VMBinding> GenCopy<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        let nursery_size = DEFAULT_NURSERY_SIZE
+            .max(options.min_nursery)
+            .min(options.max_nursery);
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+        let mut nursery = CopySpace::new(
+            "nursery",
+            false,
+            true,
+            VMRequest::discontiguous(),
+            vm_map,
+            mmapper,
+            &mut heap,
+        );
+        let mut copyspace0 = CopySpace::new(
+            "copyspace0",
+            false,
+            true,
+            VMRequest::discontiguous(),
+            vm_map,
+            mmapper,
+            &mut heap,
+        );
+        let mut copyspace1 = CopySpace::new(
+            "copyspace1",
+            true,
+            true,
+            VMRequest::discontiguous(),
+            vm_map,
+            mmapper,
+            &mut heap,
+        );
+        let card_table = if options.card_marking {
+            // The barrier dirties the cards of the nursery objects and the mature objects, and
+            // only the mature objects are recorded on the cards
+            nursery
+                .common_mut()
+                .side_metadata_specs
+                .push(CARD_SIDE_METADATA_SPEC);
+            for space in [&mut copyspace0, &mut copyspace1] {
+                let specs = &mut space.common_mut().side_metadata_specs;
+                specs.push(CARD_SIDE_METADATA_SPEC);
+                specs.extend_from_slice(&OBJECT_SIDE_METADATA_SPECS);
+            }
+            Some(CardTable)
+        } else {
+            None
+        };
+
+        GenCopy {
+            nursery,
+            hi: AtomicBool::new(false),
+            copyspace0,
+            copyspace1,
+            common: CommonPlan::new(vm_map, mmapper, options, heap, &GENCOPY_CONSTRAINTS),
+            in_nursery: AtomicBool::default(),
+            nursery_pages: AtomicUsize::new(bytes_to_pages_up(nursery_size)),
+            nursery_pages_before_gc: AtomicUsize::new(0),
+            mature_pages_before_gc: AtomicUsize::new(0),
+            card_table,
+            pinned_nursery_slots: Mutex::new(vec![]),
+            scheduler,
+        }
+    }
+
+    /// Remember the slot if it is outside the nursery, and it refers to an object kept in the
+    /// nursery by pinning. `object` is the value stored to the slot by the trace.
+    #[inline]
+    pub fn remember_pinned_nursery_slot(&self, slot: Address, object: ObjectReference) {
+        if !object.is_null()
+            && self.nursery.in_space(object)
+            && !self.nursery.address_in_space(slot)
+            && SFT_MAP.is_in_any_space(slot)
+        {
+            self.pinned_nursery_slots.lock().unwrap().push(slot);
+        }
+    }
+
+    /// A full heap GC is required if the heap is full, or if there is not enough room left in
+    /// the heap for the minimal nursery.
     fn request_full_heap_collection(&self) -> bool {
         self.get_total_pages() <= self.get_pages_reserved()
+            || self.get_pages_avail() < bytes_to_pages_up(self.options().min_nursery)
+    }
+
+    /// Adjust the nursery size for the next nursery GC by the survival rate of this nursery GC.
+    /// This needs to be called after the survivors are copied, and before the nursery is released.
+    fn resize_nursery(&self) {
+        let nursery_pages = self.nursery_pages_before_gc.load(Ordering::SeqCst);
+        if nursery_pages == 0 {
+            return;
+        }
+        let survived_pages = self
+            .tospace()
+            .reserved_pages()
+            .saturating_sub(self.mature_pages_before_gc.load(Ordering::SeqCst));
+        let current = self.nursery_pages.load(Ordering::SeqCst);
+        let new = resized_nursery_pages(
+            current,
+            nursery_pages,
+            survived_pages,
+            bytes_to_pages_up(self.options().min_nursery),
+            bytes_to_pages_up(self.options().max_nursery),
+        );
+        debug!(
+            "Nursery survival rate {}%, nursery size {} -> {} pages",
+            survived_pages * 100 / nursery_pages,
+            current,
+            new
+        );
+        self.nursery_pages.store(new, Ordering::SeqCst);
     }
 
     pub fn tospace(&self) -> &CopySpace<VM> {
@@ -216,3 +374,45 @@ impl<VM: VMBinding> GenCopy<VM> {
         }
     }
 }
+
+/// Get the nursery size (in pages) for the next nursery GC, after `survived_pages` of the
+/// `nursery_pages` allocated in the nursery survived a nursery GC with a nursery of `current` pages.
+fn resized_nursery_pages(
+    current: usize,
+    nursery_pages: usize,
+    survived_pages: usize,
+    min_pages: usize,
+    max_pages: usize,
+) -> usize {
+    let survival_percent = survived_pages * 100 / nursery_pages;
+    let new = if survival_percent > NURSERY_GROW_SURVIVAL_PERCENT {
+        current << 1
+    } else if survival_percent < NURSERY_SHRINK_SURVIVAL_PERCENT {
+        current >> 1
+    } else {
+        current
+    };
+    new.max(min_pages).min(max_pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resized_nursery_pages() {
+        // High survival rate: grow
+        assert_eq!(resized_nursery_pages(1024, 1000, 500, 256, 4096), 2048);
+        // Low survival rate: shrink
+        assert_eq!(resized_nursery_pages(1024, 1000, 10, 256, 4096), 512);
+        // In between: keep the size
+        assert_eq!(resized_nursery_pages(1024, 1000, 100, 256, 4096), 1024);
+        assert_eq!(resized_nursery_pages(1024, 1000, 50, 256, 4096), 1024);
+        assert_eq!(resized_nursery_pages(1024, 1000, 200, 256, 4096), 1024);
+        // Bounded by the min and max nursery sizes
+        assert_eq!(resized_nursery_pages(4096, 4000, 4000, 256, 4096), 4096);
+        assert_eq!(resized_nursery_pages(3000, 3000, 1000, 256, 4096), 4096);
+        assert_eq!(resized_nursery_pages(256, 200, 0, 256, 4096), 256);
+        assert_eq!(resized_nursery_pages(300, 300, 0, 256, 4096), 256);
+    }
+}
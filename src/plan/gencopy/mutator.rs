@@ -4,7 +4,6 @@ use crate::plan::barriers::*;
 use crate::plan::mutator_context::Mutator;
 use crate::plan::mutator_context::MutatorConfig;
 use crate::plan::AllocationSemantics as AllocationType;
-use crate::policy::copyspace::CopySpace;
 use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
 use crate::util::alloc::BumpAllocator;
 use crate::util::OpaquePointer;
@@ -13,15 +12,14 @@ use crate::MMTK;
 use enum_map::enum_map;
 use enum_map::EnumMap;
 
-pub fn gencopy_mutator_prepare<VM: VMBinding>(
-    _mutator: &mut Mutator<GenCopy<VM>>,
-    _tls: OpaquePointer,
-) {
-    // Do nothing
+pub fn gencopy_mutator_prepare<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // Flush the remembered set, as the mutator may not be flushed when its stack is scanned
+    // (e.g. with `Scanning::SINGLE_THREAD_MUTATOR_SCANNING`)
+    mutator.barrier.flush();
 }
 
 pub fn gencopy_mutator_release<VM: VMBinding>(
-    mutator: &mut Mutator<GenCopy<VM>>,
+    mutator: &mut Mutator<VM>,
     _tls: OpaquePointer,
 ) {
     // rebind the allocation bump pointer to the nursery space
@@ -32,7 +30,9 @@ pub fn gencopy_mutator_release<VM: VMBinding>(
     }
     .downcast_mut::<BumpAllocator<VM>>()
     .unwrap();
-    bump_allocator.rebind(Some(&mutator.plan.nursery));
+    bump_allocator.rebind(Some(
+        &mutator.plan.downcast_ref::<GenCopy<VM>>().unwrap().nursery,
+    ));
 }
 
 lazy_static! {
@@ -47,26 +47,31 @@ lazy_static! {
 pub fn create_gencopy_mutator<VM: VMBinding>(
     mutator_tls: OpaquePointer,
     mmtk: &'static MMTK<VM>,
-) -> Mutator<GenCopy<VM>> {
+) -> Mutator<VM> {
+    let gencopy = mmtk.plan.downcast_ref::<GenCopy<VM>>().unwrap();
     let config = MutatorConfig {
         allocator_mapping: &*ALLOCATOR_MAPPING,
         space_mapping: box vec![
-            (AllocatorSelector::BumpPointer(0), &mmtk.plan.nursery),
-            (AllocatorSelector::BumpPointer(1), mmtk.plan.fromspace()),
-            (AllocatorSelector::BumpPointer(2), mmtk.plan.tospace()),
+            (AllocatorSelector::BumpPointer(0), &gencopy.nursery),
+            (AllocatorSelector::BumpPointer(1), gencopy.fromspace()),
+            (AllocatorSelector::BumpPointer(2), gencopy.tospace()),
+            (AllocatorSelector::LargeObject(0), gencopy.common.get_los()),
         ],
         prepare_func: &gencopy_mutator_prepare,
         release_func: &gencopy_mutator_release,
     };
 
     Mutator {
-        allocators: Allocators::<VM>::new(mutator_tls, &mmtk.plan, &config.space_mapping),
-        barrier: box FieldRememberingBarrier::<GenCopyNurseryProcessEdges<VM>, CopySpace<VM>>::new(
-            mmtk,
-            &mmtk.plan.nursery,
-        ),
+        allocators: Allocators::<VM>::new(mutator_tls, &*mmtk.plan, &config.space_mapping),
+        barrier: match &gencopy.card_table {
+            Some(card_table) => {
+                box CardMarkingBarrier::<GenCopyNurseryProcessEdges<VM>>::new(mmtk, card_table)
+            }
+            None => box ObjectRememberingBarrier::<GenCopyNurseryProcessEdges<VM>>::new(mmtk),
+        },
+        read_barrier: box NoReadBarrier,
         mutator_tls,
         config,
-        plan: &mmtk.plan,
+        plan: gencopy,
     }
 }
@@ -1,7 +1,17 @@
 use super::controller_collector_context::ControllerCollectorContext;
-use super::MutatorContext;
+use super::PlanConstraints;
 use crate::mmtk::MMTK;
+use crate::plan::concurrentmarksweep::ConcurrentMarkSweep;
+use crate::plan::gencopy::GenCopy;
+use crate::plan::immix::Immix;
+use crate::plan::markcompact::MarkCompact;
+use crate::plan::marksweep::MarkSweep;
+use crate::plan::nogc::NoGC;
+use crate::plan::rc::ReferenceCounting;
+use crate::plan::semispace::SemiSpace;
+use crate::plan::stickymarksweep::StickyMarkSweep;
 use crate::plan::transitive_closure::TransitiveClosure;
+use crate::plan::Mutator;
 use crate::policy::immortalspace::ImmortalSpace;
 use crate::policy::largeobjectspace::LargeObjectSpace;
 use crate::policy::space::Space;
@@ -14,15 +24,17 @@ use crate::util::conversions::bytes_to_pages;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::layout::map::Map;
+use crate::util::heap::HeapGrowthManager;
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
-use crate::util::options::{Options, UnsafeOptionsWrapper};
+use crate::util::options::{Options, PlanSelector, UnsafeOptionsWrapper};
 #[cfg(feature = "sanity")]
 use crate::util::sanity::sanity_checker::*;
 use crate::util::statistics::stats::Stats;
 use crate::util::OpaquePointer;
 use crate::util::{Address, ObjectReference};
 use crate::vm::*;
+use downcast_rs::Downcast;
 use enum_map::EnumMap;
 use std::cell::UnsafeCell;
 use std::marker::PhantomData;
@@ -97,22 +109,48 @@ impl<VM: VMBinding> CopyContext for NoCopy<VM> {
     }
 }
 
+/// Create the plan that is selected by the `plan` option.
+pub fn create_plan<VM: VMBinding>(
+    plan: PlanSelector,
+    vm_map: &'static VMMap,
+    mmapper: &'static Mmapper,
+    options: Arc<UnsafeOptionsWrapper>,
+    scheduler: &'static MMTkScheduler<VM>,
+) -> Box<dyn Plan<VM = VM>> {
+    match plan {
+        PlanSelector::NoGC => Box::new(NoGC::new(vm_map, mmapper, options, scheduler)),
+        PlanSelector::SemiSpace => Box::new(SemiSpace::new(vm_map, mmapper, options, scheduler)),
+        PlanSelector::GenCopy => Box::new(GenCopy::new(vm_map, mmapper, options, scheduler)),
+        PlanSelector::MarkSweep => Box::new(MarkSweep::new(vm_map, mmapper, options, scheduler)),
+        PlanSelector::Immix => Box::new(Immix::new(vm_map, mmapper, options, scheduler)),
+        PlanSelector::MarkCompact => {
+            Box::new(MarkCompact::new(vm_map, mmapper, options, scheduler))
+        }
+        PlanSelector::ConcurrentMarkSweep => Box::new(ConcurrentMarkSweep::new(
+            vm_map, mmapper, options, scheduler,
+        )),
+        PlanSelector::StickyMarkSweep => {
+            Box::new(StickyMarkSweep::new(vm_map, mmapper, options, scheduler))
+        }
+        PlanSelector::ReferenceCounting => {
+            Box::new(ReferenceCounting::new(vm_map, mmapper, options, scheduler))
+        }
+    }
+}
+
 /// A plan describes the global core functionality for all memory management schemes.
 /// All global MMTk plans should implement this trait.
 ///
 /// The global instance defines and manages static resources
 /// (such as memory and virtual memory resources).
-pub trait Plan: Sized + 'static + Sync + Send {
+///
+/// The plan is chosen at run time, and MMTk refers to it as a `dyn Plan`. Code that
+/// knows the concrete plan type can use `downcast_ref()` to get it.
+pub trait Plan: 'static + Sync + Send + Downcast {
     type VM: VMBinding;
-    type Mutator: MutatorContext<Self::VM>;
-    type CopyContext: CopyContext;
 
-    fn new(
-        vm_map: &'static VMMap,
-        mmapper: &'static Mmapper,
-        options: Arc<UnsafeOptionsWrapper>,
-        scheduler: &'static MMTkScheduler<Self::VM>,
-    ) -> Self;
+    fn constraints(&self) -> &'static PlanConstraints;
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr;
     fn base(&self) -> &BasePlan<Self::VM>;
     fn schedule_collection(&'static self, _scheduler: &MMTkScheduler<Self::VM>);
     #[cfg(feature = "sanity")]
@@ -154,7 +192,7 @@ pub trait Plan: Sized + 'static + Sync + Send {
         &'static self,
         tls: OpaquePointer,
         mmtk: &'static MMTK<Self::VM>,
-    ) -> Box<Self::Mutator>;
+    ) -> Box<Mutator<Self::VM>>;
 
     fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector>;
 
@@ -162,6 +200,13 @@ pub trait Plan: Sized + 'static + Sync + Send {
         false
     }
 
+    /// Only process the references and the finalizable objects that were registered since the
+    /// last GC. This is sound if the referents that survived the last GC cannot die in this GC,
+    /// as in nursery GCs.
+    fn scan_new_references_only(&self) -> bool {
+        self.in_nursery()
+    }
+
     #[cfg(feature = "sanity")]
     fn enter_sanity(&self) {
         self.base().inside_sanity.store(true, Ordering::Relaxed)
@@ -201,18 +246,17 @@ pub trait Plan: Sized + 'static + Sync + Send {
             return true;
         }
 
-        // FIXME
-        /*if self.concurrent_collection_required() {
+        if self.concurrent_collection_required() {
             // FIXME
             /*if space == self.common().meta_data_space {
                 self.log_poll(space, "Triggering async concurrent collection");
-                Self::trigger_internal_collection_request();
+                self.base().trigger_internal_collection_request();
                 return false;
             } else {*/
             self.log_poll(space, "Triggering concurrent collection");
-            Self::trigger_internal_collection_request();
+            self.base().trigger_internal_collection_request();
             return true;
-        }*/
+        }
 
         false
     }
@@ -229,10 +273,7 @@ pub trait Plan: Sized + 'static + Sync + Send {
      * @param space TODO
      * @return <code>true</code> if a collection is requested by the plan.
      */
-    fn collection_required(&self, space_full: bool, _space: &dyn Space<Self::VM>) -> bool
-    where
-        Self: Sized,
-    {
+    fn collection_required(&self, space_full: bool, _space: &dyn Space<Self::VM>) -> bool {
         let stress_force_gc = self.stress_test_gc_required();
         debug!(
             "self.get_pages_reserved()={}, self.get_total_pages()={}",
@@ -244,6 +285,11 @@ pub trait Plan: Sized + 'static + Sync + Send {
         space_full || stress_force_gc || heap_full
     }
 
+    /// Return true if a concurrent collection should start. This is only used by concurrent plans.
+    fn concurrent_collection_required(&self) -> bool {
+        false
+    }
+
     fn get_pages_reserved(&self) -> usize {
         self.get_pages_used() + self.get_collection_reserve()
     }
@@ -262,6 +308,12 @@ pub trait Plan: Sized + 'static + Sync + Send {
 
     fn get_pages_used(&self) -> usize;
 
+    /// Call `f` for each object in the spaces of this plan (see `Space::enumerate_objects()`).
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference));
+
+    /// Call `f` for each space of this plan.
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<Self::VM>));
+
     fn is_emergency_collection(&self) -> bool {
         self.base().emergency_collection.load(Ordering::Relaxed)
     }
@@ -299,10 +351,24 @@ pub trait Plan: Sized + 'static + Sync + Send {
         }
     }
 
+    /// Adjust the heap size after a GC. The heap size is only considered after full heap GCs,
+    /// and it only changes if the heap size is variable.
+    fn adjust_heap_size(&self) {
+        if self.in_nursery() {
+            return;
+        }
+        self.base()
+            .heap_growth
+            .consider_heap_size(&self.base().heap, self.get_pages_reserved());
+    }
+
     fn reset_collection_trigger(&self) {
         self.base()
             .user_triggered_collection
-            .store(false, Ordering::Relaxed)
+            .store(false, Ordering::Relaxed);
+        self.base()
+            .internal_triggered_collection
+            .store(false, Ordering::Relaxed);
     }
 
     fn modify_check(&self, object: ObjectReference) {
@@ -315,6 +381,8 @@ pub trait Plan: Sized + 'static + Sync + Send {
     }
 }
 
+impl_downcast!(Plan assoc VM);
+
 #[derive(PartialEq)]
 pub enum GcStatus {
     NotInGC,
@@ -333,6 +401,9 @@ pub struct BasePlan<VM: VMBinding> {
     pub stacks_prepared: AtomicBool,
     pub emergency_collection: AtomicBool,
     pub user_triggered_collection: AtomicBool,
+    // Is the collection requested by MMTk itself, rather than by a failed allocation or the user?
+    // Concurrent plans use this to start a concurrent collection.
+    pub internal_triggered_collection: AtomicBool,
     // Has an allocation succeeded since the emergency collection?
     pub allocation_success: AtomicBool,
     // Maximum number of failed attempts by a single thread
@@ -347,6 +418,7 @@ pub struct BasePlan<VM: VMBinding> {
     pub vm_map: &'static VMMap,
     pub options: Arc<UnsafeOptionsWrapper>,
     pub heap: HeapMeta,
+    pub heap_growth: HeapGrowthManager,
     #[cfg(feature = "base_spaces")]
     pub unsync: UnsafeCell<BaseUnsync<VM>>,
     #[cfg(feature = "sanity")]
@@ -372,6 +444,7 @@ pub fn create_vm_space<VM: VMBinding>(
     mmapper: &'static Mmapper,
     heap: &mut HeapMeta,
     boot_segment_bytes: usize,
+    constraints: &'static PlanConstraints,
 ) -> ImmortalSpace<VM> {
     //    let boot_segment_bytes = BOOT_IMAGE_END - BOOT_IMAGE_DATA_START;
     debug_assert!(boot_segment_bytes > 0);
@@ -387,16 +460,19 @@ pub fn create_vm_space<VM: VMBinding>(
         vm_map,
         mmapper,
         heap,
+        constraints,
     )
 }
 
 impl<VM: VMBinding> BasePlan<VM> {
-    #[allow(unused_mut)] // 'heap' only needs to be mutable for certain features
+    // 'heap' only needs to be mutable, and 'constraints' is only used, for certain features
+    #[allow(unused_mut, unused_variables)]
     pub fn new(
         vm_map: &'static VMMap,
         mmapper: &'static Mmapper,
         options: Arc<UnsafeOptionsWrapper>,
         mut heap: HeapMeta,
+        constraints: &'static PlanConstraints,
     ) -> BasePlan<VM> {
         BasePlan {
             #[cfg(feature = "base_spaces")]
@@ -409,6 +485,7 @@ impl<VM: VMBinding> BasePlan<VM> {
                     vm_map,
                     mmapper,
                     &mut heap,
+                    constraints,
                 ),
                 #[cfg(feature = "ro_space")]
                 ro_space: ImmortalSpace::new(
@@ -418,9 +495,16 @@ impl<VM: VMBinding> BasePlan<VM> {
                     vm_map,
                     mmapper,
                     &mut heap,
+                    constraints,
                 ),
                 #[cfg(feature = "vm_space")]
-                vm_space: create_vm_space(vm_map, mmapper, &mut heap, options.vm_space_size),
+                vm_space: create_vm_space(
+                    vm_map,
+                    mmapper,
+                    &mut heap,
+                    options.vm_space_size,
+                    constraints,
+                ),
             }),
             initialized: AtomicBool::new(false),
             gc_status: Mutex::new(GcStatus::NotInGC),
@@ -428,6 +512,7 @@ impl<VM: VMBinding> BasePlan<VM> {
             stacks_prepared: AtomicBool::new(false),
             emergency_collection: AtomicBool::new(false),
             user_triggered_collection: AtomicBool::new(false),
+            internal_triggered_collection: AtomicBool::new(false),
             allocation_success: AtomicBool::new(false),
             max_collection_attempts: AtomicUsize::new(0),
             cur_collection_attempts: AtomicUsize::new(0),
@@ -436,6 +521,7 @@ impl<VM: VMBinding> BasePlan<VM> {
             stats: Stats::new(),
             mmapper,
             heap,
+            heap_growth: HeapGrowthManager::new(),
             vm_map,
             options,
             #[cfg(feature = "sanity")]
@@ -456,9 +542,24 @@ impl<VM: VMBinding> BasePlan<VM> {
             self.heap.get_discontig_start(),
             self.heap.get_discontig_end(),
         );
-        self.heap
-            .total_pages
-            .store(bytes_to_pages(heap_size), Ordering::Relaxed);
+        let (min_heap, max_heap) = if self.options.variable_size_heap {
+            // The options are validated to be in order if both are set. A bound that is not set
+            // is the heap size, within the other bound.
+            match (self.options.min_heap, self.options.max_heap) {
+                (0, 0) => (heap_size, heap_size),
+                (0, max_heap) => (heap_size.min(max_heap), max_heap),
+                (min_heap, 0) => (min_heap, heap_size.max(min_heap)),
+                (min_heap, max_heap) => (min_heap, max_heap),
+            }
+        } else {
+            (heap_size, heap_size)
+        };
+        self.heap.total_pages.store(
+            bytes_to_pages(heap_size.max(min_heap).min(max_heap)),
+            Ordering::Relaxed,
+        );
+        self.heap_growth
+            .init(bytes_to_pages(min_heap), bytes_to_pages(max_heap));
         self.control_collector_context.init(scheduler);
 
         #[cfg(feature = "base_spaces")]
@@ -504,6 +605,34 @@ impl<VM: VMBinding> BasePlan<VM> {
         0
     }
 
+    /// Call `f` for each object in the base spaces. The objects in the VM space are not
+    /// allocated by MMTk, so they are not included.
+    #[cfg(feature = "base_spaces")]
+    pub fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        let unsync = unsafe { &*self.unsync.get() };
+        #[cfg(feature = "code_space")]
+        unsync.code_space.enumerate_objects(f);
+        #[cfg(feature = "ro_space")]
+        unsync.ro_space.enumerate_objects(f);
+    }
+
+    #[cfg(not(feature = "base_spaces"))]
+    pub fn enumerate_objects(&self, _f: &mut dyn FnMut(ObjectReference)) {}
+
+    #[cfg(feature = "base_spaces")]
+    pub fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        let unsync = unsafe { &*self.unsync.get() };
+        #[cfg(feature = "code_space")]
+        f(&unsync.code_space);
+        #[cfg(feature = "ro_space")]
+        f(&unsync.ro_space);
+        #[cfg(feature = "vm_space")]
+        f(&unsync.vm_space);
+    }
+
+    #[cfg(not(feature = "base_spaces"))]
+    pub fn for_each_space(&self, _f: &mut dyn FnMut(&dyn Space<VM>)) {}
+
     pub fn trace_object<T: TransitiveClosure>(
         &self,
         _trace: &mut T,
@@ -544,11 +673,11 @@ impl<VM: VMBinding> BasePlan<VM> {
         #[cfg(feature = "base_spaces")]
         let unsync = unsafe { &mut *self.unsync.get() };
         #[cfg(feature = "code_space")]
-        unsync.code_space.prepare();
+        unsync.code_space.prepare(_primary);
         #[cfg(feature = "ro_space")]
-        unsync.ro_space.prepare();
+        unsync.ro_space.prepare(_primary);
         #[cfg(feature = "vm_space")]
-        unsync.vm_space.prepare();
+        unsync.vm_space.prepare(_primary);
     }
 
     pub fn release(&self, _tls: OpaquePointer, _primary: bool) {
@@ -589,9 +718,11 @@ impl<VM: VMBinding> BasePlan<VM> {
             self.stacks_prepared.store(false, Ordering::SeqCst);
             // FIXME stats
             self.stats.start_gc();
+            self.heap_growth.gc_start();
         }
         *gc_status = s;
         if *gc_status == GcStatus::NotInGC {
+            self.heap_growth.gc_end();
             // FIXME stats
             if self.stats.get_gathering_stats() {
                 self.stats.end_gc();
@@ -626,9 +757,16 @@ impl<VM: VMBinding> BasePlan<VM> {
         self.max_collection_attempts.load(Ordering::Relaxed)
     }
 
-    fn is_internal_triggered_collection(&self) -> bool {
-        // FIXME
-        false
+    pub fn is_internal_triggered_collection(&self) -> bool {
+        self.internal_triggered_collection.load(Ordering::Relaxed)
+    }
+
+    /// Request a collection that is not triggered by a failed allocation or the user,
+    /// e.g. to start or finish a concurrent collection.
+    pub fn trigger_internal_collection_request(&self) {
+        self.internal_triggered_collection
+            .store(true, Ordering::Relaxed);
+        self.control_collector_context.request();
     }
 
     fn last_collection_was_exhaustive(&self) -> bool {
@@ -657,6 +795,7 @@ impl<VM: VMBinding> CommonPlan<VM> {
         mmapper: &'static Mmapper,
         options: Arc<UnsafeOptionsWrapper>,
         mut heap: HeapMeta,
+        constraints: &'static PlanConstraints,
     ) -> CommonPlan<VM> {
         CommonPlan {
             unsync: UnsafeCell::new(CommonUnsync {
@@ -667,6 +806,7 @@ impl<VM: VMBinding> CommonPlan<VM> {
                     vm_map,
                     mmapper,
                     &mut heap,
+                    constraints,
                 ),
                 los: LargeObjectSpace::new(
                     "los",
@@ -675,9 +815,10 @@ impl<VM: VMBinding> CommonPlan<VM> {
                     vm_map,
                     mmapper,
                     &mut heap,
+                    constraints,
                 ),
             }),
-            base: BasePlan::new(vm_map, mmapper, options, heap),
+            base: BasePlan::new(vm_map, mmapper, options, heap, constraints),
         }
     }
 
@@ -698,6 +839,20 @@ impl<VM: VMBinding> CommonPlan<VM> {
         unsync.immortal.reserved_pages() + unsync.los.reserved_pages() + self.base.get_pages_used()
     }
 
+    pub fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        let unsync = unsafe { &*self.unsync.get() };
+        unsync.immortal.enumerate_objects(f);
+        unsync.los.enumerate_objects(f);
+        self.base.enumerate_objects(f);
+    }
+
+    pub fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        let unsync = unsafe { &*self.unsync.get() };
+        f(&unsync.immortal);
+        f(&unsync.los);
+        self.base.for_each_space(f);
+    }
+
     pub fn trace_object<T: TransitiveClosure>(
         &self,
         trace: &mut T,
@@ -718,7 +873,7 @@ impl<VM: VMBinding> CommonPlan<VM> {
 
     pub fn prepare(&self, tls: OpaquePointer, primary: bool) {
         let unsync = unsafe { &mut *self.unsync.get() };
-        unsync.immortal.prepare();
+        unsync.immortal.prepare(primary);
         unsync.los.prepare(primary);
         self.base.prepare(tls, primary)
     }
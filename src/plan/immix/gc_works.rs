@@ -0,0 +1,115 @@
+use super::global::Immix;
+use crate::plan::CopyContext;
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::util::alloc::{Allocator, ImmixAllocator};
+use crate::util::{Address, ObjectReference, OpaquePointer};
+use crate::vm::{ObjectModel, VMBinding};
+use crate::MMTK;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+pub struct ImmixCopyContext<VM: VMBinding> {
+    plan: &'static Immix<VM>,
+    immix: ImmixAllocator<VM>,
+}
+
+impl<VM: VMBinding> ImmixCopyContext<VM> {
+    /// Make sure that the object can be copied without going beyond the defrag headroom.
+    /// Returns false if the headroom is used up, and the object needs to stay in place.
+    #[inline]
+    pub fn reserve_copy(&mut self, object: ObjectReference) -> bool {
+        self.immix.reserve_copy(
+            VM::VMObjectModel::get_size_when_copied(object),
+            VM::VMObjectModel::get_align_when_copied(object),
+            VM::VMObjectModel::get_align_offset_when_copied(object),
+        )
+    }
+}
+
+impl<VM: VMBinding> CopyContext for ImmixCopyContext<VM> {
+    type VM = VM;
+    fn new(mmtk: &'static MMTK<Self::VM>) -> Self {
+        Self {
+            plan: mmtk.plan.downcast_ref::<Immix<VM>>().unwrap(),
+            immix: ImmixAllocator::new(OpaquePointer::UNINITIALIZED, None, &*mmtk.plan, true),
+        }
+    }
+    fn init(&mut self, tls: OpaquePointer) {
+        self.immix.tls = tls;
+    }
+    fn prepare(&mut self) {
+        self.immix.rebind(Some(&self.plan.immix_space));
+    }
+    fn release(&mut self) {
+        self.immix.reset();
+    }
+    #[inline(always)]
+    fn alloc_copy(
+        &mut self,
+        _original: ObjectReference,
+        bytes: usize,
+        align: usize,
+        offset: isize,
+        _semantics: crate::AllocationSemantics,
+    ) -> Address {
+        self.immix.alloc(bytes, align, offset)
+    }
+    #[inline(always)]
+    fn post_copy(
+        &mut self,
+        obj: ObjectReference,
+        _tib: Address,
+        _bytes: usize,
+        _semantics: crate::AllocationSemantics,
+    ) {
+        self.plan.immix_space.post_copy(obj);
+    }
+}
+
+#[derive(Default)]
+pub struct ImmixProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<ImmixProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for ImmixProcessEdges<VM> {
+    type VM = VM;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<Immix<VM>>();
+        if plan.immix_space.in_space(object) {
+            return plan.immix_space.trace_object(
+                self,
+                object,
+                super::global::ALLOC_IMMIX,
+                self.worker().copy_context::<ImmixCopyContext<VM>>(),
+            );
+        }
+        plan.common.trace_object(self, object)
+    }
+}
+
+impl<VM: VMBinding> Deref for ImmixProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for ImmixProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
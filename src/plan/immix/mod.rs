@@ -0,0 +1,6 @@
+mod gc_works;
+mod global;
+mod mutator;
+
+pub use self::gc_works::ImmixCopyContext;
+pub use self::global::Immix;
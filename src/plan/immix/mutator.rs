@@ -0,0 +1,63 @@
+use super::Immix;
+use crate::plan::barriers::{NoBarrier, NoReadBarrier};
+use crate::plan::mutator_context::Mutator;
+use crate::plan::mutator_context::MutatorConfig;
+use crate::plan::AllocationSemantics as AllocationType;
+use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
+use crate::util::alloc::ImmixAllocator;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+use enum_map::enum_map;
+use enum_map::EnumMap;
+
+pub fn immix_mutator_prepare<VM: VMBinding>(_mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // Do nothing
+}
+
+pub fn immix_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // The line marks change in each GC, so drop the holes and blocks held by the allocator
+    let immix_allocator = unsafe {
+        mutator
+            .allocators
+            .get_allocator_mut(mutator.config.allocator_mapping[AllocationType::Default])
+    }
+    .downcast_mut::<ImmixAllocator<VM>>()
+    .unwrap();
+    immix_allocator.reset();
+}
+
+lazy_static! {
+    pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationType, AllocatorSelector> = enum_map! {
+        AllocationType::Default => AllocatorSelector::Immix(0),
+        AllocationType::Immortal | AllocationType::Code | AllocationType::ReadOnly => AllocatorSelector::BumpPointer(0),
+        AllocationType::Los => AllocatorSelector::LargeObject(0),
+    };
+}
+
+pub fn create_immix_mutator<VM: VMBinding>(
+    mutator_tls: OpaquePointer,
+    plan: &'static Immix<VM>,
+) -> Mutator<VM> {
+    let config = MutatorConfig {
+        allocator_mapping: &*ALLOCATOR_MAPPING,
+        space_mapping: box vec![
+            (AllocatorSelector::Immix(0), &plan.immix_space),
+            (
+                AllocatorSelector::BumpPointer(0),
+                plan.common.get_immortal(),
+            ),
+            (AllocatorSelector::LargeObject(0), plan.common.get_los()),
+        ],
+        prepare_func: &immix_mutator_prepare,
+        release_func: &immix_mutator_release,
+    };
+
+    Mutator {
+        allocators: Allocators::<VM>::new(mutator_tls, plan, &config.space_mapping),
+        barrier: box NoBarrier,
+        read_barrier: box NoReadBarrier,
+        mutator_tls,
+        config,
+        plan,
+    }
+}
@@ -0,0 +1,176 @@
+use super::global::MarkCompact;
+use crate::policy::markcompactspace::MarkCompactSpace;
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::{Address, ObjectReference};
+use crate::vm::*;
+use crate::MMTK;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/// Compute the new addresses of the live objects in the mark-compact space.
+pub struct CalculateForwardingAddress<VM: VMBinding> {
+    mc_space: &'static MarkCompactSpace<VM>,
+}
+
+unsafe impl<VM: VMBinding> Sync for CalculateForwardingAddress<VM> {}
+
+impl<VM: VMBinding> CalculateForwardingAddress<VM> {
+    pub fn new(mc_space: &'static MarkCompactSpace<VM>) -> Self {
+        Self { mc_space }
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for CalculateForwardingAddress<VM> {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
+        trace!("CalculateForwardingAddress");
+        self.mc_space.calculate_forwarding_addresses();
+    }
+}
+
+/// Scan the roots again with the `ProcessEdgesWork` that updates references. The objects
+/// reachable from the roots are scanned again in the transitive closure, and their references
+/// are updated to the new addresses.
+#[derive(Default)]
+pub struct UpdateReferences<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> UpdateReferences<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for UpdateReferences<E> {
+    fn do_work(&mut self, _worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("UpdateReferences");
+        // The roots recorded for a heap dump in the marking scan are stale once the objects move
+        mmtk.heap_dumper.clear_roots();
+        if <E::VM as VMBinding>::VMScanning::SINGLE_THREAD_MUTATOR_SCANNING {
+            mmtk.scheduler
+                .second_roots_stage
+                .add(ScanStackRoots::<E>::new());
+        } else {
+            for mutator in <E::VM as VMBinding>::VMActivePlan::mutators() {
+                mmtk.scheduler
+                    .second_roots_stage
+                    .add(ScanStackRoot::<E>(mutator));
+            }
+        }
+        mmtk.scheduler
+            .second_roots_stage
+            .add(ScanVMSpecificRoots::<E>::new());
+    }
+}
+
+/// Move the live objects in the mark-compact space to their new addresses.
+pub struct Compact<VM: VMBinding> {
+    mc_space: &'static MarkCompactSpace<VM>,
+}
+
+unsafe impl<VM: VMBinding> Sync for Compact<VM> {}
+
+impl<VM: VMBinding> Compact<VM> {
+    pub fn new(mc_space: &'static MarkCompactSpace<VM>) -> Self {
+        Self { mc_space }
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for Compact<VM> {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, _mmtk: &'static MMTK<VM>) {
+        trace!("Compact");
+        self.mc_space.compact();
+    }
+}
+
+/// The `ProcessEdgesWork` that marks the live objects.
+#[derive(Default)]
+pub struct MarkingProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<MarkingProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for MarkingProcessEdges<VM> {
+    type VM = VM;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<MarkCompact<VM>>();
+        if plan.mc_space.in_space(object) {
+            return plan.mc_space.trace_mark_object(self, object);
+        }
+        plan.common.trace_object(self, object)
+    }
+}
+
+impl<VM: VMBinding> Deref for MarkingProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for MarkingProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// The `ProcessEdgesWork` that updates references to the new addresses of the objects.
+/// It visits every live object once, including the objects in the other spaces.
+#[derive(Default)]
+pub struct ForwardingProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<ForwardingProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for ForwardingProcessEdges<VM> {
+    type VM = VM;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<MarkCompact<VM>>();
+        if plan.mc_space.in_space(object) {
+            return plan.mc_space.trace_forward_object(self, object);
+        }
+        // The objects in the other spaces do not move, but they may refer to objects that move.
+        if MarkCompactSpace::<VM>::test_and_set_visited(object) {
+            plan.record_visited_object(object);
+            self.process_node(object);
+        }
+        object
+    }
+}
+
+impl<VM: VMBinding> Deref for ForwardingProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for ForwardingProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
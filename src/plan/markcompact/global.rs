@@ -0,0 +1,186 @@
+use super::gc_works::{
+    CalculateForwardingAddress, Compact, ForwardingProcessEdges, MarkingProcessEdges,
+    UpdateReferences,
+};
+use crate::mmtk::MMTK;
+use crate::plan::global::BasePlan;
+use crate::plan::global::CommonPlan;
+use crate::plan::global::GcStatus;
+use crate::plan::global::NoCopy;
+use crate::plan::markcompact::mutator::create_mc_mutator;
+use crate::plan::markcompact::mutator::ALLOCATOR_MAPPING;
+use crate::plan::mutator_context::Mutator;
+use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
+use crate::plan::Plan;
+use crate::plan::PlanConstraints;
+use crate::policy::markcompactspace::{MarkCompactSpace, MAX_MARKCOMPACT_OBJECT_SIZE};
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::*;
+use crate::util::alloc::allocators::AllocatorSelector;
+use crate::util::heap::layout::heap_layout::Mmapper;
+use crate::util::heap::layout::heap_layout::VMMap;
+use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::VMRequest;
+use crate::util::options::UnsafeOptionsWrapper;
+#[cfg(feature = "sanity")]
+use crate::util::sanity::sanity_checker::*;
+use crate::util::{ObjectReference, OpaquePointer};
+use crate::vm::VMBinding;
+use std::sync::{Arc, Mutex};
+
+use enum_map::EnumMap;
+
+pub const MC_CONSTRAINTS: PlanConstraints = PlanConstraints {
+    moves_objects: true,
+    gc_header_bits: 4,
+    gc_header_words: 0,
+    num_specialized_scans: 2,
+    max_non_los_default_alloc_bytes: MAX_MARKCOMPACT_OBJECT_SIZE,
+    needs_forward_after_liveness: true,
+    ..PlanConstraints::default()
+};
+
+pub struct MarkCompact<VM: VMBinding> {
+    pub mc_space: MarkCompactSpace<VM>,
+    pub common: CommonPlan<VM>,
+    /// The objects outside the mark-compact space that are visited when updating references.
+    /// Their visited bits are cleared at the end of the GC.
+    visited_objects: Mutex<Vec<ObjectReference>>,
+}
+
+unsafe impl<VM: VMBinding> Sync for MarkCompact<VM> {}
+
+impl<VM: VMBinding> Plan for MarkCompact<VM> {
+    type VM = VM;
+
+    fn constraints(&self) -> &'static PlanConstraints {
+        &MC_CONSTRAINTS
+    }
+
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box NoCopy::new(mmtk)
+    }
+
+    fn gc_init(
+        &mut self,
+        heap_size: usize,
+        vm_map: &'static VMMap,
+        scheduler: &Arc<MMTkScheduler<VM>>,
+    ) {
+        self.common.gc_init(heap_size, vm_map, scheduler);
+
+        self.mc_space.init(&vm_map);
+    }
+
+    fn schedule_collection(&'static self, scheduler: &MMTkScheduler<VM>) {
+        self.base().set_collection_kind();
+        self.base().set_gc_status(GcStatus::GcPrepare);
+        // Stop & scan mutators (mutator scanning can happen before STW)
+        scheduler
+            .unconstrained_works
+            .add(StopMutators::<MarkingProcessEdges<VM>>::new());
+        // Process weak references
+        scheduler.schedule_ref_processing::<MarkingProcessEdges<VM>>();
+        // Prepare global/collectors/mutators
+        scheduler.prepare_stage.add(Prepare::new(self));
+        // Compute the new addresses of the live objects
+        scheduler
+            .calculate_forwarding_stage
+            .add(CalculateForwardingAddress::new(&self.mc_space));
+        // Update the roots and the references to the new addresses
+        scheduler
+            .second_roots_stage
+            .add(UpdateReferences::<ForwardingProcessEdges<VM>>::new());
+        scheduler.schedule_ref_forwarding::<ForwardingProcessEdges<VM>>();
+        // Move the objects
+        scheduler.compact_stage.add(Compact::new(&self.mc_space));
+        // Release global/collectors/mutators
+        scheduler.release_stage.add(Release::new(self));
+        // Resume mutators
+        #[cfg(feature = "sanity")]
+        scheduler.final_stage.add(ScheduleSanityGC);
+        scheduler.set_finalizer(Some(EndOfGC));
+    }
+
+    fn bind_mutator(
+        &'static self,
+        tls: OpaquePointer,
+        _mmtk: &'static MMTK<Self::VM>,
+    ) -> Box<Mutator<VM>> {
+        Box::new(create_mc_mutator(tls, self))
+    }
+
+    fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector> {
+        &*ALLOCATOR_MAPPING
+    }
+
+    fn prepare(&self, tls: OpaquePointer) {
+        self.common.prepare(tls, true);
+        self.mc_space.prepare();
+    }
+
+    fn release(&self, tls: OpaquePointer) {
+        self.common.release(tls, true);
+        self.mc_space.release();
+        for object in self.visited_objects.lock().unwrap().drain(..) {
+            MarkCompactSpace::<VM>::clear_visited(object);
+        }
+    }
+
+    fn get_collection_reserve(&self) -> usize {
+        0
+    }
+
+    fn get_pages_used(&self) -> usize {
+        self.mc_space.reserved_pages() + self.common.get_pages_used()
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        self.mc_space.enumerate_objects(f);
+        self.common.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.mc_space);
+        self.common.for_each_space(f);
+    }
+
+    fn base(&self) -> &BasePlan<VM> {
+        &self.common.base
+    }
+
+    fn common(&self) -> &CommonPlan<VM> {
+        &self.common
+    }
+}
+
+impl<VM: VMBinding> MarkCompact<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        _scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+
+        MarkCompact {
+            mc_space: MarkCompactSpace::new(
+                "mc",
+                VMRequest::discontiguous(),
+                vm_map,
+                mmapper,
+                &mut heap,
+            ),
+            common: CommonPlan::new(vm_map, mmapper, options, heap, &MC_CONSTRAINTS),
+            visited_objects: Mutex::new(vec![]),
+        }
+    }
+
+    /// Record an object outside the mark-compact space that is visited when updating references.
+    pub fn record_visited_object(&self, object: ObjectReference) {
+        self.visited_objects.lock().unwrap().push(object);
+    }
+}
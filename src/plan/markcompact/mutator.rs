@@ -0,0 +1,69 @@
+use super::MarkCompact;
+use crate::plan::barriers::{NoBarrier, NoReadBarrier};
+use crate::plan::mutator_context::Mutator;
+use crate::plan::mutator_context::MutatorConfig;
+use crate::plan::AllocationSemantics as AllocationType;
+use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
+use crate::util::alloc::MarkCompactAllocator;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+use enum_map::enum_map;
+use enum_map::EnumMap;
+
+fn get_markcompact_allocator<VM: VMBinding>(
+    mutator: &mut Mutator<VM>,
+) -> &mut MarkCompactAllocator<VM> {
+    unsafe {
+        mutator
+            .allocators
+            .get_allocator_mut(mutator.config.allocator_mapping[AllocationType::Default])
+    }
+    .downcast_mut::<MarkCompactAllocator<VM>>()
+    .unwrap()
+}
+
+pub fn mc_mutator_prepare<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // The space walks the blocks in the GC, so it needs to know where the data in the current block ends
+    get_markcompact_allocator(mutator).retire_block();
+}
+
+pub fn mc_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // The objects in the current block may have been moved, and the block may have been released
+    get_markcompact_allocator(mutator).reset();
+}
+
+lazy_static! {
+    pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationType, AllocatorSelector> = enum_map! {
+        AllocationType::Default => AllocatorSelector::MarkCompact(0),
+        AllocationType::Immortal | AllocationType::Code | AllocationType::ReadOnly => AllocatorSelector::BumpPointer(0),
+        AllocationType::Los => AllocatorSelector::LargeObject(0),
+    };
+}
+
+pub fn create_mc_mutator<VM: VMBinding>(
+    mutator_tls: OpaquePointer,
+    plan: &'static MarkCompact<VM>,
+) -> Mutator<VM> {
+    let config = MutatorConfig {
+        allocator_mapping: &*ALLOCATOR_MAPPING,
+        space_mapping: box vec![
+            (AllocatorSelector::MarkCompact(0), &plan.mc_space),
+            (
+                AllocatorSelector::BumpPointer(0),
+                plan.common.get_immortal(),
+            ),
+            (AllocatorSelector::LargeObject(0), plan.common.get_los()),
+        ],
+        prepare_func: &mc_mutator_prepare,
+        release_func: &mc_mutator_release,
+    };
+
+    Mutator {
+        allocators: Allocators::<VM>::new(mutator_tls, plan, &config.space_mapping),
+        barrier: box NoBarrier,
+        read_barrier: box NoReadBarrier,
+        mutator_tls,
+        config,
+        plan,
+    }
+}
@@ -0,0 +1,49 @@
+use super::global::MarkSweep;
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::util::{Address, ObjectReference};
+use crate::vm::VMBinding;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+#[derive(Default)]
+pub struct MSProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<MSProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for MSProcessEdges<VM> {
+    type VM = VM;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<MarkSweep<VM>>();
+        if plan.ms_space.in_space(object) {
+            return plan.ms_space.trace_object(self, object);
+        }
+        plan.common.trace_object(self, object)
+    }
+}
+
+impl<VM: VMBinding> Deref for MSProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for MSProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
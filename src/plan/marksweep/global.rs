@@ -0,0 +1,161 @@
+use super::gc_works::MSProcessEdges;
+use crate::mmtk::MMTK;
+use crate::plan::global::BasePlan;
+use crate::plan::global::CommonPlan;
+use crate::plan::global::GcStatus;
+use crate::plan::global::NoCopy;
+use crate::plan::marksweep::mutator::create_ms_mutator;
+use crate::plan::marksweep::mutator::ALLOCATOR_MAPPING;
+use crate::plan::mutator_context::Mutator;
+use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
+use crate::plan::Plan;
+use crate::plan::PlanConstraints;
+use crate::policy::marksweepspace::{MarkSweepSpace, MAX_CELL_SIZE};
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::*;
+use crate::util::alloc::allocators::AllocatorSelector;
+use crate::util::heap::layout::heap_layout::Mmapper;
+use crate::util::heap::layout::heap_layout::VMMap;
+use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::VMRequest;
+use crate::util::options::UnsafeOptionsWrapper;
+#[cfg(feature = "sanity")]
+use crate::util::sanity::sanity_checker::*;
+use crate::util::{ObjectReference, OpaquePointer};
+use crate::vm::VMBinding;
+use std::sync::Arc;
+
+use enum_map::EnumMap;
+
+pub const MS_CONSTRAINTS: PlanConstraints = PlanConstraints {
+    moves_objects: false,
+    gc_header_bits: 2,
+    gc_header_words: 0,
+    num_specialized_scans: 1,
+    max_non_los_default_alloc_bytes: MAX_CELL_SIZE,
+    ..PlanConstraints::default()
+};
+
+pub struct MarkSweep<VM: VMBinding> {
+    pub ms_space: MarkSweepSpace<VM>,
+    pub common: CommonPlan<VM>,
+}
+
+unsafe impl<VM: VMBinding> Sync for MarkSweep<VM> {}
+
+impl<VM: VMBinding> Plan for MarkSweep<VM> {
+    type VM = VM;
+
+    fn constraints(&self) -> &'static PlanConstraints {
+        &MS_CONSTRAINTS
+    }
+
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box NoCopy::new(mmtk)
+    }
+
+    fn gc_init(
+        &mut self,
+        heap_size: usize,
+        vm_map: &'static VMMap,
+        scheduler: &Arc<MMTkScheduler<VM>>,
+    ) {
+        self.common.gc_init(heap_size, vm_map, scheduler);
+
+        self.ms_space.init(&vm_map);
+    }
+
+    fn schedule_collection(&'static self, scheduler: &MMTkScheduler<VM>) {
+        self.base().set_collection_kind();
+        self.base().set_gc_status(GcStatus::GcPrepare);
+        // Stop & scan mutators (mutator scanning can happen before STW)
+        scheduler
+            .unconstrained_works
+            .add(StopMutators::<MSProcessEdges<VM>>::new());
+        // Process weak references
+        scheduler.schedule_ref_processing::<MSProcessEdges<VM>>();
+        // Prepare global/collectors/mutators
+        scheduler.prepare_stage.add(Prepare::new(self));
+        // Release global/collectors/mutators
+        scheduler.release_stage.add(Release::new(self));
+        // Resume mutators
+        #[cfg(feature = "sanity")]
+        scheduler.final_stage.add(ScheduleSanityGC);
+        scheduler.set_finalizer(Some(EndOfGC));
+    }
+
+    fn bind_mutator(
+        &'static self,
+        tls: OpaquePointer,
+        _mmtk: &'static MMTK<Self::VM>,
+    ) -> Box<Mutator<VM>> {
+        Box::new(create_ms_mutator(tls, self))
+    }
+
+    fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector> {
+        &*ALLOCATOR_MAPPING
+    }
+
+    fn prepare(&self, tls: OpaquePointer) {
+        self.common.prepare(tls, true);
+        self.ms_space.prepare();
+    }
+
+    fn release(&self, tls: OpaquePointer) {
+        self.common.release(tls, true);
+        // sweep the mark-sweep space
+        self.ms_space.release(false);
+    }
+
+    fn get_collection_reserve(&self) -> usize {
+        0
+    }
+
+    fn get_pages_used(&self) -> usize {
+        self.ms_space.reserved_pages() + self.common.get_pages_used()
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        self.ms_space.enumerate_objects(f);
+        self.common.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.ms_space);
+        self.common.for_each_space(f);
+    }
+
+    fn base(&self) -> &BasePlan<VM> {
+        &self.common.base
+    }
+
+    fn common(&self) -> &CommonPlan<VM> {
+        &self.common
+    }
+}
+
+impl<VM: VMBinding> MarkSweep<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        _scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+
+        MarkSweep {
+            ms_space: MarkSweepSpace::new(
+                "ms",
+                false,
+                VMRequest::discontiguous(),
+                vm_map,
+                mmapper,
+                &mut heap,
+            ),
+            common: CommonPlan::new(vm_map, mmapper, options, heap, &MS_CONSTRAINTS),
+        }
+    }
+}
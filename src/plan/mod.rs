@@ -6,28 +6,22 @@ pub mod plan_constraints;
 mod trace;
 pub mod tracelocal;
 pub mod transitive_closure;
+pub use self::barriers::{ForwardingReadBarrier, ReadBarrier, ReadBarrierSelector};
 pub use self::global::AllocationSemantics;
 pub use self::global::CopyContext;
 pub use self::global::Plan;
 pub use self::mutator_context::Mutator;
 pub use self::mutator_context::MutatorContext;
+pub use self::plan_constraints::PlanConstraints;
 pub use self::tracelocal::TraceLocal;
 pub use self::transitive_closure::TransitiveClosure;
 
-#[cfg(feature = "nogc")]
+pub mod concurrentmarksweep;
+pub mod gencopy;
+pub mod immix;
+pub mod markcompact;
+pub mod marksweep;
 pub mod nogc;
-#[cfg(feature = "nogc")]
-pub use self::nogc as selected_plan;
-
-#[cfg(feature = "semispace")]
+pub mod rc;
 pub mod semispace;
-#[cfg(feature = "semispace")]
-pub use self::semispace as selected_plan;
-
-#[cfg(feature = "gencopy")]
-pub mod gencopy;
-#[cfg(feature = "gencopy")]
-pub use self::gencopy as selected_plan;
-
-pub use self::selected_plan::SelectedConstraints;
-pub use self::selected_plan::SelectedPlan;
+pub mod stickymarksweep;
@@ -1,8 +1,9 @@
-use crate::plan::barriers::{Barrier, WriteTarget};
+use crate::plan::barriers::{Barrier, ReadBarrier, WriteTarget};
 use crate::plan::global::Plan;
 use crate::plan::AllocationSemantics as AllocationType;
 use crate::policy::space::Space;
 use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
+use crate::util::alloc_bit;
 use crate::util::OpaquePointer;
 use crate::util::{Address, ObjectReference};
 use crate::vm::VMBinding;
@@ -14,20 +15,20 @@ type SpaceMapping<VM> = Vec<(AllocatorSelector, &'static dyn Space<VM>)>;
 // This struct is part of the Mutator struct.
 // We are trying to make it fixed-sized so that VM bindings can easily define a Mutator type to have the exact same layout as our Mutator struct.
 #[repr(C)]
-pub struct MutatorConfig<P: Plan> {
+pub struct MutatorConfig<VM: VMBinding> {
     // Mapping between allocation semantics and allocator selector
     pub allocator_mapping: &'static EnumMap<AllocationType, AllocatorSelector>,
     // Mapping between allocator selector and spaces. Each pair represents a mapping.
     // Put this behind a box, so it is a pointer-sized field.
     #[allow(clippy::box_vec)]
-    pub space_mapping: Box<SpaceMapping<P::VM>>,
+    pub space_mapping: Box<SpaceMapping<VM>>,
     // Plan-specific code for mutator prepare/release
-    pub prepare_func: &'static dyn Fn(&mut Mutator<P>, OpaquePointer),
-    pub release_func: &'static dyn Fn(&mut Mutator<P>, OpaquePointer),
+    pub prepare_func: &'static dyn Fn(&mut Mutator<VM>, OpaquePointer),
+    pub release_func: &'static dyn Fn(&mut Mutator<VM>, OpaquePointer),
 }
 
-unsafe impl<P: Plan> Send for MutatorConfig<P> {}
-unsafe impl<P: Plan> Sync for MutatorConfig<P> {}
+unsafe impl<VM: VMBinding> Send for MutatorConfig<VM> {}
+unsafe impl<VM: VMBinding> Sync for MutatorConfig<VM> {}
 
 /// A mutator is a per-thread data structure that manages allocations and barriers. It is usually highly coupled with the language VM.
 /// It is recommended for MMTk users 1) to have a mutator struct of the same layout in the thread local storage that can be accessed efficiently,
@@ -37,16 +38,36 @@ unsafe impl<P: Plan> Sync for MutatorConfig<P> {}
 // Currently Mutator is fixed sized, and we should try keep this invariant:
 // - Allocators are fixed-length arrays of allocators.
 // - MutatorConfig only has pointers/refs (including fat pointers), and is fixed sized.
+// The type of the mutator does not depend on the plan in use: the plan-specific parts are
+// the allocators that are initialized, the barrier, and the functions in `MutatorConfig`.
 #[repr(C)]
-pub struct Mutator<P: Plan> {
-    pub allocators: Allocators<P::VM>,
+pub struct Mutator<VM: VMBinding> {
+    pub allocators: Allocators<VM>,
     pub barrier: Box<dyn Barrier>,
+    pub read_barrier: Box<dyn ReadBarrier>,
     pub mutator_tls: OpaquePointer,
-    pub plan: &'static P,
-    pub config: MutatorConfig<P>,
+    pub plan: &'static dyn Plan<VM = VM>,
+    pub config: MutatorConfig<VM>,
 }
 
-impl<P: Plan<Mutator = Self>> MutatorContext<P::VM> for Mutator<P> {
+impl<VM: VMBinding> Mutator<VM> {
+    /// The default allocator of a plan may not be able to allocate large objects (see
+    /// `PlanConstraints::max_non_los_default_alloc_bytes`). Such objects are allocated in the
+    /// large object space instead.
+    #[inline(always)]
+    fn allocation_type(&self, bytes: usize, allocator: AllocationType) -> AllocationType {
+        match allocator {
+            AllocationType::Default
+                if bytes > self.plan.constraints().max_non_los_default_alloc_bytes =>
+            {
+                AllocationType::Los
+            }
+            _ => allocator,
+        }
+    }
+}
+
+impl<VM: VMBinding> MutatorContext<VM> for Mutator<VM> {
     fn prepare(&mut self, tls: OpaquePointer) {
         (*self.config.prepare_func)(self, tls)
     }
@@ -62,6 +83,7 @@ impl<P: Plan<Mutator = Self>> MutatorContext<P::VM> for Mutator<P> {
         offset: isize,
         allocator: AllocationType,
     ) -> Address {
+        let allocator = self.allocation_type(size, allocator);
         unsafe {
             self.allocators
                 .get_allocator_mut(self.config.allocator_mapping[allocator])
@@ -75,16 +97,20 @@ impl<P: Plan<Mutator = Self>> MutatorContext<P::VM> for Mutator<P> {
         &mut self,
         refer: ObjectReference,
         _type_refer: ObjectReference,
-        _bytes: usize,
+        bytes: usize,
         allocator: AllocationType,
     ) {
+        let allocator = self.allocation_type(bytes, allocator);
         unsafe {
             self.allocators
                 .get_allocator_mut(self.config.allocator_mapping[allocator])
         }
         .get_space()
         .unwrap()
-        .initialize_header(refer, true)
+        .initialize_header(refer, true);
+        if self.plan.options().alloc_bit {
+            alloc_bit::set_alloc_bit(refer);
+        }
     }
 
     fn get_tls(&self) -> OpaquePointer {
@@ -94,6 +120,10 @@ impl<P: Plan<Mutator = Self>> MutatorContext<P::VM> for Mutator<P> {
     fn barrier(&mut self) -> &mut dyn Barrier {
         &mut *self.barrier
     }
+
+    fn read_barrier(&mut self) -> &mut dyn ReadBarrier {
+        &mut *self.read_barrier
+    }
 }
 
 /// Each GC plan should provide their implementation of a MutatorContext. *Note that this trait is no longer needed as we removed
@@ -126,6 +156,7 @@ pub trait MutatorContext<VM: VMBinding>: Send + Sync + 'static {
     }
     fn get_tls(&self) -> OpaquePointer;
     fn barrier(&mut self) -> &mut dyn Barrier;
+    fn read_barrier(&mut self) -> &mut dyn ReadBarrier;
 
     fn record_modified_node(&mut self, obj: ObjectReference) {
         self.barrier().post_write_barrier(WriteTarget::Object(obj));
@@ -133,4 +164,43 @@ pub trait MutatorContext<VM: VMBinding>: Send + Sync + 'static {
     fn record_modified_edge(&mut self, slot: Address) {
         self.barrier().post_write_barrier(WriteTarget::Slot(slot));
     }
+    /// Call this after the reference slots from `start` to `end` are written in bulk.
+    fn record_modified_range(&mut self, start: Address, end: Address) {
+        self.barrier().record_modified_range(start, end);
+    }
+    /// Call this before `len` references are copied to the slots starting at `dst`, which are
+    /// fields of `dst_object`, if the plan's `PlanConstraints::needs_pre_write_barrier` is set.
+    fn record_pre_array_copy(&mut self, dst_object: ObjectReference, dst: Address, len: usize) {
+        self.barrier().pre_array_copy(dst_object, dst, len);
+    }
+    /// Call this after `len` references are copied from the slots starting at `src` to the slots
+    /// starting at `dst`, instead of calling `record_modified_edge` for each slot.
+    fn record_array_copy(&mut self, src: Address, dst: Address, len: usize) {
+        self.barrier().post_array_copy(src, dst, len);
+    }
+    /// Call this before the reference in `slot`, a field of `src`, is overwritten by `new_value`,
+    /// if the plan's `PlanConstraints::needs_pre_write_barrier` is set. `old_value` is the
+    /// reference in the slot before the write.
+    fn record_pre_write(
+        &mut self,
+        src: ObjectReference,
+        slot: Address,
+        old_value: ObjectReference,
+        new_value: ObjectReference,
+    ) {
+        self.barrier()
+            .pre_write_barrier(src, slot, old_value, new_value);
+    }
+    /// Call this before a reference field of `obj` is written, if the binding does not know
+    /// which field is written.
+    fn record_pre_write_node(&mut self, obj: ObjectReference) {
+        let null = unsafe { Address::ZERO.to_object_reference() };
+        self.barrier()
+            .pre_write_barrier(obj, Address::ZERO, null, null);
+    }
+    /// Call this after `value` is loaded from `slot`, if the plan's `PlanConstraints::read_barrier`
+    /// is `ReadBarrierSelector::LoadReference`. Returns the reference that the mutator should use.
+    fn load_reference(&mut self, slot: Address, value: ObjectReference) -> ObjectReference {
+        self.read_barrier().load_reference(slot, value)
+    }
 }
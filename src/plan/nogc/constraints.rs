@@ -1,4 +0,0 @@
-pub use crate::plan::plan_constraints::*;
-
-pub const GC_HEADER_BITS: usize = 0;
-pub const GC_HEADER_WORDS: usize = 0;
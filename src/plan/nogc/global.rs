@@ -4,9 +4,11 @@ use crate::plan::mutator_context::Mutator;
 use crate::plan::nogc::mutator::create_nogc_mutator;
 use crate::plan::nogc::mutator::ALLOCATOR_MAPPING;
 use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
 use crate::plan::Plan;
+use crate::plan::PlanConstraints;
 use crate::policy::space::Space;
-use crate::scheduler::MMTkScheduler;
+use crate::scheduler::{GCWorkerLocalPtr, MMTkScheduler};
 use crate::util::alloc::allocators::AllocatorSelector;
 use crate::util::heap::layout::heap_layout::Mmapper;
 use crate::util::heap::layout::heap_layout::VMMap;
@@ -15,7 +17,7 @@ use crate::util::heap::HeapMeta;
 #[allow(unused_imports)]
 use crate::util::heap::VMRequest;
 use crate::util::options::UnsafeOptionsWrapper;
-use crate::util::OpaquePointer;
+use crate::util::{ObjectReference, OpaquePointer};
 use crate::vm::VMBinding;
 use enum_map::EnumMap;
 use std::sync::Arc;
@@ -25,7 +27,7 @@ use crate::policy::immortalspace::ImmortalSpace as NoGCImmortalSpace;
 #[cfg(feature = "nogc_lock_free")]
 use crate::policy::lockfreeimmortalspace::LockFreeImmortalSpace as NoGCImmortalSpace;
 
-pub type SelectedPlan<VM> = NoGC<VM>;
+pub const NOGC_CONSTRAINTS: PlanConstraints = PlanConstraints::default();
 
 pub struct NoGC<VM: VMBinding> {
     pub base: BasePlan<VM>,
@@ -36,37 +38,13 @@ unsafe impl<VM: VMBinding> Sync for NoGC<VM> {}
 
 impl<VM: VMBinding> Plan for NoGC<VM> {
     type VM = VM;
-    type Mutator = Mutator<Self>;
-    type CopyContext = NoCopy<VM>;
 
-    fn new(
-        vm_map: &'static VMMap,
-        mmapper: &'static Mmapper,
-        options: Arc<UnsafeOptionsWrapper>,
-        _scheduler: &'static MMTkScheduler<Self::VM>,
-    ) -> Self {
-        #[cfg(not(feature = "nogc_lock_free"))]
-        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
-        #[cfg(feature = "nogc_lock_free")]
-        let heap = HeapMeta::new(HEAP_START, HEAP_END);
-
-        #[cfg(feature = "nogc_lock_free")]
-        let nogc_space =
-            NoGCImmortalSpace::new("nogc_space", cfg!(not(feature = "nogc_no_zeroing")));
-        #[cfg(not(feature = "nogc_lock_free"))]
-        let nogc_space = NoGCImmortalSpace::new(
-            "nogc_space",
-            true,
-            VMRequest::discontiguous(),
-            vm_map,
-            mmapper,
-            &mut heap,
-        );
+    fn constraints(&self) -> &'static PlanConstraints {
+        &NOGC_CONSTRAINTS
+    }
 
-        NoGC {
-            nogc_space,
-            base: BasePlan::new(vm_map, mmapper, options, heap),
-        }
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box NoCopy::new(mmtk)
     }
 
     fn gc_init(
@@ -89,7 +67,7 @@ impl<VM: VMBinding> Plan for NoGC<VM> {
         &'static self,
         tls: OpaquePointer,
         _mmtk: &'static MMTK<Self::VM>,
-    ) -> Box<Mutator<Self>> {
+    ) -> Box<Mutator<VM>> {
         Box::new(create_nogc_mutator(tls, self))
     }
 
@@ -113,7 +91,50 @@ impl<VM: VMBinding> Plan for NoGC<VM> {
         self.nogc_space.reserved_pages()
     }
 
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        self.nogc_space.enumerate_objects(f);
+        self.base.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.nogc_space);
+        self.base.for_each_space(f);
+    }
+
     fn handle_user_collection_request(&self, _tls: OpaquePointer, _force: bool) {
         println!("Warning: User attempted a collection request, but it is not supported in NoGC. The request is ignored.");
     }
 }
+
+impl<VM: VMBinding> NoGC<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        _scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        #[cfg(not(feature = "nogc_lock_free"))]
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+        #[cfg(feature = "nogc_lock_free")]
+        let heap = HeapMeta::new(HEAP_START, HEAP_END);
+
+        #[cfg(feature = "nogc_lock_free")]
+        let nogc_space =
+            NoGCImmortalSpace::new("nogc_space", cfg!(not(feature = "nogc_no_zeroing")));
+        #[cfg(not(feature = "nogc_lock_free"))]
+        let nogc_space = NoGCImmortalSpace::new(
+            "nogc_space",
+            true,
+            VMRequest::discontiguous(),
+            vm_map,
+            mmapper,
+            &mut heap,
+            &NOGC_CONSTRAINTS,
+        );
+
+        NoGC {
+            nogc_space,
+            base: BasePlan::new(vm_map, mmapper, options, heap, &NOGC_CONSTRAINTS),
+        }
+    }
+}
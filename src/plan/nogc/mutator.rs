@@ -1,4 +1,4 @@
-use crate::plan::barriers::NoBarrier;
+use crate::plan::barriers::{NoBarrier, NoReadBarrier};
 use crate::plan::mutator_context::Mutator;
 use crate::plan::mutator_context::MutatorConfig;
 use crate::plan::nogc::NoGC;
@@ -15,14 +15,14 @@ lazy_static! {
     };
 }
 
-pub fn nogc_mutator_noop<VM: VMBinding>(_mutator: &mut Mutator<NoGC<VM>>, _tls: OpaquePointer) {
+pub fn nogc_mutator_noop<VM: VMBinding>(_mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
     unreachable!();
 }
 
 pub fn create_nogc_mutator<VM: VMBinding>(
     mutator_tls: OpaquePointer,
     plan: &'static NoGC<VM>,
-) -> Mutator<NoGC<VM>> {
+) -> Mutator<VM> {
     let config = MutatorConfig {
         allocator_mapping: &*ALLOCATOR_MAPPING,
         space_mapping: box vec![(AllocatorSelector::BumpPointer(0), &plan.nogc_space)],
@@ -33,6 +33,7 @@ pub fn create_nogc_mutator<VM: VMBinding>(
     Mutator {
         allocators: Allocators::<VM>::new(mutator_tls, plan, &config.space_mapping),
         barrier: box NoBarrier,
+        read_barrier: box NoReadBarrier,
         mutator_tls,
         config,
         plan,
@@ -1,20 +1,49 @@
-// Specific plans may 'pub use' the constraints here, and overwrites some. In that case,
-// the constants that get overwritten are unused.
-#![allow(unused)]
-
+use crate::plan::barriers::ReadBarrierSelector;
 use crate::util::constants::*;
 
-pub const MOVES_OBJECTS: bool = false;
-pub const NUM_SPECIALIZED_SCANS: usize = 0;
-// The two consts below need to be consistent
-pub const NEEDS_LOG_BIT_IN_HEADER: bool = false;
-pub const NEEDS_LOG_BIT_IN_HEADER_NUM: usize = 0;
-
-pub const NEEDS_LINEAR_SCAN: bool = SUPPORT_CARD_SCANNING || LAZY_SWEEP;
-pub const NEEDS_CONCURRENT_WORKERS: bool = false;
-
-pub const GENERATE_GC_TRACE: bool = false;
-
-pub const MAX_NON_LOS_COPY_BYTES: usize = MAX_INT;
+/// This struct defines plan-specific constraints.
+/// Most of the constraints are constants. Each plan should declare a constant of this struct,
+/// and use the constant wherever possible. However, for plan-neutral implementations,
+/// these constraints are not constant.
+pub struct PlanConstraints {
+    pub moves_objects: bool,
+    pub gc_header_bits: usize,
+    pub gc_header_words: usize,
+    pub num_specialized_scans: usize,
+    /// Does the plan need the unlogged bit in the object header (see `util::header_byte`)?
+    pub needs_log_bit_in_header: bool,
+    pub needs_linear_scan: bool,
+    pub needs_concurrent_workers: bool,
+    /// Does the plan need the pre-write barrier (`MutatorContext::record_pre_write`)? If not, the
+    /// barriers ignore it, and bindings can skip the call.
+    pub needs_pre_write_barrier: bool,
+    /// The read barrier that the binding needs to emit for reference loads.
+    pub read_barrier: ReadBarrierSelector,
+    pub generate_gc_trace: bool,
+    pub max_non_los_copy_bytes: usize,
+    /// The largest object (in bytes, including alignment padding) that can be allocated with
+    /// `AllocationSemantics::Default`. `Mutator::alloc()` allocates larger objects in the large object
+    /// space, and bindings with their own allocation fast path should use `AllocationSemantics::Los` for them.
+    pub max_non_los_default_alloc_bytes: usize,
+    pub needs_forward_after_liveness: bool,
+}
 
-pub const NEEDS_FORWARD_AFTER_LIVENESS: bool = false;
+impl PlanConstraints {
+    pub const fn default() -> Self {
+        PlanConstraints {
+            moves_objects: false,
+            gc_header_bits: 0,
+            gc_header_words: 0,
+            num_specialized_scans: 0,
+            needs_log_bit_in_header: false,
+            needs_linear_scan: SUPPORT_CARD_SCANNING || LAZY_SWEEP,
+            needs_concurrent_workers: false,
+            needs_pre_write_barrier: false,
+            read_barrier: ReadBarrierSelector::NoBarrier,
+            generate_gc_trace: false,
+            max_non_los_copy_bytes: MAX_INT,
+            max_non_los_default_alloc_bytes: MAX_INT,
+            needs_forward_after_liveness: false,
+        }
+    }
+}
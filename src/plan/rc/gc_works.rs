@@ -0,0 +1,330 @@
+use super::global::ReferenceCounting;
+use crate::plan::barriers::ObjectReferents;
+use crate::policy::marksweepspace::MarkSweepSpace;
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::header_byte;
+use crate::util::{Address, ObjectReference};
+use crate::vm::{Scanning, VMBinding};
+use crate::MMTK;
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+
+/// Applies the increments in RC pauses. An object that gets its first increment is a new object:
+/// it becomes an old object (it gets the unlogged bit), and it is scanned so that its referents
+/// get increments as well. The root referents get increments that are taken back in the next GC.
+#[derive(Default)]
+pub struct RCIncProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<RCIncProcessEdges<VM>>,
+    roots: bool,
+    root_objects: Vec<ObjectReference>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for RCIncProcessEdges<VM> {
+    type VM = VM;
+    const OVERWRITE_REFERENCE: bool = false;
+    fn new(edges: Vec<Address>, roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            roots,
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<ReferenceCounting<VM>>();
+        // Only the objects in the RC space are counted
+        if !plan.rc_space.in_space(object) {
+            return object;
+        }
+        if self.roots {
+            self.root_objects.push(object);
+        }
+        if plan.rc_table.inc(MarkSweepSpace::<VM>::cell_of(object)) == 0 {
+            header_byte::mark_as_unlogged::<VM>(object);
+            self.process_node(object);
+        }
+        object
+    }
+    #[inline]
+    fn process_edges(&mut self) {
+        for i in 0..self.edges.len() {
+            self.process_edge(self.edges[i])
+        }
+        self.flush_root_objects();
+    }
+}
+
+impl<VM: VMBinding> RCIncProcessEdges<VM> {
+    /// Record the root referents, so that they get decrements in the next GC.
+    fn flush_root_objects(&mut self) {
+        if !self.root_objects.is_empty() {
+            let root_objects = mem::take(&mut self.root_objects);
+            self.plan_as::<ReferenceCounting<VM>>()
+                .root_buffers
+                .lock()
+                .unwrap()
+                .push(root_objects);
+        }
+    }
+}
+
+impl<VM: VMBinding> Deref for RCIncProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for RCIncProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// Marks objects in backup traces, and counts the references again: every reference from a
+/// marked object or a root is an increment. The root referents are also recorded, so they get
+/// decrements in the next GC, as in RC pauses.
+#[derive(Default)]
+pub struct RCTraceProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<RCTraceProcessEdges<VM>>,
+    roots: bool,
+    root_objects: Vec<ObjectReference>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for RCTraceProcessEdges<VM> {
+    type VM = VM;
+    const OVERWRITE_REFERENCE: bool = false;
+    fn new(edges: Vec<Address>, roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            roots,
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<ReferenceCounting<VM>>();
+        if !plan.rc_space.in_space(object) {
+            return plan.common.trace_object(self, object);
+        }
+        if self.roots {
+            self.root_objects.push(object);
+        }
+        plan.rc_table.inc(MarkSweepSpace::<VM>::cell_of(object));
+        // The new objects that survive become old objects
+        if !header_byte::is_unlogged::<VM>(object) {
+            header_byte::mark_as_unlogged::<VM>(object);
+        }
+        plan.rc_space.trace_object(self, object)
+    }
+    #[inline]
+    fn process_edges(&mut self) {
+        for i in 0..self.edges.len() {
+            self.process_edge(self.edges[i])
+        }
+        if !self.root_objects.is_empty() {
+            let root_objects = mem::take(&mut self.root_objects);
+            self.plan_as::<ReferenceCounting<VM>>()
+                .root_buffers
+                .lock()
+                .unwrap()
+                .push(root_objects);
+        }
+    }
+}
+
+impl<VM: VMBinding> Deref for RCTraceProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for RCTraceProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// Processes the references and the finalizable objects in RC pauses, after the decrements. An
+/// object in the RC space is live if it is referenced from the heap or the roots. Nothing is
+/// traced: the referents of the live references already have their counts. As the soft referents
+/// are not retained, the soft references are cleared like the weak references.
+#[derive(Default)]
+pub struct RCRefProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<RCRefProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for RCRefProcessEdges<VM> {
+    type VM = VM;
+    const OVERWRITE_REFERENCE: bool = false;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        object
+    }
+    /// The other spaces are not collected in RC pauses.
+    #[inline]
+    fn is_object_live(&self, object: ObjectReference) -> bool {
+        let plan = self.plan_as::<ReferenceCounting<VM>>();
+        !plan.rc_space.in_space(object)
+            || plan.rc_table.count(MarkSweepSpace::<VM>::cell_of(object)) != 0
+    }
+}
+
+impl<VM: VMBinding> Deref for RCRefProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for RCRefProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+/// Gives the reference objects and the finalizable objects increments as root referents in RC
+/// pauses, so that they are not freed before their references are processed. They are reclaimed
+/// by backup traces.
+#[derive(Default)]
+pub struct RCReferenceRoots<VM: VMBinding>(PhantomData<VM>);
+
+impl<VM: VMBinding> RCReferenceRoots<VM> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for RCReferenceRoots<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let mut w = RCIncProcessEdges::<VM>::new(vec![], true);
+        w.mmtk = Some(mmtk);
+        w.set_worker(worker);
+        mmtk.reference_processors.for_each_reference(&mut |object| {
+            w.trace_object(object);
+        });
+        mmtk.finalizable_processor
+            .lock()
+            .unwrap()
+            .for_each_object(&mut |object| {
+                w.trace_object(object);
+            });
+        w.flush_root_objects();
+        if !w.nodes.is_empty() {
+            w.flush();
+        }
+    }
+}
+
+/// Schedules the decrements logged since the last GC in RC pauses. It runs after all the
+/// increments, and before the references are processed.
+#[derive(Default)]
+pub struct RCScheduleDecrements<VM: VMBinding>(PhantomData<VM>);
+
+impl<VM: VMBinding> RCScheduleDecrements<VM> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for RCScheduleDecrements<VM> {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let plan = mmtk.plan.downcast_ref::<ReferenceCounting<VM>>().unwrap();
+        let dec_buffers = mem::take(&mut *plan.dec_buffers.lock().unwrap());
+        for decs in dec_buffers {
+            mmtk.scheduler
+                .soft_retain_stage
+                .add(RCDecrements::<VM>::new(decs));
+        }
+    }
+}
+
+/// Applies decrements, after all the increments of the GC and before the references are
+/// processed. An object whose count drops to zero is freed at once, and its referents get
+/// decrements.
+pub struct RCDecrements<VM: VMBinding> {
+    objects: Vec<ObjectReference>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> RCDecrements<VM> {
+    pub fn new(objects: Vec<ObjectReference>) -> Self {
+        Self {
+            objects,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for RCDecrements<VM> {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let plan = mmtk.plan.downcast_ref::<ReferenceCounting<VM>>().unwrap();
+        let mut decs = vec![];
+        for &object in self.objects.iter() {
+            if !plan.rc_space.in_space(object) {
+                continue;
+            }
+            if plan.rc_table.dec(MarkSweepSpace::<VM>::cell_of(object)) != 1 {
+                continue;
+            }
+            let mut referents = ObjectReferents(vec![]);
+            <VM as VMBinding>::VMScanning::scan_object(&mut referents, object, worker.tls);
+            decs.extend(referents.0.into_iter().filter(|object| !object.is_null()));
+            plan.rc_space.free(object);
+            if decs.len() >= RCIncProcessEdges::<VM>::CAPACITY {
+                mmtk.scheduler
+                    .soft_retain_stage
+                    .add(RCDecrements::<VM>::new(mem::take(&mut decs)));
+            }
+        }
+        if !decs.is_empty() {
+            mmtk.scheduler
+                .soft_retain_stage
+                .add(RCDecrements::<VM>::new(decs));
+        }
+    }
+}
+
+/// Sweeps the blocks that the mutators allocated in since the last GC, after all the decrements.
+/// The new objects that did not get any increment are dead.
+#[derive(Default)]
+pub struct RCSweepNewObjects<VM: VMBinding>(PhantomData<VM>);
+
+impl<VM: VMBinding> RCSweepNewObjects<VM> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<VM: VMBinding> GCWork<VM> for RCSweepNewObjects<VM> {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let plan = mmtk.plan.downcast_ref::<ReferenceCounting<VM>>().unwrap();
+        plan.rc_space
+            .sweep_acquired_blocks(|cell| plan.rc_table.count(cell) != 0);
+    }
+}
@@ -0,0 +1,272 @@
+use super::gc_works::{
+    RCIncProcessEdges, RCRefProcessEdges, RCReferenceRoots, RCScheduleDecrements,
+    RCSweepNewObjects, RCTraceProcessEdges,
+};
+use crate::mmtk::MMTK;
+use crate::plan::global::BasePlan;
+use crate::plan::global::CommonPlan;
+use crate::plan::global::GcStatus;
+use crate::plan::global::NoCopy;
+use crate::plan::mutator_context::Mutator;
+use crate::plan::rc::mutator::create_rc_mutator;
+use crate::plan::rc::mutator::ALLOCATOR_MAPPING;
+use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
+use crate::plan::Plan;
+use crate::plan::PlanConstraints;
+use crate::policy::marksweepspace::{MarkSweepSpace, BYTES_IN_BLOCK, MAX_CELL_SIZE};
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::*;
+use crate::util::alloc::allocators::AllocatorSelector;
+use crate::util::conversions::bytes_to_pages_up;
+use crate::util::heap::layout::heap_layout::Mmapper;
+use crate::util::heap::layout::heap_layout::VMMap;
+use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::VMRequest;
+use crate::util::options::UnsafeOptionsWrapper;
+use crate::util::rc_table::{RCTable, RC_COUNT_SPEC};
+#[cfg(feature = "sanity")]
+use crate::util::sanity::sanity_checker::*;
+use crate::util::{ObjectReference, OpaquePointer};
+use crate::vm::VMBinding;
+use std::mem;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use enum_map::EnumMap;
+
+pub const RC_CONSTRAINTS: PlanConstraints = PlanConstraints {
+    moves_objects: false,
+    gc_header_bits: 2,
+    gc_header_words: 0,
+    num_specialized_scans: 1,
+    needs_log_bit_in_header: true,
+    needs_pre_write_barrier: true,
+    max_non_los_default_alloc_bytes: MAX_CELL_SIZE,
+    ..PlanConstraints::default()
+};
+
+/// A deferred reference counting plan with a backup trace.
+///
+/// The objects in the RC space have reference counts in side metadata. The counts only include
+/// the references from the heap: the `RCBarrier` logs the modified objects, and the increments
+/// and decrements are applied in RC pauses. The root referents get increments in each RC pause,
+/// which are taken back in the next GC. New objects are not counted until they get their first
+/// increment in an RC pause, and the new objects that get none are swept at the end of the pause.
+/// An object whose count drops to zero is freed at once.
+///
+/// Reference counting cannot reclaim cycles of garbage, or objects with stuck counts. When the heap
+/// is (almost) full, or the user requests a collection, a backup trace marks the live objects,
+/// sweeps the heap, and counts the references of the live objects again.
+pub struct ReferenceCounting<VM: VMBinding> {
+    pub rc_space: MarkSweepSpace<VM>,
+    pub common: CommonPlan<VM>,
+    /// The reference counts of the objects in the RC space.
+    pub rc_table: RCTable,
+    /// Whether the current GC is a backup trace.
+    backup_trace: AtomicBool,
+    /// The decrements logged by the barriers since the last GC.
+    pub dec_buffers: Mutex<Vec<Vec<ObjectReference>>>,
+    /// The root referents of the current GC. They get decrements in the next GC.
+    pub root_buffers: Mutex<Vec<Vec<ObjectReference>>>,
+    /// The reserved pages after the last GC. An RC pause is triggered when the pages allocated
+    /// since then reach the maximal nursery size.
+    pages_after_last_gc: AtomicUsize,
+}
+
+unsafe impl<VM: VMBinding> Sync for ReferenceCounting<VM> {}
+
+impl<VM: VMBinding> Plan for ReferenceCounting<VM> {
+    type VM = VM;
+
+    fn constraints(&self) -> &'static PlanConstraints {
+        &RC_CONSTRAINTS
+    }
+
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box NoCopy::new(mmtk)
+    }
+
+    fn collection_required(&self, space_full: bool, _space: &dyn Space<Self::VM>) -> bool {
+        let nursery_full = self.get_pages_reserved()
+            >= self.pages_after_last_gc.load(Ordering::Relaxed)
+                + bytes_to_pages_up(self.options().max_nursery);
+        let heap_full = self.get_pages_reserved() > self.get_total_pages();
+        space_full || nursery_full || heap_full
+    }
+
+    fn gc_init(
+        &mut self,
+        heap_size: usize,
+        vm_map: &'static VMMap,
+        scheduler: &Arc<MMTkScheduler<VM>>,
+    ) {
+        self.common.gc_init(heap_size, vm_map, scheduler);
+
+        self.rc_space.init(&vm_map);
+    }
+
+    fn schedule_collection(&'static self, scheduler: &MMTkScheduler<VM>) {
+        self.base().set_collection_kind();
+        self.base().set_gc_status(GcStatus::GcPrepare);
+        // A user triggered GC is a backup trace. Consume the flag for the following GCs.
+        let user_triggered = self
+            .base()
+            .user_triggered_collection
+            .swap(false, Ordering::Relaxed);
+        let backup_trace = user_triggered || self.request_backup_trace();
+        self.backup_trace.store(backup_trace, Ordering::SeqCst);
+        if backup_trace {
+            // Stop & scan mutators (mutator scanning can happen before STW)
+            scheduler
+                .unconstrained_works
+                .add(StopMutators::<RCTraceProcessEdges<VM>>::new());
+            // Process weak references
+            scheduler.schedule_ref_processing::<RCTraceProcessEdges<VM>>();
+        } else {
+            // Stop & scan mutators (mutator scanning can happen before STW)
+            scheduler
+                .unconstrained_works
+                .add(StopMutators::<RCIncProcessEdges<VM>>::new());
+            // Keep the reference objects and the finalizable objects alive
+            scheduler.closure_stage.add(RCReferenceRoots::<VM>::new());
+            // Apply the decrements before the references are processed
+            scheduler
+                .soft_retain_stage
+                .add(RCScheduleDecrements::<VM>::new());
+            // Process weak references
+            scheduler.schedule_ref_processing::<RCRefProcessEdges<VM>>();
+            // Sweep the new objects that are not referenced, after the decrements
+            scheduler.final_stage.add(RCSweepNewObjects::<VM>::new());
+        }
+        // Prepare global/collectors/mutators
+        scheduler.prepare_stage.add(Prepare::new(self));
+        // Release global/collectors/mutators
+        scheduler.release_stage.add(Release::new(self));
+        // Resume mutators
+        #[cfg(feature = "sanity")]
+        scheduler.final_stage.add(ScheduleSanityGC);
+        scheduler.set_finalizer(Some(EndOfGC));
+    }
+
+    fn bind_mutator(
+        &'static self,
+        tls: OpaquePointer,
+        mmtk: &'static MMTK<Self::VM>,
+    ) -> Box<Mutator<VM>> {
+        Box::new(create_rc_mutator(tls, mmtk))
+    }
+
+    fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector> {
+        &*ALLOCATOR_MAPPING
+    }
+
+    fn prepare(&self, tls: OpaquePointer) {
+        let root_buffers = mem::take(&mut *self.root_buffers.lock().unwrap());
+        if self.backup_trace() {
+            self.common.prepare(tls, true);
+            self.rc_space.prepare();
+            // The trace counts the references again
+            self.rc_space
+                .for_each_block(|block| self.rc_table.clear_range(block, block + BYTES_IN_BLOCK));
+        } else {
+            // Take back the increments of the root referents of the last GC
+            self.dec_buffers.lock().unwrap().extend(root_buffers);
+        }
+    }
+
+    fn release(&self, tls: OpaquePointer) {
+        if self.backup_trace() {
+            self.common.release(tls, true);
+            // sweep the RC space. The counts are up to date, so the decrements are dropped.
+            self.rc_space.release(false);
+            self.dec_buffers.lock().unwrap().clear();
+        }
+        self.pages_after_last_gc
+            .store(self.get_pages_reserved(), Ordering::Relaxed);
+    }
+
+    fn get_collection_reserve(&self) -> usize {
+        0
+    }
+
+    fn get_pages_used(&self) -> usize {
+        self.rc_space.reserved_pages() + self.common.get_pages_used()
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        self.rc_space.enumerate_objects(f);
+        self.common.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.rc_space);
+        self.common.for_each_space(f);
+    }
+
+    fn base(&self) -> &BasePlan<VM> {
+        &self.common.base
+    }
+
+    fn common(&self) -> &CommonPlan<VM> {
+        &self.common
+    }
+
+    /// An RC pause only processes what changed since the last GC, like a nursery GC.
+    fn in_nursery(&self) -> bool {
+        !self.backup_trace()
+    }
+
+    /// Old referents can be freed by decrements in RC pauses.
+    fn scan_new_references_only(&self) -> bool {
+        false
+    }
+}
+
+impl<VM: VMBinding> ReferenceCounting<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        _scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+        let mut rc_space = MarkSweepSpace::new(
+            "rc",
+            false,
+            VMRequest::discontiguous(),
+            vm_map,
+            mmapper,
+            &mut heap,
+        );
+        // Only the RC space has reference counts
+        rc_space
+            .common_mut()
+            .side_metadata_specs
+            .push(RC_COUNT_SPEC);
+
+        ReferenceCounting {
+            rc_space,
+            common: CommonPlan::new(vm_map, mmapper, options, heap, &RC_CONSTRAINTS),
+            rc_table: RCTable::new(RC_COUNT_SPEC),
+            backup_trace: AtomicBool::new(false),
+            dec_buffers: Mutex::new(vec![]),
+            root_buffers: Mutex::new(vec![]),
+            pages_after_last_gc: AtomicUsize::new(0),
+        }
+    }
+
+    fn backup_trace(&self) -> bool {
+        self.backup_trace.load(Ordering::SeqCst)
+    }
+
+    /// A backup trace is required if this is an emergency GC, if the heap is full, or if there
+    /// is not enough room left in the heap for the minimal nursery.
+    fn request_backup_trace(&self) -> bool {
+        self.is_emergency_collection()
+            || self.get_total_pages() <= self.get_pages_reserved()
+            || self.get_pages_avail() < bytes_to_pages_up(self.options().min_nursery)
+    }
+}
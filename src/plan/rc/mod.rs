@@ -0,0 +1,5 @@
+mod gc_works;
+mod global;
+mod mutator;
+
+pub use self::global::ReferenceCounting;
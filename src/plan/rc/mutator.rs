@@ -0,0 +1,64 @@
+use super::gc_works::RCIncProcessEdges;
+use super::ReferenceCounting;
+use crate::plan::barriers::{NoReadBarrier, RCBarrier};
+use crate::plan::mutator_context::Mutator;
+use crate::plan::mutator_context::MutatorConfig;
+use crate::plan::AllocationSemantics as AllocationType;
+use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
+use crate::util::alloc::FreeListAllocator;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+use crate::MMTK;
+use enum_map::enum_map;
+use enum_map::EnumMap;
+
+pub fn rc_mutator_prepare<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // Flush the logged objects and the decrements, so they are processed in this GC
+    mutator.barrier.flush();
+}
+
+pub fn rc_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // The blocks the allocator took cells from are swept, so drop the cells held by the allocator
+    let free_list_allocator = unsafe {
+        mutator
+            .allocators
+            .get_allocator_mut(mutator.config.allocator_mapping[AllocationType::Default])
+    }
+    .downcast_mut::<FreeListAllocator<VM>>()
+    .unwrap();
+    free_list_allocator.reset();
+}
+
+lazy_static! {
+    pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationType, AllocatorSelector> = enum_map! {
+        AllocationType::Default => AllocatorSelector::FreeList(0),
+        AllocationType::Immortal | AllocationType::Code | AllocationType::ReadOnly => AllocatorSelector::BumpPointer(0),
+        AllocationType::Los => AllocatorSelector::LargeObject(0),
+    };
+}
+
+pub fn create_rc_mutator<VM: VMBinding>(
+    mutator_tls: OpaquePointer,
+    mmtk: &'static MMTK<VM>,
+) -> Mutator<VM> {
+    let rc = mmtk.plan.downcast_ref::<ReferenceCounting<VM>>().unwrap();
+    let config = MutatorConfig {
+        allocator_mapping: &*ALLOCATOR_MAPPING,
+        space_mapping: box vec![
+            (AllocatorSelector::FreeList(0), &rc.rc_space),
+            (AllocatorSelector::BumpPointer(0), rc.common.get_immortal()),
+            (AllocatorSelector::LargeObject(0), rc.common.get_los()),
+        ],
+        prepare_func: &rc_mutator_prepare,
+        release_func: &rc_mutator_release,
+    };
+
+    Mutator {
+        allocators: Allocators::<VM>::new(mutator_tls, &*mmtk.plan, &config.space_mapping),
+        barrier: box RCBarrier::<RCIncProcessEdges<VM>>::new(mmtk, &rc.dec_buffers, mutator_tls),
+        read_barrier: box NoReadBarrier,
+        mutator_tls,
+        config,
+        plan: rc,
+    }
+}
@@ -19,8 +19,8 @@ impl<VM: VMBinding> CopyContext for SSCopyContext<VM> {
     type VM = VM;
     fn new(mmtk: &'static MMTK<Self::VM>) -> Self {
         Self {
-            plan: &mmtk.plan,
-            ss: BumpAllocator::new(OpaquePointer::UNINITIALIZED, None, &mmtk.plan),
+            plan: mmtk.plan.downcast_ref::<SemiSpace<VM>>().unwrap(),
+            ss: BumpAllocator::new(OpaquePointer::UNINITIALIZED, None, &*mmtk.plan),
         }
     }
     fn init(&mut self, tls: OpaquePointer) {
@@ -74,23 +74,24 @@ impl<VM: VMBinding> ProcessEdgesWork for SSProcessEdges<VM> {
         if object.is_null() {
             return object;
         }
-        if self.plan().tospace().in_space(object) {
-            return self.plan().tospace().trace_object(
+        let plan = self.plan_as::<SemiSpace<VM>>();
+        if plan.tospace().in_space(object) {
+            return plan.tospace().trace_object(
                 self,
                 object,
                 super::global::ALLOC_SS,
-                self.worker().local(),
+                self.worker().copy_context::<SSCopyContext<VM>>(),
             );
         }
-        if self.plan().fromspace().in_space(object) {
-            return self.plan().fromspace().trace_object(
+        if plan.fromspace().in_space(object) {
+            return plan.fromspace().trace_object(
                 self,
                 object,
                 super::global::ALLOC_SS,
-                self.worker().local(),
+                self.worker().copy_context::<SSCopyContext<VM>>(),
             );
         }
-        self.plan().common.trace_object(self, object)
+        plan.common.trace_object(self, object)
     }
 }
 
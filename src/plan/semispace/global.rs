@@ -7,7 +7,9 @@ use crate::plan::mutator_context::Mutator;
 use crate::plan::semispace::mutator::create_ss_mutator;
 use crate::plan::semispace::mutator::ALLOCATOR_MAPPING;
 use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
 use crate::plan::Plan;
+use crate::plan::PlanConstraints;
 use crate::policy::copyspace::CopySpace;
 use crate::policy::space::Space;
 use crate::scheduler::gc_works::*;
@@ -21,17 +23,23 @@ use crate::util::heap::VMRequest;
 use crate::util::options::UnsafeOptionsWrapper;
 #[cfg(feature = "sanity")]
 use crate::util::sanity::sanity_checker::*;
-use crate::util::OpaquePointer;
+use crate::util::{ObjectReference, OpaquePointer};
 use crate::vm::VMBinding;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use enum_map::EnumMap;
 
-pub type SelectedPlan<VM> = SemiSpace<VM>;
-
 pub const ALLOC_SS: AllocationSemantics = AllocationSemantics::Default;
 
+pub const SS_CONSTRAINTS: PlanConstraints = PlanConstraints {
+    moves_objects: true,
+    gc_header_bits: 2,
+    gc_header_words: 0,
+    num_specialized_scans: 1,
+    ..PlanConstraints::default()
+};
+
 pub struct SemiSpace<VM: VMBinding> {
     pub hi: AtomicBool,
     pub copyspace0: CopySpace<VM>,
@@ -43,39 +51,13 @@ unsafe impl<VM: VMBinding> Sync for SemiSpace<VM> {}
 
 impl<VM: VMBinding> Plan for SemiSpace<VM> {
     type VM = VM;
-    type Mutator = Mutator<Self>;
-    type CopyContext = SSCopyContext<VM>;
 
-    fn new(
-        vm_map: &'static VMMap,
-        mmapper: &'static Mmapper,
-        options: Arc<UnsafeOptionsWrapper>,
-        _scheduler: &'static MMTkScheduler<Self::VM>,
-    ) -> Self {
-        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+    fn constraints(&self) -> &'static PlanConstraints {
+        &SS_CONSTRAINTS
+    }
 
-        SemiSpace {
-            hi: AtomicBool::new(false),
-            copyspace0: CopySpace::new(
-                "copyspace0",
-                false,
-                true,
-                VMRequest::discontiguous(),
-                vm_map,
-                mmapper,
-                &mut heap,
-            ),
-            copyspace1: CopySpace::new(
-                "copyspace1",
-                true,
-                true,
-                VMRequest::discontiguous(),
-                vm_map,
-                mmapper,
-                &mut heap,
-            ),
-            common: CommonPlan::new(vm_map, mmapper, options, heap),
-        }
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box SSCopyContext::new(mmtk)
     }
 
     fn gc_init(
@@ -97,6 +79,8 @@ impl<VM: VMBinding> Plan for SemiSpace<VM> {
         scheduler
             .unconstrained_works
             .add(StopMutators::<SSProcessEdges<VM>>::new());
+        // Process weak references
+        scheduler.schedule_ref_processing::<SSProcessEdges<VM>>();
         // Prepare global/collectors/mutators
         scheduler.prepare_stage.add(Prepare::new(self));
         // Release global/collectors/mutators
@@ -111,7 +95,7 @@ impl<VM: VMBinding> Plan for SemiSpace<VM> {
         &'static self,
         tls: OpaquePointer,
         _mmtk: &'static MMTK<Self::VM>,
-    ) -> Box<Mutator<Self>> {
+    ) -> Box<Mutator<VM>> {
         Box::new(create_ss_mutator(tls, self))
     }
 
@@ -134,6 +118,7 @@ impl<VM: VMBinding> Plan for SemiSpace<VM> {
         self.common.release(tls, true);
         // release the collected region
         self.fromspace().release();
+        self.tospace().release_retained_objects();
     }
 
     fn get_collection_reserve(&self) -> usize {
@@ -141,7 +126,23 @@ impl<VM: VMBinding> Plan for SemiSpace<VM> {
     }
 
     fn get_pages_used(&self) -> usize {
-        self.tospace().reserved_pages() + self.common.get_pages_used()
+        // The from-space keeps the pages of the objects pinned in the last GC
+        self.tospace().reserved_pages()
+            + self.fromspace().reserved_pages()
+            + self.common.get_pages_used()
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        // The from-space may still hold the objects that were pinned in the last GC
+        self.copyspace0.enumerate_objects(f);
+        self.copyspace1.enumerate_objects(f);
+        self.common.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.copyspace0);
+        f(&self.copyspace1);
+        self.common.for_each_space(f);
     }
 
     fn base(&self) -> &BasePlan<VM> {
@@ -154,6 +155,38 @@ impl<VM: VMBinding> Plan for SemiSpace<VM> {
 }
 
 impl<VM: VMBinding> SemiSpace<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        _scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+
+        SemiSpace {
+            hi: AtomicBool::new(false),
+            copyspace0: CopySpace::new(
+                "copyspace0",
+                false,
+                true,
+                VMRequest::discontiguous(),
+                vm_map,
+                mmapper,
+                &mut heap,
+            ),
+            copyspace1: CopySpace::new(
+                "copyspace1",
+                true,
+                true,
+                VMRequest::discontiguous(),
+                vm_map,
+                mmapper,
+                &mut heap,
+            ),
+            common: CommonPlan::new(vm_map, mmapper, options, heap, &SS_CONSTRAINTS),
+        }
+    }
+
     pub fn tospace(&self) -> &CopySpace<VM> {
         if self.hi.load(Ordering::SeqCst) {
             &self.copyspace1
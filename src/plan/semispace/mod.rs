@@ -1,9 +1,5 @@
-pub mod constraints;
 mod gc_works;
 mod global;
 mod mutator;
 
 pub use self::global::SemiSpace;
-
-pub use self::constraints as SelectedConstraints;
-pub use self::global::SelectedPlan;
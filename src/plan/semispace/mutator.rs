@@ -1,5 +1,5 @@
 use super::SemiSpace;
-use crate::plan::barriers::NoBarrier;
+use crate::plan::barriers::{NoBarrier, NoReadBarrier};
 use crate::plan::mutator_context::Mutator;
 use crate::plan::mutator_context::MutatorConfig;
 use crate::plan::AllocationSemantics as AllocationType;
@@ -11,14 +11,14 @@ use enum_map::enum_map;
 use enum_map::EnumMap;
 
 pub fn ss_mutator_prepare<VM: VMBinding>(
-    _mutator: &mut Mutator<SemiSpace<VM>>,
+    _mutator: &mut Mutator<VM>,
     _tls: OpaquePointer,
 ) {
     // Do nothing
 }
 
 pub fn ss_mutator_release<VM: VMBinding>(
-    mutator: &mut Mutator<SemiSpace<VM>>,
+    mutator: &mut Mutator<VM>,
     _tls: OpaquePointer,
 ) {
     // rebind the allocation bump pointer to the appropriate semispace
@@ -29,7 +29,13 @@ pub fn ss_mutator_release<VM: VMBinding>(
     }
     .downcast_mut::<BumpAllocator<VM>>()
     .unwrap();
-    bump_allocator.rebind(Some(mutator.plan.tospace()));
+    bump_allocator.rebind(Some(
+        mutator
+            .plan
+            .downcast_ref::<SemiSpace<VM>>()
+            .unwrap()
+            .tospace(),
+    ));
 }
 
 lazy_static! {
@@ -43,7 +49,7 @@ lazy_static! {
 pub fn create_ss_mutator<VM: VMBinding>(
     mutator_tls: OpaquePointer,
     plan: &'static SemiSpace<VM>,
-) -> Mutator<SemiSpace<VM>> {
+) -> Mutator<VM> {
     let config = MutatorConfig {
         allocator_mapping: &*ALLOCATOR_MAPPING,
         space_mapping: box vec![
@@ -61,6 +67,7 @@ pub fn create_ss_mutator<VM: VMBinding>(
     Mutator {
         allocators: Allocators::<VM>::new(mutator_tls, plan, &config.space_mapping),
         barrier: box NoBarrier,
+        read_barrier: box NoReadBarrier,
         mutator_tls,
         config,
         plan,
@@ -0,0 +1,47 @@
+use super::global::StickyMarkSweep;
+use crate::scheduler::gc_works::*;
+use crate::util::{Address, ObjectReference};
+use crate::vm::VMBinding;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+
+/// Marks objects in both nursery and full heap GCs. In a nursery GC, the objects that are
+/// marked already are old, so they are neither marked nor scanned again.
+#[derive(Default)]
+pub struct StickyMSProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<StickyMSProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for StickyMSProcessEdges<VM> {
+    type VM = VM;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if object.is_null() {
+            return object;
+        }
+        let plan = self.plan_as::<StickyMarkSweep<VM>>();
+        plan.trace_object(self, object)
+    }
+}
+
+impl<VM: VMBinding> Deref for StickyMSProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for StickyMSProcessEdges<VM> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
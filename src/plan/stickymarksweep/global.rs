@@ -0,0 +1,222 @@
+use super::gc_works::StickyMSProcessEdges;
+use crate::mmtk::MMTK;
+use crate::plan::global::BasePlan;
+use crate::plan::global::CommonPlan;
+use crate::plan::global::GcStatus;
+use crate::plan::global::NoCopy;
+use crate::plan::mutator_context::Mutator;
+use crate::plan::stickymarksweep::mutator::create_sticky_ms_mutator;
+use crate::plan::stickymarksweep::mutator::ALLOCATOR_MAPPING;
+use crate::plan::AllocationSemantics;
+use crate::plan::CopyContext;
+use crate::plan::Plan;
+use crate::plan::PlanConstraints;
+use crate::plan::TransitiveClosure;
+use crate::policy::marksweepspace::{MarkSweepSpace, MAX_CELL_SIZE};
+use crate::policy::space::Space;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::*;
+use crate::util::alloc::allocators::AllocatorSelector;
+use crate::util::conversions::bytes_to_pages_up;
+use crate::util::heap::layout::heap_layout::Mmapper;
+use crate::util::heap::layout::heap_layout::VMMap;
+use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::VMRequest;
+use crate::util::options::UnsafeOptionsWrapper;
+#[cfg(feature = "sanity")]
+use crate::util::sanity::sanity_checker::*;
+use crate::util::{ObjectReference, OpaquePointer};
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use enum_map::EnumMap;
+
+pub const STICKY_MS_CONSTRAINTS: PlanConstraints = PlanConstraints {
+    moves_objects: false,
+    gc_header_bits: 2,
+    gc_header_words: 0,
+    num_specialized_scans: 1,
+    max_non_los_default_alloc_bytes: MAX_CELL_SIZE,
+    ..PlanConstraints::default()
+};
+
+/// A generational mark-sweep plan with sticky mark bits.
+///
+/// The marks are kept after a GC, so the objects that survived a GC stay marked, and are
+/// treated as old. A nursery GC traces from the roots and the objects and fields remembered by
+/// the `FieldRememberingBarrier`, and only marks and sweeps the objects allocated since the
+/// last GC. A full heap GC clears the marks first, and traces the whole heap.
+pub struct StickyMarkSweep<VM: VMBinding> {
+    pub ms_space: MarkSweepSpace<VM>,
+    pub common: CommonPlan<VM>,
+    in_nursery: AtomicBool,
+    /// The reserved pages after the last GC. A nursery GC is triggered when the pages allocated
+    /// since then reach the maximal nursery size.
+    pages_after_last_gc: AtomicUsize,
+}
+
+unsafe impl<VM: VMBinding> Sync for StickyMarkSweep<VM> {}
+
+impl<VM: VMBinding> Plan for StickyMarkSweep<VM> {
+    type VM = VM;
+
+    fn constraints(&self) -> &'static PlanConstraints {
+        &STICKY_MS_CONSTRAINTS
+    }
+
+    fn create_worker_local(&self, mmtk: &'static MMTK<Self::VM>) -> GCWorkerLocalPtr {
+        box NoCopy::new(mmtk)
+    }
+
+    fn collection_required(&self, space_full: bool, _space: &dyn Space<Self::VM>) -> bool {
+        let nursery_full = self.get_pages_reserved()
+            >= self.pages_after_last_gc.load(Ordering::Relaxed)
+                + bytes_to_pages_up(self.options().max_nursery);
+        let heap_full = self.get_pages_reserved() > self.get_total_pages();
+        space_full || nursery_full || heap_full
+    }
+
+    fn gc_init(
+        &mut self,
+        heap_size: usize,
+        vm_map: &'static VMMap,
+        scheduler: &Arc<MMTkScheduler<VM>>,
+    ) {
+        self.common.gc_init(heap_size, vm_map, scheduler);
+
+        self.ms_space.init(&vm_map);
+    }
+
+    fn schedule_collection(&'static self, scheduler: &MMTkScheduler<VM>) {
+        self.base().set_collection_kind();
+        self.base().set_gc_status(GcStatus::GcPrepare);
+        // A user triggered GC is a full heap GC. Consume the flag for the following GCs.
+        let user_triggered = self
+            .base()
+            .user_triggered_collection
+            .swap(false, Ordering::Relaxed);
+        let in_nursery = !user_triggered && !self.request_full_heap_collection();
+        self.in_nursery.store(in_nursery, Ordering::SeqCst);
+        // Stop & scan mutators (mutator scanning can happen before STW)
+        scheduler
+            .unconstrained_works
+            .add(StopMutators::<StickyMSProcessEdges<VM>>::new());
+        // Process weak references
+        scheduler.schedule_ref_processing::<StickyMSProcessEdges<VM>>();
+        // Prepare global/collectors/mutators
+        scheduler.prepare_stage.add(Prepare::new(self));
+        // Release global/collectors/mutators
+        scheduler.release_stage.add(Release::new(self));
+        // Resume mutators
+        #[cfg(feature = "sanity")]
+        scheduler.final_stage.add(ScheduleSanityGC);
+        scheduler.set_finalizer(Some(EndOfGC));
+    }
+
+    fn bind_mutator(
+        &'static self,
+        tls: OpaquePointer,
+        mmtk: &'static MMTK<Self::VM>,
+    ) -> Box<Mutator<VM>> {
+        Box::new(create_sticky_ms_mutator(tls, mmtk))
+    }
+
+    fn get_allocator_mapping(&self) -> &'static EnumMap<AllocationSemantics, AllocatorSelector> {
+        &*ALLOCATOR_MAPPING
+    }
+
+    fn prepare(&self, tls: OpaquePointer) {
+        let full_heap = !self.in_nursery();
+        self.common.prepare(tls, full_heap);
+        self.ms_space.prepare();
+        if full_heap {
+            // Unmark the old objects, so the whole heap is traced
+            self.ms_space.clear_marks();
+        }
+    }
+
+    fn release(&self, tls: OpaquePointer) {
+        self.common.release(tls, !self.in_nursery());
+        // sweep the mark-sweep space, and keep the marks of the survivors
+        self.ms_space.release(true);
+        self.pages_after_last_gc
+            .store(self.get_pages_reserved(), Ordering::Relaxed);
+    }
+
+    fn get_collection_reserve(&self) -> usize {
+        0
+    }
+
+    fn get_pages_used(&self) -> usize {
+        self.ms_space.reserved_pages() + self.common.get_pages_used()
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        self.ms_space.enumerate_objects(f);
+        self.common.enumerate_objects(f);
+    }
+
+    fn for_each_space(&self, f: &mut dyn FnMut(&dyn Space<VM>)) {
+        f(&self.ms_space);
+        self.common.for_each_space(f);
+    }
+
+    fn base(&self) -> &BasePlan<VM> {
+        &self.common.base
+    }
+
+    fn common(&self) -> &CommonPlan<VM> {
+        &self.common
+    }
+
+    fn in_nursery(&self) -> bool {
+        self.in_nursery.load(Ordering::SeqCst)
+    }
+}
+
+impl<VM: VMBinding> StickyMarkSweep<VM> {
+    pub fn new(
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        options: Arc<UnsafeOptionsWrapper>,
+        _scheduler: &'static MMTkScheduler<VM>,
+    ) -> Self {
+        let mut heap = HeapMeta::new(HEAP_START, HEAP_END);
+
+        StickyMarkSweep {
+            ms_space: MarkSweepSpace::new(
+                "ms",
+                false,
+                VMRequest::discontiguous(),
+                vm_map,
+                mmapper,
+                &mut heap,
+            ),
+            common: CommonPlan::new(vm_map, mmapper, options, heap, &STICKY_MS_CONSTRAINTS),
+            in_nursery: AtomicBool::new(false),
+            pages_after_last_gc: AtomicUsize::new(0),
+        }
+    }
+
+    /// A full heap GC is required if this is an emergency GC, if the heap is full, or if there
+    /// is not enough room left in the heap for the minimal nursery.
+    fn request_full_heap_collection(&self) -> bool {
+        self.is_emergency_collection()
+            || self.get_total_pages() <= self.get_pages_reserved()
+            || self.get_pages_avail() < bytes_to_pages_up(self.options().min_nursery)
+    }
+
+    #[inline]
+    pub fn trace_object<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+    ) -> ObjectReference {
+        if self.ms_space.in_space(object) {
+            return self.ms_space.trace_object(trace, object);
+        }
+        self.common.trace_object(trace, object)
+    }
+}
@@ -0,0 +1,74 @@
+use super::gc_works::StickyMSProcessEdges;
+use super::StickyMarkSweep;
+use crate::plan::barriers::{FieldRememberingBarrier, NoReadBarrier};
+use crate::plan::mutator_context::Mutator;
+use crate::plan::mutator_context::MutatorConfig;
+use crate::plan::AllocationSemantics as AllocationType;
+use crate::policy::marksweepspace::MarkSweepSpace;
+use crate::util::alloc::allocators::{AllocatorSelector, Allocators};
+use crate::util::alloc::FreeListAllocator;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+use crate::MMTK;
+use enum_map::enum_map;
+use enum_map::EnumMap;
+
+pub fn sticky_ms_mutator_prepare<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // Flush the remembered set, so a nursery GC traces from the modified objects and fields
+    mutator.barrier.flush();
+}
+
+pub fn sticky_ms_mutator_release<VM: VMBinding>(mutator: &mut Mutator<VM>, _tls: OpaquePointer) {
+    // The mark-sweep space rebuilds its free lists when it sweeps, so drop the cells held by the allocator
+    let free_list_allocator = unsafe {
+        mutator
+            .allocators
+            .get_allocator_mut(mutator.config.allocator_mapping[AllocationType::Default])
+    }
+    .downcast_mut::<FreeListAllocator<VM>>()
+    .unwrap();
+    free_list_allocator.reset();
+}
+
+lazy_static! {
+    pub static ref ALLOCATOR_MAPPING: EnumMap<AllocationType, AllocatorSelector> = enum_map! {
+        AllocationType::Default => AllocatorSelector::FreeList(0),
+        AllocationType::Immortal | AllocationType::Code | AllocationType::ReadOnly => AllocatorSelector::BumpPointer(0),
+        AllocationType::Los => AllocatorSelector::LargeObject(0),
+    };
+}
+
+pub fn create_sticky_ms_mutator<VM: VMBinding>(
+    mutator_tls: OpaquePointer,
+    mmtk: &'static MMTK<VM>,
+) -> Mutator<VM> {
+    let sticky_ms = mmtk.plan.downcast_ref::<StickyMarkSweep<VM>>().unwrap();
+    let config = MutatorConfig {
+        allocator_mapping: &*ALLOCATOR_MAPPING,
+        space_mapping: box vec![
+            (AllocatorSelector::FreeList(0), &sticky_ms.ms_space),
+            (
+                AllocatorSelector::BumpPointer(0),
+                sticky_ms.common.get_immortal(),
+            ),
+            (
+                AllocatorSelector::LargeObject(0),
+                sticky_ms.common.get_los(),
+            ),
+        ],
+        prepare_func: &sticky_ms_mutator_prepare,
+        release_func: &sticky_ms_mutator_release,
+    };
+
+    Mutator {
+        allocators: Allocators::<VM>::new(mutator_tls, &*mmtk.plan, &config.space_mapping),
+        // Young and old objects share the spaces, so all the writes are remembered
+        barrier: box FieldRememberingBarrier::<StickyMSProcessEdges<VM>, MarkSweepSpace<VM>>::new(
+            mmtk, None,
+        ),
+        read_barrier: box NoReadBarrier,
+        mutator_tls,
+        config,
+        plan: sticky_ms,
+    }
+}
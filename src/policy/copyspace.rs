@@ -2,31 +2,46 @@ use crate::plan::TransitiveClosure;
 use crate::plan::{AllocationSemantics, CopyContext};
 use crate::policy::space::SpaceOptions;
 use crate::policy::space::{CommonSpace, Space, SFT};
-use crate::util::constants::CARD_META_PAGES_PER_REGION;
+use crate::util::alloc_bit;
+use crate::util::constants::{BYTES_IN_PAGE, CARD_META_PAGES_PER_REGION};
 use crate::util::forwarding_word as ForwardingWord;
 use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
 use crate::util::heap::HeapMeta;
 use crate::util::heap::VMRequest;
 use crate::util::heap::{MonotonePageResource, PageResource};
+use crate::util::pin_bit;
 use crate::util::{Address, ObjectReference};
 use crate::vm::*;
 use libc::{mprotect, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
 use std::cell::UnsafeCell;
+use std::mem;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 unsafe impl<VM: VMBinding> Sync for CopySpace<VM> {}
 
 const META_DATA_PAGES_PER_REGION: usize = CARD_META_PAGES_PER_REGION;
 
+/// A space for semi-space copying. The live objects are copied out of the from-space in a GC, and
+/// all its pages are released. If some objects are pinned, they are kept in place, and only the
+/// pages of the pinned objects are retained. The space then has to trace the retained objects in
+/// place when it is the to-space, until it is released again without pinned objects.
 pub struct CopySpace<VM: VMBinding> {
     common: UnsafeCell<CommonSpace<VM>>,
     pr: MonotonePageResource<VM>,
     from_space: AtomicBool,
+    /// Whether the space has objects that were kept in place in a previous GC.
+    has_retained_objects: AtomicBool,
+    /// The objects kept in place in previous GCs that are still alive.
+    retained_objects: Mutex<Vec<ObjectReference>>,
+    /// The objects marked in place in the current GC. The forwarding bits of these objects are
+    /// set to being forwarded, until the space is released.
+    marked_in_place: Mutex<Vec<ObjectReference>>,
 }
 
 impl<VM: VMBinding> SFT for CopySpace<VM> {
     fn is_live(&self, object: ObjectReference) -> bool {
-        !self.from_space() || ForwardingWord::is_forwarded::<VM>(object)
+        !self.from_space() || ForwardingWord::is_forwarded_or_being_forwarded::<VM>(object)
     }
     fn is_movable(&self) -> bool {
         true
@@ -36,6 +51,14 @@ impl<VM: VMBinding> SFT for CopySpace<VM> {
         !self.from_space()
     }
     fn initialize_header(&self, _object: ObjectReference, _alloc: bool) {}
+
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        alloc_bit::find_object_by_alloc_bits::<VM>(ptr, max_search_bytes)
+    }
 }
 
 impl<VM: VMBinding> Space<VM> for CopySpace<VM> {
@@ -64,6 +87,14 @@ impl<VM: VMBinding> Space<VM> for CopySpace<VM> {
     fn release_multiple_pages(&mut self, _start: Address) {
         panic!("copyspace only releases pages enmasse")
     }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        // Objects are bump-allocated (or copied) into the pages, which may have gaps between the
+        // objects, so the objects are found by their alloc bits.
+        for (start, bytes) in self.pr.allocated_extents() {
+            alloc_bit::for_each_alloced_object(start, bytes, &mut *f);
+        }
+    }
 }
 
 impl<VM: VMBinding> CopySpace<VM> {
@@ -101,6 +132,9 @@ impl<VM: VMBinding> CopySpace<VM> {
             },
             common: UnsafeCell::new(common),
             from_space: AtomicBool::new(from_space),
+            has_retained_objects: AtomicBool::new(false),
+            retained_objects: Mutex::new(vec![]),
+            marked_in_place: Mutex::new(vec![]),
         }
     }
 
@@ -108,13 +142,83 @@ impl<VM: VMBinding> CopySpace<VM> {
         self.from_space.store(from_space, Ordering::SeqCst);
     }
 
+    /// The memory that the space has allocated, as `(start, bytes)` extents.
+    pub fn allocated_extents(&self) -> Vec<(Address, usize)> {
+        self.pr.allocated_extents()
+    }
+
+    /// Release the from-space.
     pub fn release(&self) {
-        unsafe {
-            self.pr.reset();
+        let pinned = mem::take(&mut *self.marked_in_place.lock().unwrap());
+        if pinned.is_empty() {
+            unsafe {
+                self.pr.reset();
+            }
+            self.retained_objects.lock().unwrap().clear();
+        } else {
+            // Keep the pages of the pinned objects, and release the other pages. The objects that
+            // are not pinned are either copied or dead, and the dead objects on the kept pages
+            // must not be found by their alloc bits any more.
+            for (start, bytes) in self.pr.allocated_extents() {
+                alloc_bit::sweep_alloc_bits(start, bytes, |object| {
+                    ForwardingWord::is_forwarded_or_being_forwarded::<VM>(object)
+                });
+            }
+            let kept = pinned
+                .iter()
+                .map(|&object| {
+                    let start =
+                        VM::VMObjectModel::object_start_ref(object).align_down(BYTES_IN_PAGE);
+                    let end =
+                        VM::VMObjectModel::get_object_end_address(object).align_up(BYTES_IN_PAGE);
+                    (start, end - start)
+                })
+                .collect();
+            unsafe {
+                self.pr.reset_except(kept);
+            }
+            for &object in pinned.iter() {
+                ForwardingWord::clear_forwarding_bits::<VM>(object);
+            }
+            *self.retained_objects.lock().unwrap() = pinned;
         }
+        self.update_has_retained_objects();
         self.from_space.store(false, Ordering::SeqCst);
     }
 
+    /// Release the objects retained in the to-space that were not reached in this GC. This needs
+    /// to be called for the to-space at the end of each GC that traces the to-space.
+    pub fn release_retained_objects(&self) {
+        if !self.has_retained_objects() {
+            return;
+        }
+        let marked = mem::take(&mut *self.marked_in_place.lock().unwrap());
+        let mut retained_objects = self.retained_objects.lock().unwrap();
+        for &object in retained_objects.iter() {
+            if !ForwardingWord::is_forwarded_or_being_forwarded::<VM>(object) {
+                alloc_bit::unset_alloc_bit(object);
+                pin_bit::unpin_object(object);
+            }
+        }
+        for &object in marked.iter() {
+            ForwardingWord::clear_forwarding_bits::<VM>(object);
+        }
+        *retained_objects = marked;
+        drop(retained_objects);
+        self.update_has_retained_objects();
+    }
+
+    /// Does the space have objects kept in place in previous GCs?
+    pub fn has_retained_objects(&self) -> bool {
+        self.has_retained_objects.load(Ordering::SeqCst)
+    }
+
+    fn update_has_retained_objects(&self) {
+        let has_retained_objects = !self.retained_objects.lock().unwrap().is_empty();
+        self.has_retained_objects
+            .store(has_retained_objects, Ordering::SeqCst);
+    }
+
     fn from_space(&self) -> bool {
         self.from_space.load(Ordering::SeqCst)
     }
@@ -129,8 +233,14 @@ impl<VM: VMBinding> CopySpace<VM> {
     ) -> ObjectReference {
         trace!("copyspace.trace_object(, {:?}, {:?})", object, semantics,);
         if !self.from_space() {
+            if self.has_retained_objects() {
+                return self.trace_object_in_place(trace, object);
+            }
             return object;
         }
+        if pin_bit::is_pinned(object) {
+            return self.trace_object_in_place(trace, object);
+        }
         trace!("attempting to forward");
         let forwarding_status = ForwardingWord::attempt_to_forward::<VM>(object);
         trace!("checking if object is being forwarded");
@@ -151,6 +261,23 @@ impl<VM: VMBinding> CopySpace<VM> {
         }
     }
 
+    /// Mark an object without moving it, for the pinned objects in the from-space and the retained
+    /// objects in the to-space. The forwarding bits are set to being forwarded as the mark, which
+    /// does not overwrite the header. The object is never forwarded, as other workers check the
+    /// same conditions before they attempt to forward it.
+    fn trace_object_in_place<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+    ) -> ObjectReference {
+        let forwarding_status = ForwardingWord::attempt_to_forward::<VM>(object);
+        if !ForwardingWord::state_is_forwarded_or_being_forwarded(forwarding_status) {
+            self.marked_in_place.lock().unwrap().push(object);
+            trace.process_node(object);
+        }
+        object
+    }
+
     pub fn protect(&self) {
         if !self.common().contiguous {
             panic!(
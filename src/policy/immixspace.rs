@@ -0,0 +1,558 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::plan::immix::ImmixCopyContext;
+use crate::plan::{AllocationSemantics, TransitiveClosure};
+use crate::policy::space::SpaceOptions;
+use crate::policy::space::{CommonSpace, Space, SFT};
+use crate::util::alloc_bit;
+use crate::util::constants::BYTES_IN_PAGE;
+use crate::util::forwarding_word as ForwardingWord;
+use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::{FreeListPageResource, PageResource, VMRequest};
+use crate::util::pin_bit;
+use crate::util::side_metadata::MetadataSpec;
+use crate::util::OpaquePointer;
+use crate::util::{Address, ObjectReference};
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+
+pub const LOG_BYTES_IN_LINE: usize = 8;
+pub const BYTES_IN_LINE: usize = 1 << LOG_BYTES_IN_LINE;
+pub const LOG_BYTES_IN_BLOCK: usize = 15;
+pub const BYTES_IN_BLOCK: usize = 1 << LOG_BYTES_IN_BLOCK;
+pub const LINES_IN_BLOCK: usize = BYTES_IN_BLOCK / BYTES_IN_LINE;
+const PAGES_IN_BLOCK: usize = BYTES_IN_BLOCK / BYTES_IN_PAGE;
+/// The first line of each block holds the block metadata.
+pub const FIRST_USABLE_LINE: usize = 1;
+const USABLE_LINES_IN_BLOCK: usize = LINES_IN_BLOCK - FIRST_USABLE_LINE;
+
+/// The largest object that can be allocated in the immix space.
+/// Larger objects have to be allocated in the large object space.
+pub const MAX_IMMIX_OBJECT_SIZE: usize = BYTES_IN_BLOCK >> 1;
+
+/// The percentage of the heap reserved for copying objects out of defrag source blocks.
+const DEFRAG_HEADROOM_PERCENT: usize = 2;
+
+/// A GC only defragments if the free lines in the reusable blocks that the allocators left
+/// unused are at least this percentage of the usable lines in the space.
+const DEFRAG_FREE_LINES_PERCENT: usize = 10;
+
+/// The mark bit of the objects. The two lowest bits of the GC byte are used by `ForwardingWord`.
+const MARK_BIT_SPEC: MetadataSpec = MetadataSpec::InHeader {
+    bit_offset: 2,
+    num_of_bits: 1,
+};
+const MARK_BIT: u8 = 1;
+
+/// The metadata at the start of each block.
+#[repr(C)]
+struct BlockHeader {
+    /// Is there a marked object in the block in the current GC?
+    marked: AtomicBool,
+    /// Are the objects in this block evacuated in the current GC?
+    defrag_source: AtomicBool,
+    /// The number of marked lines after the last GC.
+    live_lines: usize,
+    /// One mark byte per line. The marks of the last GC tell the allocators where the holes are.
+    line_marks: [AtomicU8; LINES_IN_BLOCK],
+}
+
+const BLOCK_HEADER_BYTES: usize = std::mem::size_of::<BlockHeader>();
+
+/// Get the number of pages to reserve for defragmentation, given the size of the heap.
+pub fn defrag_headroom_pages(total_pages: usize) -> usize {
+    total_pages * DEFRAG_HEADROOM_PERCENT / 100
+}
+
+/// A mark-region space. The space is divided into blocks, and blocks are divided into lines.
+/// Marking an object marks its lines, and the allocators bump-allocate into the holes of
+/// free lines in the blocks that survived the last GC. When a GC finds the heap fragmented,
+/// it picks the sparsest blocks as defrag sources and opportunistically evacuates their
+/// objects, as long as there is headroom for the copies.
+pub struct ImmixSpace<VM: VMBinding> {
+    common: UnsafeCell<CommonSpace<VM>>,
+    pr: FreeListPageResource<VM>,
+    mark_state: AtomicU8,
+    /// All the blocks in this space.
+    blocks: Mutex<Vec<Address>>,
+    /// Blocks with free lines left from the last GC, and not yet taken by an allocator.
+    reusable_blocks: Mutex<Vec<Address>>,
+    /// Are we evacuating objects from defrag source blocks in the current GC?
+    in_defrag: AtomicBool,
+    /// The number of blocks the copying allocators can still take in the current GC.
+    defrag_headroom_blocks: AtomicUsize,
+}
+
+unsafe impl<VM: VMBinding> Sync for ImmixSpace<VM> {}
+
+impl<VM: VMBinding> SFT for ImmixSpace<VM> {
+    fn is_live(&self, object: ObjectReference) -> bool {
+        ForwardingWord::is_forwarded::<VM>(object) || self.is_marked(object)
+    }
+    fn is_movable(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "sanity")]
+    fn is_sane(&self) -> bool {
+        true
+    }
+    fn initialize_header(&self, object: ObjectReference, _alloc: bool) {
+        MARK_BIT_SPEC.store::<VM>(object, self.mark_state.load(Ordering::Relaxed));
+    }
+
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        alloc_bit::find_object_by_alloc_bits::<VM>(ptr, max_search_bytes)
+    }
+}
+
+impl<VM: VMBinding> Space<VM> for ImmixSpace<VM> {
+    fn as_space(&self) -> &dyn Space<VM> {
+        self
+    }
+    fn as_sft(&self) -> &(dyn SFT + Sync + 'static) {
+        self
+    }
+    fn get_page_resource(&self) -> &dyn PageResource<VM> {
+        &self.pr
+    }
+    fn init(&mut self, _vm_map: &'static VMMap) {
+        let me = unsafe { &*(self as *const Self) };
+        self.pr.bind_space(me);
+    }
+
+    fn common(&self) -> &CommonSpace<VM> {
+        unsafe { &*self.common.get() }
+    }
+
+    unsafe fn unsafe_common_mut(&self) -> &mut CommonSpace<VM> {
+        &mut *self.common.get()
+    }
+
+    fn release_multiple_pages(&mut self, start: Address) {
+        self.pr.release_pages(start);
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        for &block in self.blocks.lock().unwrap().iter() {
+            let data_start = block + (FIRST_USABLE_LINE << LOG_BYTES_IN_LINE);
+            alloc_bit::for_each_alloced_object(
+                data_start,
+                block + BYTES_IN_BLOCK - data_start,
+                &mut *f,
+            );
+        }
+    }
+}
+
+impl<VM: VMBinding> ImmixSpace<VM> {
+    pub fn new(
+        name: &'static str,
+        vmrequest: VMRequest,
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        heap: &mut HeapMeta,
+    ) -> Self {
+        // Make sure the block metadata fits in the lines reserved for it.
+        #[allow(clippy::assertions_on_constants)]
+        {
+            debug_assert!(BLOCK_HEADER_BYTES <= FIRST_USABLE_LINE * BYTES_IN_LINE);
+        }
+        let common = CommonSpace::new(
+            SpaceOptions {
+                name,
+                movable: true,
+                immortal: false,
+                zeroed: true,
+                vmrequest,
+            },
+            vm_map,
+            mmapper,
+            heap,
+        );
+        ImmixSpace {
+            pr: if vmrequest.is_discontiguous() {
+                FreeListPageResource::new_discontiguous(0, vm_map)
+            } else {
+                FreeListPageResource::new_contiguous(common.start, common.extent, 0, vm_map)
+            },
+            common: UnsafeCell::new(common),
+            mark_state: AtomicU8::new(0),
+            blocks: Mutex::new(vec![]),
+            reusable_blocks: Mutex::new(vec![]),
+            in_defrag: AtomicBool::new(false),
+            defrag_headroom_blocks: AtomicUsize::new(0),
+        }
+    }
+
+    /// Flip the mark state and clear the block and line marks. If this is an emergency
+    /// collection, or if the reusable blocks from the last GC were not used up and have enough
+    /// free lines (i.e. the heap is fragmented), select the sparsest blocks as defrag sources.
+    /// Outside emergency collections, the heap needs to be large enough for a block of headroom.
+    pub fn prepare(&self, emergency_collection: bool, total_pages: usize) {
+        self.mark_state.store(
+            MARK_BIT - self.mark_state.load(Ordering::SeqCst),
+            Ordering::SeqCst,
+        );
+        let blocks = self.blocks.lock().unwrap();
+        for &block in blocks.iter() {
+            let header = Self::header(block);
+            header.marked.store(false, Ordering::Relaxed);
+            header.defrag_source.store(false, Ordering::Relaxed);
+            for mark in header.line_marks.iter() {
+                mark.store(0, Ordering::Relaxed);
+            }
+        }
+        let usable_lines = blocks.len() * USABLE_LINES_IN_BLOCK;
+        drop(blocks);
+
+        let candidates = std::mem::take(&mut *self.reusable_blocks.lock().unwrap());
+        let mut headroom_blocks = defrag_headroom_pages(total_pages) / PAGES_IN_BLOCK;
+        let in_defrag = if emergency_collection {
+            headroom_blocks = headroom_blocks.max(1);
+            true
+        } else {
+            let free_lines: usize = candidates
+                .iter()
+                .map(|&block| USABLE_LINES_IN_BLOCK - Self::header(block).live_lines)
+                .sum();
+            headroom_blocks != 0 && free_lines * 100 >= usable_lines * DEFRAG_FREE_LINES_PERCENT
+        };
+        self.in_defrag.store(in_defrag, Ordering::SeqCst);
+        if !in_defrag {
+            return;
+        }
+        self.defrag_headroom_blocks
+            .store(headroom_blocks, Ordering::SeqCst);
+        Self::select_defrag_sources(candidates, headroom_blocks);
+    }
+
+    /// Mark the blocks with the fewest live lines as defrag sources, until their live lines fill the headroom.
+    fn select_defrag_sources(mut candidates: Vec<Address>, headroom_blocks: usize) {
+        candidates.sort_by_key(|&block| Self::header(block).live_lines);
+        let mut available_lines = headroom_blocks * USABLE_LINES_IN_BLOCK;
+        for block in candidates {
+            let live_lines = Self::header(block).live_lines;
+            if live_lines > available_lines {
+                break;
+            }
+            available_lines -= live_lines;
+            Self::header(block)
+                .defrag_source
+                .store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Sweep all the blocks: blocks without marked objects are returned to the page resource,
+    /// and blocks with free lines become reusable.
+    pub fn release(&self) {
+        // FIXME: We need a safe implementation
+        #[allow(clippy::cast_ref_to_mut)]
+        let pr: &mut FreeListPageResource<VM> = unsafe { &mut *(&self.pr as *const _ as *mut _) };
+        let mut reusable_blocks = self.reusable_blocks.lock().unwrap();
+        reusable_blocks.clear();
+        self.blocks.lock().unwrap().retain(|&block| {
+            if !Self::sweep_block(block, |object| self.is_marked(object)) {
+                pr.release_pages(block);
+                return false;
+            }
+            if Self::header(block).live_lines < USABLE_LINES_IN_BLOCK {
+                reusable_blocks.push(block);
+            }
+            true
+        });
+        self.in_defrag.store(false, Ordering::SeqCst);
+    }
+
+    /// Sweep a block: count its marked lines, and clear the alloc bits of the dead objects, as the
+    /// free lines may be reused. Returns false if there is no marked object in the block, in which
+    /// case the block can be released.
+    fn sweep_block<F: Fn(ObjectReference) -> bool>(block: Address, is_marked: F) -> bool {
+        let header = Self::header_mut(block);
+        if !header.marked.load(Ordering::Relaxed) {
+            return false;
+        }
+        let data_start = block + (FIRST_USABLE_LINE << LOG_BYTES_IN_LINE);
+        alloc_bit::sweep_alloc_bits(data_start, block + BYTES_IN_BLOCK - data_start, is_marked);
+        header.live_lines = header.line_marks[FIRST_USABLE_LINE..]
+            .iter()
+            .filter(|mark| mark.load(Ordering::Relaxed) != 0)
+            .count();
+        true
+    }
+
+    #[inline]
+    pub fn trace_object<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+        semantics: AllocationSemantics,
+        copy_context: &mut ImmixCopyContext<VM>,
+    ) -> ObjectReference {
+        // Pinned objects are never evacuated
+        if self.in_defrag.load(Ordering::Relaxed)
+            && Self::is_defrag_source(object)
+            && !pin_bit::is_pinned(object)
+        {
+            self.trace_object_with_opportunistic_copy(trace, object, semantics, copy_context)
+        } else {
+            self.trace_object_without_moving(trace, object)
+        }
+    }
+
+    #[inline]
+    fn trace_object_without_moving<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+    ) -> ObjectReference {
+        if self.test_and_mark(object) {
+            Self::mark_lines(object);
+            trace.process_node(object);
+        }
+        object
+    }
+
+    fn trace_object_with_opportunistic_copy<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+        semantics: AllocationSemantics,
+        copy_context: &mut ImmixCopyContext<VM>,
+    ) -> ObjectReference {
+        let mut forwarding_status = ForwardingWord::attempt_to_forward::<VM>(object);
+        if ForwardingWord::state_is_forwarded_or_being_forwarded(forwarding_status) {
+            while ForwardingWord::state_is_being_forwarded(forwarding_status) {
+                forwarding_status = ForwardingWord::get_forwarding_status::<VM>(object);
+            }
+            if ForwardingWord::state_is_forwarded_or_being_forwarded(forwarding_status) {
+                return ForwardingWord::spin_and_get_forwarded_object::<VM>(
+                    object,
+                    forwarding_status,
+                );
+            }
+            // Another worker marked the object in place.
+            return object;
+        }
+        if self.is_marked(object) {
+            // The object was marked in place before we set the forwarding bits.
+            ForwardingWord::clear_forwarding_bits::<VM>(object);
+            return object;
+        }
+        if !copy_context.reserve_copy(object) {
+            // No headroom left. Mark the object in place. The mark bit has to be set before
+            // the forwarding bits are cleared so that other workers do not copy the object.
+            self.test_and_mark(object);
+            Self::mark_lines(object);
+            ForwardingWord::clear_forwarding_bits::<VM>(object);
+            trace.process_node(object);
+            return object;
+        }
+        let new_object = ForwardingWord::forward_object::<VM, _>(object, semantics, copy_context);
+        trace.process_node(new_object);
+        new_object
+    }
+
+    /// Mark an object that was just copied into this space. This should be called in `CopyContext::post_copy()`.
+    pub fn post_copy(&self, object: ObjectReference) {
+        ForwardingWord::clear_forwarding_bits::<VM>(object);
+        self.test_and_mark(object);
+        Self::mark_lines(object);
+    }
+
+    /// Get a clean block. Blocks taken for copying count against the defrag headroom.
+    /// Returns zero if the space cannot get more memory, or if the headroom is used up.
+    pub fn get_clean_block(&self, tls: OpaquePointer, copy: bool) -> Address {
+        if copy
+            && self
+                .defrag_headroom_blocks
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |blocks| {
+                    blocks.checked_sub(1)
+                })
+                .is_err()
+        {
+            return unsafe { Address::zero() };
+        }
+        let block = self.acquire(tls, PAGES_IN_BLOCK);
+        if block.is_zero() {
+            return block;
+        }
+        debug_assert!(block.is_aligned_to(BYTES_IN_BLOCK));
+        self.blocks.lock().unwrap().push(block);
+        block
+    }
+
+    /// Get a block with free lines from the last GC, if there is any.
+    pub fn get_reusable_block(&self) -> Option<Address> {
+        self.reusable_blocks.lock().unwrap().pop()
+    }
+
+    /// Find the next hole (a range of free lines) in the block, starting from the given line.
+    /// Returns the start and the end of the hole, and the line to continue searching from.
+    pub fn get_next_hole(
+        &self,
+        block: Address,
+        start_line: usize,
+    ) -> Option<(Address, Address, usize)> {
+        let marks = &Self::header(block).line_marks;
+        let mut line = start_line.max(FIRST_USABLE_LINE);
+        while line < LINES_IN_BLOCK && marks[line].load(Ordering::Relaxed) != 0 {
+            line += 1;
+        }
+        if line == LINES_IN_BLOCK {
+            return None;
+        }
+        let hole_start = line;
+        while line < LINES_IN_BLOCK && marks[line].load(Ordering::Relaxed) == 0 {
+            line += 1;
+        }
+        Some((
+            block + (hole_start << LOG_BYTES_IN_LINE),
+            block + (line << LOG_BYTES_IN_LINE),
+            line,
+        ))
+    }
+
+    fn header(block: Address) -> &'static BlockHeader {
+        unsafe { &*block.to_ptr::<BlockHeader>() }
+    }
+
+    fn header_mut(block: Address) -> &'static mut BlockHeader {
+        unsafe { &mut *block.to_mut_ptr::<BlockHeader>() }
+    }
+
+    fn block_of(object: ObjectReference) -> Address {
+        VM::VMObjectModel::object_start_ref(object).align_down(BYTES_IN_BLOCK)
+    }
+
+    fn is_defrag_source(object: ObjectReference) -> bool {
+        Self::header(Self::block_of(object))
+            .defrag_source
+            .load(Ordering::Relaxed)
+    }
+
+    /// Mark the block and all the lines that the object spans.
+    fn mark_lines(object: ObjectReference) {
+        let start = VM::VMObjectModel::object_start_ref(object);
+        let end = VM::VMObjectModel::get_object_end_address(object);
+        let block = start.align_down(BYTES_IN_BLOCK);
+        let header = Self::header(block);
+        header.marked.store(true, Ordering::Relaxed);
+        let first_line = (start - block) >> LOG_BYTES_IN_LINE;
+        let last_line = (end - 1usize - block) >> LOG_BYTES_IN_LINE;
+        for mark in &header.line_marks[first_line..=last_line] {
+            mark.store(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Set the mark bit of the object. Returns true if the object was not marked before.
+    fn test_and_mark(&self, object: ObjectReference) -> bool {
+        let mark_state = self.mark_state.load(Ordering::Relaxed);
+        MARK_BIT_SPEC
+            .compare_exchange::<VM>(object, MARK_BIT - mark_state, mark_state)
+            .is_ok()
+    }
+
+    fn is_marked(&self, object: ObjectReference) -> bool {
+        MARK_BIT_SPEC.load::<VM>(object) == self.mark_state.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::constants::BYTES_IN_WORD;
+    use crate::util::memory;
+    use crate::util::test_util::mock_vm::MockVM;
+    use crate::util::test_util::{map_heap_memory, serial_test};
+
+    type Space = ImmixSpace<MockVM>;
+
+    /// Map and clear `n` blocks.
+    fn blocks(n: usize) -> Vec<Address> {
+        let start = map_heap_memory(6 << 22, n * BYTES_IN_BLOCK);
+        (0..n)
+            .map(|i| {
+                let block = start + i * BYTES_IN_BLOCK;
+                memory::zero(block, BYTES_IN_BLOCK);
+                block
+            })
+            .collect()
+    }
+
+    /// Put an object with `n_refs` references at the address.
+    fn object_at(start: Address, n_refs: usize) -> ObjectReference {
+        unsafe {
+            (start + BYTES_IN_WORD).store(n_refs);
+            let object = start.to_object_reference();
+            alloc_bit::set_alloc_bit(object);
+            object
+        }
+    }
+
+    fn line_is_marked(block: Address, line: usize) -> bool {
+        Space::header(block).line_marks[line].load(Ordering::Relaxed) != 0
+    }
+
+    #[test]
+    fn test_sweep_lines() {
+        serial_test(|| {
+            let block = blocks(1)[0];
+            let first = object_at(block + BYTES_IN_LINE, 0);
+            // The object spans two lines
+            let second = object_at(block + 3 * BYTES_IN_LINE - 16, 2);
+            let dead = object_at(block + 5 * BYTES_IN_LINE, 0);
+            Space::mark_lines(first);
+            Space::mark_lines(second);
+            assert!(Space::sweep_block(block, |object| object != dead));
+            assert_eq!(Space::header(block).live_lines, 3);
+            for line in 1..=3 {
+                assert!(line_is_marked(block, line));
+            }
+            assert!(!line_is_marked(block, 4));
+            assert!(!line_is_marked(block, 5));
+            assert!(alloc_bit::is_alloced(first));
+            assert!(alloc_bit::is_alloced(second));
+            assert!(!alloc_bit::is_alloced(dead));
+        })
+    }
+
+    #[test]
+    fn test_sweep_blocks() {
+        serial_test(|| {
+            let blocks = blocks(2);
+            let live = object_at(blocks[0] + BYTES_IN_LINE, 0);
+            let dead = object_at(blocks[1] + BYTES_IN_LINE, 0);
+            Space::mark_lines(live);
+            assert!(Space::sweep_block(blocks[0], |object| object == live));
+            // A block without marked objects is released without being swept
+            assert!(!Space::sweep_block(blocks[1], |_| false));
+            assert!(alloc_bit::is_alloced(dead));
+            alloc_bit::unset_alloc_bit(live);
+            alloc_bit::unset_alloc_bit(dead);
+        })
+    }
+
+    #[test]
+    fn test_select_defrag_sources() {
+        serial_test(|| {
+            let blocks = blocks(4);
+            for (&block, &live_lines) in blocks.iter().zip([100, 10, 50, 0].iter()) {
+                Space::header_mut(block).live_lines = live_lines;
+            }
+            // One block of headroom takes the sparsest blocks, as long as their live lines fit
+            Space::select_defrag_sources(blocks.clone(), 1);
+            let sources: Vec<bool> = blocks
+                .iter()
+                .map(|&block| Space::header(block).defrag_source.load(Ordering::Relaxed))
+                .collect();
+            assert_eq!(sources, vec![false, true, true, true]);
+        })
+    }
+}
@@ -1,13 +1,15 @@
 use crate::policy::space::{CommonSpace, Space, SFT};
 use crate::util::address::Address;
+use crate::util::alloc_bit;
 use crate::util::heap::{MonotonePageResource, PageResource, VMRequest};
 
 use crate::util::constants::CARD_META_PAGES_PER_REGION;
 use crate::util::ObjectReference;
 
+use crate::plan::PlanConstraints;
 use crate::plan::TransitiveClosure;
 use crate::util::header_byte;
-use crate::vm::ObjectModel;
+use crate::util::side_metadata::MetadataSpec;
 
 use crate::policy::space::SpaceOptions;
 use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
@@ -19,11 +21,17 @@ pub struct ImmortalSpace<VM: VMBinding> {
     mark_state: u8,
     common: UnsafeCell<CommonSpace<VM>>,
     pr: MonotonePageResource<VM>,
+    constraints: &'static PlanConstraints,
 }
 
 unsafe impl<VM: VMBinding> Sync for ImmortalSpace<VM> {}
 
-const GC_MARK_BIT_MASK: u8 = 1;
+/// The mark bit of the immortal objects.
+const MARK_BIT_SPEC: MetadataSpec = MetadataSpec::InHeader {
+    bit_offset: 0,
+    num_of_bits: 1,
+};
+const MARK_BIT: u8 = 1;
 const META_DATA_PAGES_PER_REGION: usize = CARD_META_PAGES_PER_REGION;
 
 impl<VM: VMBinding> SFT for ImmortalSpace<VM> {
@@ -38,12 +46,18 @@ impl<VM: VMBinding> SFT for ImmortalSpace<VM> {
         true
     }
     fn initialize_header(&self, object: ObjectReference, _alloc: bool) {
-        let old_value = VM::VMObjectModel::read_available_byte(object);
-        let mut new_value = (old_value & GC_MARK_BIT_MASK) | self.mark_state;
-        if header_byte::NEEDS_UNLOGGED_BIT {
-            new_value |= header_byte::UNLOGGED_BIT;
+        MARK_BIT_SPEC.store::<VM>(object, self.mark_state);
+        if self.constraints.needs_log_bit_in_header {
+            header_byte::mark_as_unlogged::<VM>(object);
         }
-        VM::VMObjectModel::write_available_byte(object, new_value);
+    }
+
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        alloc_bit::find_object_by_alloc_bits::<VM>(ptr, max_search_bytes)
     }
 }
 
@@ -72,6 +86,12 @@ impl<VM: VMBinding> Space<VM> for ImmortalSpace<VM> {
     fn release_multiple_pages(&mut self, _start: Address) {
         panic!("immortalspace only releases pages enmasse")
     }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        for (start, bytes) in self.pr.allocated_extents() {
+            alloc_bit::for_each_alloced_object(start, bytes, &mut *f);
+        }
+    }
 }
 
 impl<VM: VMBinding> ImmortalSpace<VM> {
@@ -82,6 +102,7 @@ impl<VM: VMBinding> ImmortalSpace<VM> {
         vm_map: &'static VMMap,
         mmapper: &'static Mmapper,
         heap: &mut HeapMeta,
+        constraints: &'static PlanConstraints,
     ) -> Self {
         let common = CommonSpace::new(
             SpaceOptions {
@@ -108,31 +129,23 @@ impl<VM: VMBinding> ImmortalSpace<VM> {
                 )
             },
             common: UnsafeCell::new(common),
+            constraints,
         }
     }
 
     fn test_and_mark(object: ObjectReference, value: u8) -> bool {
-        let mut old_value = VM::VMObjectModel::prepare_available_bits(object);
-        let mut mark_bit = (old_value as u8) & GC_MARK_BIT_MASK;
-        if mark_bit == value {
-            return false;
-        }
-        while !VM::VMObjectModel::attempt_available_bits(
-            object,
-            old_value,
-            old_value ^ (GC_MARK_BIT_MASK as usize),
-        ) {
-            old_value = VM::VMObjectModel::prepare_available_bits(object);
-            mark_bit = (old_value as u8) & GC_MARK_BIT_MASK;
-            if mark_bit == value {
-                return false;
-            }
-        }
-        true
+        MARK_BIT_SPEC
+            .compare_exchange::<VM>(object, MARK_BIT - value, value)
+            .is_ok()
     }
 
-    pub fn prepare(&mut self) {
-        self.mark_state = GC_MARK_BIT_MASK - self.mark_state;
+    /// Flip the mark state in a full heap GC, so all the objects become unmarked. A nursery GC
+    /// keeps the mark state (sticky mark bits): the objects that are marked already, including the
+    /// objects allocated since the last GC, are treated as old, and are not traced again.
+    pub fn prepare(&mut self, full_heap: bool) {
+        if full_heap {
+            self.mark_state = MARK_BIT - self.mark_state;
+        }
     }
 
     pub fn release(&mut self) {}
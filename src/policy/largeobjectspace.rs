@@ -1,5 +1,6 @@
 use std::cell::UnsafeCell;
 
+use crate::plan::PlanConstraints;
 use crate::plan::TransitiveClosure;
 use crate::policy::space::SpaceOptions;
 use crate::policy::space::{CommonSpace, Space, SFT};
@@ -8,18 +9,24 @@ use crate::util::header_byte;
 use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
 use crate::util::heap::HeapMeta;
 use crate::util::heap::{FreeListPageResource, PageResource, VMRequest};
+use crate::util::side_metadata::MetadataSpec;
 use crate::util::treadmill::TreadMill;
 use crate::util::OpaquePointer;
+use crate::util::{alloc_bit, pin_bit};
 use crate::util::{Address, ObjectReference};
 use crate::vm::ObjectModel;
 use crate::vm::VMBinding;
-use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[allow(unused)]
 const PAGE_MASK: usize = !(BYTES_IN_PAGE - 1);
-const MARK_BIT: usize = 0b01;
-const NURSERY_BIT: usize = 0b10;
-const LOS_BIT_MASK: usize = 0b11;
+/// The mark bit and the nursery bit of the large objects.
+const LOS_BITS_SPEC: MetadataSpec = MetadataSpec::InHeader {
+    bit_offset: 0,
+    num_of_bits: 2,
+};
+const MARK_BIT: u8 = 0b01;
+const NURSERY_BIT: u8 = 0b10;
+const LOS_BIT_MASK: u8 = 0b11;
 
 const USE_PRECEEDING_GC_HEADER: bool = true;
 const PRECEEDING_GC_HEADER_WORDS: usize = 1;
@@ -28,9 +35,10 @@ const PRECEEDING_GC_HEADER_BYTES: usize = PRECEEDING_GC_HEADER_WORDS << LOG_BYTE
 pub struct LargeObjectSpace<VM: VMBinding> {
     common: UnsafeCell<CommonSpace<VM>>,
     pr: FreeListPageResource<VM>,
-    mark_state: usize,
+    mark_state: u8,
     in_nursery_gc: bool,
     treadmill: TreadMill,
+    constraints: &'static PlanConstraints,
 }
 
 unsafe impl<VM: VMBinding> Sync for LargeObjectSpace<VM> {}
@@ -47,12 +55,11 @@ impl<VM: VMBinding> SFT for LargeObjectSpace<VM> {
         true
     }
     fn initialize_header(&self, object: ObjectReference, alloc: bool) {
-        let old_value = Self::read_gc_word(object);
-        let mut new_value = (old_value & (!LOS_BIT_MASK)) | self.mark_state;
+        let mut new_value = self.mark_state;
         if alloc {
             new_value |= NURSERY_BIT;
         }
-        Self::write_gc_word(object, new_value);
+        LOS_BITS_SPEC.store::<VM>(object, new_value);
         let cell = VM::VMObjectModel::object_start_ref(object)
             - if USE_PRECEEDING_GC_HEADER {
                 PRECEEDING_GC_HEADER_BYTES
@@ -60,11 +67,18 @@ impl<VM: VMBinding> SFT for LargeObjectSpace<VM> {
                 0
             };
         self.treadmill.add_to_treadmill(cell, alloc);
-        if header_byte::NEEDS_UNLOGGED_BIT {
-            let b = VM::VMObjectModel::read_available_byte(object);
-            VM::VMObjectModel::write_available_byte(object, b | header_byte::UNLOGGED_BIT);
+        if self.constraints.needs_log_bit_in_header {
+            header_byte::mark_as_unlogged::<VM>(object);
         }
     }
+
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        _max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        self.find_object_in_cell(ptr)
+    }
 }
 
 impl<VM: VMBinding> Space<VM> for LargeObjectSpace<VM> {
@@ -93,6 +107,12 @@ impl<VM: VMBinding> Space<VM> for LargeObjectSpace<VM> {
     fn release_multiple_pages(&mut self, start: Address) {
         self.pr.release_pages(start);
     }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        for cell in self.treadmill.cells() {
+            f(Self::object_in_cell(cell));
+        }
+    }
 }
 
 impl<VM: VMBinding> LargeObjectSpace<VM> {
@@ -103,6 +123,7 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
         vm_map: &'static VMMap,
         mmapper: &'static Mmapper,
         heap: &mut HeapMeta,
+        constraints: &'static PlanConstraints,
     ) -> Self {
         let common = CommonSpace::new(
             SpaceOptions {
@@ -126,6 +147,7 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
             mark_state: 0,
             in_nursery_gc: false,
             treadmill: TreadMill::new(),
+            constraints,
         }
     }
 
@@ -177,16 +199,45 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
         if sweep_nursery {
             for cell in self.treadmill.collect_nursery() {
                 // println!("- cn {}", cell);
+                Self::clear_side_bits(cell);
                 self.pr.release_pages(get_super_page(cell));
             }
         } else {
             for cell in self.treadmill.collect() {
                 // println!("- ts {}", cell);
+                Self::clear_side_bits(cell);
                 self.pr.release_pages(get_super_page(cell));
             }
         }
     }
 
+    fn object_in_cell(cell: Address) -> ObjectReference {
+        let start = if USE_PRECEEDING_GC_HEADER {
+            cell + PRECEEDING_GC_HEADER_BYTES
+        } else {
+            cell
+        };
+        unsafe { VM::VMObjectModel::get_object_from_start_address(start) }
+    }
+
+    /// Find the object whose cell in the treadmill contains the address.
+    fn find_object_in_cell(&self, ptr: Address) -> Option<ObjectReference> {
+        self.treadmill
+            .find(|cell| {
+                let object = Self::object_in_cell(cell);
+                object.to_address() <= ptr
+                    && ptr < VM::VMObjectModel::get_object_end_address(object)
+            })
+            .map(Self::object_in_cell)
+    }
+
+    /// Clear the alloc bit and the pin bit of the dead object in the cell.
+    fn clear_side_bits(cell: Address) {
+        let object = Self::object_in_cell(cell);
+        alloc_bit::unset_alloc_bit(object);
+        pin_bit::unpin_object(object);
+    }
+
     pub fn allocate_pages(&self, tls: OpaquePointer, pages: usize) -> Address {
         let start = self.acquire(tls, pages);
         if start.is_zero() {
@@ -199,71 +250,30 @@ impl<VM: VMBinding> LargeObjectSpace<VM> {
         }
     }
 
-    fn test_and_mark(&self, object: ObjectReference, value: usize) -> bool {
+    fn test_and_mark(&self, object: ObjectReference, value: u8) -> bool {
         let mask = if self.in_nursery_gc {
             LOS_BIT_MASK
         } else {
             MARK_BIT
         };
-        let mut old_value = Self::read_gc_word(object);
-        let mut mark_bit = old_value & mask;
-        if mark_bit == value {
-            return false;
-        }
-        while !Self::attempt_gc_word(
-            object,
-            old_value,
-            old_value & (!LOS_BIT_MASK as usize) | value as usize,
-        ) {
-            old_value = Self::read_gc_word(object);
-            mark_bit = old_value & mask;
-            if mark_bit == value {
-                return false;
-            }
-        }
-        true
+        // Marking an object also clears its nursery bit
+        LOS_BITS_SPEC
+            .fetch_update::<VM, _>(object, |old_value| {
+                if old_value & mask == value {
+                    None
+                } else {
+                    Some(value)
+                }
+            })
+            .is_ok()
     }
 
-    fn test_mark_bit(&self, object: ObjectReference, value: usize) -> bool {
-        Self::read_gc_word(object) & MARK_BIT == value
+    fn test_mark_bit(&self, object: ObjectReference, value: u8) -> bool {
+        LOS_BITS_SPEC.load::<VM>(object) & MARK_BIT == value
     }
 
     fn is_in_nursery(&self, object: ObjectReference) -> bool {
-        Self::read_gc_word(object) & NURSERY_BIT == NURSERY_BIT
-    }
-
-    fn read_gc_word(o: ObjectReference) -> usize {
-        if USE_PRECEEDING_GC_HEADER {
-            unsafe {
-                (VM::VMObjectModel::object_start_ref(o) - PRECEEDING_GC_HEADER_BYTES)
-                    .load::<usize>()
-            }
-        } else {
-            VM::VMObjectModel::read_available_bits_word(o)
-        }
-    }
-
-    fn write_gc_word(o: ObjectReference, value: usize) {
-        if USE_PRECEEDING_GC_HEADER {
-            unsafe {
-                (VM::VMObjectModel::object_start_ref(o) - PRECEEDING_GC_HEADER_BYTES)
-                    .store::<usize>(value)
-            };
-        } else {
-            VM::VMObjectModel::write_available_bits_word(o, value);
-        }
-    }
-
-    fn attempt_gc_word(o: ObjectReference, old: usize, new: usize) -> bool {
-        if USE_PRECEEDING_GC_HEADER {
-            unsafe {
-                (VM::VMObjectModel::object_start_ref(o) - PRECEEDING_GC_HEADER_BYTES)
-                    .compare_exchange::<AtomicUsize>(old, new, Ordering::SeqCst, Ordering::SeqCst)
-                    .is_ok()
-            }
-        } else {
-            VM::VMObjectModel::attempt_available_bits(o, old, new)
-        }
+        LOS_BITS_SPEC.load::<VM>(object) & NURSERY_BIT == NURSERY_BIT
     }
 }
 
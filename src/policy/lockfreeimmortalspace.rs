@@ -1,10 +1,11 @@
+use crate::mmtk::MMAPPER;
 use crate::policy::space::{CommonSpace, Space, SFT};
 use crate::util::address::Address;
-use crate::util::heap::PageResource;
+use crate::util::alloc_bit;
+use crate::util::heap::{HeapMeta, PageResource};
 
 use crate::util::ObjectReference;
 
-use crate::plan::Plan;
 use crate::util::conversions;
 use crate::util::heap::layout::heap_layout::VMMap;
 use crate::util::heap::layout::vm_layout_constants::{
@@ -17,7 +18,6 @@ use std::marker::PhantomData;
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct LockFreeImmortalSpace<VM: VMBinding> {
-    #[allow(unused)]
     name: &'static str,
     /// Heap range start
     ///
@@ -47,6 +47,14 @@ impl<VM: VMBinding> SFT for LockFreeImmortalSpace<VM> {
     fn initialize_header(&self, _object: ObjectReference, _alloc: bool) {
         unimplemented!()
     }
+
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        alloc_bit::find_object_by_alloc_bits::<VM>(ptr, max_search_bytes)
+    }
 }
 
 impl<VM: VMBinding> Space<VM> for LockFreeImmortalSpace<VM> {
@@ -59,6 +67,9 @@ impl<VM: VMBinding> Space<VM> for LockFreeImmortalSpace<VM> {
     fn get_page_resource(&self) -> &dyn PageResource<VM> {
         unimplemented!()
     }
+    fn get_name(&self) -> &'static str {
+        self.name
+    }
     fn common(&self) -> &CommonSpace<VM> {
         unimplemented!()
     }
@@ -70,6 +81,11 @@ impl<VM: VMBinding> Space<VM> for LockFreeImmortalSpace<VM> {
         panic!("immortalspace only releases pages enmasse")
     }
 
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        let cursor = unsafe { Address::from_usize(self.cursor.load(Ordering::Relaxed)) };
+        alloc_bit::for_each_alloced_object(AVAILABLE_START, cursor - AVAILABLE_START, f);
+    }
+
     fn init(&mut self, _vm_map: &'static VMMap) {
         let total_pages = VM::VMActivePlan::global()
             .base()
@@ -87,6 +103,10 @@ impl<VM: VMBinding> Space<VM> for LockFreeImmortalSpace<VM> {
         self.limit = AVAILABLE_START + total_bytes;
         // Eagerly memory map the entire heap (also zero all the memory)
         crate::util::memory::dzmmap(AVAILABLE_START, total_bytes).unwrap();
+        // This space does not grow, so map its side metadata now
+        for spec in HeapMeta::enabled_global_side_metadata_specs().iter() {
+            spec.ensure_mapped(&*MMAPPER, AVAILABLE_START, total_bytes);
+        }
     }
 
     fn reserved_pages(&self) -> usize {
@@ -0,0 +1,517 @@
+use std::cell::UnsafeCell;
+use std::sync::Mutex;
+
+use crate::plan::TransitiveClosure;
+use crate::policy::space::SpaceOptions;
+use crate::policy::space::{CommonSpace, Space, SFT};
+use crate::util::alloc::allocator::{align_allocation_no_fill, fill_alignment_gap};
+use crate::util::alloc_bit;
+use crate::util::constants::{BYTES_IN_INT, BYTES_IN_PAGE, BYTES_IN_WORD};
+use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::{FreeListPageResource, PageResource, VMRequest};
+use crate::util::pin_bit;
+use crate::util::side_metadata::MetadataSpec;
+use crate::util::OpaquePointer;
+use crate::util::{Address, ObjectReference};
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+
+/// Blocks are the unit the space acquires pages in. Objects are allocated contiguously in a block.
+pub const LOG_BYTES_IN_BLOCK: usize = 16;
+pub const BYTES_IN_BLOCK: usize = 1 << LOG_BYTES_IN_BLOCK;
+const PAGES_IN_BLOCK: usize = BYTES_IN_BLOCK / BYTES_IN_PAGE;
+
+/// The largest object that can be allocated in the mark-compact space.
+/// Larger objects have to be allocated in the large object space.
+pub const MAX_MARKCOMPACT_OBJECT_SIZE: usize = BYTES_IN_BLOCK >> 1;
+
+/// Each object is preceded by an extra header word, which holds the address the object
+/// is moved to during a GC. The allocator reserves the word in front of every object.
+pub const GC_EXTRA_HEADER_BYTES: usize = BYTES_IN_WORD;
+
+/// The mark bit, set by the marking trace.
+const MARK_BIT_SPEC: MetadataSpec = MetadataSpec::InHeader {
+    bit_offset: 2,
+    num_of_bits: 1,
+};
+/// Set by the trace that updates references, so that each live object is scanned once in it.
+const VISITED_BIT_SPEC: MetadataSpec = MetadataSpec::InHeader {
+    bit_offset: 3,
+    num_of_bits: 1,
+};
+
+/// The metadata at the start of each block.
+#[repr(C)]
+struct BlockHeader {
+    /// The end of the allocated data in the block. The objects lie between the block header and here.
+    data_end: Address,
+}
+
+pub const BLOCK_HEADER_BYTES: usize = std::mem::size_of::<BlockHeader>();
+
+/// A space managed by Lisp-2 style mark-compact. The space is divided into blocks, and the
+/// objects in a block are bump-allocated, so that the space can be walked linearly. A GC marks
+/// the live objects, computes the address of each live object after sliding all of them towards
+/// the start of the space, updates all the references to the new addresses, and then moves the objects.
+pub struct MarkCompactSpace<VM: VMBinding> {
+    common: UnsafeCell<CommonSpace<VM>>,
+    pr: FreeListPageResource<VM>,
+    /// All the blocks in this space.
+    blocks: Mutex<Vec<Address>>,
+}
+
+unsafe impl<VM: VMBinding> Sync for MarkCompactSpace<VM> {}
+
+impl<VM: VMBinding> SFT for MarkCompactSpace<VM> {
+    fn is_live(&self, object: ObjectReference) -> bool {
+        Self::is_marked(object)
+    }
+    fn is_movable(&self) -> bool {
+        true
+    }
+    #[cfg(feature = "sanity")]
+    fn is_sane(&self) -> bool {
+        true
+    }
+    fn initialize_header(&self, _object: ObjectReference, _alloc: bool) {}
+
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        alloc_bit::find_object_by_alloc_bits::<VM>(ptr, max_search_bytes)
+    }
+}
+
+impl<VM: VMBinding> Space<VM> for MarkCompactSpace<VM> {
+    fn as_space(&self) -> &dyn Space<VM> {
+        self
+    }
+    fn as_sft(&self) -> &(dyn SFT + Sync + 'static) {
+        self
+    }
+    fn get_page_resource(&self) -> &dyn PageResource<VM> {
+        &self.pr
+    }
+    fn init(&mut self, _vm_map: &'static VMMap) {
+        let me = unsafe { &*(self as *const Self) };
+        self.pr.bind_space(me);
+    }
+
+    fn common(&self) -> &CommonSpace<VM> {
+        unsafe { &*self.common.get() }
+    }
+
+    unsafe fn unsafe_common_mut(&self) -> &mut CommonSpace<VM> {
+        &mut *self.common.get()
+    }
+
+    fn release_multiple_pages(&mut self, start: Address) {
+        self.pr.release_pages(start);
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        // The data ends of the blocks are only recorded before GCs, so use the alloc bits.
+        for &block in self.blocks.lock().unwrap().iter() {
+            let data_start = Self::data_start(block);
+            alloc_bit::for_each_alloced_object(
+                data_start,
+                block + BYTES_IN_BLOCK - data_start,
+                &mut *f,
+            );
+        }
+    }
+}
+
+impl<VM: VMBinding> MarkCompactSpace<VM> {
+    pub fn new(
+        name: &'static str,
+        vmrequest: VMRequest,
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        heap: &mut HeapMeta,
+    ) -> Self {
+        // Linear scans skip the alignment gaps by their fill value. The extra headers hold zero
+        // or an address, so the fill value must not look like either of them.
+        #[allow(clippy::assertions_on_constants)]
+        {
+            debug_assert!(VM::ALIGNMENT_VALUE & (BYTES_IN_INT - 1) != 0);
+        }
+        let common = CommonSpace::new(
+            SpaceOptions {
+                name,
+                movable: true,
+                immortal: false,
+                zeroed: true,
+                vmrequest,
+            },
+            vm_map,
+            mmapper,
+            heap,
+        );
+        MarkCompactSpace {
+            pr: if vmrequest.is_discontiguous() {
+                FreeListPageResource::new_discontiguous(0, vm_map)
+            } else {
+                FreeListPageResource::new_contiguous(common.start, common.extent, 0, vm_map)
+            },
+            common: UnsafeCell::new(common),
+            blocks: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn prepare(&self) {}
+
+    pub fn release(&self) {}
+
+    /// Trace an object in the marking trace.
+    #[inline]
+    pub fn trace_mark_object<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+    ) -> ObjectReference {
+        if Self::test_and_set_bit(object, MARK_BIT_SPEC) {
+            trace.process_node(object);
+        }
+        object
+    }
+
+    /// Trace an object in the trace that updates references. Returns the address of the object
+    /// after compaction. The object is scanned (at its current address) when it is first visited.
+    #[inline]
+    pub fn trace_forward_object<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+    ) -> ObjectReference {
+        debug_assert!(Self::is_marked(object));
+        if Self::test_and_set_bit(object, VISITED_BIT_SPEC) {
+            trace.process_node(object);
+        }
+        Self::get_forwarded_object(object)
+    }
+
+    /// Mark an object outside this space as visited in the trace that updates references.
+    /// Returns true if it was not visited before. The caller has to clear the bit with
+    /// `clear_visited()` before the next GC.
+    #[inline]
+    pub fn test_and_set_visited(object: ObjectReference) -> bool {
+        Self::test_and_set_bit(object, VISITED_BIT_SPEC)
+    }
+
+    pub fn clear_visited(object: ObjectReference) {
+        VISITED_BIT_SPEC.store::<VM>(object, 0);
+    }
+
+    /// Get a new block for an allocator. Returns zero if the space cannot get more memory.
+    pub fn get_block(&self, tls: OpaquePointer) -> Address {
+        let block = self.acquire(tls, PAGES_IN_BLOCK);
+        if block.is_zero() {
+            return block;
+        }
+        debug_assert!(block.is_aligned_to(BYTES_IN_BLOCK));
+        Self::set_data_end(block, Self::data_start(block));
+        self.blocks.lock().unwrap().push(block);
+        block
+    }
+
+    /// Get the start of the object data in a block.
+    pub fn data_start(block: Address) -> Address {
+        block + BLOCK_HEADER_BYTES
+    }
+
+    /// Record the end of the allocated data in a block. Allocators have to do this before they
+    /// leave a block, and before each GC.
+    pub fn set_data_end(block: Address, data_end: Address) {
+        debug_assert!(data_end >= Self::data_start(block) && data_end <= block + BYTES_IN_BLOCK);
+        Self::header_mut(block).data_end = data_end;
+    }
+
+    /// Compute the address of every live object after compaction, and store it in the extra
+    /// header of the object. The live objects are slid towards the start of the space, keeping
+    /// their order and the alignment they require. Pinned objects stay where they are, and the
+    /// objects after them are slid towards them.
+    pub fn calculate_forwarding_addresses(&self) {
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.sort_by_key(|block| block.as_usize());
+        Self::calculate_forwarding_addresses_in(&blocks);
+    }
+
+    /// Compute the forwarding addresses for the objects in the blocks, which are sorted by address.
+    fn calculate_forwarding_addresses_in(blocks: &[Address]) {
+        let mut to_blocks = blocks.iter();
+        let mut to_cursor = unsafe { Address::zero() };
+        let mut to_limit = unsafe { Address::zero() };
+        for &block in blocks.iter() {
+            Self::for_each_object(block, |header, object| {
+                if !Self::is_marked(object) {
+                    return;
+                }
+                let size = VM::VMObjectModel::get_size_when_copied(object);
+                if pin_bit::is_pinned(object) {
+                    // Skip to the block of the object. The blocks skipped are left empty.
+                    while to_limit != block + BYTES_IN_BLOCK {
+                        let to_block = *to_blocks.next().unwrap();
+                        to_limit = to_block + BYTES_IN_BLOCK;
+                    }
+                    debug_assert!(to_cursor <= header);
+                    unsafe { header.store(header) };
+                    to_cursor = header + GC_EXTRA_HEADER_BYTES + size;
+                    return;
+                }
+                let align = VM::VMObjectModel::get_align_when_copied(object);
+                let offset = VM::VMObjectModel::get_align_offset_when_copied(object);
+                let region = loop {
+                    if !to_cursor.is_zero() {
+                        let region = align_allocation_no_fill::<VM>(
+                            to_cursor,
+                            align,
+                            offset + GC_EXTRA_HEADER_BYTES as isize,
+                        );
+                        if region + GC_EXTRA_HEADER_BYTES + size <= to_limit {
+                            break region;
+                        }
+                    }
+                    // The object does not fit in the rest of the block. Move on to the next block.
+                    // It never passes the block we are walking, as the object fits in it.
+                    let to_block = *to_blocks.next().unwrap();
+                    to_cursor = Self::data_start(to_block);
+                    to_limit = to_block + BYTES_IN_BLOCK;
+                };
+                debug_assert!(region <= header);
+                unsafe { header.store(region) };
+                to_cursor = region + GC_EXTRA_HEADER_BYTES + size;
+            });
+        }
+    }
+
+    /// Move the live objects to the addresses computed by `calculate_forwarding_addresses()`.
+    /// The blocks left empty are returned to the page resource.
+    pub fn compact(&self) {
+        // FIXME: We need a safe implementation
+        #[allow(clippy::cast_ref_to_mut)]
+        let pr: &mut FreeListPageResource<VM> = unsafe { &mut *(&self.pr as *const _ as *mut _) };
+        let mut blocks = self.blocks.lock().unwrap();
+        // The block we are moving objects into, and the end of the moved objects in it.
+        let mut to_block = unsafe { Address::zero() };
+        let mut to_cursor = unsafe { Address::zero() };
+        let mut data_ends = Vec::with_capacity(blocks.len());
+        for &block in blocks.iter() {
+            Self::for_each_object(block, |header, object| {
+                let alloced = alloc_bit::is_alloced(object);
+                if alloced {
+                    alloc_bit::unset_alloc_bit(object);
+                }
+                if !Self::is_marked(object) {
+                    pin_bit::unpin_object(object);
+                    return;
+                }
+                let region: Address = unsafe { header.load() };
+                if region.align_down(BYTES_IN_BLOCK) != to_block {
+                    if !to_block.is_zero() {
+                        data_ends.push((to_block, to_cursor));
+                    }
+                    to_block = region.align_down(BYTES_IN_BLOCK);
+                    to_cursor = Self::data_start(to_block);
+                }
+                // Fill the alignment gap, so that the block can be walked again.
+                fill_alignment_gap::<VM>(to_cursor, region);
+                let new_object = Self::get_forwarded_object(object);
+                MARK_BIT_SPEC.store::<VM>(object, 0);
+                VISITED_BIT_SPEC.store::<VM>(object, 0);
+                to_cursor = if region == header {
+                    VM::VMObjectModel::get_object_end_address(object)
+                } else {
+                    // The new location may overlap with the old one, and it is never after the old one.
+                    // The object model has to copy the object in a way that is safe with the overlap.
+                    VM::VMObjectModel::copy_to(object, new_object, region + GC_EXTRA_HEADER_BYTES)
+                };
+                if alloced {
+                    alloc_bit::set_alloc_bit(new_object);
+                }
+                unsafe { region.store(Address::zero()) };
+            });
+        }
+        if !to_block.is_zero() {
+            data_ends.push((to_block, to_cursor));
+        }
+
+        for &(block, data_end) in data_ends.iter() {
+            Self::set_data_end(block, data_end);
+        }
+        // The blocks are compacted in order, so the blocks with objects are in the same order as
+        // in the list. The other blocks are empty, and they are not always at the end of the list,
+        // as pinned objects do not move.
+        let mut data_ends = data_ends.iter().peekable();
+        blocks.retain(|&block| {
+            if data_ends.peek().map(|&&(to_block, _)| to_block) == Some(block) {
+                data_ends.next();
+                true
+            } else {
+                pr.release_pages(block);
+                false
+            }
+        });
+        debug_assert!(data_ends.next().is_none());
+    }
+
+    /// Walk the objects in a block in address order. `f` is called with the extra header and the
+    /// object reference of each object. The end of an object is read before `f` is called, so `f`
+    /// may move the object to an earlier address.
+    fn for_each_object<F: FnMut(Address, ObjectReference)>(block: Address, mut f: F) {
+        let data_end = Self::header(block).data_end;
+        let mut cursor = Self::data_start(block);
+        while cursor < data_end {
+            // Skip the alignment gap
+            if unsafe { cursor.load::<u32>() } == VM::ALIGNMENT_VALUE as u32 {
+                cursor += BYTES_IN_INT;
+                continue;
+            }
+            let header = cursor;
+            let object = unsafe {
+                VM::VMObjectModel::get_object_from_start_address(header + GC_EXTRA_HEADER_BYTES)
+            };
+            cursor = VM::VMObjectModel::get_object_end_address(object);
+            f(header, object);
+        }
+    }
+
+    /// Get the address of an object after compaction. This is only valid after
+    /// `calculate_forwarding_addresses()` and before `compact()` in a GC.
+    fn get_forwarded_object(object: ObjectReference) -> ObjectReference {
+        let header = VM::VMObjectModel::object_start_ref(object) - GC_EXTRA_HEADER_BYTES;
+        let region: Address = unsafe { header.load() };
+        VM::VMObjectModel::get_reference_when_copied_to(object, region + GC_EXTRA_HEADER_BYTES)
+    }
+
+    fn header(block: Address) -> &'static BlockHeader {
+        unsafe { &*block.to_ptr::<BlockHeader>() }
+    }
+
+    fn header_mut(block: Address) -> &'static mut BlockHeader {
+        unsafe { &mut *block.to_mut_ptr::<BlockHeader>() }
+    }
+
+    /// Set a bit of the object. Returns true if the bit was not set before.
+    fn test_and_set_bit(object: ObjectReference, bit: MetadataSpec) -> bool {
+        if bit.load::<VM>(object) != 0 {
+            return false;
+        }
+        bit.fetch_or::<VM>(object, 1) == 0
+    }
+
+    fn is_marked(object: ObjectReference) -> bool {
+        MARK_BIT_SPEC.load::<VM>(object) != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::mock_vm::{MockObjectModel, MockVM};
+    use crate::util::test_util::{map_heap_memory, serial_test};
+
+    type Space = MarkCompactSpace<MockVM>;
+
+    /// Map two blocks, and fill them with objects with the given numbers of references.
+    fn blocks(objects: [&[usize]; 2]) -> ([Address; 2], Vec<Vec<ObjectReference>>) {
+        let start = map_heap_memory(5 << 22, 2 * BYTES_IN_BLOCK);
+        let blocks = [start, start + BYTES_IN_BLOCK];
+        let objects = blocks
+            .iter()
+            .zip(objects.iter())
+            .map(|(&block, n_refs)| {
+                let mut cursor = Space::data_start(block);
+                let objects = n_refs
+                    .iter()
+                    .map(|&n_refs| {
+                        let object = unsafe {
+                            cursor.store(Address::zero());
+                            (cursor + GC_EXTRA_HEADER_BYTES).store(0usize);
+                            (cursor + GC_EXTRA_HEADER_BYTES + BYTES_IN_WORD).store(n_refs);
+                            (cursor + GC_EXTRA_HEADER_BYTES).to_object_reference()
+                        };
+                        cursor = MockObjectModel::get_object_end_address(object);
+                        object
+                    })
+                    .collect();
+                Space::set_data_end(block, cursor);
+                objects
+            })
+            .collect();
+        (blocks, objects)
+    }
+
+    fn mark(object: ObjectReference) {
+        assert!(Space::test_and_set_bit(object, MARK_BIT_SPEC));
+        assert!(Space::is_marked(object));
+    }
+
+    fn forwarded_to(object: ObjectReference) -> Address {
+        Space::get_forwarded_object(object).to_address()
+    }
+
+    #[test]
+    fn test_forwarding_addresses() {
+        serial_test(|| {
+            let (blocks, objects) = blocks([&[2, 0, 1, 3], &[1, 0]]);
+            let (a, b) = (&objects[0], &objects[1]);
+            for &object in [a[1], a[3], b[0], b[1]].iter() {
+                mark(object);
+            }
+            Space::calculate_forwarding_addresses_in(&blocks);
+            // The live objects are slid to the start of the first block, in order
+            let first = Space::data_start(blocks[0]) + GC_EXTRA_HEADER_BYTES;
+            assert_eq!(forwarded_to(a[1]), first);
+            let second = first + MockObjectModel::get_current_size(a[1]) + GC_EXTRA_HEADER_BYTES;
+            assert_eq!(forwarded_to(a[3]), second);
+            let third = second + MockObjectModel::get_current_size(a[3]) + GC_EXTRA_HEADER_BYTES;
+            assert_eq!(forwarded_to(b[0]), third);
+            let fourth = third + MockObjectModel::get_current_size(b[0]) + GC_EXTRA_HEADER_BYTES;
+            assert_eq!(forwarded_to(b[1]), fourth);
+            // Dead objects are not forwarded
+            for &object in [a[0], a[2]].iter() {
+                let header = object.to_address() - GC_EXTRA_HEADER_BYTES;
+                assert!(unsafe { header.load::<Address>() }.is_zero());
+            }
+        })
+    }
+
+    #[test]
+    fn test_forwarding_addresses_with_pinned_objects() {
+        serial_test(|| {
+            let (blocks, objects) = blocks([&[2, 0, 1, 3], &[1, 0, 2]]);
+            let (a, b) = (&objects[0], &objects[1]);
+            for &object in [a[1], a[2], a[3], b[0], b[1], b[2]].iter() {
+                mark(object);
+            }
+            pin_bit::pin_object(a[2]);
+            pin_bit::pin_object(b[1]);
+            Space::calculate_forwarding_addresses_in(&blocks);
+            // The objects before a pinned object are slid towards the start of the block
+            let first = Space::data_start(blocks[0]) + GC_EXTRA_HEADER_BYTES;
+            assert_eq!(forwarded_to(a[1]), first);
+            // Pinned objects stay in place
+            assert_eq!(forwarded_to(a[2]), a[2].to_address());
+            assert_eq!(forwarded_to(b[1]), b[1].to_address());
+            // The objects after a pinned object are slid towards it
+            assert_eq!(
+                forwarded_to(a[3]),
+                MockObjectModel::get_object_end_address(a[2]) + GC_EXTRA_HEADER_BYTES
+            );
+            assert_eq!(
+                forwarded_to(b[0]),
+                MockObjectModel::get_object_end_address(a[3]) + GC_EXTRA_HEADER_BYTES
+            );
+            assert_eq!(
+                forwarded_to(b[2]),
+                MockObjectModel::get_object_end_address(b[1]) + GC_EXTRA_HEADER_BYTES
+            );
+            pin_bit::unpin_object(a[2]);
+            pin_bit::unpin_object(b[1]);
+        })
+    }
+}
@@ -0,0 +1,571 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::plan::TransitiveClosure;
+use crate::policy::space::SpaceOptions;
+use crate::policy::space::{CommonSpace, Space, SFT};
+use crate::util::alloc_bit;
+use crate::util::constants::{BITS_IN_WORD, BYTES_IN_PAGE, LOG_BITS_IN_WORD};
+use crate::util::heap::layout::heap_layout::{Mmapper, VMMap};
+use crate::util::heap::HeapMeta;
+use crate::util::heap::{FreeListPageResource, PageResource, VMRequest};
+use crate::util::pin_bit;
+use crate::util::OpaquePointer;
+use crate::util::{Address, ObjectReference};
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+
+/// Blocks are the unit the space acquires pages in. Every block holds cells of a single size class.
+pub const LOG_BYTES_IN_BLOCK: usize = 16;
+pub const BYTES_IN_BLOCK: usize = 1 << LOG_BYTES_IN_BLOCK;
+const PAGES_IN_BLOCK: usize = BYTES_IN_BLOCK / BYTES_IN_PAGE;
+
+/// Cell sizes (in bytes) of the size classes. Objects larger than the largest size class
+/// have to be allocated in the large object space.
+pub const SIZE_CLASSES: [usize; 35] = [
+    16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384, 448, 512, 640, 768,
+    896, 1024, 1280, 1536, 1792, 2048, 2560, 3072, 3584, 4096, 5120, 6144, 7168, 8192,
+];
+pub const NUM_SIZE_CLASSES: usize = SIZE_CLASSES.len();
+pub const MAX_CELL_SIZE: usize = SIZE_CLASSES[NUM_SIZE_CLASSES - 1];
+
+const MAX_CELLS_IN_BLOCK: usize = BYTES_IN_BLOCK / SIZE_CLASSES[0];
+const MARK_TABLE_WORDS: usize = MAX_CELLS_IN_BLOCK >> LOG_BITS_IN_WORD;
+
+/// The metadata at the start of each block. Cells start right after it.
+#[repr(C)]
+struct BlockHeader {
+    size_class: usize,
+    /// Free cells of the block that are not handed out to any allocator.
+    free_list: Address,
+    /// One mark bit per cell.
+    marks: [AtomicUsize; MARK_TABLE_WORDS],
+}
+
+const BLOCK_HEADER_BYTES: usize = std::mem::size_of::<BlockHeader>();
+
+/// Get the size class of a cell that can hold `size` bytes, or `None` if the size is larger than any cell.
+pub fn size_class_for(size: usize) -> Option<usize> {
+    SIZE_CLASSES.iter().position(|&cell_size| cell_size >= size)
+}
+
+/// A non-moving space managed by mark-sweep. The space is divided into blocks, and each block
+/// is segregated into cells of one size class. Free cells are linked into a free list through
+/// their first word. Marking sets a side mark bit for the cell of an object, and sweeping
+/// rebuilds the free list of every block from the unmarked cells.
+///
+/// The space also supports sticky mark bits: if the marks are kept when sweeping, the marked
+/// objects are old, and a nursery GC only marks (and sweeps) the objects allocated since the last GC.
+///
+/// Without marking, a plan can also free single objects (`free()`), and sweep the blocks that
+/// were handed out to allocators since the last GC with its own liveness (`sweep_acquired_blocks()`).
+pub struct MarkSweepSpace<VM: VMBinding> {
+    common: UnsafeCell<CommonSpace<VM>>,
+    pr: FreeListPageResource<VM>,
+    /// All the blocks in this space.
+    blocks: Mutex<Vec<Address>>,
+    /// Blocks with free cells that are not owned by an allocator, for each size class.
+    available_blocks: Vec<Mutex<Vec<Address>>>,
+    /// Blocks that were handed out to allocators since the last sweep.
+    acquired_blocks: Mutex<Vec<Address>>,
+    /// Whether new objects are marked when they are allocated. This is set during concurrent
+    /// marking, so that the objects allocated during marking survive the following sweep.
+    alloc_marked: AtomicBool,
+}
+
+unsafe impl<VM: VMBinding> Sync for MarkSweepSpace<VM> {}
+
+impl<VM: VMBinding> SFT for MarkSweepSpace<VM> {
+    fn is_live(&self, object: ObjectReference) -> bool {
+        Self::is_marked(object)
+    }
+    fn is_movable(&self) -> bool {
+        false
+    }
+    #[cfg(feature = "sanity")]
+    fn is_sane(&self) -> bool {
+        true
+    }
+    fn initialize_header(&self, object: ObjectReference, _alloc: bool) {
+        if self.alloc_marked.load(Ordering::Relaxed) {
+            Self::test_and_mark(object);
+        }
+    }
+
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        alloc_bit::find_object_by_alloc_bits::<VM>(ptr, max_search_bytes)
+    }
+}
+
+impl<VM: VMBinding> Space<VM> for MarkSweepSpace<VM> {
+    fn as_space(&self) -> &dyn Space<VM> {
+        self
+    }
+    fn as_sft(&self) -> &(dyn SFT + Sync + 'static) {
+        self
+    }
+    fn get_page_resource(&self) -> &dyn PageResource<VM> {
+        &self.pr
+    }
+    fn init(&mut self, _vm_map: &'static VMMap) {
+        let me = unsafe { &*(self as *const Self) };
+        self.pr.bind_space(me);
+    }
+
+    fn common(&self) -> &CommonSpace<VM> {
+        unsafe { &*self.common.get() }
+    }
+
+    unsafe fn unsafe_common_mut(&self) -> &mut CommonSpace<VM> {
+        &mut *self.common.get()
+    }
+
+    fn release_multiple_pages(&mut self, start: Address) {
+        self.pr.release_pages(start);
+    }
+
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference)) {
+        // Free cells do not have the alloc bit set
+        for &block in self.blocks.lock().unwrap().iter() {
+            alloc_bit::for_each_alloced_object(
+                block + BLOCK_HEADER_BYTES,
+                BYTES_IN_BLOCK - BLOCK_HEADER_BYTES,
+                &mut *f,
+            );
+        }
+    }
+}
+
+impl<VM: VMBinding> MarkSweepSpace<VM> {
+    pub fn new(
+        name: &'static str,
+        zeroed: bool,
+        vmrequest: VMRequest,
+        vm_map: &'static VMMap,
+        mmapper: &'static Mmapper,
+        heap: &mut HeapMeta,
+    ) -> Self {
+        let common = CommonSpace::new(
+            SpaceOptions {
+                name,
+                movable: false,
+                immortal: false,
+                zeroed,
+                vmrequest,
+            },
+            vm_map,
+            mmapper,
+            heap,
+        );
+        MarkSweepSpace {
+            pr: if vmrequest.is_discontiguous() {
+                FreeListPageResource::new_discontiguous(0, vm_map)
+            } else {
+                FreeListPageResource::new_contiguous(common.start, common.extent, 0, vm_map)
+            },
+            common: UnsafeCell::new(common),
+            blocks: Mutex::new(vec![]),
+            available_blocks: (0..NUM_SIZE_CLASSES).map(|_| Mutex::new(vec![])).collect(),
+            acquired_blocks: Mutex::new(vec![]),
+            alloc_marked: AtomicBool::new(false),
+        }
+    }
+
+    /// Set whether new objects are marked when they are allocated. This needs to be set
+    /// before the mutators resume from the pause that starts concurrent marking.
+    pub fn set_alloc_marked(&self, alloc_marked: bool) {
+        self.alloc_marked.store(alloc_marked, Ordering::SeqCst);
+    }
+
+    pub fn prepare(&self) {}
+
+    /// Clear the marks of all the blocks. With sticky mark bits, this needs to be done before a
+    /// full heap GC, so that all the objects are traced again.
+    pub fn clear_marks(&self) {
+        for &block in self.blocks.lock().unwrap().iter() {
+            for word in Self::header(block).marks.iter() {
+                word.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Sweep all the blocks: unmarked cells go back to the free lists, and blocks without
+    /// any live cell are returned to the page resource. The marks are cleared, unless `keep_marks`
+    /// is true (sticky mark bits), in which case the live objects stay marked as old objects.
+    pub fn release(&self, keep_marks: bool) {
+        // FIXME: We need a safe implementation
+        #[allow(clippy::cast_ref_to_mut)]
+        let pr: &mut FreeListPageResource<VM> = unsafe { &mut *(&self.pr as *const _ as *mut _) };
+        for available in self.available_blocks.iter() {
+            available.lock().unwrap().clear();
+        }
+        self.acquired_blocks.lock().unwrap().clear();
+        let mut blocks = self.blocks.lock().unwrap();
+        blocks.retain(|&block| {
+            let (live_cells, free_cells) = Self::sweep_marked_block(block, keep_marks);
+            if live_cells == 0 {
+                pr.release_pages(block);
+                return false;
+            }
+            if free_cells != 0 {
+                let size_class = Self::header(block).size_class;
+                self.available_blocks[size_class]
+                    .lock()
+                    .unwrap()
+                    .push(block);
+            }
+            true
+        });
+    }
+
+    /// Sweep the blocks that were handed out to allocators since the last sweep. The cells for
+    /// which `is_live` returns false go back to the free lists, and blocks without any live cell
+    /// are returned to the page resource. The cells held by allocators need to be dropped first.
+    pub fn sweep_acquired_blocks<F: Fn(Address) -> bool>(&self, is_live: F) {
+        // FIXME: We need a safe implementation
+        #[allow(clippy::cast_ref_to_mut)]
+        let pr: &mut FreeListPageResource<VM> = unsafe { &mut *(&self.pr as *const _ as *mut _) };
+        let mut acquired = vec![];
+        std::mem::swap(&mut acquired, &mut *self.acquired_blocks.lock().unwrap());
+        acquired.sort_unstable_by_key(|block| block.as_usize());
+        acquired.dedup();
+        let is_acquired = |block: &Address| {
+            acquired
+                .binary_search_by_key(&block.as_usize(), |block| block.as_usize())
+                .is_ok()
+        };
+        // The blocks may have got free cells from `free()`. They are added back after sweeping.
+        for available in self.available_blocks.iter() {
+            available
+                .lock()
+                .unwrap()
+                .retain(|block| !is_acquired(block));
+        }
+        let mut released = vec![];
+        for &block in acquired.iter() {
+            let (live_cells, free_cells) = Self::sweep_block(block, |_, cell| is_live(cell));
+            if live_cells == 0 {
+                pr.release_pages(block);
+                released.push(block);
+            } else if free_cells != 0 {
+                let size_class = Self::header(block).size_class;
+                self.available_blocks[size_class]
+                    .lock()
+                    .unwrap()
+                    .push(block);
+            }
+        }
+        if !released.is_empty() {
+            self.blocks
+                .lock()
+                .unwrap()
+                .retain(|block| !released.contains(block));
+        }
+    }
+
+    /// Return the cell of a dead object to the free list of its block.
+    pub fn free(&self, object: ObjectReference) {
+        let cell = Self::cell_of(object);
+        let block = cell.align_down(BYTES_IN_BLOCK);
+        let header = Self::header_mut(block);
+        alloc_bit::unset_alloc_bit(object);
+        pin_bit::unpin_object(object);
+        let mut available = self.available_blocks[header.size_class].lock().unwrap();
+        unsafe { cell.store(header.free_list) };
+        if header.free_list.is_zero() {
+            available.push(block);
+        }
+        header.free_list = cell;
+    }
+
+    /// Call `f` for each block of the space.
+    pub fn for_each_block<F: FnMut(Address)>(&self, mut f: F) {
+        for &block in self.blocks.lock().unwrap().iter() {
+            f(block);
+        }
+    }
+
+    #[inline]
+    pub fn trace_object<T: TransitiveClosure>(
+        &self,
+        trace: &mut T,
+        object: ObjectReference,
+    ) -> ObjectReference {
+        if Self::test_and_mark(object) {
+            trace.process_node(object);
+        }
+        object
+    }
+
+    /// Get a block of the given size class with free cells, and take its free list.
+    /// Returns the first cell of the free list, or zero if the space cannot get more memory.
+    pub fn acquire_cells(&self, tls: OpaquePointer, size_class: usize) -> Address {
+        let block = self.available_blocks[size_class].lock().unwrap().pop();
+        let block = match block {
+            Some(block) => block,
+            None => {
+                let block = self.acquire(tls, PAGES_IN_BLOCK);
+                if block.is_zero() {
+                    return block;
+                }
+                // The block header is found by aligning down addresses in the block
+                debug_assert!(
+                    block.is_aligned_to(BYTES_IN_BLOCK),
+                    "{} is not aligned to a block",
+                    block
+                );
+                Self::init_block(block, size_class);
+                self.blocks.lock().unwrap().push(block);
+                block
+            }
+        };
+        self.acquired_blocks.lock().unwrap().push(block);
+        let header = Self::header_mut(block);
+        let cells = header.free_list;
+        header.free_list = unsafe { Address::zero() };
+        debug_assert!(!cells.is_zero());
+        cells
+    }
+
+    fn init_block(block: Address, size_class: usize) {
+        let header = Self::header_mut(block);
+        header.size_class = size_class;
+        for word in header.marks.iter() {
+            word.store(0, Ordering::Relaxed);
+        }
+        let cell_size = SIZE_CLASSES[size_class];
+        let mut free_list = unsafe { Address::zero() };
+        let mut cell = Self::cells_end(block, cell_size);
+        while cell > block + BLOCK_HEADER_BYTES {
+            cell -= cell_size;
+            unsafe { cell.store(free_list) };
+            free_list = cell;
+        }
+        header.free_list = free_list;
+    }
+
+    /// Rebuild the free list of the block from the unmarked cells, and clear the marks unless
+    /// `keep_marks` is true. Returns the number of live cells and free cells in the block.
+    fn sweep_marked_block(block: Address, keep_marks: bool) -> (usize, usize) {
+        let header = Self::header(block);
+        let result = Self::sweep_block(block, |index, _| {
+            header.marks[index >> LOG_BITS_IN_WORD].load(Ordering::Relaxed)
+                & (1 << (index & (BITS_IN_WORD - 1)))
+                != 0
+        });
+        if !keep_marks {
+            for word in header.marks.iter() {
+                word.store(0, Ordering::Relaxed);
+            }
+        }
+        result
+    }
+
+    /// Rebuild the free list of the block from the cells for which `is_live` (called with the
+    /// index and the address of each cell) returns false. Returns the number of live cells and
+    /// free cells in the block.
+    fn sweep_block<F: Fn(usize, Address) -> bool>(block: Address, is_live: F) -> (usize, usize) {
+        let header = Self::header_mut(block);
+        let cell_size = SIZE_CLASSES[header.size_class];
+        let mut live_cells = 0;
+        let mut free_cells = 0;
+        let mut free_list = unsafe { Address::zero() };
+        let mut cell = Self::cells_end(block, cell_size);
+        let mut index = (cell - (block + BLOCK_HEADER_BYTES)) / cell_size;
+        while index > 0 {
+            index -= 1;
+            cell -= cell_size;
+            if is_live(index, cell) {
+                live_cells += 1;
+            } else {
+                alloc_bit::bzero_alloc_bit(cell, cell_size);
+                pin_bit::bzero_pin_bit(cell, cell_size);
+                unsafe { cell.store(free_list) };
+                free_list = cell;
+                free_cells += 1;
+            }
+        }
+        header.free_list = free_list;
+        (live_cells, free_cells)
+    }
+
+    fn cells_end(block: Address, cell_size: usize) -> Address {
+        let cells = (BYTES_IN_BLOCK - BLOCK_HEADER_BYTES) / cell_size;
+        block + BLOCK_HEADER_BYTES + cells * cell_size
+    }
+
+    fn header(block: Address) -> &'static BlockHeader {
+        unsafe { &*block.to_ptr::<BlockHeader>() }
+    }
+
+    fn header_mut(block: Address) -> &'static mut BlockHeader {
+        unsafe { &mut *block.to_mut_ptr::<BlockHeader>() }
+    }
+
+    /// Get the cell that holds the object.
+    pub fn cell_of(object: ObjectReference) -> Address {
+        let start = VM::VMObjectModel::object_start_ref(object);
+        let block = start.align_down(BYTES_IN_BLOCK);
+        let cell_size = SIZE_CLASSES[Self::header(block).size_class];
+        let first_cell = block + BLOCK_HEADER_BYTES;
+        first_cell + (start - first_cell) / cell_size * cell_size
+    }
+
+    /// Get the mark word and the bit mask for the cell of the object.
+    fn mark_bit(object: ObjectReference) -> (&'static AtomicUsize, usize) {
+        let start = VM::VMObjectModel::object_start_ref(object);
+        let block = start.align_down(BYTES_IN_BLOCK);
+        let header = Self::header(block);
+        let index = (start - (block + BLOCK_HEADER_BYTES)) / SIZE_CLASSES[header.size_class];
+        (
+            &header.marks[index >> LOG_BITS_IN_WORD],
+            1 << (index & (BITS_IN_WORD - 1)),
+        )
+    }
+
+    /// Set the mark bit of the object. Returns true if the object was not marked before.
+    fn test_and_mark(object: ObjectReference) -> bool {
+        let (word, mask) = Self::mark_bit(object);
+        if word.load(Ordering::Relaxed) & mask != 0 {
+            return false;
+        }
+        word.fetch_or(mask, Ordering::SeqCst) & mask == 0
+    }
+
+    fn is_marked(object: ObjectReference) -> bool {
+        let (word, mask) = Self::mark_bit(object);
+        word.load(Ordering::SeqCst) & mask != 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::mock_vm::MockVM;
+    use crate::util::test_util::{map_heap_memory, serial_test};
+
+    type Space = MarkSweepSpace<MockVM>;
+
+    fn block() -> Address {
+        map_heap_memory(1 << 24, BYTES_IN_BLOCK)
+    }
+
+    fn free_list(block: Address) -> Vec<Address> {
+        let mut cells = vec![];
+        let mut cell = Space::header(block).free_list;
+        while !cell.is_zero() {
+            cells.push(cell);
+            cell = unsafe { cell.load::<Address>() };
+        }
+        cells
+    }
+
+    #[test]
+    fn test_size_class_for() {
+        assert_eq!(size_class_for(1), Some(0));
+        assert_eq!(size_class_for(16), Some(0));
+        assert_eq!(size_class_for(17), Some(1));
+        assert_eq!(SIZE_CLASSES[size_class_for(1000).unwrap()], 1024);
+        assert_eq!(size_class_for(MAX_CELL_SIZE), Some(NUM_SIZE_CLASSES - 1));
+        assert_eq!(size_class_for(MAX_CELL_SIZE + 1), None);
+        // The smallest cell that fits
+        for size in 1..=MAX_CELL_SIZE {
+            let size_class = size_class_for(size).unwrap();
+            assert!(SIZE_CLASSES[size_class] >= size);
+            assert!(size_class == 0 || SIZE_CLASSES[size_class - 1] < size);
+        }
+    }
+
+    #[test]
+    fn test_init_block() {
+        serial_test(|| {
+            let block = block();
+            let size_class = size_class_for(48).unwrap();
+            Space::init_block(block, size_class);
+            let cells = free_list(block);
+            assert_eq!(cells.len(), (BYTES_IN_BLOCK - BLOCK_HEADER_BYTES) / 48);
+            assert_eq!(cells[0], block + BLOCK_HEADER_BYTES);
+            assert!(cells.windows(2).all(|pair| pair[1] - pair[0] == 48));
+            assert!(*cells.last().unwrap() + 48usize <= block + BYTES_IN_BLOCK);
+            let object = unsafe { (cells[3] + 16usize).to_object_reference() };
+            assert_eq!(Space::cell_of(object), cells[3]);
+        })
+    }
+
+    #[test]
+    fn test_sweep_block() {
+        serial_test(|| {
+            let block = block();
+            Space::init_block(block, size_class_for(32).unwrap());
+            let cells = free_list(block);
+            let objects: Vec<ObjectReference> = cells
+                .iter()
+                .map(|cell| unsafe { cell.to_object_reference() })
+                .collect();
+            for &object in objects.iter() {
+                alloc_bit::set_alloc_bit(object);
+                pin_bit::pin_object(object);
+            }
+            // Mark every third object
+            for &object in objects.iter().step_by(3) {
+                assert!(Space::test_and_mark(object));
+                assert!(!Space::test_and_mark(object));
+            }
+            let (live_cells, free_cells) = Space::sweep_marked_block(block, true);
+            assert_eq!(live_cells, (cells.len() + 2) / 3);
+            assert_eq!(live_cells + free_cells, cells.len());
+            // The free list holds the dead cells in address order
+            let dead: Vec<Address> = (0..cells.len())
+                .filter(|i| i % 3 != 0)
+                .map(|i| cells[i])
+                .collect();
+            assert_eq!(free_list(block), dead);
+            for (i, &object) in objects.iter().enumerate() {
+                let live = i % 3 == 0;
+                assert_eq!(Space::is_marked(object), live);
+                assert_eq!(alloc_bit::is_alloced(object), live);
+                assert_eq!(pin_bit::is_pinned(object), live);
+            }
+            // Sweeping again with no live cell frees the whole block
+            let (live_cells, free_cells) = Space::sweep_block(block, |_, _| false);
+            assert_eq!(live_cells, 0);
+            assert_eq!(free_list(block), cells);
+            assert_eq!(free_cells, cells.len());
+        })
+    }
+
+    #[test]
+    fn test_sweep_block_with_sticky_marks() {
+        serial_test(|| {
+            let block = block();
+            Space::init_block(block, size_class_for(64).unwrap());
+            let cells = free_list(block);
+            let objects: Vec<ObjectReference> = cells
+                .iter()
+                .map(|cell| unsafe { cell.to_object_reference() })
+                .collect();
+            // A full heap GC marks the first two objects, which stay marked as old objects
+            Space::test_and_mark(objects[0]);
+            Space::test_and_mark(objects[1]);
+            assert_eq!(Space::sweep_marked_block(block, true).0, 2);
+            assert!(Space::is_marked(objects[0]) && Space::is_marked(objects[1]));
+            // A nursery GC only marks the new objects that survive. The old objects are not traced,
+            // but they are not swept either.
+            Space::test_and_mark(objects[2]);
+            let (live_cells, free_cells) = Space::sweep_marked_block(block, true);
+            assert_eq!(live_cells, 3);
+            assert_eq!(free_list(block), cells[3..].to_vec());
+            assert_eq!(free_cells, cells.len() - 3);
+            // Without sticky marks, the marks are cleared, so the next sweep frees all the cells
+            assert_eq!(Space::sweep_marked_block(block, false).0, 3);
+            assert!(objects.iter().all(|&object| !Space::is_marked(object)));
+            assert_eq!(Space::sweep_marked_block(block, false).0, 0);
+            assert_eq!(free_list(block), cells);
+        })
+    }
+}
@@ -13,14 +13,12 @@
 /// memory).
 pub mod space;
 
-#[cfg(feature = "immortalspace")]
-pub mod immortalspace;
-
-#[cfg(feature = "copyspace")]
 pub mod copyspace;
-
-#[cfg(feature = "largeobjectspace")]
+pub mod immixspace;
+pub mod immortalspace;
 pub mod largeobjectspace;
+pub mod markcompactspace;
+pub mod marksweepspace;
 
 #[cfg(feature = "lockfreeimmortalspace")]
 pub mod lockfreeimmortalspace;
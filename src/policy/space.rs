@@ -7,8 +7,6 @@ use crate::util::heap::layout::vm_layout_constants::{AVAILABLE_END, AVAILABLE_ST
 use crate::util::heap::{PageResource, VMRequest};
 use crate::vm::{ActivePlan, Collection, ObjectModel};
 
-use crate::plan::Plan;
-
 use crate::util::constants::LOG_BYTES_IN_MBYTE;
 use crate::util::conversions;
 use crate::util::OpaquePointer;
@@ -21,6 +19,7 @@ use crate::util::heap::layout::vm_layout_constants::BYTES_IN_CHUNK;
 use crate::util::heap::layout::vm_layout_constants::MAX_CHUNKS;
 use crate::util::heap::space_descriptor::SpaceDescriptor;
 use crate::util::heap::HeapMeta;
+use crate::util::side_metadata::SideMetadataSpec;
 
 use crate::vm::VMBinding;
 use std::marker::PhantomData;
@@ -53,6 +52,14 @@ pub trait SFT {
     #[cfg(feature = "sanity")]
     fn is_sane(&self) -> bool;
     fn initialize_header(&self, object: ObjectReference, alloc: bool);
+    /// Find the object in this space that contains the address (see
+    /// `memory_manager::find_object_from_internal_pointer()`), searching back at most
+    /// `max_search_bytes` from the address if the space finds objects by their alloc bits.
+    fn find_object_from_internal_pointer(
+        &self,
+        ptr: Address,
+        max_search_bytes: usize,
+    ) -> Option<ObjectReference>;
 }
 
 unsafe impl Sync for SFTMap {}
@@ -66,6 +73,14 @@ impl SFT for EmptySpaceSFT {
             object
         )
     }
+
+    fn find_object_from_internal_pointer(
+        &self,
+        _ptr: Address,
+        _max_search_bytes: usize,
+    ) -> Option<ObjectReference> {
+        None
+    }
     #[cfg(feature = "sanity")]
     fn is_sane(&self) -> bool {
         false
@@ -116,6 +131,12 @@ impl SFTMap {
         unsafe { &*self.sft[address.chunk_index()] }
     }
 
+    /// Is the address in a chunk that belongs to a space?
+    pub fn is_in_any_space(&self, address: Address) -> bool {
+        self.get(address) as *const _ as *const u8
+            != &EMPTY_SPACE_SFT as *const EmptySpaceSFT as *const u8
+    }
+
     pub fn update(&self, space: *const (dyn SFT + Sync), start: Address, chunks: usize) {
         let first = start.chunk_index();
         for chunk in first..(first + chunks) {
@@ -230,6 +251,12 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
         if new_chunk {
             let chunks = conversions::bytes_to_chunks_up(bytes);
             SFT_MAP.update(self.as_sft() as *const (dyn SFT + Sync), start, chunks);
+            // Map the side metadata of the new chunks
+            let chunk_start = conversions::chunk_align_down(start);
+            let chunk_end = conversions::chunk_align_up(start + bytes);
+            for spec in &self.common().side_metadata_specs {
+                spec.ensure_mapped(self.common().mmapper, chunk_start, chunk_end - chunk_start);
+            }
         }
     }
 
@@ -248,6 +275,13 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
         self.common()
             .mmapper
             .mark_as_mapped(self.common().start, self.common().extent);
+        for spec in &self.common().side_metadata_specs {
+            spec.ensure_mapped(
+                self.common().mmapper,
+                self.common().start,
+                self.common().extent,
+            );
+        }
     }
 
     fn reserved_pages(&self) -> usize {
@@ -281,6 +315,11 @@ pub trait Space<VM: VMBinding>: 'static + SFT + Sync + Downcast {
 
     fn release_multiple_pages(&mut self, start: Address);
 
+    /// Call `f` for each object in this space. The objects are found in the memory that the space
+    /// has allocated, so this can only be used when no object is allocated, moved or freed at the
+    /// same time, e.g. when the mutators are stopped.
+    fn enumerate_objects(&self, f: &mut dyn FnMut(ObjectReference));
+
     /// # Safety
     /// TODO: I am not sure why this is unsafe.
     unsafe fn release_all_chunks(&self) {
@@ -351,6 +390,9 @@ pub struct CommonSpace<VM: VMBinding> {
 
     pub vm_map: &'static VMMap,
     pub mmapper: &'static Mmapper,
+    /// The side metadata that is mapped for the chunks of this space. It includes the specs in
+    /// `HeapMeta::side_metadata_specs`, and the specs that are only used by this space.
+    pub side_metadata_specs: Vec<SideMetadataSpec>,
 
     p: PhantomData<VM>,
 }
@@ -387,6 +429,7 @@ impl<VM: VMBinding> CommonSpace<VM> {
             head_discontiguous_region: unsafe { Address::zero() },
             vm_map,
             mmapper,
+            side_metadata_specs: heap.side_metadata_specs.clone(),
             p: PhantomData,
         };
 
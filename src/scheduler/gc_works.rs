@@ -1,5 +1,8 @@
 use super::*;
 use crate::plan::global::GcStatus;
+use crate::util::card_table::{CardTable, BYTES_IN_CARD};
+use crate::util::constants::BYTES_IN_ADDRESS;
+use crate::util::heap_dump::{HeapDumpRoots, ScheduleHeapDump};
 use crate::util::*;
 use crate::vm::*;
 use crate::*;
@@ -14,7 +17,11 @@ unsafe impl Sync for ScheduleCollection {}
 
 impl<VM: VMBinding> GCWork<VM> for ScheduleCollection {
     fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        mmtk.gc_event_log.gc_start(mmtk, worker);
         mmtk.plan.schedule_collection(worker.scheduler());
+        if mmtk.heap_dumper.start(worker.scheduler().num_workers()) {
+            worker.scheduler().final_stage.add(ScheduleHeapDump);
+        }
     }
 }
 
@@ -52,13 +59,13 @@ impl<P: Plan> GCWork<P::VM> for Prepare<P> {
 pub struct PrepareMutator<VM: VMBinding> {
     // The mutator reference has static lifetime.
     // It is safe because the actual lifetime of this work-packet will not exceed the lifetime of a GC.
-    pub mutator: &'static mut Mutator<SelectedPlan<VM>>,
+    pub mutator: &'static mut Mutator<VM>,
 }
 
 unsafe impl<VM: VMBinding> Sync for PrepareMutator<VM> {}
 
 impl<VM: VMBinding> PrepareMutator<VM> {
-    pub fn new(mutator: &'static mut Mutator<SelectedPlan<VM>>) -> Self {
+    pub fn new(mutator: &'static mut Mutator<VM>) -> Self {
         Self { mutator }
     }
 }
@@ -104,21 +111,19 @@ impl<P: Plan> GCWork<P::VM> for Release<P> {
         for w in &mmtk.scheduler.worker_group().workers {
             w.local_works.add(ReleaseCollector::default());
         }
-        // TODO: Process weak references properly
-        mmtk.reference_processors.clear();
     }
 }
 
 pub struct ReleaseMutator<VM: VMBinding> {
     // The mutator reference has static lifetime.
     // It is safe because the actual lifetime of this work-packet will not exceed the lifetime of a GC.
-    pub mutator: &'static mut Mutator<SelectedPlan<VM>>,
+    pub mutator: &'static mut Mutator<VM>,
 }
 
 unsafe impl<VM: VMBinding> Sync for ReleaseMutator<VM> {}
 
 impl<VM: VMBinding> ReleaseMutator<VM> {
-    pub fn new(mutator: &'static mut Mutator<SelectedPlan<VM>>) -> Self {
+    pub fn new(mutator: &'static mut Mutator<VM>) -> Self {
         Self { mutator }
     }
 }
@@ -202,6 +207,17 @@ pub struct EndOfGC;
 impl<VM: VMBinding> GCWork<VM> for EndOfGC {
     fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
         mmtk.plan.common().base.set_gc_status(GcStatus::NotInGC);
+        mmtk.conservative_roots.unpin_all();
+        mmtk.plan.adjust_heap_size();
+        // The triggers of this GC are consumed, so the next GC does not appear to have the same triggers
+        mmtk.plan.reset_collection_trigger();
+        let kind = if mmtk.plan.in_nursery() {
+            "nursery"
+        } else {
+            "full"
+        };
+        mmtk.gc_event_log.gc_end(mmtk, worker, kind);
+        mmtk.work_trace.flush(mmtk, worker);
         <VM as VMBinding>::VMCollection::resume_mutators(worker.tls);
     }
 }
@@ -220,23 +236,29 @@ impl<E: ProcessEdgesWork> ScanStackRoots<E> {
 impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanStackRoots<E> {
     fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
         trace!("ScanStackRoots");
-        <E::VM as VMBinding>::VMScanning::scan_thread_roots::<E>();
+        if mmtk.heap_dumper.is_recording_roots() {
+            <E::VM as VMBinding>::VMScanning::scan_thread_roots::<HeapDumpRoots<E>>();
+        } else {
+            <E::VM as VMBinding>::VMScanning::scan_thread_roots::<E>();
+        }
         <E::VM as VMBinding>::VMScanning::notify_initial_thread_scan_complete(false, worker.tls);
         mmtk.plan.common().base.set_gc_status(GcStatus::GcProper);
     }
 }
 
-pub struct ScanStackRoot<Edges: ProcessEdgesWork>(
-    pub &'static mut Mutator<SelectedPlan<Edges::VM>>,
-);
+pub struct ScanStackRoot<Edges: ProcessEdgesWork>(pub &'static mut Mutator<Edges::VM>);
 
 impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanStackRoot<E> {
     fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
         trace!("ScanStackRoot for mutator {:?}", self.0.get_tls());
-        <E::VM as VMBinding>::VMScanning::scan_thread_root::<E>(
-            unsafe { &mut *(self.0 as *mut _) },
-            worker.tls,
-        );
+        let mutator = unsafe { &mut *(self.0 as *mut _) };
+        if mmtk.heap_dumper.is_recording_roots() {
+            <E::VM as VMBinding>::VMScanning::scan_thread_root::<HeapDumpRoots<E>>(
+                mutator, worker.tls,
+            );
+        } else {
+            <E::VM as VMBinding>::VMScanning::scan_thread_root::<E>(mutator, worker.tls);
+        }
         self.0.flush();
         let old = mmtk
             .plan
@@ -263,9 +285,13 @@ impl<E: ProcessEdgesWork> ScanVMSpecificRoots<E> {
 }
 
 impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanVMSpecificRoots<E> {
-    fn do_work(&mut self, _worker: &mut GCWorker<E::VM>, _mmtk: &'static MMTK<E::VM>) {
+    fn do_work(&mut self, _worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
         trace!("ScanStaticRoots");
-        <E::VM as VMBinding>::VMScanning::scan_vm_specific_roots::<E>();
+        if mmtk.heap_dumper.is_recording_roots() {
+            <E::VM as VMBinding>::VMScanning::scan_vm_specific_roots::<HeapDumpRoots<E>>();
+        } else {
+            <E::VM as VMBinding>::VMScanning::scan_vm_specific_roots::<E>();
+        }
     }
 }
 
@@ -311,8 +337,14 @@ impl<E: ProcessEdgesWork> ProcessEdgesBase<E> {
         self.mmtk.unwrap()
     }
     #[inline]
-    pub fn plan(&self) -> &'static SelectedPlan<E::VM> {
-        &self.mmtk.unwrap().plan
+    pub fn plan(&self) -> &'static dyn Plan<VM = E::VM> {
+        &*self.mmtk.unwrap().plan
+    }
+    /// Get the plan as its concrete type. Plan-specific `ProcessEdgesWork` should only run with
+    /// their own plan.
+    #[inline]
+    pub fn plan_as<P: Plan<VM = E::VM>>(&self) -> &'static P {
+        self.plan().downcast_ref::<P>().unwrap()
     }
 }
 
@@ -327,6 +359,13 @@ pub trait ProcessEdgesWork:
     fn new(edges: Vec<Address>, roots: bool) -> Self;
     fn trace_object(&mut self, object: ObjectReference) -> ObjectReference;
 
+    /// Is the object live in this GC? The reference and finalizable processors ask this before
+    /// they trace the reference objects, the referents and the finalizable objects.
+    #[inline]
+    fn is_object_live(&self, object: ObjectReference) -> bool {
+        object.is_live()
+    }
+
     #[inline]
     fn process_node(&mut self, object: ObjectReference) {
         if self.nodes.is_empty() {
@@ -417,18 +456,97 @@ impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanObjects<E> {
     }
 }
 
+/// Mark objects while the mutators are running, for plans that mark concurrently. The objects, and
+/// everything reachable from them, are marked with `E::trace_object()`. Mutators may write to the
+/// objects at the same time, so the references are never updated, and `E` must not move objects.
+pub struct ConcurrentMark<E: ProcessEdgesWork> {
+    objects: Vec<ObjectReference>,
+    /// Whether the objects are already marked, and only need to be scanned.
+    marked: bool,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork> ConcurrentMark<E> {
+    pub fn new(objects: Vec<ObjectReference>, marked: bool) -> Self {
+        Self {
+            objects,
+            marked,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// Traces the referents of the objects scanned by `ConcurrentMark`, without updating the fields.
+struct ConcurrentMarkClosure<'a, E: ProcessEdgesWork>(&'a mut E);
+
+impl<E: ProcessEdgesWork> TransitiveClosure for ConcurrentMarkClosure<'_, E> {
+    #[inline]
+    fn process_edge(&mut self, slot: Address) {
+        let object = unsafe { slot.load::<ObjectReference>() };
+        self.0.trace_object(object);
+    }
+    #[inline]
+    fn process_node(&mut self, object: ObjectReference) {
+        ProcessEdgesWork::process_node(self.0, object);
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ConcurrentMark<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("ConcurrentMark");
+        let mut trace = E::new(vec![], false);
+        trace.mmtk = Some(mmtk);
+        trace.set_worker(worker);
+        let objects = mem::take(&mut self.objects);
+        if self.marked {
+            trace.nodes = objects;
+        } else {
+            for object in objects {
+                trace.trace_object(object);
+            }
+        }
+        // Newly marked objects are pushed to `trace.nodes`
+        while let Some(object) = trace.nodes.pop() {
+            if trace.nodes.len() >= E::CAPACITY {
+                // Share the marked objects with other workers
+                let half = trace.nodes.len() / 2;
+                let objects = trace.nodes.split_off(half);
+                mmtk.scheduler
+                    .add_concurrent_work(ConcurrentMark::<E>::new(objects, true));
+            }
+            <E::VM as VMBinding>::VMScanning::scan_object(
+                &mut ConcurrentMarkClosure(&mut trace),
+                object,
+                worker.tls,
+            );
+        }
+        if mmtk.scheduler.concurrent_work_finished() && !mmtk.plan.base().gc_in_progress() {
+            // All the objects are marked. Request a GC to finish the collection.
+            mmtk.plan.base().trigger_internal_collection_request();
+        }
+        trace!("ConcurrentMark End");
+    }
+}
+
 #[derive(Default)]
 pub struct ProcessModBuf<E: ProcessEdgesWork> {
     modified_nodes: Vec<ObjectReference>,
     modified_edges: Vec<Address>,
+    /// The ranges of slots modified in bulk (e.g. by array copies), as `(start, end)`.
+    modified_ranges: Vec<(Address, Address)>,
     phantom: PhantomData<E>,
 }
 
 impl<E: ProcessEdgesWork> ProcessModBuf<E> {
-    pub fn new(modified_nodes: Vec<ObjectReference>, modified_edges: Vec<Address>) -> Self {
+    pub fn new(
+        modified_nodes: Vec<ObjectReference>,
+        modified_edges: Vec<Address>,
+        modified_ranges: Vec<(Address, Address)>,
+    ) -> Self {
         Self {
             modified_nodes,
             modified_edges,
+            modified_ranges,
             phantom: PhantomData,
         }
     }
@@ -451,8 +569,152 @@ impl<E: ProcessEdgesWork> GCWork<E::VM> for ProcessModBuf<E> {
                 .scheduler()
                 .closure_stage
                 .add(E::new(modified_edges, true));
+
+            schedule_modified_ranges::<E>(worker, &self.modified_ranges);
         } else {
             // Do nothing
         }
     }
 }
+
+/// Split the ranges of modified slots into packets of slots to trace in the closure stage.
+fn schedule_modified_ranges<E: ProcessEdgesWork>(
+    worker: &mut GCWorker<E::VM>,
+    ranges: &[(Address, Address)],
+) {
+    let mut edges = Vec::with_capacity(E::CAPACITY);
+    for &(start, end) in ranges {
+        let mut slot = start;
+        while slot < end {
+            edges.push(slot);
+            if edges.len() >= E::CAPACITY {
+                let packet = mem::replace(&mut edges, Vec::with_capacity(E::CAPACITY));
+                worker.scheduler().closure_stage.add(E::new(packet, true));
+            }
+            slot += BYTES_IN_ADDRESS;
+        }
+    }
+    if !edges.is_empty() {
+        worker.scheduler().closure_stage.add(E::new(edges, true));
+    }
+}
+
+/// Process the objects and slots remembered by an `ObjectRememberingBarrier`. The objects get
+/// their unlogged bit back, so they are logged again by the first write after this GC, and they
+/// are scanned in a nursery GC. The unlogged bit is in the object header, so this is done in the
+/// prepare stage, before any object is copied and the header holds a forwarding pointer.
+pub struct ProcessObjectModBuf<E: ProcessEdgesWork> {
+    modified_nodes: Vec<ObjectReference>,
+    modified_edges: Vec<Address>,
+    /// The ranges of slots modified in bulk (e.g. by array copies), as `(start, end)`.
+    modified_ranges: Vec<(Address, Address)>,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork> ProcessObjectModBuf<E> {
+    pub fn new(
+        modified_nodes: Vec<ObjectReference>,
+        modified_edges: Vec<Address>,
+        modified_ranges: Vec<(Address, Address)>,
+    ) -> Self {
+        Self {
+            modified_nodes,
+            modified_edges,
+            modified_ranges,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ProcessObjectModBuf<E> {
+    #[inline]
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        for object in &self.modified_nodes {
+            header_byte::mark_as_unlogged::<E::VM>(*object);
+        }
+        if mmtk.plan.in_nursery() {
+            let mut modified_nodes = vec![];
+            ::std::mem::swap(&mut modified_nodes, &mut self.modified_nodes);
+            worker
+                .scheduler()
+                .closure_stage
+                .add(ScanObjects::<E>::new(modified_nodes, false));
+
+            let mut modified_edges = vec![];
+            ::std::mem::swap(&mut modified_edges, &mut self.modified_edges);
+            worker
+                .scheduler()
+                .closure_stage
+                .add(E::new(modified_edges, true));
+
+            schedule_modified_ranges::<E>(worker, &self.modified_ranges);
+        }
+    }
+}
+
+/// Scan the objects on the dirty cards from `start` to `end` in a nursery GC, and clean the cards.
+/// The dirty cards are marked by a `CardMarkingBarrier`.
+pub struct ScanDirtyCards<E: ProcessEdgesWork> {
+    card_table: &'static CardTable,
+    start: Address,
+    end: Address,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork> ScanDirtyCards<E> {
+    /// The number of cards scanned by one work packet.
+    const CARDS_IN_PACKET: usize = 1024;
+
+    pub fn new(card_table: &'static CardTable, start: Address, end: Address) -> Self {
+        Self {
+            card_table,
+            start,
+            end,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Schedule the works to scan all the cards of the recorded objects, which are in the
+    /// `(start, bytes)` extents of memory.
+    pub fn schedule_all(
+        card_table: &'static CardTable,
+        extents: &[(Address, usize)],
+        scheduler: &MMTkScheduler<E::VM>,
+    ) {
+        for &(start, bytes) in extents {
+            let end = start + bytes;
+            let mut packet_start = start.align_down(BYTES_IN_CARD);
+            while packet_start < end {
+                let mut packet_end = packet_start + Self::CARDS_IN_PACKET * BYTES_IN_CARD;
+                if packet_end > end {
+                    packet_end = end;
+                }
+                scheduler
+                    .closure_stage
+                    .add(Self::new(card_table, packet_start, packet_end));
+                packet_start = packet_end;
+            }
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ScanDirtyCards<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, _mmtk: &'static MMTK<E::VM>) {
+        let mut objects = vec![];
+        let mut card = self.start;
+        while card < self.end {
+            if self.card_table.is_dirty(card) {
+                self.card_table.clean(card);
+                self.card_table
+                    .for_each_object_on_card::<E::VM, _>(card, |object| objects.push(object));
+            }
+            card += BYTES_IN_CARD;
+        }
+        if !objects.is_empty() {
+            worker
+                .scheduler()
+                .closure_stage
+                .add(ScanObjects::<E>::new(objects, false));
+        }
+    }
+}
@@ -4,13 +4,36 @@ use super::work_bucket::*;
 use super::worker::{Worker, WorkerGroup};
 use super::*;
 use crate::mmtk::MMTK;
-use crate::plan::Plan;
+use crate::scheduler::gc_works::ProcessEdgesWork;
+use crate::util::finalizable_processor::{Finalization, ForwardFinalization};
+use crate::util::reference_processor::{
+    PhantomRefProcessing, RefEnqueue, RefForwarding, SoftRefProcessing, SoftRefRetention,
+    WeakRefProcessing,
+};
 use crate::util::OpaquePointer;
 use crate::vm::VMBinding;
 use std::collections::HashMap;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender};
 use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::Instant;
+
+/// The names of the stop-the-world buckets, in the order they are opened.
+pub const STW_STAGE_NAMES: [&str; 13] = [
+    "prepare",
+    "closure",
+    "soft_retain",
+    "soft_refs",
+    "weak_refs",
+    "finalizable",
+    "phantom_refs",
+    "calculate_forwarding",
+    "second_roots",
+    "ref_forwarding",
+    "compact",
+    "release",
+    "final",
+];
 
 pub enum CoordinatorMessage<C: Context> {
     Work(Box<dyn CoordinatorWork<C>>),
@@ -21,11 +44,30 @@ pub enum CoordinatorMessage<C: Context> {
 pub struct Scheduler<C: Context> {
     /// Works that are scheduable at any time
     pub unconstrained_works: WorkBucket<C>,
+    /// The number of concurrent works (see `add_concurrent_work()`) that are not finished yet
+    pending_concurrent_works: AtomicUsize,
     /// Works that are scheduable within Stop-the-world
     pub prepare_stage: WorkBucket<C>,
     pub closure_stage: WorkBucket<C>,
+    /// Reference processing stages. Each of them opens after the closure triggered
+    /// by the previous stages is finished. The referents of reachable soft references are
+    /// retained first, so the soft references that they reach are found by the closure
+    /// before the soft references are processed.
+    pub soft_retain_stage: WorkBucket<C>,
+    pub soft_refs_stage: WorkBucket<C>,
+    pub weak_refs_stage: WorkBucket<C>,
+    pub finalizable_stage: WorkBucket<C>,
+    pub phantom_refs_stage: WorkBucket<C>,
+    /// Stages for plans that move objects after the liveness is known (e.g. mark-compact).
+    /// They compute the new addresses, update the roots and the references, and then move the objects.
+    pub calculate_forwarding_stage: WorkBucket<C>,
+    pub second_roots_stage: WorkBucket<C>,
+    pub ref_forwarding_stage: WorkBucket<C>,
+    pub compact_stage: WorkBucket<C>,
     pub release_stage: WorkBucket<C>,
     pub final_stage: WorkBucket<C>,
+    /// When each stop-the-world bucket was opened in the current pause, in the order of `stw_buckets()`
+    stage_open_times: Mutex<[Option<Instant>; 13]>,
     /// Works for the coordinator thread
     pub coordinator_works: WorkBucket<C>,
     /// workers
@@ -51,10 +93,21 @@ impl<C: Context> Scheduler<C> {
         let worker_monitor: Arc<(Mutex<()>, Condvar)> = Default::default();
         Arc::new(Self {
             unconstrained_works: WorkBucket::new(true, worker_monitor.clone()), // `default_bucket` is always activated
+            pending_concurrent_works: AtomicUsize::new(0),
             prepare_stage: WorkBucket::new(false, worker_monitor.clone()),
             closure_stage: WorkBucket::new(false, worker_monitor.clone()),
+            soft_retain_stage: WorkBucket::new(false, worker_monitor.clone()),
+            soft_refs_stage: WorkBucket::new(false, worker_monitor.clone()),
+            weak_refs_stage: WorkBucket::new(false, worker_monitor.clone()),
+            finalizable_stage: WorkBucket::new(false, worker_monitor.clone()),
+            phantom_refs_stage: WorkBucket::new(false, worker_monitor.clone()),
+            calculate_forwarding_stage: WorkBucket::new(false, worker_monitor.clone()),
+            second_roots_stage: WorkBucket::new(false, worker_monitor.clone()),
+            ref_forwarding_stage: WorkBucket::new(false, worker_monitor.clone()),
+            compact_stage: WorkBucket::new(false, worker_monitor.clone()),
             release_stage: WorkBucket::new(false, worker_monitor.clone()),
             final_stage: WorkBucket::new(false, worker_monitor.clone()),
+            stage_open_times: Mutex::new([None; 13]),
             coordinator_works: WorkBucket::new(true, worker_monitor.clone()),
             worker_group: None,
             worker_monitor,
@@ -95,24 +148,52 @@ impl<C: Context> Scheduler<C> {
             .unwrap()
             .spawn_workers(tls, context);
 
-        self_mut.closure_stage.set_open_condition(move || {
-            self.unconstrained_works.is_drained()
-                && self.prepare_stage.is_drained()
-                && self.worker_group().all_parked()
-        });
-        self_mut.release_stage.set_open_condition(move || {
-            self.unconstrained_works.is_drained()
-                && self.prepare_stage.is_drained()
-                && self.closure_stage.is_drained()
-                && self.worker_group().all_parked()
-        });
-        self_mut.final_stage.set_open_condition(move || {
-            self.unconstrained_works.is_drained()
-                && self.prepare_stage.is_drained()
-                && self.closure_stage.is_drained()
-                && self.release_stage.is_drained()
-                && self.worker_group().all_parked()
-        });
+        // Each stage (except the prepare stage, which is opened when mutators are paused)
+        // opens when all the previous stages are drained.
+        for (i, bucket) in self_mut.stw_buckets_mut().iter_mut().enumerate().skip(1) {
+            bucket.set_open_condition(move || {
+                self.unconstrained_works.is_drained()
+                    && self.stw_buckets()[..i].iter().all(|b| b.is_drained())
+                    && self.worker_group().all_parked()
+            });
+        }
+    }
+
+    /// The stop-the-world buckets, in the order they are opened.
+    fn stw_buckets(&self) -> [&WorkBucket<C>; 13] {
+        [
+            &self.prepare_stage,
+            &self.closure_stage,
+            &self.soft_retain_stage,
+            &self.soft_refs_stage,
+            &self.weak_refs_stage,
+            &self.finalizable_stage,
+            &self.phantom_refs_stage,
+            &self.calculate_forwarding_stage,
+            &self.second_roots_stage,
+            &self.ref_forwarding_stage,
+            &self.compact_stage,
+            &self.release_stage,
+            &self.final_stage,
+        ]
+    }
+
+    fn stw_buckets_mut(&mut self) -> [&mut WorkBucket<C>; 13] {
+        [
+            &mut self.prepare_stage,
+            &mut self.closure_stage,
+            &mut self.soft_retain_stage,
+            &mut self.soft_refs_stage,
+            &mut self.weak_refs_stage,
+            &mut self.finalizable_stage,
+            &mut self.phantom_refs_stage,
+            &mut self.calculate_forwarding_stage,
+            &mut self.second_roots_stage,
+            &mut self.ref_forwarding_stage,
+            &mut self.compact_stage,
+            &mut self.release_stage,
+            &mut self.final_stage,
+        ]
     }
 
     pub fn initialize_worker(self: &Arc<Self>, tls: OpaquePointer) {
@@ -133,20 +214,18 @@ impl<C: Context> Scheduler<C> {
     }
 
     fn all_buckets_empty(&self) -> bool {
-        self.unconstrained_works.is_empty()
-            && self.prepare_stage.is_empty()
-            && self.closure_stage.is_empty()
-            && self.release_stage.is_empty()
-            && self.final_stage.is_empty()
+        self.unconstrained_works.is_empty() && self.stw_buckets().iter().all(|b| b.is_empty())
     }
 
     /// Open buckets if their conditions are met
     fn update_buckets(&self) {
         let mut buckets_updated = false;
-        buckets_updated |= self.prepare_stage.update();
-        buckets_updated |= self.closure_stage.update();
-        buckets_updated |= self.release_stage.update();
-        buckets_updated |= self.final_stage.update();
+        for (i, bucket) in self.stw_buckets().iter().enumerate() {
+            if bucket.update() {
+                self.stage_open_times.lock().unwrap()[i] = Some(Instant::now());
+                buckets_updated = true;
+            }
+        }
         if buckets_updated {
             // Notify the workers for new works
             let _guard = self.worker_monitor.0.lock().unwrap();
@@ -196,24 +275,34 @@ impl<C: Context> Scheduler<C> {
         if let Some(finalizer) = self.finalizer.lock().unwrap().take() {
             self.process_coordinator_work(finalizer);
         }
-        debug_assert!(!self.prepare_stage.is_activated());
-        debug_assert!(!self.closure_stage.is_activated());
-        debug_assert!(!self.final_stage.is_activated());
-        debug_assert!(!self.release_stage.is_activated());
+        debug_assert!(!self.stw_buckets().iter().any(|b| b.is_activated()));
     }
 
     pub fn deactivate_all(&self) {
-        self.prepare_stage.deactivate();
-        self.closure_stage.deactivate();
-        self.release_stage.deactivate();
-        self.final_stage.deactivate();
+        for bucket in &self.stw_buckets() {
+            bucket.deactivate();
+        }
     }
 
     pub fn reset_state(&self) {
         // self.prepare_stage.deactivate();
-        self.closure_stage.deactivate();
-        self.release_stage.deactivate();
-        self.final_stage.deactivate();
+        for bucket in &self.stw_buckets()[1..] {
+            bucket.deactivate();
+        }
+    }
+
+    /// Add a work that runs concurrently with the mutators, e.g. concurrent marking. Concurrent works
+    /// are unconstrained works, so they are executed between GCs. If a GC starts before they are done,
+    /// they are finished before any stop-the-world stage (except the prepare stage) opens.
+    /// A concurrent work needs to call `concurrent_work_finished()` when it is done.
+    pub fn add_concurrent_work<W: Work<C>>(&self, work: W) {
+        self.pending_concurrent_works.fetch_add(1, Ordering::SeqCst);
+        self.unconstrained_works.add(work);
+    }
+
+    /// Record that a concurrent work is done. Returns true if all the concurrent works are done.
+    pub fn concurrent_work_finished(&self) -> bool {
+        self.pending_concurrent_works.fetch_sub(1, Ordering::SeqCst) == 1
     }
 
     pub fn add_coordinator_work(&self, work: impl CoordinatorWork<C>, worker: &Worker<C>) {
@@ -231,17 +320,10 @@ impl<C: Context> Scheduler<C> {
         if let Some(work) = self.unconstrained_works.poll() {
             return Some((work, self.unconstrained_works.is_empty()));
         }
-        if let Some(work) = self.prepare_stage.poll() {
-            return Some((work, self.prepare_stage.is_empty()));
-        }
-        if let Some(work) = self.closure_stage.poll() {
-            return Some((work, self.closure_stage.is_empty()));
-        }
-        if let Some(work) = self.release_stage.poll() {
-            return Some((work, self.release_stage.is_empty()));
-        }
-        if let Some(work) = self.final_stage.poll() {
-            return Some((work, self.final_stage.is_empty()));
+        for bucket in &self.stw_buckets() {
+            if let Some(work) = bucket.poll() {
+                return Some((work, bucket.is_empty()));
+            }
         }
         None
     }
@@ -293,6 +375,27 @@ impl<C: Context> Scheduler<C> {
         }
     }
 
+    /// The stop-the-world buckets opened in the current pause (see `STW_STAGE_NAMES`), and when
+    /// they were opened.
+    pub fn stage_open_times(&self) -> Vec<(&'static str, Instant)> {
+        let open_times = self.stage_open_times.lock().unwrap();
+        STW_STAGE_NAMES
+            .iter()
+            .zip(open_times.iter())
+            .filter_map(|(name, time)| time.map(|time| (*name, time)))
+            .collect()
+    }
+
+    /// Take the number of works executed by the workers (not including the coordinator) since
+    /// the last call.
+    pub fn take_work_count(&self) -> usize {
+        self.worker_group()
+            .workers
+            .iter()
+            .map(|worker| worker.stat.take_work_count())
+            .sum()
+    }
+
     pub fn enable_stat(&self) {
         for worker in &self.worker_group().workers {
             worker.stat.enable();
@@ -301,6 +404,15 @@ impl<C: Context> Scheduler<C> {
         coordinator_worker.stat.enable();
     }
 
+    /// Record the start and the end of every work executed from now on (see `WorkerLocalStat::take_trace()`).
+    pub fn enable_trace(&self) {
+        for worker in &self.worker_group().workers {
+            worker.stat.enable_trace();
+        }
+        let coordinator_worker = self.coordinator_worker.as_ref().unwrap().read().unwrap();
+        coordinator_worker.stat.enable_trace();
+    }
+
     pub fn statistics(&self) -> HashMap<String, String> {
         let mut summary = SchedulerStat::default();
         for worker in &self.worker_group().workers {
@@ -315,10 +427,38 @@ impl<C: Context> Scheduler<C> {
 pub type MMTkScheduler<VM> = Scheduler<MMTK<VM>>;
 
 impl<VM: VMBinding> MMTkScheduler<VM> {
+    /// Schedule reference processing and finalization for the current GC. `E` is the `ProcessEdgesWork`
+    /// used by the plan for this GC, which is used to trace and forward objects.
+    pub fn schedule_ref_processing<E: ProcessEdgesWork<VM = VM>>(&self) {
+        self.soft_retain_stage.add(SoftRefRetention::<E>::new());
+        self.soft_refs_stage.add(SoftRefProcessing::<E>::new());
+        self.weak_refs_stage.add(WeakRefProcessing::<E>::new());
+        if !self.context.unwrap().options.no_finalizer {
+            self.finalizable_stage.add(Finalization::<E>::new());
+        }
+        self.phantom_refs_stage.add(PhantomRefProcessing::<E>::new());
+        self.release_stage.add(RefEnqueue);
+    }
+
+    /// Schedule forwarding the references and the finalizable objects, for plans that move objects
+    /// after the liveness is known (`PlanConstraints::needs_forward_after_liveness`). `E` is the
+    /// `ProcessEdgesWork` that returns the new address of an object.
+    pub fn schedule_ref_forwarding<E: ProcessEdgesWork<VM = VM>>(&self) {
+        self.ref_forwarding_stage.add(RefForwarding::<E>::new());
+        if !self.context.unwrap().options.no_finalizer {
+            self.ref_forwarding_stage.add(ForwardFinalization::<E>::new());
+        }
+    }
+
     pub fn notify_mutators_paused(&self, mmtk: &'static MMTK<VM>) {
         mmtk.plan.base().control_collector_context.clear_request();
         debug_assert!(!self.prepare_stage.is_activated());
         self.prepare_stage.activate();
+        {
+            let mut open_times = self.stage_open_times.lock().unwrap();
+            *open_times = [None; 13];
+            open_times[0] = Some(Instant::now());
+        }
         let _guard = self.worker_monitor.0.lock().unwrap();
         self.worker_monitor.1.notify_all();
     }
@@ -1,7 +1,8 @@
 use std::any::TypeId;
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, SystemTime};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
 
 #[derive(Default)]
 pub struct SchedulerStat {
@@ -10,18 +11,18 @@ pub struct SchedulerStat {
     work_durations: HashMap<TypeId, Vec<Duration>>,
 }
 
-impl SchedulerStat {
-    /// Extract the work-packet name from the full type name.
-    /// i.e. simplifies `crate::scheduler::gc_works::SomeWorkPacket<Semispace>` to `SomeWorkPacket`.
-    fn work_name(&self, name: &str) -> String {
-        let end_index = name.find('<').unwrap_or_else(|| name.len());
-        let name = name[..end_index].to_owned();
-        match name.rfind(':') {
-            Some(start_index) => name[(start_index + 1)..end_index].to_owned(),
-            _ => name,
-        }
+/// Extract the work-packet name from the full type name.
+/// i.e. simplifies `crate::scheduler::gc_works::SomeWorkPacket<Semispace>` to `SomeWorkPacket`.
+pub fn work_name(name: &str) -> String {
+    let end_index = name.find('<').unwrap_or_else(|| name.len());
+    let name = name[..end_index].to_owned();
+    match name.rfind(':') {
+        Some(start_index) => name[(start_index + 1)..end_index].to_owned(),
+        _ => name,
     }
+}
 
+impl SchedulerStat {
     fn geomean(&self, values: &[f64]) -> f64 {
         // Geomean(xs, N=xs.len()) = (PI(xs))^(1/N) = e^{log{PI(xs)^(1/N)}} = e^{ (1/N) * sum_{x \in xs}{ log(x) } }
         let logs = values.iter().map(|v| v.ln());
@@ -54,10 +55,7 @@ impl SchedulerStat {
         for (t, c) in &self.work_counts {
             total_count += c;
             let n = self.work_id_name_map[t];
-            stat.insert(
-                format!("works.{}.count", self.work_name(n)),
-                format!("{}", c),
-            );
+            stat.insert(format!("works.{}.count", work_name(n)), format!("{}", c));
         }
         stat.insert("total-works.count".to_owned(), format!("{}", total_count));
         // Work execution times
@@ -74,7 +72,7 @@ impl SchedulerStat {
                     .collect::<Vec<_>>(),
             );
             stat.insert(
-                format!("works.{}.time.geomean", self.work_name(n)),
+                format!("works.{}.time.geomean", work_name(n)),
                 format!("{:.2}", geomean),
             );
         }
@@ -128,11 +126,21 @@ pub struct WorkStat {
     type_id: TypeId,
     type_name: &'static str,
     start_time: SystemTime,
+    /// The start of the work, if the works are traced
+    trace_start: Option<Instant>,
 }
 
 impl WorkStat {
     #[inline(always)]
     pub fn end_of_work(&self, worker_stat: &mut WorkerLocalStat) {
+        worker_stat.work_count.fetch_add(1, Ordering::Relaxed);
+        if let Some(start) = self.trace_start {
+            worker_stat.trace.lock().unwrap().push(WorkTraceEvent {
+                name: self.type_name,
+                start,
+                end: Instant::now(),
+            });
+        }
         if !worker_stat.is_enabled() {
             return;
         };
@@ -149,12 +157,27 @@ impl WorkStat {
     }
 }
 
+/// The execution of a work by a worker, which is recorded if the works are traced (see
+/// `Scheduler::enable_trace()`).
+pub struct WorkTraceEvent {
+    /// The type name of the work
+    pub name: &'static str,
+    pub start: Instant,
+    pub end: Instant,
+}
+
 #[derive(Default)]
 pub struct WorkerLocalStat {
     work_id_name_map: HashMap<TypeId, &'static str>,
     work_counts: HashMap<TypeId, usize>,
     work_durations: HashMap<TypeId, Vec<Duration>>,
     enabled: AtomicBool,
+    /// The number of works executed since `take_work_count()` was called. This is counted even
+    /// if the stat is not enabled.
+    work_count: AtomicUsize,
+    tracing: AtomicBool,
+    /// The works executed since `take_trace()` was called, if the works are traced
+    trace: Mutex<Vec<WorkTraceEvent>>,
 }
 
 impl WorkerLocalStat {
@@ -167,11 +190,31 @@ impl WorkerLocalStat {
         self.enabled.store(true, Ordering::SeqCst);
     }
     #[inline]
+    pub fn is_tracing(&self) -> bool {
+        self.tracing.load(Ordering::Relaxed)
+    }
+    #[inline]
+    pub fn enable_trace(&self) {
+        self.tracing.store(true, Ordering::SeqCst);
+    }
+    pub fn take_trace(&self) -> Vec<WorkTraceEvent> {
+        std::mem::take(&mut *self.trace.lock().unwrap())
+    }
+    #[inline]
+    pub fn take_work_count(&self) -> usize {
+        self.work_count.swap(0, Ordering::Relaxed)
+    }
+    #[inline]
     pub fn measure_work(&mut self, work_id: TypeId, work_name: &'static str) -> WorkStat {
         WorkStat {
             type_id: work_id,
             type_name: work_name,
             start_time: SystemTime::now(),
+            trace_start: if self.is_tracing() {
+                Some(Instant::now())
+            } else {
+                None
+            },
         }
     }
 }
@@ -1,14 +1,23 @@
 use std::mem::MaybeUninit;
 
-use crate::plan::selected_plan::SelectedPlan;
+use crate::plan::Plan;
+use crate::policy::immixspace::ImmixSpace;
 use crate::policy::largeobjectspace::LargeObjectSpace;
+use crate::policy::markcompactspace::MarkCompactSpace;
+use crate::policy::marksweepspace::MarkSweepSpace;
 use crate::policy::space::Space;
-use crate::util::alloc::{Allocator, BumpAllocator, LargeObjectAllocator};
+use crate::util::alloc::{
+    Allocator, BumpAllocator, FreeListAllocator, ImmixAllocator, LargeObjectAllocator,
+    MarkCompactAllocator,
+};
 use crate::util::OpaquePointer;
 use crate::vm::VMBinding;
 
 const MAX_BUMP_ALLOCATORS: usize = 5;
 const MAX_LARGE_OBJECT_ALLOCATORS: usize = 1;
+const MAX_FREE_LIST_ALLOCATORS: usize = 1;
+const MAX_IMMIX_ALLOCATORS: usize = 1;
+const MAX_MARK_COMPACT_ALLOCATORS: usize = 1;
 
 // The allocators set owned by each mutator. We provide a fixed number of allocators for each allocator type in the mutator,
 // and each plan will select part of the allocators to use.
@@ -18,6 +27,9 @@ const MAX_LARGE_OBJECT_ALLOCATORS: usize = 1;
 pub struct Allocators<VM: VMBinding> {
     pub bump_pointer: [MaybeUninit<BumpAllocator<VM>>; MAX_BUMP_ALLOCATORS],
     pub large_object: [MaybeUninit<LargeObjectAllocator<VM>>; MAX_LARGE_OBJECT_ALLOCATORS],
+    pub free_list: [MaybeUninit<FreeListAllocator<VM>>; MAX_FREE_LIST_ALLOCATORS],
+    pub immix: [MaybeUninit<ImmixAllocator<VM>>; MAX_IMMIX_ALLOCATORS],
+    pub markcompact: [MaybeUninit<MarkCompactAllocator<VM>>; MAX_MARK_COMPACT_ALLOCATORS],
 }
 
 impl<VM: VMBinding> Allocators<VM> {
@@ -27,6 +39,9 @@ impl<VM: VMBinding> Allocators<VM> {
         match selector {
             AllocatorSelector::BumpPointer(index) => self.bump_pointer[index as usize].get_ref(),
             AllocatorSelector::LargeObject(index) => self.large_object[index as usize].get_ref(),
+            AllocatorSelector::FreeList(index) => self.free_list[index as usize].get_ref(),
+            AllocatorSelector::Immix(index) => self.immix[index as usize].get_ref(),
+            AllocatorSelector::MarkCompact(index) => self.markcompact[index as usize].get_ref(),
         }
     }
 
@@ -39,17 +54,23 @@ impl<VM: VMBinding> Allocators<VM> {
         match selector {
             AllocatorSelector::BumpPointer(index) => self.bump_pointer[index as usize].get_mut(),
             AllocatorSelector::LargeObject(index) => self.large_object[index as usize].get_mut(),
+            AllocatorSelector::FreeList(index) => self.free_list[index as usize].get_mut(),
+            AllocatorSelector::Immix(index) => self.immix[index as usize].get_mut(),
+            AllocatorSelector::MarkCompact(index) => self.markcompact[index as usize].get_mut(),
         }
     }
 
     pub fn new(
         mutator_tls: OpaquePointer,
-        plan: &'static SelectedPlan<VM>,
+        plan: &'static dyn Plan<VM = VM>,
         space_mapping: &[(AllocatorSelector, &'static dyn Space<VM>)],
     ) -> Self {
         let mut ret = Allocators {
             bump_pointer: unsafe { MaybeUninit::uninit().assume_init() },
             large_object: unsafe { MaybeUninit::uninit().assume_init() },
+            free_list: unsafe { MaybeUninit::uninit().assume_init() },
+            immix: unsafe { MaybeUninit::uninit().assume_init() },
+            markcompact: unsafe { MaybeUninit::uninit().assume_init() },
         };
 
         for &(selector, space) in space_mapping.iter() {
@@ -68,6 +89,28 @@ impl<VM: VMBinding> Allocators<VM> {
                         plan,
                     ));
                 }
+                AllocatorSelector::FreeList(index) => {
+                    ret.free_list[index as usize].write(FreeListAllocator::new(
+                        mutator_tls,
+                        Some(space.downcast_ref::<MarkSweepSpace<VM>>().unwrap()),
+                        plan,
+                    ));
+                }
+                AllocatorSelector::Immix(index) => {
+                    ret.immix[index as usize].write(ImmixAllocator::new(
+                        mutator_tls,
+                        Some(space.downcast_ref::<ImmixSpace<VM>>().unwrap()),
+                        plan,
+                        false,
+                    ));
+                }
+                AllocatorSelector::MarkCompact(index) => {
+                    ret.markcompact[index as usize].write(MarkCompactAllocator::new(
+                        mutator_tls,
+                        Some(space.downcast_ref::<MarkCompactSpace<VM>>().unwrap()),
+                        plan,
+                    ));
+                }
             }
         }
 
@@ -86,10 +129,16 @@ impl<VM: VMBinding> Allocators<VM> {
 // enum AllocatorSelectorTag {
 //   BumpPointer,
 //   LargeObject,
+//   FreeList,
+//   Immix,
+//   MarkCompact,
 // }
 #[repr(C, u8)]
 #[derive(Copy, Clone)]
 pub enum AllocatorSelector {
     BumpPointer(u8),
     LargeObject(u8),
+    FreeList(u8),
+    Immix(u8),
+    MarkCompact(u8),
 }
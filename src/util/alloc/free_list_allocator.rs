@@ -0,0 +1,104 @@
+use crate::plan::Plan;
+use crate::policy::marksweepspace::{self, MarkSweepSpace, NUM_SIZE_CLASSES};
+use crate::policy::space::Space;
+use crate::util::alloc::{allocator, Allocator};
+use crate::util::conversions::raw_align_up;
+use crate::util::memory;
+use crate::util::Address;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+
+/// A segregated free-list allocator. It keeps a free list of cells for each size class,
+/// and refills an empty free list with the free cells of a block from the `MarkSweepSpace`.
+#[repr(C)]
+pub struct FreeListAllocator<VM: VMBinding> {
+    pub tls: OpaquePointer,
+    free_lists: [Address; NUM_SIZE_CLASSES],
+    space: Option<&'static MarkSweepSpace<VM>>,
+    plan: &'static dyn Plan<VM = VM>,
+}
+
+impl<VM: VMBinding> Allocator<VM> for FreeListAllocator<VM> {
+    fn get_tls(&self) -> OpaquePointer {
+        self.tls
+    }
+    fn get_plan(&self) -> &'static dyn Plan<VM = VM> {
+        self.plan
+    }
+
+    fn get_space(&self) -> Option<&'static dyn Space<VM>> {
+        // Casting the interior of the Option: from &MarkSweepSpace to &dyn Space
+        self.space.map(|s| s as &'static dyn Space<VM>)
+    }
+
+    #[inline]
+    fn alloc(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        let bytes = Self::max_bytes(size, align);
+        let size_class = Self::size_class(bytes);
+        let cell = self.free_lists[size_class];
+        if cell.is_zero() {
+            return self.alloc_slow(size, align, offset);
+        }
+        self.free_lists[size_class] = unsafe { cell.load::<Address>() };
+        Self::init_cell(cell, bytes, align, offset)
+    }
+
+    fn alloc_slow_once(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        let bytes = Self::max_bytes(size, align);
+        let size_class = Self::size_class(bytes);
+        let cells = self.space.unwrap().acquire_cells(self.tls, size_class);
+        if cells.is_zero() {
+            return cells;
+        }
+        self.free_lists[size_class] = unsafe { cells.load::<Address>() };
+        Self::init_cell(cells, bytes, align, offset)
+    }
+}
+
+impl<VM: VMBinding> FreeListAllocator<VM> {
+    pub fn new(
+        tls: OpaquePointer,
+        space: Option<&'static MarkSweepSpace<VM>>,
+        plan: &'static dyn Plan<VM = VM>,
+    ) -> Self {
+        FreeListAllocator {
+            tls,
+            free_lists: unsafe { [Address::zero(); NUM_SIZE_CLASSES] },
+            space,
+            plan,
+        }
+    }
+
+    /// Drop all the cells held by this allocator. The space rebuilds the free lists of
+    /// its blocks when it sweeps, so this needs to be called in each GC.
+    pub fn reset(&mut self) {
+        self.free_lists = unsafe { [Address::zero(); NUM_SIZE_CLASSES] };
+    }
+
+    /// Zero the first `bytes` of a cell taken from a free list (they may hold the free list link
+    /// and a dead object), and align the allocation in it.
+    #[inline(always)]
+    fn init_cell(cell: Address, bytes: usize, align: usize, offset: isize) -> Address {
+        memory::zero(cell, bytes);
+        allocator::align_allocation::<VM>(cell, align, offset, VM::MIN_ALIGNMENT, true)
+    }
+
+    /// The bytes needed for an object of `size` bytes with the alignment, including the padding.
+    #[inline(always)]
+    fn max_bytes(size: usize, align: usize) -> usize {
+        let size = raw_align_up(size, VM::MIN_ALIGNMENT);
+        allocator::get_maximum_aligned_size::<VM>(size, align, VM::MIN_ALIGNMENT)
+    }
+
+    #[inline(always)]
+    fn size_class(bytes: usize) -> usize {
+        match marksweepspace::size_class_for(bytes) {
+            Some(size_class) => size_class,
+            None => panic!(
+                "Cannot allocate {} bytes with the free-list allocator: objects larger than {} bytes need to be allocated in the large object space",
+                bytes,
+                marksweepspace::MAX_CELL_SIZE
+            ),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use super::allocator::{align_allocation_no_fill, fill_alignment_gap};
+use crate::plan::Plan;
+use crate::policy::immixspace::{self, ImmixSpace};
+use crate::policy::space::Space;
+use crate::util::alloc::Allocator;
+use crate::util::memory;
+use crate::util::Address;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+
+/// The allocator for the immix space. It bump-allocates into holes of free lines in the
+/// reusable blocks, and into clean blocks. Medium objects (larger than a line) that do not
+/// fit in the current hole are bump-allocated into a separate overflow block instead, so
+/// that they do not waste the rest of the hole.
+#[repr(C)]
+pub struct ImmixAllocator<VM: VMBinding> {
+    pub tls: OpaquePointer,
+    cursor: Address,
+    limit: Address,
+    space: Option<&'static ImmixSpace<VM>>,
+    plan: &'static dyn Plan<VM = VM>,
+    /// Is this allocator used by the GC to copy objects? The copying allocator only uses clean blocks.
+    copy: bool,
+    /// The bump pointer for medium objects.
+    large_cursor: Address,
+    large_limit: Address,
+    /// Is the current request for a medium object?
+    request_for_large: bool,
+    /// The reusable block we are allocating into, and the line to search for the next hole from.
+    line_block: Address,
+    next_line: usize,
+}
+
+impl<VM: VMBinding> ImmixAllocator<VM> {
+    pub fn new(
+        tls: OpaquePointer,
+        space: Option<&'static ImmixSpace<VM>>,
+        plan: &'static dyn Plan<VM = VM>,
+        copy: bool,
+    ) -> Self {
+        ImmixAllocator {
+            tls,
+            cursor: unsafe { Address::zero() },
+            limit: unsafe { Address::zero() },
+            space,
+            plan,
+            copy,
+            large_cursor: unsafe { Address::zero() },
+            large_limit: unsafe { Address::zero() },
+            request_for_large: false,
+            line_block: unsafe { Address::zero() },
+            next_line: 0,
+        }
+    }
+
+    /// Drop the current blocks and holes. The line marks change in each GC, so this needs
+    /// to be called in each GC.
+    pub fn reset(&mut self) {
+        self.cursor = unsafe { Address::zero() };
+        self.limit = unsafe { Address::zero() };
+        self.large_cursor = unsafe { Address::zero() };
+        self.large_limit = unsafe { Address::zero() };
+        self.request_for_large = false;
+        self.line_block = unsafe { Address::zero() };
+        self.next_line = 0;
+    }
+
+    pub fn rebind(&mut self, space: Option<&'static ImmixSpace<VM>>) {
+        self.reset();
+        self.space = space;
+    }
+
+    /// Allocate a medium object into the overflow block.
+    fn overflow_alloc(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        let result = align_allocation_no_fill::<VM>(self.large_cursor, align, offset);
+        let new_cursor = result + size;
+        if new_cursor > self.large_limit {
+            self.request_for_large = true;
+            let rtn = self.alloc_slow_inline(size, align, offset);
+            self.request_for_large = false;
+            rtn
+        } else {
+            fill_alignment_gap::<VM>(self.large_cursor, result);
+            self.large_cursor = new_cursor;
+            result
+        }
+    }
+
+    /// The current hole is used up. Try the next holes before getting a new block.
+    fn alloc_slow_hot(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        if self.acquire_recyclable_lines() {
+            self.alloc(size, align, offset)
+        } else {
+            self.alloc_slow(size, align, offset)
+        }
+    }
+
+    /// Bump-allocate into a clean block, for medium objects if `large` is true.
+    fn bump_into_clean_block(&mut self, block: Address, large: bool) {
+        let start = block + (immixspace::FIRST_USABLE_LINE << immixspace::LOG_BYTES_IN_LINE);
+        let end = block + immixspace::BYTES_IN_BLOCK;
+        if large {
+            self.large_cursor = start;
+            self.large_limit = end;
+        } else {
+            self.cursor = start;
+            self.limit = end;
+        }
+    }
+
+    /// For the copying allocator: make sure that the next allocation of `size` bytes does not
+    /// need another block, getting a clean block for it if needed. Returns false if the space
+    /// has no clean block for copying, i.e. the defrag headroom is used up.
+    pub fn reserve_copy(&mut self, size: usize, align: usize, offset: isize) -> bool {
+        debug_assert!(self.copy);
+        // The same bump pointers as `alloc()` and `overflow_alloc()`
+        let fits = |cursor: Address, limit: Address| {
+            align_allocation_no_fill::<VM>(cursor, align, offset) + size <= limit
+        };
+        let large = size > immixspace::BYTES_IN_LINE;
+        if fits(self.cursor, self.limit) || (large && fits(self.large_cursor, self.large_limit)) {
+            return true;
+        }
+        let block = self.space.unwrap().get_clean_block(self.tls, true);
+        if block.is_zero() {
+            return false;
+        }
+        self.bump_into_clean_block(block, large);
+        true
+    }
+
+    /// Move the bump pointer to the next hole in the reusable blocks. Returns false if there is no hole left.
+    fn acquire_recyclable_lines(&mut self) -> bool {
+        if self.copy {
+            return false;
+        }
+        let space = self.space.unwrap();
+        loop {
+            if self.line_block.is_zero() {
+                match space.get_reusable_block() {
+                    Some(block) => {
+                        self.line_block = block;
+                        self.next_line = immixspace::FIRST_USABLE_LINE;
+                    }
+                    None => return false,
+                }
+            }
+            if let Some((start, end, next_line)) =
+                space.get_next_hole(self.line_block, self.next_line)
+            {
+                // The free lines may hold dead objects from before the last GC.
+                memory::zero(start, end - start);
+                self.cursor = start;
+                self.limit = end;
+                self.next_line = next_line;
+                return true;
+            }
+            self.line_block = unsafe { Address::zero() };
+        }
+    }
+}
+
+impl<VM: VMBinding> Allocator<VM> for ImmixAllocator<VM> {
+    fn get_tls(&self) -> OpaquePointer {
+        self.tls
+    }
+    fn get_plan(&self) -> &'static dyn Plan<VM = VM> {
+        self.plan
+    }
+
+    fn get_space(&self) -> Option<&'static dyn Space<VM>> {
+        // Casting the interior of the Option: from &ImmixSpace to &dyn Space
+        self.space.map(|s| s as &'static dyn Space<VM>)
+    }
+
+    #[inline]
+    fn alloc(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        debug_assert!(
+            size <= immixspace::MAX_IMMIX_OBJECT_SIZE,
+            "Objects larger than {} bytes need to be allocated in the large object space",
+            immixspace::MAX_IMMIX_OBJECT_SIZE
+        );
+        let result = align_allocation_no_fill::<VM>(self.cursor, align, offset);
+        let new_cursor = result + size;
+        if new_cursor > self.limit {
+            if size > immixspace::BYTES_IN_LINE {
+                self.overflow_alloc(size, align, offset)
+            } else {
+                self.alloc_slow_hot(size, align, offset)
+            }
+        } else {
+            fill_alignment_gap::<VM>(self.cursor, result);
+            self.cursor = new_cursor;
+            result
+        }
+    }
+
+    #[inline(never)]
+    fn alloc_slow(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        if self.copy {
+            // The GC cannot trigger another GC, and the copying allocator gets clean blocks regardless of the heap budget.
+            self.alloc_slow_once(size, align, offset)
+        } else {
+            self.alloc_slow_inline(size, align, offset)
+        }
+    }
+
+    fn alloc_slow_once(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        let block = self.space.unwrap().get_clean_block(self.tls, self.copy);
+        if block.is_zero() {
+            return block;
+        }
+        self.bump_into_clean_block(block, self.request_for_large);
+        if self.request_for_large {
+            self.overflow_alloc(size, align, offset)
+        } else {
+            self.alloc(size, align, offset)
+        }
+    }
+}
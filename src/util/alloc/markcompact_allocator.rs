@@ -0,0 +1,101 @@
+use super::allocator::{align_allocation_no_fill, fill_alignment_gap};
+use crate::plan::Plan;
+use crate::policy::markcompactspace::{self, MarkCompactSpace, GC_EXTRA_HEADER_BYTES};
+use crate::policy::space::Space;
+use crate::util::alloc::Allocator;
+use crate::util::Address;
+use crate::util::OpaquePointer;
+use crate::vm::VMBinding;
+
+/// The allocator for the mark-compact space. It bump-allocates into blocks, and reserves
+/// an extra header word in front of each object for the forwarding address.
+#[repr(C)]
+pub struct MarkCompactAllocator<VM: VMBinding> {
+    pub tls: OpaquePointer,
+    cursor: Address,
+    limit: Address,
+    space: Option<&'static MarkCompactSpace<VM>>,
+    plan: &'static dyn Plan<VM = VM>,
+}
+
+impl<VM: VMBinding> MarkCompactAllocator<VM> {
+    pub fn new(
+        tls: OpaquePointer,
+        space: Option<&'static MarkCompactSpace<VM>>,
+        plan: &'static dyn Plan<VM = VM>,
+    ) -> Self {
+        MarkCompactAllocator {
+            tls,
+            cursor: unsafe { Address::zero() },
+            limit: unsafe { Address::zero() },
+            space,
+            plan,
+        }
+    }
+
+    /// Record the end of the data in the current block, so that the space can walk it.
+    /// This needs to be called before each GC.
+    pub fn retire_block(&mut self) {
+        if !self.limit.is_zero() {
+            MarkCompactSpace::<VM>::set_data_end(
+                self.limit - markcompactspace::BYTES_IN_BLOCK,
+                self.cursor,
+            );
+        }
+    }
+
+    /// Drop the current block. The objects are moved in each GC, so this needs to be called in each GC.
+    pub fn reset(&mut self) {
+        self.cursor = unsafe { Address::zero() };
+        self.limit = unsafe { Address::zero() };
+    }
+}
+
+impl<VM: VMBinding> Allocator<VM> for MarkCompactAllocator<VM> {
+    fn get_tls(&self) -> OpaquePointer {
+        self.tls
+    }
+    fn get_plan(&self) -> &'static dyn Plan<VM = VM> {
+        self.plan
+    }
+
+    fn get_space(&self) -> Option<&'static dyn Space<VM>> {
+        // Casting the interior of the Option: from &MarkCompactSpace to &dyn Space
+        self.space.map(|s| s as &'static dyn Space<VM>)
+    }
+
+    #[inline]
+    fn alloc(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        debug_assert!(
+            size <= markcompactspace::MAX_MARKCOMPACT_OBJECT_SIZE,
+            "Objects larger than {} bytes need to be allocated in the large object space",
+            markcompactspace::MAX_MARKCOMPACT_OBJECT_SIZE
+        );
+        let header = align_allocation_no_fill::<VM>(
+            self.cursor,
+            align,
+            offset + GC_EXTRA_HEADER_BYTES as isize,
+        );
+        let result = header + GC_EXTRA_HEADER_BYTES;
+        let new_cursor = result + size;
+        if new_cursor > self.limit {
+            self.alloc_slow(size, align, offset)
+        } else {
+            fill_alignment_gap::<VM>(self.cursor, header);
+            unsafe { header.store(Address::zero()) };
+            self.cursor = new_cursor;
+            result
+        }
+    }
+
+    fn alloc_slow_once(&mut self, size: usize, align: usize, offset: isize) -> Address {
+        let block = self.space.unwrap().get_block(self.tls);
+        if block.is_zero() {
+            return block;
+        }
+        self.retire_block();
+        self.cursor = MarkCompactSpace::<VM>::data_start(block);
+        self.limit = block + markcompactspace::BYTES_IN_BLOCK;
+        self.alloc(size, align, offset)
+    }
+}
@@ -0,0 +1,227 @@
+//! The alloc bit: one bit in side metadata for each word, which is set if an object
+//! reference points to the word. The bit is set when an object is allocated or copied, and
+//! cleared when the object is found dead or its memory is released, so a binding can check
+//! whether an arbitrary address is a reference to an object allocated by MMTk (e.g. for
+//! conservative stack scanning). The bits are only set with the `alloc_bit` option, and the
+//! copies of an object have the bit if the object has it.
+//!
+//! With the option, the alloc bit is mapped for all the spaces (see
+//! `HeapMeta::side_metadata_specs`). Otherwise it is neither mapped nor accessed: the queries find
+//! no objects, and clearing the bits does nothing. Object references need to be word-aligned. The
+//! objects in the VM space are not allocated by MMTk, and do not have alloc bits. The
+//! `LockFreeImmortalSpace` sets the alloc bits, but it is not in the SFT map, so its objects are
+//! not found by the queries.
+
+use crate::mmtk::SFT_MAP;
+use crate::util::constants::{BYTES_IN_WORD, LOG_BYTES_IN_WORD};
+use crate::util::heap::layout::vm_layout_constants::{BYTES_IN_CHUNK, HEAP_END, HEAP_START};
+use crate::util::pin_bit;
+use crate::util::side_metadata::SideMetadataSpec;
+use crate::util::{Address, ObjectReference};
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const ALLOC_SIDE_METADATA_SPEC: SideMetadataSpec =
+    SideMetadataSpec::new("alloc bit", 0, 0, LOG_BYTES_IN_WORD as usize);
+
+/// Set once an MMTk instance is created with the `alloc_bit` option.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Keep the alloc bits. This needs to be called before the spaces are created.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+pub fn set_alloc_bit(object: ObjectReference) {
+    debug_assert!(
+        !is_alloced(object),
+        "{} already has the alloc bit set",
+        object
+    );
+    ALLOC_SIDE_METADATA_SPEC.store(object.to_address(), 1);
+}
+
+pub fn unset_alloc_bit(object: ObjectReference) {
+    if is_enabled() {
+        ALLOC_SIDE_METADATA_SPEC.store(object.to_address(), 0);
+    }
+}
+
+pub fn is_alloced(object: ObjectReference) -> bool {
+    is_enabled() && ALLOC_SIDE_METADATA_SPEC.load(object.to_address()) == 1
+}
+
+/// Clear the alloc bits for the addresses from `start` to `start + bytes`.
+pub fn bzero_alloc_bit(start: Address, bytes: usize) {
+    if is_enabled() {
+        ALLOC_SIDE_METADATA_SPEC.bzero(start, bytes);
+    }
+}
+
+/// Call `f` for each object from `start` to `start + bytes` that has the alloc bit set, in
+/// address order.
+pub fn for_each_alloced_object<F: FnMut(ObjectReference)>(start: Address, bytes: usize, mut f: F) {
+    let mut cursor = start;
+    while cursor < start + bytes {
+        let object = unsafe { cursor.to_object_reference() };
+        if is_alloced(object) {
+            f(object);
+        }
+        cursor += BYTES_IN_WORD;
+    }
+}
+
+/// Clear the alloc bits of the dead objects from `start` to `start + bytes`, and unpin the
+/// dead objects. This is for the spaces that cannot find their dead objects otherwise.
+pub fn sweep_alloc_bits<F: Fn(ObjectReference) -> bool>(start: Address, bytes: usize, is_live: F) {
+    if !is_enabled() {
+        return;
+    }
+    for_each_alloced_object(start, bytes, |object| {
+        if !is_live(object) {
+            unset_alloc_bit(object);
+            pin_bit::unpin_object(object);
+        }
+    });
+}
+
+/// Is the address in a chunk of a space? The side metadata is mapped for such chunks.
+fn is_in_mmtk_spaces(address: Address) -> bool {
+    address >= HEAP_START && address < HEAP_END && SFT_MAP.is_in_any_space(address)
+}
+
+/// Is there an object allocated by MMTk at the address? The address can be any value.
+pub fn is_alloced_object(address: Address) -> bool {
+    is_enabled()
+        && address.is_aligned_to(BYTES_IN_WORD)
+        && is_in_mmtk_spaces(address)
+        && is_alloced(unsafe { address.to_object_reference() })
+}
+
+/// Find the object that contains the address, i.e. the object with the highest reference that is not
+/// above the address, if the address is before the end of the object. The space of the address
+/// searches for the object (see `SFT::find_object_from_internal_pointer()`). An object that
+/// contains the address starts less than `max_search_bytes` before it, unless it is in the large
+/// object space.
+pub fn find_object_from_internal_pointer(
+    internal_ptr: Address,
+    max_search_bytes: usize,
+) -> Option<ObjectReference> {
+    if !is_enabled() || !is_in_mmtk_spaces(internal_ptr) {
+        return None;
+    }
+    SFT_MAP
+        .get(internal_ptr)
+        .find_object_from_internal_pointer(internal_ptr, max_search_bytes)
+}
+
+/// Find the object that contains the address by its alloc bit, searching back at most
+/// `max_search_bytes` from the address. The search does not leave the space of the address.
+pub fn find_object_by_alloc_bits<VM: VMBinding>(
+    internal_ptr: Address,
+    max_search_bytes: usize,
+) -> Option<ObjectReference> {
+    let mut limit = if internal_ptr - HEAP_START > max_search_bytes {
+        internal_ptr - max_search_bytes
+    } else {
+        HEAP_START
+    };
+    // Stop at the start of the space
+    let space = SFT_MAP.get(internal_ptr) as *const _ as *const u8;
+    let mut chunk = internal_ptr.align_down(BYTES_IN_CHUNK);
+    while chunk > limit {
+        if SFT_MAP.get(chunk - BYTES_IN_WORD) as *const _ as *const u8 != space {
+            limit = chunk;
+            break;
+        }
+        chunk -= BYTES_IN_CHUNK;
+    }
+    let start = ALLOC_SIDE_METADATA_SPEC.find_prev_non_zero(internal_ptr, limit)?;
+    let object = unsafe { start.to_object_reference() };
+    if VM::VMObjectModel::get_object_end_address(object) > internal_ptr {
+        Some(object)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::heap_layout::Mmapper;
+    use crate::util::test_util::mock_vm::{init_object, MockVM};
+    use crate::util::test_util::{map_heap_memory, serial_test};
+
+    #[test]
+    fn test_alloc_bits() {
+        serial_test(|| {
+            enable();
+            pin_bit::enable();
+            let start = HEAP_START + (1usize << 23);
+            let mmapper = Mmapper::new();
+            ALLOC_SIDE_METADATA_SPEC.ensure_mapped(&mmapper, start, 1 << 20);
+            // Sweeping unpins the dead objects
+            pin_bit::PIN_SIDE_METADATA_SPEC.ensure_mapped(&mmapper, start, 1 << 20);
+            let objects: Vec<ObjectReference> = (0..16)
+                .map(|i| unsafe { (start + i * 3 * BYTES_IN_WORD).to_object_reference() })
+                .collect();
+            for &object in objects.iter() {
+                set_alloc_bit(object);
+            }
+            assert!(objects.iter().all(|&object| is_alloced(object)));
+            assert!(!is_alloced(unsafe {
+                (start + BYTES_IN_WORD).to_object_reference()
+            }));
+            let mut found = vec![];
+            for_each_alloced_object(start, 48 * BYTES_IN_WORD, |object| found.push(object));
+            assert_eq!(found, objects);
+            pin_bit::pin_object(objects[0]);
+            pin_bit::pin_object(objects[1]);
+            // Keep every other object
+            sweep_alloc_bits(start, 48 * BYTES_IN_WORD, |object| {
+                objects.iter().step_by(2).any(|&live| live == object)
+            });
+            assert!(objects.iter().step_by(2).all(|&object| is_alloced(object)));
+            assert!(objects
+                .iter()
+                .skip(1)
+                .step_by(2)
+                .all(|&object| !is_alloced(object)));
+            assert!(pin_bit::is_pinned(objects[0]));
+            assert!(!pin_bit::is_pinned(objects[1]));
+            pin_bit::unpin_object(objects[0]);
+            unset_alloc_bit(objects[0]);
+            assert!(!is_alloced(objects[0]));
+            bzero_alloc_bit(start, 48 * BYTES_IN_WORD);
+            assert!(objects.iter().all(|&object| !is_alloced(object)));
+        })
+    }
+
+    #[test]
+    fn test_find_object_by_alloc_bits() {
+        serial_test(|| {
+            let start = map_heap_memory(2 << 22, 1 << 20);
+            let small = init_object(start + 64usize, 2);
+            let large = init_object(start + 1024usize, 1000);
+            set_alloc_bit(small);
+            set_alloc_bit(large);
+            let find = |address: Address, max_search_bytes: usize| {
+                find_object_by_alloc_bits::<MockVM>(address, max_search_bytes)
+            };
+            assert_eq!(find(start + 64usize, 1024), Some(small));
+            assert_eq!(find(start + 95usize, 1024), Some(small));
+            assert_eq!(find(start + 96usize, 1024), None);
+            assert_eq!(find(start + 63usize, 1024), None);
+            // The search stops after max_search_bytes
+            assert_eq!(find(start + 9000usize, 1024), None);
+            assert_eq!(find(start + 9000usize, 8192), Some(large));
+            bzero_alloc_bit(start, 1 << 20);
+        })
+    }
+}
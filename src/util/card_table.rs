@@ -0,0 +1,323 @@
+//! A side card table for card-marking barriers.
+
+use crate::util::constants::{LOG_BYTES_IN_WORD, LOG_CARD_BYTES};
+use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+use crate::util::rc_table::RC_COUNT_SPEC;
+use crate::util::side_metadata::{
+    SideMetadataSpec, LOG_MAX_NUM_OF_BITS, SIDE_METADATA_BASE_ADDRESS,
+};
+use crate::util::{Address, ObjectReference};
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+pub const BYTES_IN_CARD: usize = 1 << LOG_CARD_BYTES;
+
+const CLEAN: u8 = 0;
+const DIRTY: u8 = 1;
+
+/// The card bytes, one for each card.
+pub const CARD_SIDE_METADATA_SPEC: SideMetadataSpec = SideMetadataSpec::new(
+    "card",
+    RC_COUNT_SPEC.next_offset(),
+    LOG_MAX_NUM_OF_BITS,
+    LOG_CARD_BYTES,
+);
+
+/// The word offset (plus one) of the first object that starts on each card. Zero if there is none.
+const FIRST_OBJECT_SPEC: SideMetadataSpec = SideMetadataSpec::new(
+    "card first object",
+    CARD_SIDE_METADATA_SPEC.next_offset(),
+    LOG_MAX_NUM_OF_BITS,
+    LOG_CARD_BYTES,
+);
+
+/// The word offset (plus one) of the last object that starts on each card. Zero if there is none.
+const LAST_OBJECT_SPEC: SideMetadataSpec = SideMetadataSpec::new(
+    "card last object",
+    FIRST_OBJECT_SPEC.next_offset(),
+    LOG_MAX_NUM_OF_BITS,
+    LOG_CARD_BYTES,
+);
+
+/// The number of cards back to the card where the object that overlaps the start of each card
+/// starts. Zero if no recorded object overlaps the start of the card. See `MAX_CARDS_BACK`.
+const CARDS_BACK_SPEC: SideMetadataSpec = SideMetadataSpec::new(
+    "card crossing object",
+    LAST_OBJECT_SPEC.next_offset(),
+    LOG_MAX_NUM_OF_BITS,
+    LOG_CARD_BYTES,
+);
+
+/// A distance of `MAX_CARDS_BACK` cards or more is recorded as `MAX_CARDS_BACK`. The card
+/// `MAX_CARDS_BACK - 1` cards back is still overlapped by the same object, and has the rest of the distance.
+const MAX_CARDS_BACK: usize = u8::MAX as usize;
+
+/// The side metadata of the objects recorded on the cards.
+pub const OBJECT_SIDE_METADATA_SPECS: [SideMetadataSpec; 3] =
+    [FIRST_OBJECT_SPEC, LAST_OBJECT_SPEC, CARDS_BACK_SPEC];
+
+/// A card table with one byte for each card of the heap. A card-marking barrier dirties the card
+/// of a modified object or field, and a nursery GC scans the objects on the dirty cards.
+///
+/// To find the objects on a card, the table also records the offsets of the first and the last
+/// object that start on each card, and for each card that an object extends into, how many cards
+/// back the object starts (the crossing map). Objects between the first and the last are found
+/// with `ObjectModel::get_next_object`, so the objects that start on a card need to be allocated
+/// contiguously. Only the recorded objects (i.e. the mature objects) can be found.
+///
+/// The table is in side metadata. `CARD_SIDE_METADATA_SPEC` needs to be mapped for the spaces of
+/// the objects that the barrier writes to, and `OBJECT_SIDE_METADATA_SPECS` for the spaces of the
+/// recorded objects (see `CommonSpace::side_metadata_specs`).
+pub struct CardTable;
+
+impl CardTable {
+    /// The address of the card byte for address zero. The card byte of an address is at
+    /// `biased_base() + (address >> LOG_CARD_BYTES)`, so a binding can inline the barrier
+    /// as a shift and a byte store.
+    pub fn biased_base(&self) -> Address {
+        SIDE_METADATA_BASE_ADDRESS + CARD_SIDE_METADATA_SPEC.offset
+            - (HEAP_START.as_usize() >> LOG_CARD_BYTES)
+    }
+
+    /// Check that an address has a card. A card outside of the heap range is not in the side metadata.
+    #[inline(always)]
+    fn check_in_heap(addr: Address) {
+        assert!(
+            addr >= HEAP_START && addr < HEAP_END,
+            "{} is out of the card table",
+            addr
+        );
+    }
+
+    #[inline(always)]
+    fn card_byte(&self, addr: Address) -> &AtomicU8 {
+        Self::check_in_heap(addr);
+        unsafe { &*(self.biased_base() + (addr.as_usize() >> LOG_CARD_BYTES)).to_ptr::<AtomicU8>() }
+    }
+
+    /// Dirty the card of an address. This is the fast path of the card-marking barrier.
+    #[inline(always)]
+    pub fn mark_card(&self, addr: Address) {
+        self.card_byte(addr).store(DIRTY, Ordering::Relaxed);
+    }
+
+    pub fn is_dirty(&self, card: Address) -> bool {
+        self.card_byte(card).load(Ordering::Relaxed) == DIRTY
+    }
+
+    pub fn clean(&self, card: Address) {
+        self.card_byte(card).store(CLEAN, Ordering::Relaxed);
+    }
+
+    /// Record an object of `bytes` bytes that starts at `start`, so that it can be found when its
+    /// cards are scanned. Objects on a card are expected to be recorded by one thread at a time.
+    pub fn record_object(&self, start: Address, bytes: usize) {
+        Self::check_in_heap(start);
+        debug_assert!(start.is_aligned_to(1 << LOG_BYTES_IN_WORD));
+        let card = start.align_down(BYTES_IN_CARD);
+        let offset = (((start - card) >> LOG_BYTES_IN_WORD) + 1) as u8;
+        let first = FIRST_OBJECT_SPEC.load(card);
+        if first == 0 || offset < first {
+            FIRST_OBJECT_SPEC.store(card, offset);
+        }
+        if offset > LAST_OBJECT_SPEC.load(card) {
+            LAST_OBJECT_SPEC.store(card, offset);
+        }
+        // The object is the last one on its card, and overlaps the start of the cards it extends into
+        let end = start + bytes;
+        let mut next_card = card + BYTES_IN_CARD;
+        let mut cards_back = 1;
+        while next_card < end {
+            CARDS_BACK_SPEC.store(next_card, cards_back.min(MAX_CARDS_BACK) as u8);
+            next_card += BYTES_IN_CARD;
+            cards_back += 1;
+        }
+    }
+
+    /// Forget the objects recorded from `start` to `start + bytes`, and clean their cards. This
+    /// needs to be done when the recorded objects are moved or released, e.g. in a full heap GC.
+    pub fn clear(&self, start: Address, bytes: usize) {
+        debug_assert!(start.is_aligned_to(BYTES_IN_CARD));
+        let bytes = (bytes + BYTES_IN_CARD - 1) & !(BYTES_IN_CARD - 1);
+        CARD_SIDE_METADATA_SPEC.bzero(start, bytes);
+        for spec in OBJECT_SIDE_METADATA_SPECS.iter() {
+            spec.bzero(start, bytes);
+        }
+    }
+
+    /// The start of the first object and the last object recorded on a card, if any.
+    pub fn objects_on_card(&self, card: Address) -> Option<(Address, Address)> {
+        Self::check_in_heap(card);
+        let first = FIRST_OBJECT_SPEC.load(card) as usize;
+        if first == 0 {
+            return None;
+        }
+        let last = LAST_OBJECT_SPEC.load(card) as usize;
+        Some((
+            card + ((first - 1) << LOG_BYTES_IN_WORD),
+            card + ((last - 1) << LOG_BYTES_IN_WORD),
+        ))
+    }
+
+    /// The start of the recorded object that starts on an earlier card and overlaps the start of
+    /// a card, if any.
+    fn object_crossing_card(&self, card: Address) -> Option<Address> {
+        Self::check_in_heap(card);
+        let mut cards_back = CARDS_BACK_SPEC.load(card) as usize;
+        if cards_back == 0 {
+            return None;
+        }
+        let mut start_card = card;
+        while cards_back == MAX_CARDS_BACK {
+            start_card -= (MAX_CARDS_BACK - 1) * BYTES_IN_CARD;
+            cards_back = CARDS_BACK_SPEC.load(start_card) as usize;
+        }
+        start_card -= cards_back * BYTES_IN_CARD;
+        self.objects_on_card(start_card).map(|(_, last)| last)
+    }
+
+    /// Call `f` for each recorded object that overlaps a card: the objects that start on the card,
+    /// and the object that starts on an earlier card and extends into this card.
+    pub fn for_each_object_on_card<VM: VMBinding, F: FnMut(ObjectReference)>(
+        &self,
+        card: Address,
+        mut f: F,
+    ) {
+        if let Some(start) = self.object_crossing_card(card) {
+            f(unsafe { VM::VMObjectModel::get_object_from_start_address(start) });
+        }
+        // The objects that start on this card are contiguous.
+        if let Some((first, last)) = self.objects_on_card(card) {
+            let mut object = unsafe { VM::VMObjectModel::get_object_from_start_address(first) };
+            loop {
+                f(object);
+                if VM::VMObjectModel::object_start_ref(object) >= last {
+                    break;
+                }
+                object = VM::VMObjectModel::get_next_object(object);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::heap_layout::Mmapper;
+    use crate::util::test_util::mock_vm::{init_object, MockVM};
+    use crate::util::test_util::{map_heap_memory, serial_test};
+
+    const TEST_BYTES: usize = 1 << 20;
+
+    fn card_table() -> (CardTable, Address) {
+        let start = map_heap_memory(7 << 22, TEST_BYTES);
+        let mmapper = Mmapper::new();
+        CARD_SIDE_METADATA_SPEC.ensure_mapped(&mmapper, start, TEST_BYTES);
+        for spec in OBJECT_SIDE_METADATA_SPECS.iter() {
+            spec.ensure_mapped(&mmapper, start, TEST_BYTES);
+        }
+        let table = CardTable;
+        table.clear(start, TEST_BYTES);
+        (table, start)
+    }
+
+    fn objects_on_card(table: &CardTable, card: Address) -> Vec<Address> {
+        let mut objects = vec![];
+        table
+            .for_each_object_on_card::<MockVM, _>(card, |object| objects.push(object.to_address()));
+        objects
+    }
+
+    #[test]
+    fn test_mark_and_clean() {
+        serial_test(|| {
+            let (table, start) = card_table();
+            let addr = start + 3 * BYTES_IN_CARD + 8usize;
+            assert!(!table.is_dirty(addr.align_down(BYTES_IN_CARD)));
+            table.mark_card(addr);
+            assert!(table.is_dirty(addr.align_down(BYTES_IN_CARD)));
+            assert!(!table.is_dirty(start + 4 * BYTES_IN_CARD));
+            let byte = table.biased_base() + (addr.as_usize() >> LOG_CARD_BYTES);
+            assert_eq!(unsafe { byte.load::<u8>() }, DIRTY);
+            table.clean(addr.align_down(BYTES_IN_CARD));
+            assert!(!table.is_dirty(addr.align_down(BYTES_IN_CARD)));
+        })
+    }
+
+    #[test]
+    #[should_panic(expected = "out of the card table")]
+    fn test_mark_card_out_of_heap() {
+        CardTable.mark_card(HEAP_END);
+    }
+
+    #[test]
+    fn test_record_objects() {
+        serial_test(|| {
+            let (table, start) = card_table();
+            let card = start + 2 * BYTES_IN_CARD;
+            assert_eq!(table.objects_on_card(card), None);
+            table.record_object(card + 16usize, 16);
+            table.record_object(card, 16);
+            table.record_object(card + 512usize, 16);
+            assert_eq!(table.objects_on_card(card), Some((card, card + 512usize)));
+            table.mark_card(card);
+            table.clear(start, TEST_BYTES);
+            assert_eq!(table.objects_on_card(card), None);
+            assert!(!table.is_dirty(card));
+        })
+    }
+
+    #[test]
+    fn test_objects_on_cards() {
+        serial_test(|| {
+            let (table, start) = card_table();
+            // A small object at the end of the first card, a large object from the second card
+            // to the middle of the fourth card, and a small object after it
+            let small = start + BYTES_IN_CARD - 16usize;
+            let large = start + BYTES_IN_CARD;
+            let large_refs = (2 * BYTES_IN_CARD + BYTES_IN_CARD / 2) / 8 - 2;
+            let after = start + 3 * BYTES_IN_CARD + BYTES_IN_CARD / 2;
+            for (object, n_refs) in [(small, 0), (large, large_refs), (after, 0)] {
+                let object = init_object(object, n_refs);
+                table.record_object(
+                    object.to_address(),
+                    <MockVM as VMBinding>::VMObjectModel::get_current_size(object),
+                );
+            }
+            assert_eq!(objects_on_card(&table, start), vec![small]);
+            assert_eq!(objects_on_card(&table, start + BYTES_IN_CARD), vec![large]);
+            assert_eq!(
+                objects_on_card(&table, start + 2 * BYTES_IN_CARD),
+                vec![large]
+            );
+            assert_eq!(
+                objects_on_card(&table, start + 3 * BYTES_IN_CARD),
+                vec![large, after]
+            );
+            assert!(objects_on_card(&table, start + 4 * BYTES_IN_CARD).is_empty());
+        })
+    }
+
+    #[test]
+    fn test_object_crossing_many_cards() {
+        serial_test(|| {
+            let (table, start) = card_table();
+            // The object starts more than `MAX_CARDS_BACK` cards before its last card
+            let object = start + 8usize;
+            let cards = 2 * MAX_CARDS_BACK + 3;
+            table.record_object(object, cards * BYTES_IN_CARD - 8);
+            for i in 1..cards {
+                assert_eq!(
+                    table.object_crossing_card(start + i * BYTES_IN_CARD),
+                    Some(object)
+                );
+            }
+            assert_eq!(table.object_crossing_card(start), None);
+            assert_eq!(
+                table.object_crossing_card(start + cards * BYTES_IN_CARD),
+                None
+            );
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use crate::scheduler::gc_works::ProcessEdgesWork;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::constants::BYTES_IN_WORD;
+use crate::util::{alloc_bit, pin_bit};
+use crate::util::{Address, ObjectReference};
+use crate::MMTK;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+/// Roots found by scanning memory conservatively, for VMs that cannot tell which words on their
+/// stacks are references.
+///
+/// Every word in the scanned memory that is the reference of an object allocated by MMTk (see
+/// `alloc_bit::is_alloced_object()`) is taken as a root. The word may be an integer that happens
+/// to look like a reference, so it cannot be updated, and the object is pinned for the GC. The
+/// objects that were not pinned before are unpinned when the GC ends.
+#[derive(Default)]
+pub struct ConservativeRoots {
+    /// The objects pinned by the conservative roots in the current GC.
+    pinned: Mutex<Vec<ObjectReference>>,
+}
+
+impl ConservativeRoots {
+    pub fn new() -> Self {
+        Self {
+            pinned: Mutex::new(vec![]),
+        }
+    }
+
+    /// Find the objects referenced by the words from `start` to `end`, and pin them. Returns the
+    /// objects found. This has to be done before the objects are traced.
+    pub fn scan(&self, start: Address, end: Address) -> Vec<ObjectReference> {
+        let mut objects = vec![];
+        let mut newly_pinned = vec![];
+        let mut cursor = start.align_up(BYTES_IN_WORD);
+        while cursor + BYTES_IN_WORD <= end {
+            let value: Address = unsafe { cursor.load() };
+            if alloc_bit::is_alloced_object(value) {
+                let object = unsafe { value.to_object_reference() };
+                if pin_bit::pin_object(object) {
+                    newly_pinned.push(object);
+                }
+                objects.push(object);
+            }
+            cursor += BYTES_IN_WORD;
+        }
+        if !newly_pinned.is_empty() {
+            self.pinned.lock().unwrap().append(&mut newly_pinned);
+        }
+        objects
+    }
+
+    /// Unpin the objects pinned by `scan()` in the current GC.
+    pub fn unpin_all(&self) {
+        for object in self.pinned.lock().unwrap().drain(..) {
+            pin_bit::unpin_object(object);
+        }
+    }
+}
+
+/// Trace the objects found by `ConservativeRoots::scan()` as roots. The objects are pinned,
+/// so `E` does not move them.
+pub struct ProcessConservativeRoots<E: ProcessEdgesWork> {
+    objects: Vec<ObjectReference>,
+    phantom: PhantomData<E>,
+}
+
+impl<E: ProcessEdgesWork> ProcessConservativeRoots<E> {
+    pub fn new(objects: Vec<ObjectReference>) -> Self {
+        Self {
+            objects,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ProcessConservativeRoots<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("ProcessConservativeRoots");
+        // The plans treat roots as slots, so the objects are traced as the referents of slots in
+        // this work. The slots are never changed, as the objects do not move.
+        let edges = self.objects.iter().map(Address::from_ref).collect();
+        E::new(edges, true).do_work(worker, mmtk);
+    }
+}
@@ -0,0 +1,153 @@
+use crate::scheduler::gc_works::ProcessEdgesWork;
+use crate::scheduler::{GCWork, GCWorker};
+use crate::util::reference_processor::with_trace;
+use crate::util::{ObjectReference, OpaquePointer};
+use crate::vm::Collection;
+use crate::vm::VMBinding;
+use crate::MMTK;
+use std::marker::PhantomData;
+
+/// A special processor for finalizable objects.
+///
+/// The VM registers objects that need to be finalized with `add()`. If a registered object
+/// becomes unreachable in a GC, it is resurrected (i.e. traced, so that it and the objects
+/// it refers to stay alive), and queued as ready for finalization. The VM then gets the
+/// objects with `get_ready_object()` after the GC and runs their finalizers.
+// TODO: we should consider if we want to merge FinalizableProcessor with ReferenceProcessor,
+// and treat final reference as a special reference type in ReferenceProcessor.
+#[derive(Default)]
+pub struct FinalizableProcessor {
+    /// Candidate objects that have finalizers with them
+    candidates: Vec<ObjectReference>,
+    /// Index into candidates to record where we are up to in the last scan of the candidates.
+    /// Index after nursery_index are new objects inserted after the last GC.
+    nursery_index: usize,
+    /// Objects that can be finalized. They are actually dead, but we keep them alive
+    /// until the VM gets them and finalizes them.
+    ready_for_finalize: Vec<ObjectReference>,
+}
+
+impl FinalizableProcessor {
+    pub fn new() -> Self {
+        Self {
+            candidates: vec![],
+            nursery_index: 0,
+            ready_for_finalize: vec![],
+        }
+    }
+
+    pub fn add(&mut self, object: ObjectReference) {
+        self.candidates.push(object);
+    }
+
+    /// Scan the candidates. Live candidates are forwarded and kept as candidates, and
+    /// unreachable candidates are resurrected and moved to the ready-for-finalize queue.
+    /// The objects that are already in the queue (i.e. the VM has not got them since an earlier
+    /// GC) are kept alive and forwarded as well.
+    pub fn scan<E: ProcessEdgesWork>(&mut self, tls: OpaquePointer, e: &mut E, nursery: bool) {
+        for reff in self.ready_for_finalize.iter_mut() {
+            *reff = e.trace_object(*reff);
+        }
+
+        let start = if nursery { self.nursery_index } else { 0 };
+
+        for reff in self
+            .candidates
+            .drain(start..)
+            .collect::<Vec<ObjectReference>>()
+        {
+            trace!("Pop {:?} for finalization", reff);
+            if e.is_object_live(reff) {
+                let forwarded = e.trace_object(reff);
+                trace!(
+                    "{:?} is live, push {:?} back to candidates",
+                    reff,
+                    forwarded
+                );
+                self.candidates.push(forwarded);
+                continue;
+            }
+
+            // The object is unreachable. Retain it (and the objects it refers to) so that
+            // the VM can finalize it.
+            let retained = e.trace_object(reff);
+            trace!(
+                "{:?} is dead, push {:?} to ready_for_finalize",
+                reff,
+                retained
+            );
+            self.ready_for_finalize.push(retained);
+        }
+        self.nursery_index = self.candidates.len();
+
+        // Let the VM know that there are objects to finalize. The VM should get them with
+        // `memory_manager::get_finalized_object()` after the GC.
+        if !self.ready_for_finalize.is_empty() {
+            <E::VM as VMBinding>::VMCollection::schedule_finalization(tls);
+        }
+    }
+
+    /// Forward the candidates and the objects that are ready for finalization. This is only needed
+    /// by collectors that compute new object addresses after the liveness trace (e.g. MarkCompact).
+    pub fn forward<E: ProcessEdgesWork>(&mut self, e: &mut E) {
+        for reff in self
+            .candidates
+            .iter_mut()
+            .chain(self.ready_for_finalize.iter_mut())
+        {
+            *reff = e.trace_object(*reff);
+        }
+    }
+
+    /// Call `f` on each candidate and each object that is ready for finalization.
+    pub fn for_each_object(&self, f: &mut dyn FnMut(ObjectReference)) {
+        for &reff in self.candidates.iter().chain(self.ready_for_finalize.iter()) {
+            f(reff);
+        }
+    }
+
+    /// Get an object that is ready for finalization, or `None` if there isn't any.
+    pub fn get_ready_object(&mut self) -> Option<ObjectReference> {
+        self.ready_for_finalize.pop()
+    }
+}
+
+#[derive(Default)]
+pub struct Finalization<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> Finalization<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for Finalization<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("Finalization");
+        let mut finalizable_processor = mmtk.finalizable_processor.lock().unwrap();
+        let tls = worker.tls;
+        // The resurrected objects are scanned in the closure stage.
+        with_trace::<E>(worker, mmtk, |w| {
+            finalizable_processor.scan(tls, w, mmtk.plan.scan_new_references_only())
+        });
+    }
+}
+
+/// Forward the finalizable objects for collectors that compute new object addresses
+/// after the liveness trace (see `PlanConstraints::needs_forward_after_liveness`).
+#[derive(Default)]
+pub struct ForwardFinalization<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> ForwardFinalization<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for ForwardFinalization<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("ForwardFinalization");
+        let mut finalizable_processor = mmtk.finalizable_processor.lock().unwrap();
+        with_trace::<E>(worker, mmtk, |w| finalizable_processor.forward(w));
+    }
+}
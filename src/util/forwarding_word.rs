@@ -1,11 +1,21 @@
 /// https://github.com/JikesRVM/JikesRVM/blob/master/MMTk/src/org/mmtk/utility/ForwardingWord.java
+use crate::util::alloc_bit;
+use crate::util::pin_bit;
+use crate::util::side_metadata::MetadataSpec;
 use crate::util::{Address, ObjectReference};
 use crate::vm::ObjectModel;
-use std::sync::atomic::Ordering;
 
 use crate::plan::{AllocationSemantics, CopyContext};
 use crate::vm::VMBinding;
 
+/// The forwarding state of an object. The forwarding pointer itself is written over the available
+/// bits word of the object, so with the state in the header, the state and the pointer are written
+/// at once.
+pub const FORWARDING_BITS_SPEC: MetadataSpec = MetadataSpec::InHeader {
+    bit_offset: 0,
+    num_of_bits: FORWARDING_BITS,
+};
+
 // ...00
 const FORWARDING_NOT_TRIGGERED_YET: u8 = 0;
 // ...10
@@ -14,43 +24,36 @@ const BEING_FORWARDED: u8 = 2;
 const FORWARDED: u8 = 3;
 // ...11
 const FORWARDING_MASK: u8 = 3;
-#[allow(unused)]
 const FORWARDING_BITS: usize = 2;
 
+/// Get the forwarding state of an object.
+pub fn get_forwarding_status<VM: VMBinding>(object: ObjectReference) -> u8 {
+    FORWARDING_BITS_SPEC.load::<VM>(object)
+}
+
+/// Set the object to the being-forwarded state if nobody has started forwarding it. Returns the
+/// forwarding state before the call: the caller forwards the object if it is
+/// `FORWARDING_NOT_TRIGGERED_YET`.
 pub fn attempt_to_forward<VM: VMBinding>(object: ObjectReference) -> u8 {
-    let gc_byte = VM::VMObjectModel::get_gc_byte(object);
-    let mut old_value = gc_byte.load(Ordering::SeqCst);
-    if old_value & FORWARDING_MASK != FORWARDING_NOT_TRIGGERED_YET {
-        return old_value;
-    }
-    while old_value
-        != gc_byte.compare_and_swap(old_value, old_value | BEING_FORWARDED, Ordering::SeqCst)
-    {
-        old_value = gc_byte.load(Ordering::SeqCst);
-        if old_value & FORWARDING_MASK != FORWARDING_NOT_TRIGGERED_YET {
-            return old_value;
-        }
-    }
-    old_value
+    FORWARDING_BITS_SPEC
+        .compare_exchange::<VM>(object, FORWARDING_NOT_TRIGGERED_YET, BEING_FORWARDED)
+        .unwrap_or_else(|state| state)
 }
 
 pub fn spin_and_get_forwarded_object<VM: VMBinding>(
     object: ObjectReference,
-    gc_byte: u8,
+    forwarding_status: u8,
 ) -> ObjectReference {
-    let mut gc_byte = gc_byte;
-    let gc_byte_slot = VM::VMObjectModel::get_gc_byte(object);
-    while gc_byte & FORWARDING_MASK == BEING_FORWARDED {
-        gc_byte = gc_byte_slot.load(Ordering::SeqCst);
+    let mut forwarding_status = forwarding_status;
+    while forwarding_status & FORWARDING_MASK == BEING_FORWARDED {
+        forwarding_status = get_forwarding_status::<VM>(object);
     }
-    if gc_byte & FORWARDING_MASK == FORWARDED {
-        let status_word = VM::VMObjectModel::read_available_bits_word(object);
-        let a = status_word & !((FORWARDING_MASK as usize) << VM::VMObjectModel::GC_BYTE_OFFSET);
-        unsafe { Address::from_usize(a).to_object_reference() }
+    if forwarding_status & FORWARDING_MASK == FORWARDED {
+        read_forwarding_pointer::<VM>(object)
     } else {
         panic!(
-            "Invalid header value 0x{:x} 0x{:x}",
-            gc_byte,
+            "Invalid forwarding state 0x{:x} 0x{:x}",
+            forwarding_status,
             VM::VMObjectModel::read_available_bits_word(object)
         )
     }
@@ -61,42 +64,59 @@ pub fn forward_object<VM: VMBinding, CC: CopyContext>(
     semantics: AllocationSemantics,
     copy_context: &mut CC,
 ) -> ObjectReference {
+    debug_assert!(!pin_bit::is_pinned(object), "{} is pinned", object);
     let new_object = VM::VMObjectModel::copy(object, semantics, copy_context);
-    let forwarded = (FORWARDED as usize) << VM::VMObjectModel::GC_BYTE_OFFSET;
-    VM::VMObjectModel::write_available_bits_word(
-        object,
-        new_object.to_address().as_usize() | forwarded,
-    );
+    if alloc_bit::is_alloced(object) {
+        alloc_bit::unset_alloc_bit(object);
+        alloc_bit::set_alloc_bit(new_object);
+    }
+    set_forwarding_pointer::<VM>(object, new_object);
     new_object
 }
 
+/// The bits of the available bits word that hold the forwarding state.
+fn forwarding_bits_in_word<VM: VMBinding>() -> usize {
+    match FORWARDING_BITS_SPEC {
+        MetadataSpec::InHeader { bit_offset, .. } => {
+            (FORWARDING_MASK as usize) << (VM::VMObjectModel::GC_BYTE_OFFSET + bit_offset)
+        }
+        MetadataSpec::OnSide(_) => 0,
+    }
+}
+
+fn read_forwarding_pointer<VM: VMBinding>(object: ObjectReference) -> ObjectReference {
+    let status_word = VM::VMObjectModel::read_available_bits_word(object);
+    unsafe {
+        Address::from_usize(status_word & !forwarding_bits_in_word::<VM>()).to_object_reference()
+    }
+}
+
 pub fn set_forwarding_pointer<VM: VMBinding>(object: ObjectReference, ptr: ObjectReference) {
-    let forwarded = (FORWARDED as usize) << VM::VMObjectModel::GC_BYTE_OFFSET;
+    let forwarded = forwarding_bits_in_word::<VM>();
     VM::VMObjectModel::write_available_bits_word(object, ptr.to_address().as_usize() | forwarded);
+    if let MetadataSpec::OnSide(_) = FORWARDING_BITS_SPEC {
+        FORWARDING_BITS_SPEC.store::<VM>(object, FORWARDED);
+    }
 }
 
 pub fn is_forwarded<VM: VMBinding>(object: ObjectReference) -> bool {
-    VM::VMObjectModel::get_gc_byte(object).load(Ordering::Relaxed) & FORWARDING_MASK == FORWARDED
+    get_forwarding_status::<VM>(object) == FORWARDED
 }
 
 pub fn is_forwarded_or_being_forwarded<VM: VMBinding>(object: ObjectReference) -> bool {
-    VM::VMObjectModel::get_gc_byte(object).load(Ordering::Relaxed) & FORWARDING_MASK != 0
+    get_forwarding_status::<VM>(object) != FORWARDING_NOT_TRIGGERED_YET
 }
 
-pub fn state_is_forwarded_or_being_forwarded(gc_byte: u8) -> bool {
-    gc_byte & FORWARDING_MASK != 0
+pub fn state_is_forwarded_or_being_forwarded(forwarding_status: u8) -> bool {
+    forwarding_status & FORWARDING_MASK != 0
 }
 
-pub fn state_is_being_forwarded(gc_byte: u8) -> bool {
-    gc_byte & FORWARDING_MASK == BEING_FORWARDED
+pub fn state_is_being_forwarded(forwarding_status: u8) -> bool {
+    forwarding_status & FORWARDING_MASK == BEING_FORWARDED
 }
 
 pub fn clear_forwarding_bits<VM: VMBinding>(object: ObjectReference) {
-    let gc_byte = VM::VMObjectModel::get_gc_byte(object);
-    gc_byte.store(
-        gc_byte.load(Ordering::SeqCst) & !FORWARDING_MASK,
-        Ordering::SeqCst,
-    );
+    FORWARDING_BITS_SPEC.store::<VM>(object, FORWARDING_NOT_TRIGGERED_YET);
 }
 
 // pub fn extract_forwarding_pointer(forwarding_word: usize) -> ObjectReference {
@@ -1,25 +1,121 @@
-use crate::plan::SelectedConstraints;
+use crate::util::side_metadata::MetadataSpec;
 use crate::util::ObjectReference;
-use crate::vm::ObjectModel;
 use crate::vm::VMBinding;
 
 pub const TOTAL_BITS: usize = 8;
-pub const NEEDS_UNLOGGED_BIT: bool = SelectedConstraints::NEEDS_LOG_BIT_IN_HEADER;
-pub const UNLOGGED_BIT_NUMBER: usize = SelectedConstraints::NEEDS_LOG_BIT_IN_HEADER_NUM;
-pub const UNLOGGED_BIT: u8 = 1 << UNLOGGED_BIT_NUMBER;
-pub const USED_GLOBAL_BITS: usize = TOTAL_BITS - UNLOGGED_BIT_NUMBER;
+// Only plans with `PlanConstraints::needs_log_bit_in_header` use this bit. It is the highest bit,
+// as the low bits are used by the policies for marking and forwarding.
+pub const UNLOGGED_BIT_NUMBER: usize = TOTAL_BITS - 1;
+pub const USED_GLOBAL_BITS: usize = TOTAL_BITS - UNLOGGED_BIT_NUMBER + 1;
+
+/// The log state of an object: the unlogged bit, and the being-logged bit right below it.
+pub const LOG_BITS_SPEC: MetadataSpec = MetadataSpec::InHeader {
+    bit_offset: UNLOGGED_BIT_NUMBER - 1,
+    num_of_bits: 2,
+};
+const UNLOGGED_BIT: u8 = 0b10;
+// Set while a thread takes the snapshot of an object that it logs (see `attempt_log_and_lock`).
+const BEING_LOGGED_BIT: u8 = 0b01;
 
 pub fn mark_as_unlogged<VM: VMBinding>(object: ObjectReference) {
-    let value = VM::VMObjectModel::read_available_byte(object);
-    VM::VMObjectModel::write_available_byte(object, value | UNLOGGED_BIT);
+    LOG_BITS_SPEC.fetch_or::<VM>(object, UNLOGGED_BIT);
 }
 
 pub fn mark_as_logged<VM: VMBinding>(object: ObjectReference) {
-    let value = VM::VMObjectModel::read_available_byte(object);
-    VM::VMObjectModel::write_available_byte(object, value & !UNLOGGED_BIT);
+    LOG_BITS_SPEC.fetch_and::<VM>(object, !UNLOGGED_BIT);
 }
 
 pub fn is_unlogged<VM: VMBinding>(object: ObjectReference) -> bool {
-    let value = VM::VMObjectModel::read_available_byte(object);
-    (value & UNLOGGED_BIT) == UNLOGGED_BIT
+    LOG_BITS_SPEC.load::<VM>(object) & UNLOGGED_BIT == UNLOGGED_BIT
+}
+
+/// Atomically clear the unlogged bit of an object. Returns true if the bit is cleared by this call,
+/// so that only one thread logs the object.
+pub fn attempt_log<VM: VMBinding>(object: ObjectReference) -> bool {
+    if !is_unlogged::<VM>(object) {
+        return false;
+    }
+    LOG_BITS_SPEC.fetch_and::<VM>(object, !UNLOGGED_BIT) & UNLOGGED_BIT != 0
+}
+
+/// Like `attempt_log`, but the object stays in the being-logged state until `finish_logging` is
+/// called, so the caller can read the object before anyone else writes to it. If another thread
+/// is logging the object, this waits for it to finish and returns false.
+pub fn attempt_log_and_lock<VM: VMBinding>(object: ObjectReference) -> bool {
+    loop {
+        let result = LOG_BITS_SPEC.fetch_update::<VM, _>(object, |value| {
+            if value == UNLOGGED_BIT {
+                Some(BEING_LOGGED_BIT)
+            } else {
+                None
+            }
+        });
+        match result {
+            Ok(_) => return true,
+            Err(value) if value & BEING_LOGGED_BIT != 0 => std::hint::spin_loop(),
+            Err(_) => return false,
+        }
+    }
+}
+
+/// Leave the being-logged state entered by `attempt_log_and_lock`.
+pub fn finish_logging<VM: VMBinding>(object: ObjectReference) {
+    LOG_BITS_SPEC.fetch_and::<VM>(object, !BEING_LOGGED_BIT);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::test_util::mock_vm::{alloc_object, MockObjectModel, MockVM};
+    use crate::util::Address;
+    use crate::vm::ObjectModel;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_attempt_log() {
+        let object = alloc_object(0);
+        // The other bits of the GC byte and the status word are kept
+        MockObjectModel::write_available_bits_word(object, 0x1234);
+        MockObjectModel::write_available_byte(object, 0b11);
+        mark_as_unlogged::<MockVM>(object);
+        assert!(is_unlogged::<MockVM>(object));
+        assert!(attempt_log::<MockVM>(object));
+        assert!(!is_unlogged::<MockVM>(object));
+        assert!(!attempt_log::<MockVM>(object));
+        assert_eq!(MockObjectModel::read_available_byte(object), 0b11);
+        assert_eq!(
+            MockObjectModel::read_available_bits_word(object),
+            (0b11 << MockObjectModel::GC_BYTE_OFFSET) | 0x1234
+        );
+    }
+
+    #[test]
+    fn test_attempt_log_and_lock() {
+        let object = alloc_object(0);
+        MockObjectModel::write_available_byte(object, 0b11);
+        assert!(!attempt_log_and_lock::<MockVM>(object));
+        mark_as_unlogged::<MockVM>(object);
+        assert!(attempt_log_and_lock::<MockVM>(object));
+        assert!(!is_unlogged::<MockVM>(object));
+        // Another writer waits until the object is logged
+        let address = object.to_address().as_usize();
+        let waited = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let waited = waited.clone();
+            thread::spawn(move || {
+                let object = unsafe { Address::from_usize(address).to_object_reference() };
+                let logged = attempt_log_and_lock::<MockVM>(object);
+                waited.store(true, Ordering::SeqCst);
+                logged
+            })
+        };
+        thread::sleep(Duration::from_millis(10));
+        assert!(!waited.load(Ordering::SeqCst));
+        finish_logging::<MockVM>(object);
+        assert!(!writer.join().unwrap());
+        assert_eq!(MockObjectModel::read_available_byte(object), 0b11);
+    }
 }
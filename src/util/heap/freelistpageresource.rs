@@ -310,6 +310,11 @@ impl<VM: VMBinding> FreeListPageResource<VM> {
         // if (VM.config.ZERO_PAGES_ON_RELEASE)
         //     VM.memory.zero(false, first, Conversions.pagesToBytes(pages));
         debug_assert!(pages as usize <= self.common.get_committed());
+        // Clear the side metadata of the pages before they can be reused
+        let space = self.common.space.unwrap();
+        for spec in space.common().side_metadata_specs.iter() {
+            spec.bzero(first, conversions::pages_to_bytes(pages as _));
+        }
         let me = unsafe { &mut *(self as *mut Self) };
         let freed = {
             let mut sync = self.sync.lock().unwrap();
@@ -0,0 +1,208 @@
+/// https://github.com/JikesRVM/JikesRVM/blob/master/MMTk/src/org/mmtk/utility/heap/HeapGrowthManager.java
+use crate::util::constants::{BYTES_IN_MBYTE, LOG_BYTES_IN_PAGE};
+use crate::util::heap::HeapMeta;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const PAGES_IN_MBYTE: usize = BYTES_IN_MBYTE >> LOG_BYTES_IN_PAGE;
+
+/// The live ratios (reserved pages / heap pages) of the columns in `HEAP_CHANGE_RATIOS`.
+const LIVE_RATIOS: [f64; 6] = [0.00, 0.10, 0.30, 0.60, 0.80, 1.00];
+/// The GC loads (GC time / elapsed time) of the rows in `HEAP_CHANGE_RATIOS`.
+const GC_LOADS: [f64; 7] = [0.01, 0.02, 0.05, 0.07, 0.15, 0.40, 1.00];
+/// The ratio to change the heap size by, for a GC load (row) and a live ratio (column).
+/// A low GC load shrinks the heap, unless most of the heap is live. A high GC load grows the heap,
+/// unless most of the heap is garbage.
+const HEAP_CHANGE_RATIOS: [[f64; 6]; 7] = [
+    [0.90, 0.90, 0.95, 1.00, 1.00, 1.00],
+    [0.90, 0.90, 0.95, 1.00, 1.00, 1.00],
+    [0.95, 0.95, 1.00, 1.00, 1.00, 1.00],
+    [1.00, 1.00, 1.10, 1.15, 1.20, 1.20],
+    [1.00, 1.00, 1.20, 1.25, 1.35, 1.30],
+    [1.00, 1.00, 1.25, 1.30, 1.50, 1.50],
+    [1.00, 1.00, 1.25, 1.30, 1.50, 1.50],
+];
+
+struct HeapGrowthSync {
+    /// The start of the current GC.
+    gc_start: Option<Instant>,
+    /// The time spent in GCs since the heap size was last considered.
+    gc_time: Duration,
+    /// When the heap size was last considered.
+    last_considered: Instant,
+}
+
+/// Adjusts the heap size (the total pages in `HeapMeta`) between a minimal and a maximal heap size,
+/// based on how much of the heap is live and how much of the time is spent in GC.
+pub struct HeapGrowthManager {
+    min_pages: usize,
+    max_pages: usize,
+    sync: Mutex<HeapGrowthSync>,
+}
+
+impl HeapGrowthManager {
+    pub fn new() -> Self {
+        HeapGrowthManager {
+            min_pages: 0,
+            max_pages: 0,
+            sync: Mutex::new(HeapGrowthSync {
+                gc_start: None,
+                gc_time: Duration::default(),
+                last_considered: Instant::now(),
+            }),
+        }
+    }
+
+    /// Set the bounds of the heap size. The heap size is fixed if they are the same.
+    pub fn init(&mut self, min_pages: usize, max_pages: usize) {
+        debug_assert!(min_pages <= max_pages);
+        self.min_pages = min_pages;
+        self.max_pages = max_pages;
+        self.sync.lock().unwrap().last_considered = Instant::now();
+    }
+
+    pub fn is_variable(&self) -> bool {
+        self.min_pages < self.max_pages
+    }
+
+    pub fn get_min_pages(&self) -> usize {
+        self.min_pages
+    }
+
+    pub fn get_max_pages(&self) -> usize {
+        self.max_pages
+    }
+
+    pub fn gc_start(&self) {
+        self.sync.lock().unwrap().gc_start = Some(Instant::now());
+    }
+
+    pub fn gc_end(&self) {
+        let mut sync = self.sync.lock().unwrap();
+        if let Some(start) = sync.gc_start.take() {
+            sync.gc_time += start.elapsed();
+        }
+    }
+
+    /// Compute a new heap size from the reserved pages after a GC and the GC load since the
+    /// heap size was last considered, and store it to `heap`. Returns true if the heap size changed.
+    pub fn consider_heap_size(&self, heap: &HeapMeta, reserved_pages: usize) -> bool {
+        let mut sync = self.sync.lock().unwrap();
+        let elapsed = sync.last_considered.elapsed();
+        let gc_load = if elapsed.as_nanos() == 0 {
+            0f64
+        } else {
+            sync.gc_time.as_secs_f64() / elapsed.as_secs_f64()
+        };
+        sync.gc_time = Duration::default();
+        sync.last_considered = Instant::now();
+        if !self.is_variable() {
+            return false;
+        }
+
+        let old_pages = heap.get_total_pages();
+        let live_ratio = reserved_pages as f64 / old_pages as f64;
+        let ratio = Self::compute_heap_change_ratio(live_ratio, gc_load);
+        let new_pages = (old_pages as f64 * ratio) as usize;
+        // The heap cannot be smaller than what is reserved. Round the heap size up to megabytes.
+        let new_pages =
+            (new_pages.max(reserved_pages) + PAGES_IN_MBYTE - 1) & !(PAGES_IN_MBYTE - 1);
+        let new_pages = new_pages.max(self.min_pages).min(self.max_pages);
+        if new_pages == old_pages {
+            return false;
+        }
+        debug!(
+            "Live ratio {:.3}, GC load {:.3}: heap size {} -> {} pages",
+            live_ratio, gc_load, old_pages, new_pages
+        );
+        heap.total_pages.store(new_pages, Ordering::Relaxed);
+        true
+    }
+
+    /// Get the ratio to change the heap size by, interpolated from `HEAP_CHANGE_RATIOS`.
+    pub fn compute_heap_change_ratio(live_ratio: f64, gc_load: f64) -> f64 {
+        let (col, col_fraction) = Self::locate(&LIVE_RATIOS, live_ratio);
+        let (row, row_fraction) = Self::locate(&GC_LOADS, gc_load);
+        let interpolate = |row: &[f64; 6]| row[col] + (row[col + 1] - row[col]) * col_fraction;
+        let below = interpolate(&HEAP_CHANGE_RATIOS[row]);
+        let above = interpolate(&HEAP_CHANGE_RATIOS[row + 1]);
+        below + (above - below) * row_fraction
+    }
+
+    /// Find the interval `[points[i], points[i + 1]]` that the value falls in, after clamping the
+    /// value to the points. Returns `i` and where the value lies in the interval (from 0 to 1).
+    fn locate(points: &[f64], value: f64) -> (usize, f64) {
+        let value = value.max(points[0]).min(points[points.len() - 1]);
+        let i = (0..points.len() - 2)
+            .find(|&i| value <= points[i + 1])
+            .unwrap_or(points.len() - 2);
+        (i, (value - points[i]) / (points[i + 1] - points[i]))
+    }
+}
+
+impl Default for HeapGrowthManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START};
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_change_ratio_at_points() {
+        for (row, &gc_load) in GC_LOADS.iter().enumerate() {
+            for (col, &live_ratio) in LIVE_RATIOS.iter().enumerate() {
+                assert_close(
+                    HeapGrowthManager::compute_heap_change_ratio(live_ratio, gc_load),
+                    HEAP_CHANGE_RATIOS[row][col],
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_change_ratio_interpolated() {
+        // Halfway between live ratios 0.30 and 0.60, with the GC load of the fourth row
+        assert_close(
+            HeapGrowthManager::compute_heap_change_ratio(0.45, 0.07),
+            1.125,
+        );
+        // Values outside the table are clamped
+        assert_close(HeapGrowthManager::compute_heap_change_ratio(2.0, 0.0), 1.0);
+        assert_close(HeapGrowthManager::compute_heap_change_ratio(0.0, 2.0), 1.0);
+    }
+
+    #[test]
+    fn test_fixed_heap() {
+        let mut manager = HeapGrowthManager::new();
+        manager.init(1024, 1024);
+        let heap = HeapMeta::new(HEAP_START, HEAP_END);
+        heap.total_pages.store(1024, Ordering::Relaxed);
+        assert!(!manager.consider_heap_size(&heap, 10));
+        assert_eq!(heap.get_total_pages(), 1024);
+    }
+
+    #[test]
+    fn test_shrink_within_bounds() {
+        let mut manager = HeapGrowthManager::new();
+        manager.init(PAGES_IN_MBYTE * 10, PAGES_IN_MBYTE * 100);
+        let heap = HeapMeta::new(HEAP_START, HEAP_END);
+        heap.total_pages
+            .store(PAGES_IN_MBYTE * 11, Ordering::Relaxed);
+        // Almost no GC time, and little live data: the heap shrinks, but not below the minimum.
+        assert!(manager.consider_heap_size(&heap, 1));
+        assert_eq!(heap.get_total_pages(), PAGES_IN_MBYTE * 10);
+        assert!(!manager.consider_heap_size(&heap, 1));
+        // The heap grows to hold what is reserved.
+        assert!(manager.consider_heap_size(&heap, PAGES_IN_MBYTE * 20 + 1));
+        assert_eq!(heap.get_total_pages(), PAGES_IN_MBYTE * 21);
+    }
+}
@@ -1,3 +1,6 @@
+use crate::util::alloc_bit::{self, ALLOC_SIDE_METADATA_SPEC};
+use crate::util::pin_bit::{self, PIN_SIDE_METADATA_SPEC};
+use crate::util::side_metadata::SideMetadataSpec;
 use crate::util::Address;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
@@ -6,6 +9,9 @@ pub struct HeapMeta {
     pub heap_cursor: Address,
     pub heap_limit: Address,
     pub total_pages: AtomicUsize,
+    /// The side metadata that is mapped for all the spaces. It includes the alloc bit and the pin
+    /// bit if they are enabled, and a plan can add its specs before it creates the spaces.
+    pub side_metadata_specs: Vec<SideMetadataSpec>,
 }
 
 impl HeapMeta {
@@ -14,9 +20,22 @@ impl HeapMeta {
             heap_cursor: start,
             heap_limit: end,
             total_pages: AtomicUsize::new(0),
+            side_metadata_specs: Self::enabled_global_side_metadata_specs(),
         }
     }
 
+    /// The alloc bit and the pin bit specs, for the ones that are enabled by their options.
+    pub fn enabled_global_side_metadata_specs() -> Vec<SideMetadataSpec> {
+        let mut specs = vec![];
+        if alloc_bit::is_enabled() {
+            specs.push(ALLOC_SIDE_METADATA_SPEC);
+        }
+        if pin_bit::is_enabled() {
+            specs.push(PIN_SIDE_METADATA_SPEC);
+        }
+        specs
+    }
+
     pub fn reserve(&mut self, extent: usize, top: bool) -> Address {
         let ret = if top {
             self.heap_limit -= extent;
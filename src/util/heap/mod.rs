@@ -1,6 +1,7 @@
 #[macro_use]
 pub mod layout;
 pub mod freelistpageresource;
+pub mod heap_growth_manager;
 mod heap_meta;
 pub mod monotonepageresource;
 pub mod pageresource;
@@ -8,6 +9,7 @@ pub mod space_descriptor;
 mod vmrequest;
 
 pub use self::freelistpageresource::FreeListPageResource;
+pub use self::heap_growth_manager::HeapGrowthManager;
 pub use self::heap_meta::HeapMeta;
 pub use self::monotonepageresource::MonotonePageResource;
 pub use self::pageresource::PageResource;
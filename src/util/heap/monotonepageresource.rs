@@ -34,6 +34,9 @@ struct MonotonePageResourceSync {
     sentinel: Address,
     /** Base address of the current chunk of addresses */
     current_chunk: Address,
+    /// The page-aligned ranges above the cursor that are kept by `reset_except()`, in address
+    /// order. The allocation skips them.
+    kept: Vec<(Address, Address)>,
     conditional: MonotonePageResourceConditional,
 }
 
@@ -67,10 +70,7 @@ impl<VM: VMBinding> PageResource<VM> for MonotonePageResource<VM> {
         zeroed: bool,
         tls: OpaquePointer,
     ) -> Address {
-        let mut required_pages = immut_required_pages;
-        let mut new_chunk = false;
         let mut sync = self.sync.lock().unwrap();
-        let mut rtn = sync.cursor;
 
         if cfg!(debug = "true") {
             /*
@@ -92,27 +92,43 @@ impl<VM: VMBinding> PageResource<VM> for MonotonePageResource<VM> {
             );
         }
 
-        if self.meta_data_pages_per_region != 0 {
-            /* adjust allocation for metadata */
-            let region_start = Self::get_region_start(sync.cursor + pages_to_bytes(required_pages));
-            let region_delta = region_start.get_offset(sync.cursor);
-            if region_delta >= 0 {
-                new_chunk = true;
-                /* start new region, so adjust pages and return address accordingly */
-                required_pages +=
-                    bytes_to_pages(region_delta as usize) + self.meta_data_pages_per_region;
-                rtn = region_start + pages_to_bytes(self.meta_data_pages_per_region);
+        let (mut required_pages, mut new_chunk, mut rtn, mut tmp);
+        loop {
+            required_pages = immut_required_pages;
+            new_chunk = false;
+            rtn = sync.cursor;
+            if self.meta_data_pages_per_region != 0 {
+                /* adjust allocation for metadata */
+                let region_start =
+                    Self::get_region_start(sync.cursor + pages_to_bytes(required_pages));
+                let region_delta = region_start.get_offset(sync.cursor);
+                if region_delta >= 0 {
+                    new_chunk = true;
+                    /* start new region, so adjust pages and return address accordingly */
+                    required_pages +=
+                        bytes_to_pages(region_delta as usize) + self.meta_data_pages_per_region;
+                    rtn = region_start + pages_to_bytes(self.meta_data_pages_per_region);
+                }
+            } else {
+                let region_start =
+                    Self::get_region_start(sync.cursor + pages_to_bytes(required_pages));
+                let region_delta = region_start.get_offset(sync.cursor);
+                if region_delta >= 0 {
+                    new_chunk = true;
+                }
             }
-        } else {
-            let region_start = Self::get_region_start(sync.cursor + pages_to_bytes(required_pages));
-            let region_delta = region_start.get_offset(sync.cursor);
-            if region_delta >= 0 {
-                new_chunk = true;
+            tmp = sync.cursor + pages_to_bytes(required_pages);
+            // Skip the kept pages that the allocation would overlap
+            match sync.kept.first() {
+                Some(&(start, end)) if start < tmp => {
+                    sync.cursor = end;
+                    sync.kept.remove(0);
+                }
+                _ => break,
             }
         }
         let bytes = pages_to_bytes(required_pages);
         trace!("bytes={}", bytes);
-        let mut tmp = sync.cursor + bytes;
         trace!("tmp={:?}", tmp);
 
         if !self.common().contiguous && tmp > sync.sentinel {
@@ -207,6 +223,7 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
                 cursor: start,
                 current_chunk: chunk_align_down(start),
                 sentinel,
+                kept: vec![],
                 conditional: MonotonePageResourceConditional::Contiguous {
                     start,
                     zeroing_cursor: sentinel,
@@ -225,6 +242,7 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
                 cursor: unsafe { Address::zero() },
                 current_chunk: unsafe { Address::zero() },
                 sentinel: unsafe { Address::zero() },
+                kept: vec![],
                 conditional: MonotonePageResourceConditional::Discontiguous,
             }),
         }
@@ -242,6 +260,41 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
         );
     }
 
+    /// Get the memory allocated from this page resource, as a list of (start, bytes).
+    pub fn allocated_extents(&self) -> Vec<(Address, usize)> {
+        let sync = self.sync.lock().unwrap();
+        self.allocated_extents_locked(&sync)
+    }
+
+    fn allocated_extents_locked(
+        &self,
+        sync: &MutexGuard<MonotonePageResourceSync>,
+    ) -> Vec<(Address, usize)> {
+        if let MonotonePageResourceConditional::Contiguous { start, .. } = sync.conditional {
+            // The kept pages above the cursor are allocated too
+            let end = sync.kept.last().map_or(sync.cursor, |&(_, end)| end);
+            return vec![(start, end - start)];
+        }
+        // The current chunk is allocated up to the cursor, and the other chunks of the space are
+        // full, or kept by `reset_except()`.
+        let mut extents = vec![];
+        let mut chunk = self
+            .common()
+            .space
+            .unwrap()
+            .common()
+            .head_discontiguous_region;
+        while !chunk.is_zero() {
+            if chunk == sync.current_chunk {
+                extents.push((chunk, sync.cursor - chunk));
+            } else {
+                extents.push((chunk, self.vm_map().get_contiguous_region_size(chunk)));
+            }
+            chunk = self.vm_map().get_next_contiguous_region(chunk);
+        }
+        extents
+    }
+
     fn get_region_start(addr: Address) -> Address {
         addr.align_down(BYTES_IN_REGION)
     }
@@ -252,10 +305,92 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
         let mut guard = self.sync.lock().unwrap();
         self.common().reset_reserved();
         self.common().reset_committed();
+        guard.kept.clear();
         self.release_pages(&mut guard);
         drop(guard);
     }
 
+    /// Release all the pages except the page-aligned extents in `kept`, e.g. the pages of the
+    /// objects that cannot move. The allocation skips the kept pages in a contiguous space. In a
+    /// discontiguous space, the chunks with kept pages are not allocated in again until they are
+    /// released by a later reset.
+    ///
+    /// # Safety
+    /// The same as `reset()`.
+    pub unsafe fn reset_except(&self, mut kept: Vec<(Address, usize)>) {
+        if kept.is_empty() {
+            self.reset();
+            return;
+        }
+        // Merge the overlapping extents, e.g. of the objects on the same page
+        kept.sort_unstable_by_key(|&(start, _)| start.as_usize());
+        let mut merged: Vec<(Address, Address)> = vec![];
+        for (start, bytes) in kept {
+            match merged.last_mut() {
+                Some((_, end)) if start <= *end => {
+                    if start + bytes > *end {
+                        *end = start + bytes;
+                    }
+                }
+                _ => merged.push((start, start + bytes)),
+            }
+        }
+        let kept = merged;
+        let kept_pages: usize = kept
+            .iter()
+            .map(|&(start, end)| bytes_to_pages(end - start))
+            .sum();
+        let mut guard = self.sync.lock().unwrap();
+        self.common().reset_reserved();
+        self.common().reset_committed();
+        self.common().reserve(kept_pages);
+        self.common().commit(kept_pages);
+        // Release the pages between the kept pages in each extent
+        let extents = self.allocated_extents_locked(&guard);
+        for &(start, bytes) in extents.iter() {
+            let mut cursor = start;
+            for &(kept_start, kept_end) in kept.iter() {
+                if kept_end <= start || kept_start >= start + bytes {
+                    continue;
+                }
+                if kept_start > cursor {
+                    self.release_pages_extent(cursor, kept_start - cursor);
+                }
+                cursor = kept_end;
+            }
+            if start + bytes > cursor {
+                self.release_pages_extent(cursor, start + bytes - cursor);
+            }
+        }
+        if self.common().contiguous {
+            let start = match guard.conditional {
+                MonotonePageResourceConditional::Contiguous { start, .. } => start,
+                _ => unreachable!(),
+            };
+            guard.cursor = start;
+            guard.current_chunk = chunk_align_down(start);
+            guard.kept = kept;
+        } else {
+            // Free the chunks without kept pages
+            let space = self.common().space.unwrap();
+            for &(start, bytes) in extents.iter() {
+                let has_kept_pages = kept
+                    .iter()
+                    .any(|&(kept_start, kept_end)| kept_start < start + bytes && kept_end > start);
+                if !has_kept_pages {
+                    if start == space.common().head_discontiguous_region {
+                        space.unsafe_common_mut().head_discontiguous_region =
+                            self.vm_map().get_next_contiguous_region(start);
+                    }
+                    self.vm_map().free_contiguous_chunks(start);
+                }
+            }
+            guard.current_chunk = Address::zero();
+            guard.sentinel = Address::zero();
+            guard.cursor = Address::zero();
+        }
+    }
+
     /*/**
     * Release all pages associated with this page resource, optionally
     * zeroing on release and optionally memory protecting on release.
@@ -294,10 +429,12 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
     unsafe fn release_pages(&self, guard: &mut MutexGuard<MonotonePageResourceSync>) {
         // TODO: concurrent zeroing
         if self.common().contiguous {
-            guard.cursor = match guard.conditional {
+            let start = match guard.conditional {
                 MonotonePageResourceConditional::Contiguous { start: _start, .. } => _start,
                 _ => unreachable!(),
             };
+            self.release_pages_extent(start, guard.cursor - start);
+            guard.cursor = start;
         } else if !guard.cursor.is_zero() {
             let bytes = guard.cursor - guard.current_chunk;
             self.release_pages_extent(guard.current_chunk, bytes);
@@ -313,9 +450,14 @@ impl<VM: VMBinding> MonotonePageResource<VM> {
         }
     }
 
-    fn release_pages_extent(&self, _first: Address, bytes: usize) {
+    fn release_pages_extent(&self, first: Address, bytes: usize) {
         let pages = crate::util::conversions::bytes_to_pages(bytes);
         debug_assert!(bytes == crate::util::conversions::pages_to_bytes(pages));
+        // Clear the side metadata of the pages before they can be reused
+        let space = self.common().space.unwrap();
+        for spec in space.common().side_metadata_specs.iter() {
+            spec.bzero(first, bytes);
+        }
         // FIXME ZERO_PAGES_ON_RELEASE
         // FIXME Options.protectOnRelease
         // FIXME VM.events.tracePageReleased
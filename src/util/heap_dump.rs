@@ -0,0 +1,568 @@
+//! Heap dumps: a snapshot of the objects reachable from the roots, written to a file for offline
+//! inspection (e.g. to chase leaks). A dump is requested with `memory_manager::dump_heap()`. The
+//! GC that takes the dump records the objects referenced by the roots as it scans them (see
+//! `HeapDumpRoots`). At the end of the GC, when the mutators are still stopped, the objects are
+//! traced again from the recorded roots with `HeapDumpProcessEdges`, which does not move or mark
+//! objects in the spaces. Each GC worker buffers the records of the objects it finds, and the
+//! dump is written to the file when the trace is finished.
+//!
+//! # Format
+//!
+//! All the integers are unsigned and little-endian. A dump starts with a header:
+//!
+//! * the magic bytes `MMTKHEAP`,
+//! * the version of the format (`u32`, currently 1),
+//! * the size of an address in bytes (`u32`, always 8, as the addresses are written as `u64`).
+//!
+//! The header is followed by records. Each record starts with a tag byte:
+//!
+//! * `ROOT` (1): the address of an object referenced by a root (`u64`).
+//! * `OBJECT` (2): an object, which is
+//!   * the address of the object (`u64`),
+//!   * the size of the object in bytes (`u64`),
+//!   * the length of the type descriptor (`u32`), followed by the bytes of the descriptor (see
+//!     `ObjectModel::get_type_descriptor()`),
+//!   * the number of outgoing edges (`u32`), followed by the address of the referent of each
+//!     non-null reference field in the object (`u64` each).
+//! * `END` (255): the end of the dump.
+//!
+//! The records are in no particular order. Each object has one `OBJECT` record, and each object
+//! referenced by the roots has one `ROOT` record. `HeapDump::read()` parses a dump back.
+
+use crate::plan::barriers::ObjectReferents;
+use crate::scheduler::gc_works::*;
+use crate::scheduler::*;
+use crate::util::{Address, ObjectReference, OpaquePointer};
+use crate::vm::*;
+use crate::MMTK;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, RwLock};
+
+pub const MAGIC: &[u8; 8] = b"MMTKHEAP";
+pub const VERSION: u32 = 1;
+
+const TAG_ROOT: u8 = 1;
+const TAG_OBJECT: u8 = 2;
+const TAG_END: u8 = 255;
+
+/// An object in a heap dump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeapDumpObject {
+    pub address: Address,
+    pub size: usize,
+    pub type_descriptor: Vec<u8>,
+    /// The referents of the non-null reference fields of the object.
+    pub edges: Vec<Address>,
+}
+
+/// Writes a heap dump in the format described in the module documentation.
+pub struct HeapDumpWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> HeapDumpWriter<W> {
+    /// Create a writer, and write the header.
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        out.write_all(&8u32.to_le_bytes())?;
+        Ok(Self { out })
+    }
+
+    pub fn write_root(&mut self, object: Address) -> io::Result<()> {
+        self.out.write_all(&[TAG_ROOT])?;
+        self.write_address(object)
+    }
+
+    pub fn write_object(&mut self, object: &HeapDumpObject) -> io::Result<()> {
+        self.out.write_all(&[TAG_OBJECT])?;
+        self.write_address(object.address)?;
+        self.out.write_all(&(object.size as u64).to_le_bytes())?;
+        self.out
+            .write_all(&(object.type_descriptor.len() as u32).to_le_bytes())?;
+        self.out.write_all(&object.type_descriptor)?;
+        self.out
+            .write_all(&(object.edges.len() as u32).to_le_bytes())?;
+        for &edge in object.edges.iter() {
+            self.write_address(edge)?;
+        }
+        Ok(())
+    }
+
+    /// Write the end of the dump, and flush the output. Returns the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[TAG_END])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    fn write_address(&mut self, address: Address) -> io::Result<()> {
+        self.out
+            .write_all(&(address.as_usize() as u64).to_le_bytes())
+    }
+}
+
+/// A heap dump parsed by `HeapDump::read()`.
+#[derive(Debug, Default)]
+pub struct HeapDump {
+    pub roots: Vec<Address>,
+    pub objects: Vec<HeapDumpObject>,
+}
+
+impl HeapDump {
+    /// Parse a heap dump in the format described in the module documentation.
+    pub fn read<R: Read>(mut input: R) -> io::Result<HeapDump> {
+        let mut magic = [0u8; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a heap dump"));
+        }
+        let version = read_u32(&mut input)?;
+        if version != VERSION {
+            return Err(invalid_data(format!("unsupported version {}", version)));
+        }
+        let address_size = read_u32(&mut input)?;
+        if address_size != 8 {
+            return Err(invalid_data(format!(
+                "unsupported address size {}",
+                address_size
+            )));
+        }
+        let mut dump = HeapDump::default();
+        loop {
+            let mut tag = [0u8];
+            input.read_exact(&mut tag)?;
+            match tag[0] {
+                TAG_ROOT => dump.roots.push(read_address(&mut input)?),
+                TAG_OBJECT => {
+                    let address = read_address(&mut input)?;
+                    let size = read_u64(&mut input)? as usize;
+                    let mut type_descriptor = vec![0u8; read_u32(&mut input)? as usize];
+                    input.read_exact(&mut type_descriptor)?;
+                    let edges = (0..read_u32(&mut input)?)
+                        .map(|_| read_address(&mut input))
+                        .collect::<io::Result<_>>()?;
+                    dump.objects.push(HeapDumpObject {
+                        address,
+                        size,
+                        type_descriptor,
+                        edges,
+                    });
+                }
+                TAG_END => return Ok(dump),
+                tag => return Err(invalid_data(format!("unknown record tag {}", tag))),
+            }
+        }
+    }
+
+    /// Read the heap dump in the file.
+    pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<HeapDump> {
+        Self::read(io::BufReader::new(File::open(path)?))
+    }
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+fn read_u32<R: Read>(input: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    input.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(input: &mut R) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    input.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_address<R: Read>(input: &mut R) -> io::Result<Address> {
+    Ok(unsafe { Address::from_usize(read_u64(input)? as usize) })
+}
+
+/// The record of an object for a heap dump.
+fn object_record<VM: VMBinding>(object: ObjectReference, tls: OpaquePointer) -> HeapDumpObject {
+    let mut referents = ObjectReferents(vec![]);
+    VM::VMScanning::scan_object(&mut referents, object, tls);
+    HeapDumpObject {
+        address: object.to_address(),
+        size: VM::VMObjectModel::get_current_size(object),
+        type_descriptor: VM::VMObjectModel::get_type_descriptor(object)
+            .iter()
+            .map(|&c| c as u8)
+            .collect(),
+        edges: referents
+            .0
+            .iter()
+            .filter(|referent| !referent.is_null())
+            .map(|referent| referent.to_address())
+            .collect(),
+    }
+}
+
+/// The state of a heap dump in the GC that takes it.
+struct DumpState {
+    writer: HeapDumpWriter<BufWriter<File>>,
+    roots: Mutex<HashSet<ObjectReference>>,
+    visited: Mutex<HashSet<ObjectReference>>,
+    /// The records of the objects found by each GC worker (see `record_buffer()`). They are
+    /// written when the trace is finished.
+    records: Vec<Mutex<Vec<HeapDumpObject>>>,
+}
+
+impl DumpState {
+    /// The buffer in `records` of the worker. The coordinator has the last buffer.
+    fn record_buffer<VM: VMBinding>(&self, worker: &GCWorker<VM>) -> &Mutex<Vec<HeapDumpObject>> {
+        if worker.is_coordinator() {
+            self.records.last().unwrap()
+        } else {
+            &self.records[worker.ordinal]
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        let mut writer = self.writer;
+        for root in self.roots.into_inner().unwrap() {
+            writer.write_root(root.to_address())?;
+        }
+        for records in self.records {
+            for record in records.into_inner().unwrap().iter() {
+                writer.write_object(record)?;
+            }
+        }
+        writer.finish().map(|_| ())
+    }
+}
+
+/// Takes the heap dumps requested by `memory_manager::dump_heap()`.
+#[derive(Default)]
+pub struct HeapDumper {
+    /// The file for the dump requested for the next GC.
+    requested: Mutex<Option<PathBuf>>,
+    /// Set while the GC that takes the dump scans the roots, until the objects are traced.
+    recording_roots: AtomicBool,
+    state: RwLock<Option<DumpState>>,
+    /// The result of the last dump.
+    result: Mutex<Option<io::Result<()>>>,
+}
+
+impl HeapDumper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request a dump to the file at `path` in the next GC.
+    pub fn request(&self, path: PathBuf) {
+        *self.result.lock().unwrap() = None;
+        *self.requested.lock().unwrap() = Some(path);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.requested.lock().unwrap().is_some()
+    }
+
+    /// Drop the request if no GC has taken it, e.g. if the GC was declined, or if the GC that the
+    /// requester waited for had started before the request. A later GC should not take the dump.
+    pub fn cancel_request(&self) {
+        *self.requested.lock().unwrap() = None;
+    }
+
+    /// Take the result of the last dump. Returns `None` if no dump was taken since `request()`.
+    pub fn take_result(&self) -> Option<io::Result<()>> {
+        self.result.lock().unwrap().take()
+    }
+
+    /// Create the file for the requested dump at the start of a GC, and record the roots that the
+    /// GC scans from now on. Returns false if there is no dump to take.
+    pub fn start(&self, num_workers: usize) -> bool {
+        let path = match self.requested.lock().unwrap().take() {
+            Some(path) => path,
+            None => return false,
+        };
+        match File::create(&path).and_then(|file| HeapDumpWriter::new(BufWriter::new(file))) {
+            Ok(writer) => {
+                *self.state.write().unwrap() = Some(DumpState {
+                    writer,
+                    roots: Mutex::new(HashSet::new()),
+                    visited: Mutex::new(HashSet::new()),
+                    records: (0..=num_workers).map(|_| Mutex::new(vec![])).collect(),
+                });
+                self.recording_roots.store(true, Ordering::SeqCst);
+                true
+            }
+            Err(e) => {
+                *self.result.lock().unwrap() = Some(Err(e));
+                false
+            }
+        }
+    }
+
+    /// Are the roots recorded for a dump in this GC?
+    #[inline]
+    pub fn is_recording_roots(&self) -> bool {
+        self.recording_roots.load(Ordering::Relaxed)
+    }
+
+    /// Record the objects referenced by the root slots for the dump.
+    fn record_roots(&self, slots: &[Address]) {
+        let state = self.state.read().unwrap();
+        let mut roots = state.as_ref().unwrap().roots.lock().unwrap();
+        for slot in slots.iter() {
+            let object = unsafe { slot.load::<ObjectReference>() };
+            if !object.is_null() {
+                roots.insert(object);
+            }
+        }
+    }
+
+    /// Forget the roots recorded so far, e.g. when the roots are scanned again after the objects
+    /// have moved.
+    pub fn clear_roots(&self) {
+        if self.is_recording_roots() {
+            let state = self.state.read().unwrap();
+            state.as_ref().unwrap().roots.lock().unwrap().clear();
+        }
+    }
+
+    /// Stop recording the roots, and get the recorded roots.
+    fn stop_recording_roots(&self) -> Vec<ObjectReference> {
+        self.recording_roots.store(false, Ordering::SeqCst);
+        let state = self.state.read().unwrap();
+        let roots = state.as_ref().unwrap().roots.lock().unwrap();
+        roots.iter().copied().collect()
+    }
+
+    /// Mark the objects as visited, and buffer the records of the objects that were not visited
+    /// before for the worker. Returns these objects.
+    fn visit<VM: VMBinding>(
+        &self,
+        objects: Vec<ObjectReference>,
+        worker: &GCWorker<VM>,
+    ) -> Vec<ObjectReference> {
+        let state = self.state.read().unwrap();
+        let state = state.as_ref().unwrap();
+        let objects: Vec<ObjectReference> = {
+            let mut visited = state.visited.lock().unwrap();
+            objects
+                .into_iter()
+                .filter(|&object| visited.insert(object))
+                .collect()
+        };
+        let mut records = objects
+            .iter()
+            .map(|&object| object_record::<VM>(object, worker.tls))
+            .collect();
+        state
+            .record_buffer(worker)
+            .lock()
+            .unwrap()
+            .append(&mut records);
+        objects
+    }
+
+    fn finish(&self) {
+        if let Some(state) = self.state.write().unwrap().take() {
+            *self.result.lock().unwrap() = Some(state.finish());
+        }
+    }
+}
+
+/// Trace the objects reachable from the recorded roots for the heap dump. This is scheduled in the
+/// final stage of the GC that takes the dump, after the plan has released its spaces.
+///
+/// As with `ScheduleSanityGC`, the trace reuses the stages of the GC. `reset_state()` closes all the
+/// stages after the prepare stage, including the final stage that this runs in, and they are opened
+/// again in order as the dump is traced. The final stage works that have not started yet (e.g. a
+/// `ScheduleSanityGC`) run after the dump is finished, and the GC ends when they are done.
+#[derive(Default)]
+pub struct ScheduleHeapDump;
+
+impl<VM: VMBinding> GCWork<VM> for ScheduleHeapDump {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let roots = mmtk.heap_dumper.stop_recording_roots();
+        let scheduler = worker.scheduler();
+        scheduler.reset_state();
+        for roots in roots.chunks(HeapDumpProcessEdges::<VM>::CAPACITY) {
+            scheduler
+                .closure_stage
+                .add(TraceHeapDumpRoots(roots.to_vec()));
+        }
+        scheduler.release_stage.add(FinishHeapDump);
+    }
+}
+
+/// Trace the objects referenced by the recorded roots. They are traced as the referents of slots
+/// in this work, which are never changed.
+pub struct TraceHeapDumpRoots(Vec<ObjectReference>);
+
+impl<VM: VMBinding> GCWork<VM> for TraceHeapDumpRoots {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        let edges = self.0.iter().map(Address::from_ref).collect();
+        GCWork::do_work(
+            &mut HeapDumpProcessEdges::<VM>::new(edges, true),
+            worker,
+            mmtk,
+        );
+    }
+}
+
+/// Write the heap dump when all the objects are traced.
+pub struct FinishHeapDump;
+
+impl<VM: VMBinding> GCWork<VM> for FinishHeapDump {
+    fn do_work(&mut self, _worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        mmtk.heap_dumper.finish();
+    }
+}
+
+/// Trace objects for a heap dump. The objects are recorded in a set instead of being marked, and
+/// the references are never updated.
+#[derive(Default)]
+pub struct HeapDumpProcessEdges<VM: VMBinding> {
+    base: ProcessEdgesBase<HeapDumpProcessEdges<VM>>,
+    phantom: PhantomData<VM>,
+}
+
+impl<VM: VMBinding> Deref for HeapDumpProcessEdges<VM> {
+    type Target = ProcessEdgesBase<Self>;
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<VM: VMBinding> DerefMut for HeapDumpProcessEdges<VM> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl<VM: VMBinding> HeapDumpProcessEdges<VM> {
+    /// Record and scan the objects that are not visited yet.
+    fn visit(&mut self, objects: Vec<ObjectReference>) {
+        for object in self.mmtk().heap_dumper.visit(objects, self.worker()) {
+            ProcessEdgesWork::process_node(self, object);
+        }
+    }
+}
+
+impl<VM: VMBinding> ProcessEdgesWork for HeapDumpProcessEdges<VM> {
+    type VM = VM;
+    const OVERWRITE_REFERENCE: bool = false;
+    fn new(edges: Vec<Address>, _roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    fn trace_object(&mut self, object: ObjectReference) -> ObjectReference {
+        if !object.is_null() {
+            self.visit(vec![object]);
+        }
+        object
+    }
+
+    /// Visit the objects of all the edges together, so that the visited set and the record buffer
+    /// of the worker are locked once for the edges.
+    fn process_edges(&mut self) {
+        let objects = self
+            .edges
+            .iter()
+            .map(|slot| unsafe { slot.load::<ObjectReference>() })
+            .filter(|object| !object.is_null())
+            .collect();
+        self.visit(objects);
+    }
+}
+
+/// Records the objects referenced by the roots for the heap dump taken in this GC. The root
+/// scanning works scan the roots with this in place of `E` while the roots are recorded, so the
+/// roots are scanned once for both the GC and the dump. The slots are processed by `E` first, so
+/// the objects are recorded at their new addresses if `E` moves them.
+pub struct HeapDumpRoots<E: ProcessEdgesWork> {
+    base: ProcessEdgesBase<Self>,
+    roots: bool,
+}
+
+impl<E: ProcessEdgesWork> Deref for HeapDumpRoots<E> {
+    type Target = ProcessEdgesBase<Self>;
+    fn deref(&self) -> &Self::Target {
+        &self.base
+    }
+}
+
+impl<E: ProcessEdgesWork> DerefMut for HeapDumpRoots<E> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.base
+    }
+}
+
+impl<E: ProcessEdgesWork> ProcessEdgesWork for HeapDumpRoots<E> {
+    type VM = E::VM;
+    fn new(edges: Vec<Address>, roots: bool) -> Self {
+        Self {
+            base: ProcessEdgesBase::new(edges),
+            roots,
+        }
+    }
+
+    fn trace_object(&mut self, _object: ObjectReference) -> ObjectReference {
+        unreachable!("HeapDumpRoots only passes its slots to the ProcessEdgesWork of the GC")
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for HeapDumpRoots<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        let edges = mem::take(&mut self.edges);
+        GCWork::do_work(&mut E::new(edges.clone(), self.roots), worker, mmtk);
+        if self.roots {
+            mmtk.heap_dumper.record_roots(&edges);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_and_read() {
+        let objects: Vec<HeapDumpObject> = (0..4usize)
+            .map(|i| HeapDumpObject {
+                address: unsafe { Address::from_usize(0x1000 + i * 0x20) },
+                size: 0x20,
+                type_descriptor: format!("Type{}", i).into_bytes(),
+                edges: (0..i)
+                    .map(|j| unsafe { Address::from_usize(0x1000 + j * 0x20) })
+                    .collect(),
+            })
+            .collect();
+        let mut writer = HeapDumpWriter::new(vec![]).unwrap();
+        writer.write_root(objects[3].address).unwrap();
+        for object in objects.iter() {
+            writer.write_object(object).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let dump = HeapDump::read(&bytes[..]).unwrap();
+        assert_eq!(dump.roots, vec![objects[3].address]);
+        assert_eq!(dump.objects, objects);
+    }
+
+    #[test]
+    fn test_read_invalid() {
+        assert!(HeapDump::read(&b"NOTAHEAP"[..]).is_err());
+        // Truncated before the end record
+        let bytes = HeapDumpWriter::new(vec![]).unwrap().out;
+        assert!(HeapDump::read(&bytes[..]).is_err());
+    }
+}
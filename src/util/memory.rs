@@ -31,6 +31,19 @@ pub fn dzmmap(start: Address, size: usize) -> Result<Address> {
     }
 }
 
+/// Map zeroed memory for side metadata, anywhere in the address space. No swap space is reserved
+/// for the mapping, so that only the pages that are touched use memory.
+pub fn mmap_noreserve(size: usize) -> Result<Address> {
+    let prot = libc::PROT_READ | libc::PROT_WRITE;
+    let flags = libc::MAP_ANON | libc::MAP_PRIVATE | libc::MAP_NORESERVE;
+    let result: *mut c_void = unsafe { libc::mmap(std::ptr::null_mut(), size, prot, flags, -1, 0) };
+    if result == libc::MAP_FAILED {
+        Err(Error::last_os_error())
+    } else {
+        Ok(Address::from_mut_ptr(result))
+    }
+}
+
 pub fn munprotect(start: Address, size: usize) -> Result<()> {
     let result =
         unsafe { libc::mprotect(start.to_mut_ptr(), size, PROT_READ | PROT_WRITE | PROT_EXEC) };
@@ -6,21 +6,29 @@ pub mod macros;
 pub mod conversions;
 pub mod address;
 pub mod alloc;
+pub mod alloc_bit;
+pub mod card_table;
+pub mod conservative_roots;
 pub mod constants;
+pub mod finalizable_processor;
 pub mod forwarding_word;
 pub mod generic_freelist;
 pub mod header_byte;
 pub mod heap;
+pub mod heap_dump;
 pub mod int_array_freelist;
 pub mod logger;
 pub mod memory;
 pub mod opaque_pointer;
 pub mod options;
+pub mod pin_bit;
 pub mod queue;
 pub mod raw_memory_freelist;
+pub mod rc_table;
 pub mod reference_processor;
 #[cfg(feature = "sanity")]
 pub mod sanity;
+pub mod side_metadata;
 pub mod statistics;
 mod synchronized_counter;
 pub mod treadmill;
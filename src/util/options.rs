@@ -1,10 +1,26 @@
 use crate::util::constants::LOG_BYTES_IN_PAGE;
 use std::cell::UnsafeCell;
 use std::default::Default;
+use std::fmt;
 use std::ops::Deref;
 
 custom_derive! {
-    #[derive(Copy, Clone, EnumFromStr)]
+    #[derive(Copy, Clone, EnumFromStr, Debug, PartialEq)]
+    pub enum PlanSelector {
+        NoGC,
+        SemiSpace,
+        GenCopy,
+        MarkSweep,
+        Immix,
+        MarkCompact,
+        ConcurrentMarkSweep,
+        StickyMarkSweep,
+        ReferenceCounting,
+    }
+}
+
+custom_derive! {
+    #[derive(Copy, Clone, EnumFromStr, Debug, PartialEq)]
     pub enum NurseryZeroingOptions {
         Temporal,
         Nontemporal,
@@ -24,7 +40,18 @@ impl UnsafeOptionsWrapper {
     /// This method is not thread safe, as internally it acquires a mutable reference to self.
     /// It is supposed to be used by one thread during boot time.
     pub unsafe fn process(&self, name: &str, value: &str) -> bool {
-        (&mut *self.0.get()).set_from_camelcase_str(name, value)
+        let options = &mut *self.0.get();
+        let old = options.clone();
+        if !options.set_from_camelcase_str(name, value) {
+            return false;
+        }
+        // The new value may break the bounds with another option
+        if let Err(e) = options.validate() {
+            warn!("{}", e);
+            *options = old;
+            return false;
+        }
+        true
     }
 }
 impl Deref for UnsafeOptionsWrapper {
@@ -34,7 +61,129 @@ impl Deref for UnsafeOptionsWrapper {
     }
 }
 
-fn always_valid<T>(_: T) -> bool {
+/// Why an option cannot be set.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OptionError {
+    /// There is no option with the name.
+    UnknownOption(String),
+    /// The value cannot be parsed as the type of the option.
+    ParseError { name: &'static str, value: String },
+    /// The value is rejected by the validator of the option. `requirement` is the validator, or the
+    /// relation to another option that the value breaks.
+    InvalidValue {
+        name: &'static str,
+        value: String,
+        requirement: &'static str,
+    },
+}
+
+impl fmt::Display for OptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OptionError::UnknownOption(name) => write!(f, "unknown option {}", name),
+            OptionError::ParseError { name, value } => {
+                write!(
+                    f,
+                    "cannot parse {:?} as the value of option {}",
+                    value, name
+                )
+            }
+            OptionError::InvalidValue {
+                name,
+                value,
+                requirement,
+            } => write!(
+                f,
+                "invalid value {} for option {}: the value needs to satisfy `{}`",
+                value, name, requirement
+            ),
+        }
+    }
+}
+
+impl std::error::Error for OptionError {}
+
+/// An operation needs an option that was not enabled when the MMTk instance was created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OptionNotEnabled(pub &'static str);
+
+impl fmt::Display for OptionNotEnabled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "the {} option needs to be enabled when the MMTk instance is created",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for OptionNotEnabled {}
+
+/// Sets the options of an MMTk instance programmatically, before the instance is created with
+/// `MMTK::with_options()`. Each option has a typed setter with the name of the option, e.g.
+///
+/// ```ignore
+/// let options = OptionsBuilder::new()
+///     .plan(PlanSelector::GenCopy)
+///     .threads(4)
+///     .set("min_nursery", "1048576")?
+///     .build()?;
+/// let mmtk = MMTK::<VM>::with_options(options);
+/// ```
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+impl OptionsBuilder {
+    /// Start from the default options, including the ones set by `MMTK_*` environment variables.
+    pub fn new() -> Self {
+        OptionsBuilder {
+            options: Options::default(),
+        }
+    }
+
+    /// Set an option from a string, e.g. `set("threads", "4")`. The name is in snake case.
+    pub fn set(mut self, name: &str, value: &str) -> Result<Self, OptionError> {
+        self.options.try_set_from_str(name, value)?;
+        Ok(self)
+    }
+
+    /// Check the values of all the options, and return the options if they are valid.
+    pub fn build(self) -> Result<Options, OptionError> {
+        self.options.validate()?;
+        Ok(self.options)
+    }
+}
+
+impl Options {
+    /// Check that the minimal sizes are not larger than the maximal sizes. A heap size of 0 is not
+    /// checked, as it is the heap size given to `gc_init()` (within the other heap size).
+    fn validate_bounds(&self) -> Result<(), OptionError> {
+        if self.min_nursery > self.max_nursery {
+            return Err(OptionError::InvalidValue {
+                name: "min_nursery",
+                value: format!("{:?}", self.min_nursery),
+                requirement: "min_nursery <= max_nursery",
+            });
+        }
+        if self.min_heap != 0 && self.max_heap != 0 && self.min_heap > self.max_heap {
+            return Err(OptionError::InvalidValue {
+                name: "min_heap",
+                value: format!("{:?}", self.min_heap),
+                requirement: "min_heap <= max_heap",
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Default for OptionsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn always_valid<T>(_: &T) -> bool {
     true
 }
 macro_rules! options {
@@ -42,28 +191,78 @@ macro_rules! options {
         options!($($name: $type[$validator] = $default),*);
     ];
     ($($name:ident: $type:ty[$validator:expr] = $default:expr),*) => [
+        #[derive(Clone)]
         pub struct Options {
             $(pub $name: $type),*
         }
         impl Options {
-            pub fn set_from_str(&mut self, s: &str, val: &str)->bool {
-                match s {
-                    $(stringify!($name) => if let Ok(val) = val.parse() {
-                        self.$name = val;
-                        let validate_fn = $validator;
-                        validate_fn(val)
-                    } else {
+            /// Set an option from a string. Returns false if the option cannot be set, and the
+            /// option keeps its value.
+            pub fn set_from_str(&mut self, s: &str, val: &str) -> bool {
+                match self.try_set_from_str(s, val) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        warn!("{}", e);
                         false
-                    })*
-                    _ => panic!("Invalid Options key")
+                    }
                 }
             }
+
+            /// Set an option from a string. The option keeps its value if this returns an error.
+            pub fn try_set_from_str(&mut self, s: &str, val: &str) -> Result<(), OptionError> {
+                match s {
+                    $(stringify!($name) => match val.parse::<$type>() {
+                        Ok(value) => {
+                            let validate_fn = $validator;
+                            if validate_fn(&value) {
+                                self.$name = value;
+                                Ok(())
+                            } else {
+                                Err(OptionError::InvalidValue {
+                                    name: stringify!($name),
+                                    value: val.to_owned(),
+                                    requirement: stringify!($validator),
+                                })
+                            }
+                        }
+                        Err(_) => Err(OptionError::ParseError {
+                            name: stringify!($name),
+                            value: val.to_owned(),
+                        }),
+                    },)*
+                    _ => Err(OptionError::UnknownOption(s.to_owned())),
+                }
+            }
+
+            /// Check the value of each option with its validator, and the options that bound each other.
+            pub fn validate(&self) -> Result<(), OptionError> {
+                $(
+                    let validate_fn = $validator;
+                    if !validate_fn(&self.$name) {
+                        return Err(OptionError::InvalidValue {
+                            name: stringify!($name),
+                            value: format!("{:?}", self.$name),
+                            requirement: stringify!($validator),
+                        });
+                    }
+                )*
+                self.validate_bounds()
+            }
+        }
+        impl OptionsBuilder {
+            $(
+                pub fn $name(mut self, value: $type) -> Self {
+                    self.options.$name = value;
+                    self
+                }
+            )*
         }
         impl Default for Options {
             fn default() -> Self {
                 let mut options = Options {
                     $($name: $default),*
                 };
+                let defaults = options.clone();
 
                 // If we have env vars that start with MMTK_ and matches any option (such as MMTK_STRESS_FACTOR),
                 // we set the option to its value (if it is a valid value). Otherwise, use the defualt value.
@@ -78,31 +277,65 @@ macro_rules! options {
                         }
                     }
                 }
-                return options;
+                // The variables may bound each other (e.g. MMTK_MIN_NURSERY and MMTK_MAX_NURSERY), so
+                // they are checked together.
+                if let Err(e) = options.validate() {
+                    warn!("Ignoring the MMTK_ environment variables: {}", e);
+                    return defaults;
+                }
+                options
             }
         }
     ]
 }
 options! {
-    threads:               usize                [|v| v > 0]    = num_cpus::get(),
+    // The GC plan to use. The plan is created along with the MMTk instance, so this needs to be set
+    // before that (e.g. MMTK_PLAN=GenCopy).
+    plan:                  PlanSelector         [always_valid] = PlanSelector::NoGC,
+    threads:               usize                [|v: &usize| *v > 0] = num_cpus::get(),
     use_short_stack_scans: bool                 [always_valid] = false,
     use_return_barrier:    bool                 [always_valid] = false,
     eager_complete_sweep:  bool                 [always_valid] = false,
     ignore_system_g_c:     bool                 [always_valid] = false,
-    // Note: Not used. To workaround cmd args passed by the running script
+    // Whether the heap size can change between `min_heap` and `max_heap` (in bytes). Each of them is the heap
+    // size given to `gc_init()` (but within the other one) if it is 0. The heap starts with the size given to `gc_init()`, and is resized
+    // after full heap GCs based on the live ratio and the GC load.
     variable_size_heap:    bool                 [always_valid] = true,
+    min_heap:              usize                [always_valid] = 0,
+    max_heap:              usize                [always_valid] = 0,
     no_finalizer:          bool                 [always_valid] = false,
     no_reference_types:    bool                 [always_valid] = false,
     nursery_zeroing:       NurseryZeroingOptions[always_valid] = NurseryZeroingOptions::Temporal,
+    // The bounds of the nursery size in bytes, for generational plans. The nursery is resized within
+    // the bounds after each nursery GC, and a full heap GC is triggered if less than the minimal nursery
+    // size is left in the heap.
+    min_nursery:           usize                [|v: &usize| *v > 0] = 2 * 1024 * 1024,
+    max_nursery:           usize                [|v: &usize| *v > 0] = 32 * 1024 * 1024,
+    // Use a card-marking barrier instead of the object remembering barrier, for generational plans that support it (GenCopy).
+    card_marking:          bool                 [always_valid] = false,
+    // Set the alloc bit of each object (see `util::alloc_bit`). `is_mmtk_object()`, `find_object_from_internal_pointer()`,
+    // conservative root scanning and `enumerate_objects()` need the alloc bits.
+    alloc_bit:             bool                 [always_valid] = false,
+    // Set the pin bit of each pinned object (see `util::pin_bit`). `pin_object()` and conservative root
+    // scanning need the pin bits.
+    pin_bit:               bool                 [always_valid] = false,
+    // The percentage of the heap that needs to be used to trigger a concurrent collection, for concurrent plans.
+    concurrent_trigger:    usize                [|v: &usize| *v > 0 && *v <= 100] = 50,
     // Note: This gets ignored. Use RUST_LOG to specify log level.
     // TODO: Delete this option.
     verbose:               usize                [always_valid] = 0,
     stress_factor:         usize                [always_valid] = usize::max_value() >> LOG_BYTES_IN_PAGE,
+    // The file that a JSON record of each GC is appended to, one per line (see `GCEventLog`). No records
+    // are written if this is empty.
+    gc_event_log:          String               [always_valid] = String::new(),
+    // The file that a timeline of the work packets is written to, in the Chrome trace event format (see
+    // `WorkTrace`). The works are not traced if this is empty.
+    work_trace:            String               [always_valid] = String::new(),
     // vmspace
     // FIXME: These options are set for JikesRVM. We need a proper way to set options.
     //   We need to set these values programmatically in VM specific code.
     vm_space:              bool                 [always_valid] = true,
-    vm_space_size:         usize                [|v| v > 0]    = 0x7cc_cccc,
+    vm_space_size:         usize                [|v: &usize| *v > 0] = 0x7cc_cccc,
 }
 
 impl Options {
@@ -137,7 +370,10 @@ impl Options {
 #[cfg(test)]
 mod tests {
     use crate::util::constants::LOG_BYTES_IN_PAGE;
-    use crate::util::options::Options;
+    use crate::util::options::{
+        NurseryZeroingOptions, OptionError, Options, OptionsBuilder, PlanSelector,
+        UnsafeOptionsWrapper,
+    };
     use crate::util::test_util::serial_test;
 
     const DEFAULT_STRESS_FACTOR: usize = usize::max_value() >> LOG_BYTES_IN_PAGE;
@@ -199,6 +435,76 @@ mod tests {
         })
     }
 
+    #[test]
+    fn with_plan_env_var() {
+        serial_test(|| {
+            std::env::set_var("MMTK_PLAN", "GenCopy");
+
+            let res = std::panic::catch_unwind(|| {
+                let options = Options::default();
+                assert_eq!(options.plan, PlanSelector::GenCopy);
+            });
+            assert!(res.is_ok());
+
+            std::env::remove_var("MMTK_PLAN");
+        })
+    }
+
+    #[test]
+    fn with_nursery_env_vars() {
+        serial_test(|| {
+            std::env::set_var("MMTK_MIN_NURSERY", "1048576");
+            std::env::set_var("MMTK_MAX_NURSERY", "8388608");
+
+            let res = std::panic::catch_unwind(|| {
+                let options = Options::default();
+                assert_eq!(options.min_nursery, 1048576);
+                assert_eq!(options.max_nursery, 8388608);
+            });
+            assert!(res.is_ok());
+
+            std::env::remove_var("MMTK_MIN_NURSERY");
+            std::env::remove_var("MMTK_MAX_NURSERY");
+        })
+    }
+
+    #[test]
+    fn with_invalid_nursery_env_vars() {
+        serial_test(|| {
+            std::env::set_var("MMTK_MIN_NURSERY", "8388608");
+            std::env::set_var("MMTK_MAX_NURSERY", "1048576");
+            std::env::set_var("MMTK_STRESS_FACTOR", "4096");
+
+            let res = std::panic::catch_unwind(|| {
+                // The bounds are broken, so none of the variables is used
+                let options = Options::default();
+                assert_eq!(options.min_nursery, 2 * 1024 * 1024);
+                assert_eq!(options.max_nursery, 32 * 1024 * 1024);
+                assert_eq!(options.stress_factor, DEFAULT_STRESS_FACTOR);
+            });
+            assert!(res.is_ok());
+
+            std::env::remove_var("MMTK_MIN_NURSERY");
+            std::env::remove_var("MMTK_MAX_NURSERY");
+            std::env::remove_var("MMTK_STRESS_FACTOR");
+        })
+    }
+
+    #[test]
+    fn with_gc_event_log_env_var() {
+        serial_test(|| {
+            std::env::set_var("MMTK_GC_EVENT_LOG", "/tmp/gc-events.jsonl");
+
+            let res = std::panic::catch_unwind(|| {
+                let options = Options::default();
+                assert_eq!(options.gc_event_log, "/tmp/gc-events.jsonl");
+            });
+            assert!(res.is_ok());
+
+            std::env::remove_var("MMTK_GC_EVENT_LOG");
+        })
+    }
+
     #[test]
     fn with_invalid_env_var_key() {
         serial_test(|| {
@@ -214,4 +520,127 @@ mod tests {
             std::env::remove_var("MMTK_ABC");
         })
     }
+
+    #[test]
+    fn set_unknown_key() {
+        serial_test(|| {
+            let mut options = Options::default();
+            assert!(!options.set_from_str("abc", "42"));
+            assert_eq!(
+                options.try_set_from_str("abc", "42"),
+                Err(OptionError::UnknownOption("abc".to_owned()))
+            );
+        })
+    }
+
+    #[test]
+    fn set_invalid_value() {
+        serial_test(|| {
+            let mut options = Options::default();
+            let threads = options.threads;
+            assert_eq!(
+                options.try_set_from_str("threads", "four"),
+                Err(OptionError::ParseError {
+                    name: "threads",
+                    value: "four".to_owned()
+                })
+            );
+            assert!(matches!(
+                options.try_set_from_str("threads", "0"),
+                Err(OptionError::InvalidValue {
+                    name: "threads",
+                    ..
+                })
+            ));
+            // The option keeps its value
+            assert_eq!(options.threads, threads);
+            assert!(options.set_from_str("threads", "3"));
+            assert_eq!(options.threads, 3);
+        })
+    }
+
+    #[test]
+    fn process_invalid_bounds() {
+        serial_test(|| {
+            let options = UnsafeOptionsWrapper::new(Options::default());
+            unsafe {
+                assert!(!options.process("minNursery", "67108864"));
+                assert_eq!(options.min_nursery, 2 * 1024 * 1024);
+                assert!(options.process("maxNursery", "134217728"));
+                assert!(options.process("minNursery", "67108864"));
+                assert!(!options.process("maxNursery", "1048576"));
+                assert_eq!(options.max_nursery, 134217728);
+            }
+        })
+    }
+
+    #[test]
+    fn with_options_builder() {
+        serial_test(|| {
+            let options = OptionsBuilder::new()
+                .plan(PlanSelector::GenCopy)
+                .threads(2)
+                .nursery_zeroing(NurseryZeroingOptions::Nontemporal)
+                .set("max_nursery", "8388608")
+                .unwrap()
+                .build()
+                .unwrap();
+            assert_eq!(options.plan, PlanSelector::GenCopy);
+            assert_eq!(options.threads, 2);
+            assert_eq!(options.nursery_zeroing, NurseryZeroingOptions::Nontemporal);
+            assert_eq!(options.max_nursery, 8388608);
+        })
+    }
+
+    #[test]
+    fn with_invalid_options_builder() {
+        serial_test(|| {
+            assert_eq!(
+                OptionsBuilder::new().threads(0).build().err(),
+                Some(OptionError::InvalidValue {
+                    name: "threads",
+                    value: "0".to_owned(),
+                    requirement: stringify!(|v: &usize| *v > 0),
+                })
+            );
+            assert!(OptionsBuilder::new().set("no_such_option", "1").is_err());
+        })
+    }
+
+    #[test]
+    fn with_invalid_bounds_builder() {
+        serial_test(|| {
+            assert_eq!(
+                OptionsBuilder::new()
+                    .min_nursery(8 << 20)
+                    .max_nursery(4 << 20)
+                    .build()
+                    .err(),
+                Some(OptionError::InvalidValue {
+                    name: "min_nursery",
+                    value: (8 << 20).to_string(),
+                    requirement: "min_nursery <= max_nursery",
+                })
+            );
+            assert_eq!(
+                OptionsBuilder::new()
+                    .min_heap(64 << 20)
+                    .max_heap(32 << 20)
+                    .build()
+                    .err(),
+                Some(OptionError::InvalidValue {
+                    name: "min_heap",
+                    value: (64 << 20).to_string(),
+                    requirement: "min_heap <= max_heap",
+                })
+            );
+            // A bound of 0 is the heap size given to gc_init()
+            assert!(OptionsBuilder::new().min_heap(64 << 20).build().is_ok());
+            assert!(OptionsBuilder::new()
+                .min_nursery(4 << 20)
+                .max_nursery(4 << 20)
+                .build()
+                .is_ok());
+        })
+    }
 }
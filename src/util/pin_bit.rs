@@ -0,0 +1,82 @@
+//! The pin bit: one bit in side metadata for each word, which is set if the object that the word
+//! is the reference of must not be moved by a GC. The moving spaces (`CopySpace`, the defrag in
+//! `ImmixSpace`, and `MarkCompactSpace`) keep pinned objects in place. Pinning does not keep an
+//! object alive, and the pin bit is cleared when the memory of a dead object is reclaimed.
+//!
+//! The pin bits are only kept with the `pin_bit` option, and the pin bit is then mapped for all the
+//! spaces (see `HeapMeta::side_metadata_specs`). Otherwise no object is pinned.
+
+use crate::util::alloc_bit::ALLOC_SIDE_METADATA_SPEC;
+use crate::util::constants::LOG_BYTES_IN_WORD;
+use crate::util::side_metadata::SideMetadataSpec;
+use crate::util::{Address, ObjectReference};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const PIN_SIDE_METADATA_SPEC: SideMetadataSpec = SideMetadataSpec::new(
+    "pin bit",
+    ALLOC_SIDE_METADATA_SPEC.next_offset(),
+    0,
+    LOG_BYTES_IN_WORD as usize,
+);
+
+/// Set once an MMTk instance is created with the `pin_bit` option.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Keep the pin bits. This needs to be called before the spaces are created.
+pub fn enable() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Pin the object. Returns true if the object was not pinned before.
+pub fn pin_object(object: ObjectReference) -> bool {
+    assert!(is_enabled(), "Pinning objects needs the pin_bit option");
+    PIN_SIDE_METADATA_SPEC.fetch_or(object.to_address(), 1) == 0
+}
+
+/// Unpin the object. Returns true if the object was pinned before.
+pub fn unpin_object(object: ObjectReference) -> bool {
+    is_enabled() && PIN_SIDE_METADATA_SPEC.fetch_and(object.to_address(), 0) == 1
+}
+
+pub fn is_pinned(object: ObjectReference) -> bool {
+    is_enabled() && PIN_SIDE_METADATA_SPEC.load(object.to_address()) == 1
+}
+
+/// Clear the pin bits for the addresses from `start` to `start + bytes`.
+pub fn bzero_pin_bit(start: Address, bytes: usize) {
+    if is_enabled() {
+        PIN_SIDE_METADATA_SPEC.bzero(start, bytes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::heap_layout::Mmapper;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::test_util::serial_test;
+
+    #[test]
+    fn test_pin_bits() {
+        serial_test(|| {
+            enable();
+            let start = HEAP_START + (3usize << 22);
+            PIN_SIDE_METADATA_SPEC.ensure_mapped(&Mmapper::new(), start, 1 << 20);
+            let object = unsafe { (start + 64usize).to_object_reference() };
+            let next = unsafe { (start + 72usize).to_object_reference() };
+            assert!(!is_pinned(object));
+            assert!(pin_object(object));
+            assert!(!pin_object(object));
+            assert!(is_pinned(object));
+            assert!(!is_pinned(next));
+            assert!(unpin_object(object));
+            assert!(!unpin_object(object));
+            assert!(!is_pinned(object));
+        })
+    }
+}
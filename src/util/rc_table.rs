@@ -0,0 +1,134 @@
+//! Reference counts in side metadata.
+
+use crate::util::pin_bit::PIN_SIDE_METADATA_SPEC;
+use crate::util::side_metadata::{SideMetadataSpec, LOG_MAX_NUM_OF_BITS};
+use crate::util::Address;
+
+/// Each count covers this many bytes (log2). This is the smallest cell size of the
+/// `MarkSweepSpace`, so the cells never share a count.
+pub const LOG_BYTES_IN_GRANULE: usize = 4;
+pub const BYTES_IN_GRANULE: usize = 1 << LOG_BYTES_IN_GRANULE;
+
+/// A count that reaches this value is stuck. It is neither incremented nor decremented any more,
+/// and the object can only be reclaimed by a tracing GC.
+pub const STUCK_COUNT: u8 = u8::MAX;
+
+/// The side metadata for the counts: one byte for each granule.
+pub const RC_COUNT_SPEC: SideMetadataSpec = SideMetadataSpec::new(
+    "rc count",
+    PIN_SIDE_METADATA_SPEC.next_offset(),
+    LOG_MAX_NUM_OF_BITS,
+    LOG_BYTES_IN_GRANULE,
+);
+
+/// A table with one byte of reference count for each granule. The count of an object is kept
+/// for the granule where its cell starts. The counts are in side metadata, which needs to be
+/// mapped for the spaces of the counted objects (see `CommonSpace::side_metadata_specs`).
+pub struct RCTable {
+    spec: SideMetadataSpec,
+}
+
+impl RCTable {
+    pub const fn new(spec: SideMetadataSpec) -> Self {
+        RCTable { spec }
+    }
+
+    /// The side metadata of the counts.
+    pub fn spec(&self) -> SideMetadataSpec {
+        self.spec
+    }
+
+    pub fn count(&self, addr: Address) -> u8 {
+        self.spec.load(addr)
+    }
+
+    /// Increment the count. Returns the old count.
+    #[inline]
+    pub fn inc(&self, addr: Address) -> u8 {
+        self.spec
+            .fetch_update(addr, |count| {
+                if count == STUCK_COUNT {
+                    None
+                } else {
+                    Some(count + 1)
+                }
+            })
+            .unwrap_or_else(|count| count)
+    }
+
+    /// Decrement the count. Returns the old count, so the object is dead if this returns 1.
+    /// Stuck counts and zero counts are not changed.
+    #[inline]
+    pub fn dec(&self, addr: Address) -> u8 {
+        self.spec
+            .fetch_update(addr, |count| {
+                if count == STUCK_COUNT || count == 0 {
+                    None
+                } else {
+                    Some(count - 1)
+                }
+            })
+            .unwrap_or_else(|count| count)
+    }
+
+    /// Reset the count to zero.
+    pub fn clear(&self, addr: Address) {
+        self.spec.store(addr, 0);
+    }
+
+    /// Reset the counts of the addresses from `start` to `end` to zero.
+    pub fn clear_range(&self, start: Address, end: Address) {
+        self.spec.bzero(start, end - start);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::heap_layout::Mmapper;
+    use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+    use crate::util::test_util::serial_test;
+
+    fn rc_table() -> (RCTable, Address) {
+        let table = RCTable::new(RC_COUNT_SPEC);
+        let start = HEAP_START + (1usize << 22);
+        table.spec().ensure_mapped(&Mmapper::new(), start, 1 << 20);
+        (table, start)
+    }
+
+    #[test]
+    fn test_inc_dec() {
+        serial_test(|| {
+            let (table, start) = rc_table();
+            let cell = start + 64usize;
+            assert_eq!(table.inc(cell), 0);
+            assert_eq!(table.inc(cell), 1);
+            assert_eq!(table.count(cell), 2);
+            assert_eq!(table.count(cell + BYTES_IN_GRANULE), 0);
+            assert_eq!(table.dec(cell), 2);
+            assert_eq!(table.dec(cell), 1);
+            assert_eq!(table.dec(cell), 0);
+            assert_eq!(table.count(cell), 0);
+            table.inc(cell);
+            table.inc(cell + BYTES_IN_GRANULE);
+            table.clear_range(start, start + 128usize);
+            assert_eq!(table.count(cell), 0);
+            assert_eq!(table.count(cell + BYTES_IN_GRANULE), 0);
+        })
+    }
+
+    #[test]
+    fn test_stuck_count() {
+        serial_test(|| {
+            let (table, start) = rc_table();
+            for _ in 0..STUCK_COUNT {
+                table.inc(start);
+            }
+            assert_eq!(table.inc(start), STUCK_COUNT);
+            assert_eq!(table.dec(start), STUCK_COUNT);
+            assert_eq!(table.count(start), STUCK_COUNT);
+            table.clear(start);
+            assert_eq!(table.count(start), 0);
+        })
+    }
+}
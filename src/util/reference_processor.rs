@@ -1,12 +1,15 @@
 use std::cell::UnsafeCell;
+use std::marker::PhantomData;
 use std::sync::Mutex;
 use std::vec::Vec;
 
-use crate::plan::{MutatorContext, TraceLocal};
+use crate::scheduler::gc_works::ProcessEdgesWork;
+use crate::scheduler::{GCWork, GCWorker};
 use crate::util::OpaquePointer;
 use crate::util::{Address, ObjectReference};
+use crate::vm::ReferenceGlue;
 use crate::vm::VMBinding;
-use crate::vm::{ActivePlan, ReferenceGlue};
+use crate::MMTK;
 
 pub struct ReferenceProcessors {
     soft: ReferenceProcessor,
@@ -61,27 +64,51 @@ impl ReferenceProcessors {
         self.phantom.add_candidate::<VM>(reff, referent);
     }
 
-    pub fn forward_refs<VM: VMBinding, T: TraceLocal>(&self, trace: &mut T) {
-        self.soft.forward::<VM, T>(trace, false);
-        self.weak.forward::<VM, T>(trace, false);
-        self.phantom.forward::<VM, T>(trace, false);
+    pub fn forward_refs<E: ProcessEdgesWork>(&self, trace: &mut E) {
+        self.soft.forward::<E>(trace);
+        self.weak.forward::<E>(trace);
+        self.phantom.forward::<E>(trace);
     }
 
-    pub fn scan_weak_refs<VM: VMBinding, T: TraceLocal>(&self, trace: &mut T, tls: OpaquePointer) {
-        self.soft.scan::<VM, T>(trace, false, false, tls);
-        self.weak.scan::<VM, T>(trace, false, false, tls);
+    /// Retain the referents of the (so far) reachable soft references, unless we are running out
+    /// of memory. The closure from the retained referents needs to finish before `scan_soft_refs()`.
+    pub fn retain_soft_refs<E: ProcessEdgesWork>(&self, trace: &mut E, mmtk: &'static MMTK<E::VM>) {
+        if !mmtk.plan.is_emergency_collection() {
+            self.soft
+                .scan::<E>(trace, mmtk.plan.scan_new_references_only(), true);
+        }
+    }
+
+    pub fn scan_soft_refs<E: ProcessEdgesWork>(&self, trace: &mut E, mmtk: &'static MMTK<E::VM>) {
+        self.soft
+            .scan::<E>(trace, mmtk.plan.scan_new_references_only(), false);
     }
 
-    pub fn scan_soft_refs<VM: VMBinding, T: TraceLocal>(&self, trace: &mut T, tls: OpaquePointer) {
-        self.soft.scan::<VM, T>(trace, false, false, tls);
+    pub fn scan_weak_refs<E: ProcessEdgesWork>(&self, trace: &mut E, mmtk: &'static MMTK<E::VM>) {
+        self.weak
+            .scan::<E>(trace, mmtk.plan.scan_new_references_only(), false);
     }
 
-    pub fn scan_phantom_refs<VM: VMBinding, T: TraceLocal>(
+    pub fn scan_phantom_refs<E: ProcessEdgesWork>(
         &self,
-        trace: &mut T,
-        tls: OpaquePointer,
+        trace: &mut E,
+        mmtk: &'static MMTK<E::VM>,
     ) {
-        self.phantom.scan::<VM, T>(trace, false, false, tls);
+        self.phantom
+            .scan::<E>(trace, mmtk.plan.scan_new_references_only(), false);
+    }
+
+    /// Call `f` on each reference object in the tables.
+    pub fn for_each_reference(&self, f: &mut dyn FnMut(ObjectReference)) {
+        self.soft.for_each_reference(f);
+        self.weak.for_each_reference(f);
+        self.phantom.for_each_reference(f);
+    }
+
+    pub fn enqueue_refs<VM: VMBinding>(&self, tls: OpaquePointer) {
+        self.soft.enqueue::<VM>(tls);
+        self.weak.enqueue::<VM>(tls);
+        self.phantom.enqueue::<VM>(tls);
     }
 }
 
@@ -137,11 +164,10 @@ struct ReferenceProcessorSync {
     references: Vec<Address>,
 
     /**
-     * In a MarkCompact (or similar) collector, we need to update the {@code references}
-     * field, and then update its contents.  We implement this by saving the pointer in
-     * this untraced field for use during the {@code forward} pass.
+     * The references whose referents were cleared in this GC. They are handed to the VM
+     * (see `ReferenceGlue::enqueue_references()`) at the end of the GC.
      */
-    unforwarded_references: Option<Vec<Address>>,
+    enqueued_references: Vec<ObjectReference>,
 
     /**
      * Index into the <code>references</code> table for the start of
@@ -155,7 +181,7 @@ impl ReferenceProcessor {
         ReferenceProcessor {
             sync: UnsafeCell::new(Mutex::new(ReferenceProcessorSync {
                 references: Vec::with_capacity(INITIAL_SIZE),
-                unforwarded_references: None,
+                enqueued_references: vec![],
                 nursery_index: 0,
             })),
             semantics,
@@ -177,7 +203,7 @@ impl ReferenceProcessor {
     pub fn clear(&self) {
         let mut sync = self.sync().lock().unwrap();
         sync.references.clear();
-        sync.unforwarded_references = None;
+        sync.enqueued_references.clear();
         sync.nursery_index = 0;
     }
 
@@ -187,56 +213,63 @@ impl ReferenceProcessor {
         sync.references.push(reff.to_address());
     }
 
-    pub fn forward<VM: VMBinding, T: TraceLocal>(&self, trace: &mut T, _nursery: bool) {
-        let mut sync = unsafe { self.sync_mut() };
-        let references: &mut Vec<Address> = &mut sync.references;
-        // XXX: Copies `unforwarded_references` out. Should be fine since it's not accessed
-        //      concurrently & it's set to `None` at the end anyway..
-        let mut unforwarded_references: Vec<Address> = sync.unforwarded_references.clone().unwrap();
+    pub fn for_each_reference(&self, f: &mut dyn FnMut(ObjectReference)) {
+        let sync = self.sync().lock().unwrap();
+        for addr in sync.references.iter() {
+            f(unsafe { addr.to_object_reference() });
+        }
+    }
+
+    /**
+     * Forward the references and their referents. This is only needed by collectors
+     * that determine liveness before computing the new addresses of objects (e.g. MarkCompact).
+     * For other collectors, `scan()` already updates the references.
+     */
+    pub fn forward<E: ProcessEdgesWork>(&self, trace: &mut E) {
+        let sync = unsafe { self.sync_mut() };
         if TRACE {
             trace!("Starting ReferenceProcessor.forward({:?})", self.semantics);
         }
         if TRACE_DETAIL {
-            trace!("{:?} Reference table is {:?}", self.semantics, references);
             trace!(
-                "{:?} unforwardedReferences is {:?}",
+                "{:?} Reference table is {:?}",
                 self.semantics,
-                unforwarded_references
+                sync.references
             );
         }
 
-        for (i, unforwarded_ref) in unforwarded_references
-            .iter_mut()
-            .enumerate()
-            .take(references.len())
-        {
-            let reference = unsafe { unforwarded_ref.to_object_reference() };
+        for (i, slot) in sync.references.iter_mut().enumerate() {
+            let reference = unsafe { slot.to_object_reference() };
             if TRACE_DETAIL {
                 trace!("slot {:?}: forwarding {:?}", i, reference);
             }
-            VM::VMReferenceGlue::set_referent(
+            let referent = <E::VM as VMBinding>::VMReferenceGlue::get_referent(reference);
+            <E::VM as VMBinding>::VMReferenceGlue::set_referent(
                 reference,
-                trace.get_forwarded_referent(VM::VMReferenceGlue::get_referent(reference)),
+                trace.trace_object(referent),
             );
-            let new_reference = trace.get_forwarded_reference(reference);
-            *unforwarded_ref = new_reference.to_address();
+            *slot = trace.trace_object(reference).to_address();
+        }
+        for reference in sync.enqueued_references.iter_mut() {
+            *reference = trace.trace_object(*reference);
         }
 
         if TRACE {
             trace!("Ending ReferenceProcessor.forward({:?})", self.semantics)
         }
-        sync.unforwarded_references = None;
     }
 
-    fn scan<VM: VMBinding, T: TraceLocal>(
-        &self,
-        trace: &mut T,
-        nursery: bool,
-        retain: bool,
-        tls: OpaquePointer,
-    ) {
+    /**
+     * Scan the references. Dead references are dropped from the table, and references
+     * with unreachable referents get their referents cleared and are enqueued. Reachable
+     * references and referents are forwarded.
+     * @param trace the trace used to get the new addresses of objects.
+     * @param nursery only scan the references added since the last GC.
+     * @param retain retain the referents of reachable references (soft references only)
+     * instead of processing the references.
+     */
+    fn scan<E: ProcessEdgesWork>(&self, trace: &mut E, nursery: bool, retain: bool) {
         let sync = unsafe { self.sync_mut() };
-        sync.unforwarded_references = Some(sync.references.clone());
         let references: &mut Vec<Address> = &mut sync.references;
 
         if TRACE {
@@ -251,14 +284,15 @@ impl ReferenceProcessor {
         if retain {
             for addr in references.iter().skip(from_index) {
                 let reference = unsafe { addr.to_object_reference() };
-                self.retain_referent::<VM, T>(trace, reference);
+                self.retain_referent::<E>(trace, reference);
             }
         } else {
             for i in from_index..references.len() {
                 let reference = unsafe { references[i].to_object_reference() };
 
                 /* Determine liveness (and forward if necessary) the reference */
-                let new_reference = VM::VMReferenceGlue::process_reference(trace, reference, tls);
+                let new_reference =
+                    self.process_reference(trace, reference, &mut sync.enqueued_references);
                 if !new_reference.is_null() {
                     references[to_index] = new_reference.to_address();
                     to_index += 1;
@@ -283,15 +317,99 @@ impl ReferenceProcessor {
             references.truncate(to_index);
         }
 
-        /* flush out any remset entries generated during the above activities */
-        unsafe {
-            VM::VMActivePlan::mutator(tls).flush_remembered_sets();
-        }
         if TRACE {
             trace!("Ending ReferenceProcessor.scan({:?})", self.semantics);
         }
     }
 
+    /**
+     * Process a reference with the current semantics.
+     * @param reference the reference to process.
+     * @param enqueued_references the references whose referents are cleared are pushed to this.
+     * @return the updated reference if it is still alive and has a live referent, or a null reference
+     * if it should be removed from the table.
+     */
+    fn process_reference<E: ProcessEdgesWork>(
+        &self,
+        trace: &mut E,
+        reference: ObjectReference,
+        enqueued_references: &mut Vec<ObjectReference>,
+    ) -> ObjectReference {
+        debug_assert!(!reference.is_null());
+
+        if TRACE_DETAIL {
+            trace!("Processing reference: {:?}", reference);
+        }
+
+        /*
+         * If the reference is dead, we're done with it. Let it (and
+         * possibly its referent) be garbage-collected.
+         */
+        if !trace.is_object_live(reference) {
+            <E::VM as VMBinding>::VMReferenceGlue::clear_referent(reference);
+            if TRACE_UNREACHABLE {
+                trace!(" UNREACHABLE reference: {:?}", reference);
+            }
+            return unsafe { Address::zero().to_object_reference() };
+        }
+
+        /* The reference object is live */
+        let new_reference = trace.trace_object(reference);
+        let old_referent = <E::VM as VMBinding>::VMReferenceGlue::get_referent(reference);
+
+        /*
+         * If the application has cleared the referent the Java spec says
+         * this does not cause the Reference object to be enqueued. We
+         * simply allow the Reference object to fall out of our
+         * waiting list.
+         */
+        if old_referent.is_null() {
+            if TRACE_DETAIL {
+                trace!(" (cleared referent)");
+            }
+            return unsafe { Address::zero().to_object_reference() };
+        }
+
+        if trace.is_object_live(old_referent) {
+            /*
+             * Referent is still reachable in a way that is as strong as
+             * or stronger than the current reference level.
+             */
+            let new_referent = trace.trace_object(old_referent);
+            if TRACE_DETAIL {
+                trace!(" ~> {:?}", new_referent);
+            }
+            <E::VM as VMBinding>::VMReferenceGlue::set_referent(new_reference, new_referent);
+            new_reference
+        } else {
+            /* Referent is unreachable. Clear the referent and enqueue the reference object. */
+            if TRACE_DETAIL {
+                trace!(" UNREACHABLE referent: {:?}", old_referent);
+            }
+            <E::VM as VMBinding>::VMReferenceGlue::clear_referent(new_reference);
+            enqueued_references.push(new_reference);
+            unsafe { Address::zero().to_object_reference() }
+        }
+    }
+
+    /**
+     * Hand the references whose referents were cleared in this GC to the VM.
+     */
+    pub fn enqueue<VM: VMBinding>(&self, tls: OpaquePointer) {
+        let sync = unsafe { self.sync_mut() };
+        if !sync.enqueued_references.is_empty() {
+            if TRACE {
+                trace!(
+                    "Enqueuing {} {:?} references",
+                    sync.enqueued_references.len(),
+                    self.semantics
+                );
+            }
+            VM::VMReferenceGlue::enqueue_references(&sync.enqueued_references, tls);
+            sync.enqueued_references.clear();
+        }
+    }
+
     /**
      * This method deals only with soft references. It retains the referent
      * if the reference is definitely reachable.
@@ -299,11 +417,7 @@ impl ReferenceProcessor {
      * be the address of a heap object, depending on the VM.
      * @param trace the thread local trace element.
      */
-    fn retain_referent<VM: VMBinding, T: TraceLocal>(
-        &self,
-        trace: &mut T,
-        reference: ObjectReference,
-    ) {
+    fn retain_referent<E: ProcessEdgesWork>(&self, trace: &mut E, reference: ObjectReference) {
         debug_assert!(!reference.is_null());
         debug_assert!(self.semantics == Semantics::SOFT);
 
@@ -311,7 +425,7 @@ impl ReferenceProcessor {
             trace!("Processing reference: {:?}", reference);
         }
 
-        if !reference.is_live() {
+        if !trace.is_object_live(reference) {
             /*
              * Reference is currently unreachable but may get reachable by the
              * following trace. We postpone the decision.
@@ -322,12 +436,128 @@ impl ReferenceProcessor {
         /*
          * Reference is definitely reachable.  Retain the referent.
          */
-        let referent = VM::VMReferenceGlue::get_referent(reference);
+        let referent = <E::VM as VMBinding>::VMReferenceGlue::get_referent(reference);
         if !referent.is_null() {
-            trace.retain_referent(referent);
+            trace.trace_object(referent);
         }
         if TRACE_DETAIL {
             trace!(" ~> {:?} (retained)", referent.to_address());
         }
     }
 }
+
+/// Create a `ProcessEdgesWork` to trace (and forward) objects for reference processing,
+/// run `f` with it, and flush the objects it has traced.
+pub(crate) fn with_trace<E: ProcessEdgesWork>(
+    worker: &mut GCWorker<E::VM>,
+    mmtk: &'static MMTK<E::VM>,
+    f: impl FnOnce(&mut E),
+) {
+    let mut w = E::new(vec![], false);
+    w.mmtk = Some(mmtk);
+    w.set_worker(worker);
+    f(&mut w);
+    if !w.nodes.is_empty() {
+        w.flush();
+    }
+}
+
+#[derive(Default)]
+pub struct SoftRefRetention<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> SoftRefRetention<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for SoftRefRetention<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("SoftRefRetention");
+        with_trace::<E>(worker, mmtk, |w| {
+            mmtk.reference_processors.retain_soft_refs(w, mmtk)
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct SoftRefProcessing<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> SoftRefProcessing<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for SoftRefProcessing<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("SoftRefProcessing");
+        with_trace::<E>(worker, mmtk, |w| {
+            mmtk.reference_processors.scan_soft_refs(w, mmtk)
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct WeakRefProcessing<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> WeakRefProcessing<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for WeakRefProcessing<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("WeakRefProcessing");
+        with_trace::<E>(worker, mmtk, |w| {
+            mmtk.reference_processors.scan_weak_refs(w, mmtk)
+        });
+    }
+}
+
+#[derive(Default)]
+pub struct PhantomRefProcessing<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> PhantomRefProcessing<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for PhantomRefProcessing<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("PhantomRefProcessing");
+        with_trace::<E>(worker, mmtk, |w| {
+            mmtk.reference_processors.scan_phantom_refs(w, mmtk)
+        });
+    }
+}
+
+/// Forward the references for collectors that compute new object addresses
+/// after the liveness trace (see `PlanConstraints::needs_forward_after_liveness`).
+#[derive(Default)]
+pub struct RefForwarding<E: ProcessEdgesWork>(PhantomData<E>);
+
+impl<E: ProcessEdgesWork> RefForwarding<E> {
+    pub fn new() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<E: ProcessEdgesWork> GCWork<E::VM> for RefForwarding<E> {
+    fn do_work(&mut self, worker: &mut GCWorker<E::VM>, mmtk: &'static MMTK<E::VM>) {
+        trace!("RefForwarding");
+        with_trace::<E>(worker, mmtk, |w| mmtk.reference_processors.forward_refs(w));
+    }
+}
+
+#[derive(Default)]
+pub struct RefEnqueue;
+
+impl<VM: VMBinding> GCWork<VM> for RefEnqueue {
+    fn do_work(&mut self, worker: &mut GCWorker<VM>, mmtk: &'static MMTK<VM>) {
+        trace!("RefEnqueue");
+        mmtk.reference_processors.enqueue_refs::<VM>(worker.tls);
+    }
+}
@@ -37,19 +37,19 @@ impl<VM: VMBinding> GCWork<VM> for ScheduleSanityGC {
     }
 }
 
-pub struct SanityPrepare<P: Plan> {
+pub struct SanityPrepare<P: Plan + ?Sized> {
     pub plan: &'static P,
 }
 
-unsafe impl<P: Plan> Sync for SanityPrepare<P> {}
+unsafe impl<P: Plan + ?Sized> Sync for SanityPrepare<P> {}
 
-impl<P: Plan> SanityPrepare<P> {
+impl<P: Plan + ?Sized> SanityPrepare<P> {
     pub fn new(plan: &'static P) -> Self {
         Self { plan }
     }
 }
 
-impl<P: Plan> GCWork<P::VM> for SanityPrepare<P> {
+impl<P: Plan + ?Sized> GCWork<P::VM> for SanityPrepare<P> {
     fn do_work(&mut self, _worker: &mut GCWorker<P::VM>, mmtk: &'static MMTK<P::VM>) {
         mmtk.plan.enter_sanity();
         {
@@ -67,19 +67,19 @@ impl<P: Plan> GCWork<P::VM> for SanityPrepare<P> {
     }
 }
 
-pub struct SanityRelease<P: Plan> {
+pub struct SanityRelease<P: Plan + ?Sized> {
     pub plan: &'static P,
 }
 
-unsafe impl<P: Plan> Sync for SanityRelease<P> {}
+unsafe impl<P: Plan + ?Sized> Sync for SanityRelease<P> {}
 
-impl<P: Plan> SanityRelease<P> {
+impl<P: Plan + ?Sized> SanityRelease<P> {
     pub fn new(plan: &'static P) -> Self {
         Self { plan }
     }
 }
 
-impl<P: Plan> GCWork<P::VM> for SanityRelease<P> {
+impl<P: Plan + ?Sized> GCWork<P::VM> for SanityRelease<P> {
     fn do_work(&mut self, _worker: &mut GCWorker<P::VM>, mmtk: &'static MMTK<P::VM>) {
         mmtk.plan.leave_sanity();
         for mutator in <P::VM as VMBinding>::VMActivePlan::mutators() {
@@ -0,0 +1,448 @@
+//! Side metadata: per-region bits that are kept in tables outside of the objects.
+//!
+//! A side metadata is described by a `SideMetadataSpec`, which gives the number of bits for each
+//! region of `1 << log_bytes_in_region` bytes. The metadata of a spec covers the whole heap range,
+//! and is laid out contiguously from `SIDE_METADATA_BASE_ADDRESS + offset`, so the metadata of an
+//! address is found with a shift and an add. The metadata is only mapped for the chunks that are
+//! used by the spaces: a space maps the metadata of its specs when it acquires new chunks (see
+//! `Space::grow_space()`). The specs in `HeapMeta::side_metadata_specs` are mapped by all the
+//! spaces, and a space can map more specs with `CommonSpace::side_metadata_specs`.
+//!
+//! A plan can keep a bit either in the GC byte of the object header or on the side, and use
+//! `MetadataSpec` to access the bit in the same way.
+
+use crate::util::constants::{BITS_IN_BYTE, BYTES_IN_PAGE, LOG_BITS_IN_BYTE, LOG_BYTES_IN_PAGE};
+use crate::util::heap::layout::vm_layout_constants::{HEAP_END, HEAP_START, MMAP_CHUNK_BYTES};
+use crate::util::heap::layout::Mmapper;
+use crate::util::memory;
+use crate::util::{Address, ObjectReference};
+use crate::vm::ObjectModel;
+use crate::vm::VMBinding;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Mutex;
+
+/// The side metadata is placed right after the heap range.
+pub const SIDE_METADATA_BASE_ADDRESS: Address = HEAP_END;
+
+/// A region has at most `1 << LOG_MAX_NUM_OF_BITS` bits, so the bits of a region never span bytes.
+pub const LOG_MAX_NUM_OF_BITS: usize = 3;
+
+lazy_static! {
+    /// Spaces may map the same metadata pages at the same time, and the mmapper may map a page
+    /// again (and zero it) if it races with another thread. So the metadata is mapped under this lock.
+    static ref MAP_LOCK: Mutex<()> = Mutex::default();
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SideMetadataSpec {
+    pub name: &'static str,
+    /// The offset of the metadata from `SIDE_METADATA_BASE_ADDRESS`. Use `next_offset()` of
+    /// another spec to place the metadata after it.
+    pub offset: usize,
+    /// The number of bits (log2) for each region. At most `LOG_MAX_NUM_OF_BITS`.
+    pub log_num_of_bits: usize,
+    /// The size of a region (log2), e.g. the minimal object alignment for per-object metadata.
+    pub log_bytes_in_region: usize,
+}
+
+impl SideMetadataSpec {
+    pub const fn new(
+        name: &'static str,
+        offset: usize,
+        log_num_of_bits: usize,
+        log_bytes_in_region: usize,
+    ) -> Self {
+        SideMetadataSpec {
+            name,
+            offset,
+            log_num_of_bits,
+            log_bytes_in_region,
+        }
+    }
+
+    /// The number of heap bytes (log2) covered by one byte of metadata.
+    #[inline(always)]
+    const fn log_bytes_per_meta_byte(&self) -> usize {
+        self.log_bytes_in_region + LOG_BITS_IN_BYTE as usize - self.log_num_of_bits
+    }
+
+    /// The size of the metadata for the whole heap range.
+    pub const fn metadata_bytes(&self) -> usize {
+        HEAP_END.get_extent(HEAP_START) >> self.log_bytes_per_meta_byte()
+    }
+
+    /// The offset right after the metadata of this spec, aligned to mmap chunks.
+    pub const fn next_offset(&self) -> usize {
+        (self.offset + self.metadata_bytes() + MMAP_CHUNK_BYTES - 1) & !(MMAP_CHUNK_BYTES - 1)
+    }
+
+    #[inline(always)]
+    fn meta_byte_address(&self, addr: Address) -> Address {
+        debug_assert!(self.log_num_of_bits <= LOG_MAX_NUM_OF_BITS);
+        debug_assert!(
+            addr >= HEAP_START && addr < HEAP_END,
+            "{} is out of the heap range for side metadata {}",
+            addr,
+            self.name
+        );
+        SIDE_METADATA_BASE_ADDRESS
+            + self.offset
+            + ((addr - HEAP_START) >> self.log_bytes_per_meta_byte())
+    }
+
+    #[inline(always)]
+    fn meta_byte(&self, addr: Address) -> &AtomicU8 {
+        unsafe { &*self.meta_byte_address(addr).to_ptr::<AtomicU8>() }
+    }
+
+    #[inline(always)]
+    fn meta_bit_shift(&self, addr: Address) -> usize {
+        (((addr - HEAP_START) >> self.log_bytes_in_region) << self.log_num_of_bits)
+            & (BITS_IN_BYTE - 1)
+    }
+
+    #[inline(always)]
+    fn mask(&self) -> u8 {
+        ((1u16 << (1 << self.log_num_of_bits)) - 1) as u8
+    }
+
+    /// Map the metadata for the addresses from `start` to `start + bytes`, if it is not mapped yet.
+    pub fn ensure_mapped(&self, mmapper: &dyn Mmapper, start: Address, bytes: usize) {
+        let meta_bytes = (bytes >> self.log_bytes_per_meta_byte()).max(1);
+        let meta_start = self.meta_byte_address(start).align_down(BYTES_IN_PAGE);
+        let meta_end = (self.meta_byte_address(start) + meta_bytes).align_up(BYTES_IN_PAGE);
+        let _lock = MAP_LOCK.lock().unwrap();
+        mmapper.ensure_mapped(meta_start, (meta_end - meta_start) >> LOG_BYTES_IN_PAGE);
+    }
+
+    /// Clear the metadata for the addresses from `start` to `start + bytes`. The whole bytes of
+    /// metadata are zeroed, and the regions at both ends that share bytes with other regions are
+    /// cleared one by one.
+    pub fn bzero(&self, start: Address, bytes: usize) {
+        let bytes_in_region = 1 << self.log_bytes_in_region;
+        let bytes_per_meta_byte = 1 << self.log_bytes_per_meta_byte();
+        debug_assert!(start.is_aligned_to(bytes_in_region));
+        let end = start + bytes;
+        let mut cursor = start;
+        while cursor < end && !cursor.is_aligned_to(bytes_per_meta_byte) {
+            self.store(cursor, 0);
+            cursor += bytes_in_region;
+        }
+        let whole_bytes_end = end.align_down(bytes_per_meta_byte);
+        if whole_bytes_end > cursor {
+            memory::zero(
+                self.meta_byte_address(cursor),
+                (whole_bytes_end - cursor) >> self.log_bytes_per_meta_byte(),
+            );
+            cursor = whole_bytes_end;
+        }
+        while cursor < end {
+            self.store(cursor, 0);
+            cursor += bytes_in_region;
+        }
+    }
+
+    #[inline]
+    pub fn load(&self, addr: Address) -> u8 {
+        (self.meta_byte(addr).load(Ordering::SeqCst) >> self.meta_bit_shift(addr)) & self.mask()
+    }
+
+    #[inline]
+    pub fn store(&self, addr: Address, value: u8) {
+        if self.log_num_of_bits == LOG_MAX_NUM_OF_BITS {
+            self.meta_byte(addr).store(value, Ordering::SeqCst);
+        } else {
+            let _ = self.fetch_update(addr, |_| Some(value));
+        }
+    }
+
+    /// Atomically update the metadata with `f`, in the same way as `AtomicU8::fetch_update()`.
+    /// Returns `Ok(old_value)` if `f` returns a new value, and `Err(old_value)` otherwise.
+    #[inline]
+    pub fn fetch_update<F: FnMut(u8) -> Option<u8>>(&self, addr: Address, f: F) -> Result<u8, u8> {
+        update_bits(
+            self.meta_byte(addr),
+            self.meta_bit_shift(addr),
+            self.mask(),
+            f,
+        )
+    }
+
+    /// Set the metadata to `new` if it is `old`. Returns `Ok(old)` if it succeeds, and
+    /// `Err(current_value)` otherwise.
+    #[inline]
+    pub fn compare_exchange(&self, addr: Address, old: u8, new: u8) -> Result<u8, u8> {
+        self.fetch_update(addr, |value| if value == old { Some(new) } else { None })
+    }
+
+    /// Bitwise or the metadata with `value`. Returns the old value.
+    #[inline]
+    pub fn fetch_or(&self, addr: Address, value: u8) -> u8 {
+        self.fetch_update(addr, |old| Some(old | value))
+            .unwrap_or_else(|old| old)
+    }
+
+    /// Bitwise and the metadata with `value`. Returns the old value.
+    #[inline]
+    pub fn fetch_and(&self, addr: Address, value: u8) -> u8 {
+        self.fetch_update(addr, |old| Some(old & value))
+            .unwrap_or_else(|old| old)
+    }
+
+    /// Find the last region that has non-zero metadata, from the region of `addr` back to the
+    /// first region that starts at or after `start`. Returns the start of the region. The metadata
+    /// is read a byte at a time, and the regions of a zero byte are skipped at once.
+    pub fn find_prev_non_zero(&self, addr: Address, start: Address) -> Option<Address> {
+        let bytes_in_region = 1 << self.log_bytes_in_region;
+        let bytes_per_meta_byte = 1 << self.log_bytes_per_meta_byte();
+        let mut cursor = addr.align_down(bytes_in_region);
+        while cursor >= start {
+            if self.meta_byte(cursor).load(Ordering::SeqCst) == 0 {
+                // Skip the other regions of the byte
+                cursor = cursor.align_down(bytes_per_meta_byte);
+            } else if self.load(cursor) != 0 {
+                return Some(cursor);
+            }
+            if cursor <= start {
+                break;
+            }
+            cursor -= bytes_in_region;
+        }
+        None
+    }
+}
+
+/// Atomically update the bits at `shift` with `mask` of a byte.
+#[inline(always)]
+fn update_bits<F: FnMut(u8) -> Option<u8>>(
+    byte: &AtomicU8,
+    shift: usize,
+    mask: u8,
+    mut f: F,
+) -> Result<u8, u8> {
+    byte.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |old| {
+        f((old >> shift) & mask).map(|new| (old & !(mask << shift)) | ((new & mask) << shift))
+    })
+    .map(|old| (old >> shift) & mask)
+    .map_err(|old| (old >> shift) & mask)
+}
+
+/// Where a piece of per-object metadata is kept. A plan can choose for each of its bits (e.g. mark
+/// bits, log bits or forwarding states) whether it is kept in the header or on the side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataSpec {
+    /// In the GC byte of the object header (see `ObjectModel::get_gc_byte()`), starting from `bit_offset`.
+    InHeader {
+        bit_offset: usize,
+        num_of_bits: usize,
+    },
+    /// In side metadata, for the region of the object reference. The spec needs to be mapped for
+    /// the spaces of the objects.
+    OnSide(SideMetadataSpec),
+}
+
+impl MetadataSpec {
+    #[inline]
+    pub fn load<VM: VMBinding>(&self, object: ObjectReference) -> u8 {
+        match self {
+            MetadataSpec::InHeader {
+                bit_offset,
+                num_of_bits,
+            } => {
+                (VM::VMObjectModel::get_gc_byte(object).load(Ordering::SeqCst) >> bit_offset)
+                    & header_mask(*num_of_bits)
+            }
+            MetadataSpec::OnSide(spec) => spec.load(object.to_address()),
+        }
+    }
+
+    #[inline]
+    pub fn store<VM: VMBinding>(&self, object: ObjectReference, value: u8) {
+        let _ = self.fetch_update::<VM, _>(object, |_| Some(value));
+    }
+
+    /// Atomically update the metadata with `f`. See `SideMetadataSpec::fetch_update()`.
+    #[inline]
+    pub fn fetch_update<VM: VMBinding, F: FnMut(u8) -> Option<u8>>(
+        &self,
+        object: ObjectReference,
+        f: F,
+    ) -> Result<u8, u8> {
+        match self {
+            MetadataSpec::InHeader {
+                bit_offset,
+                num_of_bits,
+            } => update_bits(
+                VM::VMObjectModel::get_gc_byte(object),
+                *bit_offset,
+                header_mask(*num_of_bits),
+                f,
+            ),
+            MetadataSpec::OnSide(spec) => spec.fetch_update(object.to_address(), f),
+        }
+    }
+
+    #[inline]
+    pub fn compare_exchange<VM: VMBinding>(
+        &self,
+        object: ObjectReference,
+        old: u8,
+        new: u8,
+    ) -> Result<u8, u8> {
+        self.fetch_update::<VM, _>(object, |value| if value == old { Some(new) } else { None })
+    }
+
+    #[inline]
+    pub fn fetch_or<VM: VMBinding>(&self, object: ObjectReference, value: u8) -> u8 {
+        self.fetch_update::<VM, _>(object, |old| Some(old | value))
+            .unwrap_or_else(|old| old)
+    }
+
+    #[inline]
+    pub fn fetch_and<VM: VMBinding>(&self, object: ObjectReference, value: u8) -> u8 {
+        self.fetch_update::<VM, _>(object, |old| Some(old & value))
+            .unwrap_or_else(|old| old)
+    }
+}
+
+#[inline(always)]
+fn header_mask(num_of_bits: usize) -> u8 {
+    ((1u16 << num_of_bits) - 1) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::heap::layout::heap_layout::Mmapper as MmapperImpl;
+    use crate::util::test_util::mock_vm::{alloc_object, MockObjectModel, MockVM};
+    use crate::util::test_util::serial_test;
+
+    const TEST_BYTES: usize = 1 << 20;
+
+    #[test]
+    fn test_spec_layout() {
+        let spec = SideMetadataSpec::new("test", 0, 0, 3);
+        assert_eq!(spec.metadata_bytes(), HEAP_END.get_extent(HEAP_START) >> 6);
+        assert!(spec.next_offset() >= spec.metadata_bytes());
+        assert_eq!(spec.next_offset() % MMAP_CHUNK_BYTES, 0);
+        assert_eq!(spec.mask(), 1);
+        assert_eq!(SideMetadataSpec::new("test", 0, 3, 4).mask(), u8::MAX);
+    }
+
+    #[test]
+    fn test_one_bit_metadata() {
+        serial_test(|| {
+            let spec = SideMetadataSpec::new("one bit", 0, 0, 3);
+            let start = HEAP_START;
+            spec.ensure_mapped(&MmapperImpl::new(), start, TEST_BYTES);
+            assert_eq!(spec.load(start + 8usize), 0);
+            assert_eq!(spec.fetch_or(start + 8usize, 1), 0);
+            assert_eq!(spec.fetch_or(start + 8usize, 1), 1);
+            assert_eq!(spec.load(start), 0);
+            assert_eq!(spec.load(start + 16usize), 0);
+            assert_eq!(spec.compare_exchange(start, 1, 0), Err(0));
+            assert_eq!(spec.compare_exchange(start, 0, 1), Ok(0));
+            assert_eq!(spec.load(start), 1);
+            assert_eq!(spec.fetch_and(start + 8usize, 0), 1);
+            assert_eq!(spec.load(start + 8usize), 0);
+            assert_eq!(spec.load(start), 1);
+            spec.bzero(start, TEST_BYTES);
+            assert_eq!(spec.load(start), 0);
+        })
+    }
+
+    #[test]
+    fn test_multi_bit_metadata() {
+        serial_test(|| {
+            let one_bit = SideMetadataSpec::new("one bit", 0, 0, 3);
+            let spec = SideMetadataSpec::new("two bits", one_bit.next_offset(), 1, 4);
+            let start = HEAP_START + TEST_BYTES;
+            spec.ensure_mapped(&MmapperImpl::new(), start, TEST_BYTES);
+            spec.store(start, 3);
+            spec.store(start + 16usize, 2);
+            assert_eq!(spec.load(start), 3);
+            assert_eq!(spec.load(start + 16usize), 2);
+            assert_eq!(spec.load(start + 32usize), 0);
+            assert_eq!(
+                spec.fetch_update(start + 16usize, |value| Some(value + 1)),
+                Ok(2)
+            );
+            assert_eq!(spec.load(start + 16usize), 3);
+            assert_eq!(spec.load(start), 3);
+            spec.bzero(start, 64);
+            assert_eq!(spec.load(start), 0);
+            assert_eq!(spec.load(start + 16usize), 0);
+        })
+    }
+
+    #[test]
+    fn test_bzero_unaligned_range() {
+        serial_test(|| {
+            let spec = SideMetadataSpec::new("one bit", 0, 0, 3);
+            let start = HEAP_START + 2 * TEST_BYTES;
+            spec.ensure_mapped(&MmapperImpl::new(), start, TEST_BYTES);
+            for i in 0..32usize {
+                spec.store(start + i * 8, 1);
+            }
+            // Clear the bits from the second region to the 24th region
+            spec.bzero(start + 8usize, 23 * 8);
+            assert_eq!(spec.load(start), 1);
+            for i in 1..24usize {
+                assert_eq!(spec.load(start + i * 8), 0);
+            }
+            for i in 24..32usize {
+                assert_eq!(spec.load(start + i * 8), 1);
+            }
+        })
+    }
+
+    #[test]
+    fn test_find_prev_non_zero() {
+        serial_test(|| {
+            let spec = SideMetadataSpec::new("one bit", 0, 0, 3);
+            let start = HEAP_START + 3 * TEST_BYTES;
+            spec.ensure_mapped(&MmapperImpl::new(), start, TEST_BYTES);
+            spec.store(start + 8usize, 1);
+            spec.store(start + 1000usize, 1);
+            assert_eq!(
+                spec.find_prev_non_zero(start + 1003usize, start),
+                Some(start + 1000usize)
+            );
+            assert_eq!(
+                spec.find_prev_non_zero(start + 999usize, start),
+                Some(start + 8usize)
+            );
+            assert_eq!(
+                spec.find_prev_non_zero(start + 999usize, start + 16usize),
+                None
+            );
+            assert_eq!(spec.find_prev_non_zero(start + 7usize, start), None);
+            spec.bzero(start, TEST_BYTES);
+        })
+    }
+
+    #[test]
+    fn test_in_header_metadata() {
+        let object = alloc_object(0);
+        MockObjectModel::write_available_byte(object, 0b1000_0001);
+        let spec = MetadataSpec::InHeader {
+            bit_offset: 2,
+            num_of_bits: 2,
+        };
+        assert_eq!(spec.load::<MockVM>(object), 0);
+        spec.store::<MockVM>(object, 0b11);
+        assert_eq!(
+            spec.compare_exchange::<MockVM>(object, 0b10, 0b01),
+            Err(0b11)
+        );
+        assert_eq!(
+            spec.compare_exchange::<MockVM>(object, 0b11, 0b01),
+            Ok(0b11)
+        );
+        assert_eq!(spec.fetch_or::<MockVM>(object, 0b10), 0b01);
+        assert_eq!(spec.fetch_and::<MockVM>(object, 0b10), 0b11);
+        assert_eq!(spec.load::<MockVM>(object), 0b10);
+        // The other bits of the GC byte are kept
+        assert_eq!(MockObjectModel::read_available_byte(object), 0b1000_1001);
+    }
+}
@@ -0,0 +1,302 @@
+//! A log of the GCs for offline analysis. If the `gc_event_log` option is set, a record of each
+//! GC pause is appended to the file as a JSON object on its own line (JSON lines), e.g. (wrapped
+//! here)
+//!
+//! ```text
+//! {"gc":3,"kind":"full","trigger":"allocation","emergency":false,"pause_start_us":1634472000000000,
+//!  "pause_end_us":1634472000001523,"pause_us":1523,"stages_us":{"prepare":31,"closure":1208,...},
+//!  "spaces":[{"name":"ms","pages_before":2048,"pages_after":730},...],"work_packets":412}
+//! ```
+//!
+//! The fields are:
+//!
+//! * `gc`: the number of the pause, starting from 1.
+//! * `kind`: `nursery` or `full`, or `initial_mark` for the initial mark pause of a concurrent plan.
+//! * `trigger`: what requested the GC: `user` (`handle_user_collection_request()`), `internal` (the
+//!   plan itself, e.g. to start or finish concurrent marking) or `allocation` (a failed allocation,
+//!   a full heap or the stress test).
+//! * `emergency`: whether the GC is an emergency collection.
+//! * `pause_start_us` and `pause_end_us`: when the mutators were stopped, and when the GC finished
+//!   (before the mutators are resumed), in microseconds since the Unix epoch. `pause_us` is the
+//!   length of the pause.
+//! * `stages_us`: how long each stop-the-world stage (see `STW_STAGE_NAMES`) was open in the
+//!   pause, in microseconds, i.e. the time from its opening to the opening of the next stage or
+//!   the end of the pause.
+//! * `spaces`: the pages reserved in each space of the plan when the GC was requested, and when it
+//!   finished.
+//! * `work_packets`: the number of work packets executed from the GC request to the end of the
+//!   pause.
+
+use crate::scheduler::GCWorker;
+use crate::vm::VMBinding;
+use crate::MMTK;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The pages reserved in a space before and after a GC.
+#[derive(Debug, PartialEq)]
+pub struct SpacePages {
+    pub name: &'static str,
+    pub pages_before: usize,
+    pub pages_after: usize,
+}
+
+/// The record of a GC pause.
+#[derive(Debug)]
+pub struct GCEvent {
+    pub gc: usize,
+    pub kind: &'static str,
+    pub trigger: &'static str,
+    pub emergency: bool,
+    /// The start of the pause, since the Unix epoch
+    pub pause_start: Duration,
+    /// The end of the pause, since the Unix epoch
+    pub pause_end: Duration,
+    /// The stages opened in the pause, and how long each of them was open
+    pub stages: Vec<(&'static str, Duration)>,
+    pub spaces: Vec<SpacePages>,
+    pub work_packets: usize,
+}
+
+impl GCEvent {
+    /// The record as a JSON object, without a line break.
+    pub fn to_json(&self) -> String {
+        let stages = self
+            .stages
+            .iter()
+            .map(|(name, duration)| format!("{}:{}", json_string(name), duration.as_micros()))
+            .collect::<Vec<_>>()
+            .join(",");
+        let spaces = self
+            .spaces
+            .iter()
+            .map(|space| {
+                format!(
+                    "{{\"name\":{},\"pages_before\":{},\"pages_after\":{}}}",
+                    json_string(space.name),
+                    space.pages_before,
+                    space.pages_after
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(
+            "{{\"gc\":{},\"kind\":{},\"trigger\":{},\"emergency\":{},\"pause_start_us\":{},\"pause_end_us\":{},\"pause_us\":{},\"stages_us\":{{{}}},\"spaces\":[{}],\"work_packets\":{}}}",
+            self.gc,
+            json_string(self.kind),
+            json_string(self.trigger),
+            self.emergency,
+            self.pause_start.as_micros(),
+            self.pause_end.as_micros(),
+            self.pause_end
+                .checked_sub(self.pause_start)
+                .unwrap_or_default()
+                .as_micros(),
+            stages,
+            spaces,
+            self.work_packets
+        )
+    }
+}
+
+/// Quote a string for JSON.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The pages reserved in each space of the plan.
+fn space_pages<VM: VMBinding>(mmtk: &MMTK<VM>) -> Vec<(&'static str, usize)> {
+    let mut pages = vec![];
+    mmtk.plan
+        .for_each_space(&mut |space| pages.push((space.get_name(), space.reserved_pages())));
+    pages
+}
+
+/// What is known about a GC when it is requested.
+struct GCStart {
+    trigger: &'static str,
+    time: Instant,
+    pages_before: Vec<(&'static str, usize)>,
+}
+
+/// Writes the records of the GCs to the file given by the `gc_event_log` option. Nothing is
+/// recorded if the option is empty.
+pub struct GCEventLog {
+    /// The log file, which is opened for the first record
+    file: Mutex<Option<File>>,
+    /// The GC in progress
+    current: Mutex<Option<GCStart>>,
+    gc_count: AtomicUsize,
+}
+
+impl GCEventLog {
+    pub fn new() -> Self {
+        Self {
+            file: Mutex::new(None),
+            current: Mutex::new(None),
+            gc_count: AtomicUsize::new(0),
+        }
+    }
+
+    /// Record the start of a GC. This is called by the coordinator when the GC is scheduled,
+    /// before the plan consumes its collection triggers.
+    pub fn gc_start<VM: VMBinding>(&self, mmtk: &MMTK<VM>, worker: &GCWorker<VM>) {
+        if mmtk.options.gc_event_log.is_empty() {
+            return;
+        }
+        let base = mmtk.plan.base();
+        let trigger = if base.user_triggered_collection.load(Ordering::Relaxed) {
+            "user"
+        } else if base.is_internal_triggered_collection() {
+            "internal"
+        } else {
+            "allocation"
+        };
+        // Only count the works of this GC
+        mmtk.scheduler.take_work_count();
+        worker.stat.take_work_count();
+        *self.current.lock().unwrap() = Some(GCStart {
+            trigger,
+            time: Instant::now(),
+            pages_before: space_pages(mmtk),
+        });
+    }
+
+    /// Write the record of the GC pause that is ending. This is called by the coordinator
+    /// before the mutators are resumed. `kind` is the `kind` field of the record.
+    pub fn gc_end<VM: VMBinding>(
+        &self,
+        mmtk: &MMTK<VM>,
+        worker: &GCWorker<VM>,
+        kind: &'static str,
+    ) {
+        let start = match self.current.lock().unwrap().take() {
+            Some(start) => start,
+            None => return,
+        };
+        let now = Instant::now();
+        let pause_end = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let stage_open_times = mmtk.scheduler.stage_open_times();
+        // The prepare stage is opened when the mutators are stopped
+        let pause_start = stage_open_times
+            .first()
+            .map_or(start.time, |(_, time)| *time);
+        let stages = stage_open_times
+            .iter()
+            .enumerate()
+            .map(|(i, (name, time))| {
+                let end = stage_open_times.get(i + 1).map_or(now, |(_, time)| *time);
+                (*name, end.saturating_duration_since(*time))
+            })
+            .collect();
+        let spaces = start
+            .pages_before
+            .into_iter()
+            .zip(space_pages(mmtk))
+            .map(|((name, pages_before), (_, pages_after))| SpacePages {
+                name,
+                pages_before,
+                pages_after,
+            })
+            .collect();
+        let event = GCEvent {
+            gc: self.gc_count.fetch_add(1, Ordering::Relaxed) + 1,
+            kind,
+            trigger: start.trigger,
+            emergency: mmtk.plan.is_emergency_collection(),
+            pause_start: pause_end
+                .checked_sub(now.saturating_duration_since(pause_start))
+                .unwrap_or_default(),
+            pause_end,
+            stages,
+            spaces,
+            work_packets: mmtk.scheduler.take_work_count() + worker.stat.take_work_count(),
+        };
+        self.write(&mmtk.options.gc_event_log, &event);
+    }
+
+    fn write(&self, path: &str, event: &GCEvent) {
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            match OpenOptions::new().create(true).append(true).open(path) {
+                Ok(f) => *file = Some(f),
+                Err(e) => {
+                    warn!("Cannot open the GC event log {}: {}", path, e);
+                    return;
+                }
+            }
+        }
+        let line = event.to_json() + "\n";
+        if let Err(e) = file.as_mut().unwrap().write_all(line.as_bytes()) {
+            warn!("Cannot write to the GC event log {}: {}", path, e);
+        }
+    }
+}
+
+impl Default for GCEventLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("los"), "\"los\"");
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+
+    #[test]
+    fn test_to_json() {
+        let event = GCEvent {
+            gc: 2,
+            kind: "nursery",
+            trigger: "allocation",
+            emergency: false,
+            pause_start: Duration::from_micros(1_000_000),
+            pause_end: Duration::from_micros(1_001_500),
+            stages: vec![
+                ("prepare", Duration::from_micros(100)),
+                ("closure", Duration::from_micros(1400)),
+            ],
+            spaces: vec![
+                SpacePages {
+                    name: "immortal",
+                    pages_before: 4,
+                    pages_after: 4,
+                },
+                SpacePages {
+                    name: "los",
+                    pages_before: 10,
+                    pages_after: 2,
+                },
+            ],
+            work_packets: 42,
+        };
+        assert_eq!(
+            event.to_json(),
+            "{\"gc\":2,\"kind\":\"nursery\",\"trigger\":\"allocation\",\"emergency\":false,\
+             \"pause_start_us\":1000000,\"pause_end_us\":1001500,\"pause_us\":1500,\
+             \"stages_us\":{\"prepare\":100,\"closure\":1400},\
+             \"spaces\":[{\"name\":\"immortal\",\"pages_before\":4,\"pages_after\":4},\
+             {\"name\":\"los\",\"pages_before\":10,\"pages_after\":2}],\"work_packets\":42}"
+        );
+    }
+}
@@ -2,4 +2,6 @@ pub use self::counter::Counter;
 pub use self::counter::Timer;
 
 pub mod counter;
+pub mod gc_event_log;
 pub mod stats;
+pub mod work_trace;
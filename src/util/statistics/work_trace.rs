@@ -0,0 +1,150 @@
+//! A timeline of the work packets in the Chrome trace event format, which can be loaded into
+//! `chrome://tracing` or Perfetto to see how the works of a GC are spread over the workers (e.g.
+//! load imbalance, idle workers and stalls on the coordinator). If the `work_trace` option is set,
+//! each worker records the start and the end of every work it executes, and the works are
+//! appended to the file at the end of each GC pause. The works executed between the pauses (e.g.
+//! concurrent marking) are written at the end of the next pause.
+//!
+//! The file uses the JSON array format. Each work is a complete event (`"ph":"X"`) named after
+//! the type of the work, on the thread of the worker that executed it: the coordinator is
+//! thread 0, and the GC worker with ordinal `i` is thread `i + 1`. The timestamps are in
+//! microseconds since the MMTk instance was created. The array is never closed, which the trace
+//! viewers accept, so the file stays valid while it grows.
+
+use crate::scheduler::stat::{work_name, WorkTraceEvent};
+use crate::scheduler::GCWorker;
+use crate::util::statistics::gc_event_log::json_string;
+use crate::vm::VMBinding;
+use crate::MMTK;
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The thread of the coordinator in the trace
+const COORDINATOR_TID: usize = 0;
+
+/// Microseconds, with the precision of nanoseconds.
+fn micros(duration: Duration) -> String {
+    format!("{:.3}", duration.as_nanos() as f64 / 1000.0)
+}
+
+/// A work as a complete event. `origin` is the time zero of the trace.
+fn trace_event(event: &WorkTraceEvent, tid: usize, origin: Instant) -> String {
+    format!(
+        "{{\"name\":{},\"cat\":\"work\",\"ph\":\"X\",\"pid\":0,\"tid\":{},\"ts\":{},\"dur\":{}}}",
+        json_string(&work_name(event.name)),
+        tid,
+        micros(event.start.saturating_duration_since(origin)),
+        micros(event.end.saturating_duration_since(event.start))
+    )
+}
+
+/// A metadata event that names a thread in the trace.
+fn thread_name_event(tid: usize, name: &str) -> String {
+    format!(
+        "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":{},\"args\":{{\"name\":{}}}}}",
+        tid,
+        json_string(name)
+    )
+}
+
+/// Writes the works traced by the workers to the file given by the `work_trace` option. Nothing
+/// is written if the option is empty.
+pub struct WorkTrace {
+    /// The time zero of the trace
+    origin: Instant,
+    /// The trace file, which is created at the first write
+    file: Mutex<Option<File>>,
+}
+
+impl WorkTrace {
+    pub fn new() -> Self {
+        Self {
+            origin: Instant::now(),
+            file: Mutex::new(None),
+        }
+    }
+
+    /// Append the works executed since the last call to the file. This is called by the
+    /// coordinator (`coordinator` is its worker) at the end of each GC pause, when the other
+    /// workers are parked.
+    pub fn flush<VM: VMBinding>(&self, mmtk: &MMTK<VM>, coordinator: &GCWorker<VM>) {
+        let path = &mmtk.options.work_trace;
+        if path.is_empty() {
+            return;
+        }
+        let workers = mmtk.scheduler.worker_group();
+        let mut out = String::new();
+        let mut file = self.file.lock().unwrap();
+        if file.is_none() {
+            match File::create(path) {
+                Ok(f) => *file = Some(f),
+                Err(e) => {
+                    warn!("Cannot create the work trace {}: {}", path, e);
+                    return;
+                }
+            }
+            out.push('[');
+            out.push_str(&thread_name_event(COORDINATOR_TID, "Coordinator"));
+            for worker in &workers.workers {
+                out.push_str(",\n");
+                out.push_str(&thread_name_event(
+                    worker.ordinal + 1,
+                    &format!("GC worker {}", worker.ordinal),
+                ));
+            }
+        }
+        let events = coordinator
+            .stat
+            .take_trace()
+            .into_iter()
+            .map(|event| (COORDINATOR_TID, event))
+            .chain(workers.workers.iter().flat_map(|worker| {
+                let tid = worker.ordinal + 1;
+                worker
+                    .stat
+                    .take_trace()
+                    .into_iter()
+                    .map(move |event| (tid, event))
+            }));
+        for (tid, event) in events {
+            out.push_str(",\n");
+            out.push_str(&trace_event(&event, tid, self.origin));
+        }
+        if let Err(e) = file.as_mut().unwrap().write_all(out.as_bytes()) {
+            warn!("Cannot write to the work trace {}: {}", path, e);
+        }
+    }
+}
+
+impl Default for WorkTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_event() {
+        let origin = Instant::now();
+        let event = WorkTraceEvent {
+            name: "mmtk::scheduler::gc_works::ProcessEdges<mmtk::plan::Foo>",
+            start: origin + Duration::from_nanos(12_345_678),
+            end: origin + Duration::from_nanos(12_355_678),
+        };
+        assert_eq!(
+            trace_event(&event, 3, origin),
+            "{\"name\":\"ProcessEdges\",\"cat\":\"work\",\"ph\":\"X\",\"pid\":0,\"tid\":3,\
+             \"ts\":12345.678,\"dur\":10.000}"
+        );
+        assert_eq!(
+            thread_name_event(0, "Coordinator"),
+            "{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":0,\"tid\":0,\
+             \"args\":{\"name\":\"Coordinator\"}}"
+        );
+    }
+}
@@ -0,0 +1,9 @@
+//! The mock VM binding of the integration tests (`tests/mock_vm`), for unit tests that need an
+//! object model. Unit tests that do not run an MMTk instance allocate their objects with
+//! `alloc_object()`.
+
+#![allow(dead_code)]
+
+use crate as mmtk;
+
+include!("../../../tests/mock_vm/vm.rs");
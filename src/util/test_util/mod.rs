@@ -0,0 +1,60 @@
+pub mod mock_vm;
+
+use crate::util::alloc_bit::{self, ALLOC_SIDE_METADATA_SPEC};
+use crate::util::constants::LOG_BYTES_IN_PAGE;
+use crate::util::heap::layout::heap_layout::Mmapper;
+use crate::util::heap::layout::vm_layout_constants::HEAP_START;
+use crate::util::heap::layout::Mmapper as IMmapper;
+use crate::util::pin_bit::{self, PIN_SIDE_METADATA_SPEC};
+use crate::util::Address;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+// https://github.com/rust-lang/rfcs/issues/2798#issuecomment-552949300
+pub fn panic_after<T, F>(millis: u64, f: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T,
+    F: Send + 'static,
+{
+    let (done_tx, done_rx) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let val = f();
+        done_tx.send(()).expect("Unable to send completion signal");
+        val
+    });
+
+    match done_rx.recv_timeout(Duration::from_millis(millis)) {
+        Ok(_) => handle.join().expect("Thread panicked"),
+        Err(e) => panic!("Thread took too long: {}", e),
+    }
+}
+
+lazy_static! {
+    static ref SERIAL_TEST_LOCK: Mutex<()> = Mutex::default();
+}
+
+// force some tests to be executed serially
+pub fn serial_test<F>(f: F)
+where
+    F: FnOnce(),
+{
+    let _lock = SERIAL_TEST_LOCK.lock();
+    f();
+}
+
+/// Map `bytes` of memory at `HEAP_START + offset`, and the alloc bits and pin bits of the memory,
+/// for the tests of a policy that do not create the space. Each test module uses its own offset.
+/// This enables the alloc bits and the pin bits.
+pub fn map_heap_memory(offset: usize, bytes: usize) -> Address {
+    alloc_bit::enable();
+    pin_bit::enable();
+    let start = HEAP_START + offset;
+    let mmapper = Mmapper::new();
+    mmapper.ensure_mapped(start, bytes >> LOG_BYTES_IN_PAGE);
+    ALLOC_SIDE_METADATA_SPEC.ensure_mapped(&mmapper, start, bytes);
+    PIN_SIDE_METADATA_SPEC.ensure_mapped(&mmapper, start, bytes);
+    start
+}
@@ -63,6 +63,39 @@ impl TreadMill {
         self.to_space.lock().unwrap().insert(cell);
     }
 
+    /// Get all the cells in the treadmill.
+    pub fn cells(&self) -> Vec<Address> {
+        let mut cells = vec![];
+        for set in [
+            &self.from_space,
+            &self.to_space,
+            &self.collect_nursery,
+            &self.alloc_nursery,
+        ]
+        .iter()
+        {
+            cells.extend(set.lock().unwrap().iter().copied());
+        }
+        cells
+    }
+
+    /// Find a cell in the treadmill that satisfies `f`.
+    pub fn find<F: FnMut(Address) -> bool>(&self, mut f: F) -> Option<Address> {
+        for set in [
+            &self.from_space,
+            &self.to_space,
+            &self.collect_nursery,
+            &self.alloc_nursery,
+        ]
+        .iter()
+        {
+            if let Some(cell) = set.lock().unwrap().iter().copied().find(|cell| f(*cell)) {
+                return Some(cell);
+            }
+        }
+        None
+    }
+
     pub fn to_space_empty(&self) -> bool {
         self.to_space.lock().unwrap().is_empty()
     }
@@ -57,4 +57,13 @@ pub trait Collection<VM: VMBinding> {
     fn out_of_memory(_tls: OpaquePointer) {
         panic!("Out of memory!");
     }
+
+    /// Inform the VM to schedule finalization threads. MMTk calls this method in a GC when some
+    /// objects registered with [`add_finalizer()`](../memory_manager/fn.add_finalizer.html) become unreachable.
+    /// The VM can get those objects with [`get_finalized_object()`](../memory_manager/fn.get_finalized_object.html)
+    /// after the GC, and run their finalizers.
+    ///
+    /// Arguments:
+    /// * `tls`: The thread pointer for the current GC thread.
+    fn schedule_finalization(_tls: OpaquePointer) {}
 }
@@ -39,7 +39,9 @@ pub trait ObjectModel<VM: VMBinding> {
     /// collection, MMTk reserves a region in the heap for an object as per
     /// requirements found from `ObjectModel` and then asks `ObjectModel` to
     /// determine what the object's reference will be post-copy. Return the address
-    /// past the end of the copied object.
+    /// past the end of the copied object. A sliding compactor may copy an object to
+    /// a region that overlaps with the object itself (the region never starts after
+    /// the object), so the copy needs to handle overlapping memory (e.g. `ptr::copy`).
     ///
     /// Arguments:
     /// * `from`: The address of the object to be copied.
@@ -1,4 +1,3 @@
-use crate::plan::TraceLocal;
 use crate::util::Address;
 use crate::util::ObjectReference;
 use crate::util::OpaquePointer;
@@ -30,16 +29,13 @@ pub trait ReferenceGlue<VM: VMBinding> {
     /// * `referent`: The referent object reference.
     fn set_referent(reff: ObjectReference, referent: ObjectReference);
 
-    /// Process a reference with the current semantics and return an updated reference (e.g. with a new address)
-    /// if the reference is still alive, otherwise return a null object reference.
+    /// Enqueue references whose referents have been cleared in this GC. MMTk processes
+    /// the references with their semantics (soft, weak or phantom), clears the referents
+    /// that are no longer reachable, and hands these references to the VM (e.g. so that
+    /// the VM can add them to their reference queues).
     ///
     /// Arguments:
-    /// * `trace`: A reference to a `TraceLocal` object for this reference.
-    /// * `reference`: The address of the reference. This may or may not be the address of a heap object, depending on the VM.
-    /// * `tls`: The GC thread that is processing this reference.
-    fn process_reference<T: TraceLocal>(
-        trace: &mut T,
-        reference: ObjectReference,
-        tls: OpaquePointer,
-    ) -> ObjectReference;
+    /// * `references`: The references whose referents have been cleared.
+    /// * `tls`: The GC thread that is enqueueing these references.
+    fn enqueue_references(references: &[ObjectReference], tls: OpaquePointer);
 }
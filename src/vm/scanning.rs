@@ -1,4 +1,4 @@
-use crate::plan::{Mutator, SelectedPlan, TransitiveClosure};
+use crate::plan::{Mutator, TransitiveClosure};
 use crate::scheduler::gc_works::ProcessEdgesWork;
 use crate::util::ObjectReference;
 use crate::util::OpaquePointer;
@@ -51,11 +51,15 @@ pub trait Scanning<VM: VMBinding> {
 
     /// Scan one mutator for roots.
     ///
+    /// The roots are the slots passed to `W::new()`. A VM that cannot find the references on
+    /// the stack precisely can pass the stack range to `memory_manager::scan_roots_conservatively()`
+    /// instead, which pins the objects found for this GC.
+    ///
     /// Arguments:
     /// * `mutator`: The reference to the mutator whose roots will be scanned.
     /// * `tls`: The GC thread that is performing this scanning.
     fn scan_thread_root<W: ProcessEdgesWork<VM = VM>>(
-        mutator: &'static mut Mutator<SelectedPlan<VM>>,
+        mutator: &'static mut Mutator<VM>,
         tls: OpaquePointer,
     );
 
@@ -0,0 +1,30 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mmtk::AllocationSemantics;
+use mock_vm::*;
+
+// With the alloc_bit option, a large object is found by its alloc bit while it is alive, and
+// its alloc bit and pin bit are cleared when it is swept.
+#[test]
+pub fn alloc_bit_large_objects() {
+    init(
+        &[
+            ("plan", "MarkSweep"),
+            ("alloc_bit", "true"),
+            ("pin_bit", "true"),
+        ],
+        32 << 20,
+    );
+
+    let live = alloc_with(1, 0, AllocationSemantics::Los);
+    let dead = alloc_with(1, 0, AllocationSemantics::Los);
+    set_root(0, live);
+    assert!(memory_manager::is_mmtk_object(dead.to_address()));
+    // Pinning does not keep the object alive
+    memory_manager::pin_object(dead);
+    gc();
+    assert!(memory_manager::is_mmtk_object(live.to_address()));
+    assert!(!memory_manager::is_mmtk_object(dead.to_address()));
+    assert!(!memory_manager::is_pinned(dead));
+}
@@ -0,0 +1,29 @@
+mod mock_vm;
+
+use mmtk::AllocationSemantics;
+use mock_vm::*;
+
+// With the card-marking barrier, a nursery GC traces from the mature objects on the dirty cards,
+// and from the large objects that are written to (which are not in the card table).
+#[test]
+pub fn card_marking_large_objects() {
+    init(&[("plan", "GenCopy"), ("card_marking", "true")], 64 << 20);
+
+    let los = alloc_with(1, 0, AllocationSemantics::Los);
+    set_root(0, los);
+    set_root(1, alloc(1));
+    // Copy the second object to the mature space
+    gc();
+    for n_refs in 2..5 {
+        let young = alloc(n_refs);
+        write_field(get_root(0), 0, young);
+        write_field(get_root(1), 0, young);
+        gc();
+        // The young object is copied out of the nursery, and the fields are updated
+        let object = read_field(get_root(0), 0);
+        assert_ne!(object, young);
+        assert_eq!(num_refs(object), n_refs);
+        assert_eq!(read_field(get_root(1), 0), object);
+    }
+    assert_eq!(get_root(0), los);
+}
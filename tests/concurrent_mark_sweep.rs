@@ -0,0 +1,44 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// A concurrent collection: allocation starts concurrent marking, and the SATB barrier keeps an
+// object alive when its only reference is overwritten during marking.
+#[test]
+pub fn concurrent_mark_sweep() {
+    init(
+        &[("plan", "ConcurrentMarkSweep"), ("concurrent_trigger", "5")],
+        32 << 20,
+    );
+
+    let a = alloc(1);
+    let b = alloc(5);
+    // Tell b apart from the objects that may reuse its cell
+    write_field(b, 0, b);
+    write_field(a, 0, b);
+    set_root(0, a);
+
+    // Allocate until the used heap reaches the concurrent trigger, which starts concurrent marking
+    // with the initial mark pause
+    while gc_count() == 0 {
+        alloc(0);
+    }
+
+    // Move the only reference to b into an object allocated during marking
+    let c = alloc(1);
+    set_root(1, c);
+    write_field(c, 0, b);
+    write_field(a, 0, null());
+
+    // Wait for the final mark pause
+    while gc_count() < 2 {
+        safepoint();
+    }
+    assert_eq!(read_field(get_root(1), 0), b);
+    // Reuse the free cells of the size class of b
+    for _ in 0..10000 {
+        alloc(5);
+    }
+    assert_eq!(num_refs(b), 5);
+    assert_eq!(read_field(b, 0), b);
+}
@@ -0,0 +1,13 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// With the alloc_bit option, enumerate_objects() finds the objects in each space of ConcurrentMarkSweep.
+#[test]
+pub fn enumerate_objects_concurrent_mark_sweep() {
+    init(
+        &[("plan", "ConcurrentMarkSweep"), ("alloc_bit", "true")],
+        32 << 20,
+    );
+    check_enumerate_objects(true);
+}
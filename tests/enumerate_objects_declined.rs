@@ -0,0 +1,20 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mmtk::util::options::OptionNotEnabled;
+use mock_vm::*;
+
+// Without the alloc_bit option, enumerate_objects() returns an error and does not call the
+// function.
+#[test]
+pub fn enumerate_objects_declined() {
+    init(&[("plan", "MarkSweep")], 32 << 20);
+
+    alloc(0);
+    let mut found = 0;
+    assert_eq!(
+        memory_manager::enumerate_objects(mmtk(), |_| found += 1),
+        Err(OptionNotEnabled("alloc_bit"))
+    );
+    assert_eq!(found, 0);
+}
@@ -0,0 +1,11 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// With the alloc_bit option, enumerate_objects() finds the objects in each space of NoGC, which
+// never collects.
+#[test]
+pub fn enumerate_objects_no_gc() {
+    init(&[("plan", "NoGC"), ("alloc_bit", "true")], 32 << 20);
+    check_enumerate_objects(false);
+}
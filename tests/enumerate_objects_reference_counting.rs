@@ -0,0 +1,13 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// With the alloc_bit option, enumerate_objects() finds the objects in each space of ReferenceCounting.
+#[test]
+pub fn enumerate_objects_reference_counting() {
+    init(
+        &[("plan", "ReferenceCounting"), ("alloc_bit", "true")],
+        32 << 20,
+    );
+    check_enumerate_objects(true);
+}
@@ -0,0 +1,10 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// With the alloc_bit option, enumerate_objects() finds the objects in each space of SemiSpace.
+#[test]
+pub fn enumerate_objects_semispace() {
+    init(&[("plan", "SemiSpace"), ("alloc_bit", "true")], 32 << 20);
+    check_enumerate_objects(true);
+}
@@ -0,0 +1,13 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// With the alloc_bit option, enumerate_objects() finds the objects in each space of StickyMarkSweep.
+#[test]
+pub fn enumerate_objects_sticky_mark_sweep() {
+    init(
+        &[("plan", "StickyMarkSweep"), ("alloc_bit", "true")],
+        32 << 20,
+    );
+    check_enumerate_objects(true);
+}
@@ -0,0 +1,33 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mock_vm::*;
+
+// An object that is ready for finalization is kept alive (and moved) by every GC until the VM
+// gets it.
+#[test]
+pub fn finalizable_kept_across_gcs() {
+    init(&[("plan", "SemiSpace")], 64 << 20);
+
+    let object = alloc(1);
+    write_field(object, 0, alloc(0));
+    memory_manager::add_finalizer(mmtk(), object);
+
+    // The object is unreachable, so it becomes ready for finalization
+    gc();
+    // The VM does not get the object before the next GCs
+    gc();
+    // Copy some other objects, so they would overwrite the object if it were left behind
+    for i in 0..4 {
+        set_root(i, alloc(3));
+    }
+    gc();
+
+    let object = memory_manager::get_finalized_object(mmtk()).unwrap();
+    assert!(memory_manager::is_live_object(object));
+    assert_eq!(num_refs(object), 1);
+    let child = read_field(object, 0);
+    assert!(memory_manager::is_live_object(child));
+    assert_eq!(num_refs(child), 0);
+    assert!(memory_manager::get_finalized_object(mmtk()).is_none());
+}
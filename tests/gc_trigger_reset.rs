@@ -0,0 +1,30 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// The triggers of a GC are reset at the end of the GC: a user-triggered GC does not make the
+// following allocation-triggered GC look user-triggered.
+#[test]
+pub fn gc_trigger_reset() {
+    let path = std::env::temp_dir().join("mmtk_gc_trigger_reset.jsonl");
+    let _ = std::fs::remove_file(&path);
+    init(
+        &[
+            ("plan", "MarkSweep"),
+            ("gc_event_log", path.to_str().unwrap()),
+        ],
+        8 << 20,
+    );
+
+    gc();
+    while gc_count() < 2 {
+        alloc(0);
+    }
+
+    let log = std::fs::read_to_string(&path).unwrap();
+    let triggers: Vec<bool> = log
+        .lines()
+        .map(|event| event.contains("\"trigger\":\"user\""))
+        .collect();
+    assert_eq!(triggers, vec![true, false]);
+}
@@ -0,0 +1,59 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mmtk::util::heap_dump::HeapDump;
+use mmtk::util::Address;
+use mock_vm::*;
+use std::collections::HashMap;
+
+// A heap dump taken by a copying GC has the objects reachable from the roots at their new
+// addresses, with their edges, and not the unreachable objects.
+#[test]
+pub fn heap_dump() {
+    init(&[("plan", "SemiSpace")], 32 << 20);
+
+    let a = alloc(2);
+    set_root(0, a);
+    let b = alloc(1);
+    let c = alloc(1);
+    write_field(a, 0, b);
+    write_field(a, 1, c);
+    write_field(b, 0, c);
+    // A cycle back to the root object
+    write_field(c, 0, a);
+    set_root(1, alloc(0));
+    // Garbage that refers to a live object
+    let d = alloc(1);
+    write_field(d, 0, a);
+
+    let path = std::env::temp_dir().join("mmtk_heap_dump.bin");
+    memory_manager::dump_heap(mmtk(), mutator_tls(), &path).unwrap();
+    let dump = HeapDump::read_file(&path).unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    // The objects are copied, and the dump has their new addresses
+    assert_ne!(get_root(0), a);
+    let (a, e) = (get_root(0), get_root(1));
+    let (b, c) = (read_field(a, 0), read_field(a, 1));
+    let mut roots = dump.roots.clone();
+    roots.sort_by_key(|root| root.as_usize());
+    let mut expected_roots = vec![a.to_address(), e.to_address()];
+    expected_roots.sort_by_key(|root| root.as_usize());
+    assert_eq!(roots, expected_roots);
+
+    let objects: HashMap<Address, _> = dump
+        .objects
+        .iter()
+        .map(|object| (object.address, object))
+        .collect();
+    assert_eq!(objects.len(), 4);
+    assert_eq!(dump.objects.len(), 4);
+    let expected_edges = [(a, vec![b, c]), (b, vec![c]), (c, vec![a]), (e, vec![])];
+    for (object, edges) in expected_edges.iter() {
+        let record = objects[&object.to_address()];
+        let edges: Vec<Address> = edges.iter().map(|edge| edge.to_address()).collect();
+        assert_eq!(record.edges, edges);
+        assert_eq!(record.size, (2 + num_refs(*object)) * 8);
+        assert_eq!(record.type_descriptor, b"Mock".to_vec());
+    }
+}
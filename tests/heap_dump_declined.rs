@@ -0,0 +1,16 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mock_vm::*;
+
+// A heap dump that no GC takes fails, and it is not left for a later GC.
+#[test]
+pub fn heap_dump_declined() {
+    init(&[("plan", "NoGC")], 32 << 20);
+
+    let path = std::env::temp_dir().join("mmtk_heap_dump_declined.bin");
+    let _ = std::fs::remove_file(&path);
+    assert!(memory_manager::dump_heap(mmtk(), mutator_tls(), &path).is_err());
+    assert!(!mmtk().heap_dumper.is_requested());
+    assert!(!path.exists());
+}
@@ -0,0 +1,39 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+// A bit more than a quarter of a block, so that the copies of the objects that fit in the live lines of
+// the defrag sources do not fit in the blocks of defrag headroom.
+const OBJECT_REFS: usize = 1100;
+const LIVE_OBJECTS: usize = 32;
+
+// Defragmentation evacuates the sparse blocks as long as there is headroom, and marks the
+// other objects in place. Each object refers to itself, so a moved object that is not updated
+// (or an object that is copied twice) breaks the self reference.
+#[test]
+pub fn immix_defrag() {
+    init(&[("plan", "Immix")], 4 << 20);
+
+    let holder = alloc(LIVE_OBJECTS);
+    set_root(0, holder);
+    for i in 0..LIVE_OBJECTS * 3 {
+        let object = alloc(OBJECT_REFS);
+        write_field(object, 0, object);
+        if i % 3 == 0 {
+            write_field(get_root(0), i / 3, object);
+        }
+    }
+    // The first GC leaves sparse reusable blocks, which the next GC evacuates
+    gc();
+    let before: Vec<_> = (0..LIVE_OBJECTS)
+        .map(|i| read_field(get_root(0), i))
+        .collect();
+    gc();
+    let after: Vec<_> = (0..LIVE_OBJECTS)
+        .map(|i| read_field(get_root(0), i))
+        .collect();
+    assert!(before.iter().zip(after.iter()).any(|(b, a)| b != a));
+    for &object in after.iter() {
+        assert_eq!(read_field(object, 0), object);
+    }
+}
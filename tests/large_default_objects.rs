@@ -0,0 +1,23 @@
+mod mock_vm;
+
+use mmtk::policy::space::Space;
+use mmtk::AllocationSemantics;
+use mock_vm::*;
+
+// An object that is too large for the free-list allocator of MarkSweep is allocated in the
+// large object space, even if it is allocated with the default semantics.
+#[test]
+pub fn large_default_objects() {
+    init(&[("plan", "MarkSweep")], 32 << 20);
+
+    let los = mmtk().plan.common().get_los();
+    let small = alloc(1);
+    let large = alloc_with(2048, 0, AllocationSemantics::Default);
+    assert!(!los.in_space(small));
+    assert!(los.in_space(large));
+    write_field(large, 2047, small);
+    set_root(0, large);
+    gc();
+    assert!(los.in_space(get_root(0)));
+    assert_eq!(read_field(get_root(0), 2047), small);
+}
@@ -0,0 +1,17 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mock_vm::*;
+
+// A minimal heap size above the heap size given to gc_init() is kept, and the maximal heap size
+// (not set, so it is the heap size) is raised to it.
+#[test]
+pub fn min_heap_above_heap_size() {
+    init(&[("plan", "MarkSweep"), ("min_heap", "16777216")], 8 << 20);
+
+    assert_eq!(memory_manager::total_bytes(mmtk()), 16 << 20);
+    let object = alloc(1);
+    set_root(0, object);
+    gc();
+    assert_eq!(memory_manager::total_bytes(mmtk()), 16 << 20);
+}
@@ -0,0 +1,19 @@
+//! A mock VM binding for end-to-end tests that run real GCs. Each test file is a separate test
+//! process, and it should only create one MMTk instance (with `init()`), as we do not have proper
+//! setup/teardown procedures for MMTk instances.
+//!
+//! The test thread is the only mutator. An object is laid out as:
+//! * word 0: the status word. The GC byte is its highest byte, as in OpenJDK.
+//! * word 1: the number of reference fields (low 32 bits), and whether the object is a
+//!   reference object (`REFERENCE_FLAG`). The first field of a reference object is its referent,
+//!   which is not traced.
+//! * word 2..: the reference fields.
+//!
+//! The roots are the slots returned by `root()`. Objects can move during a GC, so tests should
+//! keep their objects in the roots (or in the fields of rooted objects) across GCs.
+//!
+//! The binding is in `vm.rs`, which the unit tests also use (as `util::test_util::mock_vm`).
+
+#![allow(dead_code)]
+
+include!("vm.rs");
@@ -0,0 +1,571 @@
+use lazy_static::lazy_static;
+use mmtk::memory_manager;
+use mmtk::scheduler::gc_works::ProcessEdgesWork;
+use mmtk::scheduler::*;
+use mmtk::util::options::OptionsBuilder;
+use mmtk::util::{Address, ObjectReference, OpaquePointer};
+use mmtk::vm::*;
+use mmtk::MMTK;
+use mmtk::{AllocationSemantics, CopyContext, Mutator, MutatorContext, Plan, TransitiveClosure};
+use std::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex};
+
+const BYTES_IN_WORD: usize = 8;
+const HEADER_WORDS: usize = 2;
+const REFERENCE_FLAG: usize = 1 << 32;
+const NUM_REFS_MASK: usize = REFERENCE_FLAG - 1;
+pub const NUM_ROOTS: usize = 64;
+
+#[derive(Default)]
+pub struct MockVM;
+
+impl VMBinding for MockVM {
+    type VMObjectModel = MockObjectModel;
+    type VMScanning = MockScanning;
+    type VMCollection = MockCollection;
+    type VMActivePlan = MockActivePlan;
+    type VMReferenceGlue = MockReferenceGlue;
+}
+
+static MMTK_INSTANCE: AtomicUsize = AtomicUsize::new(0);
+static MUTATOR: AtomicUsize = AtomicUsize::new(0);
+static MUTATOR_ITERATED: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref ROOTS: Box<[AtomicUsize]> = (0..NUM_ROOTS).map(|_| AtomicUsize::new(0)).collect();
+    static ref ENQUEUED: Mutex<Vec<ObjectReference>> = Mutex::new(vec![]);
+    static ref MUTATOR_STATE: (Mutex<MutatorState>, Condvar) = Default::default();
+}
+
+/// Whether a GC is waiting for the mutator to stop, whether the mutator is stopped (in
+/// `block_for_gc()` or `safepoint()`), and the number of GCs that have resumed the mutator.
+#[derive(Default)]
+struct MutatorState {
+    stopping: bool,
+    blocked: bool,
+    gc_count: usize,
+}
+
+pub fn mmtk() -> &'static MMTK<MockVM> {
+    unsafe { &*(MMTK_INSTANCE.load(Ordering::SeqCst) as *const MMTK<MockVM>) }
+}
+
+pub fn mutator() -> &'static mut Mutator<MockVM> {
+    unsafe { &mut *(MUTATOR.load(Ordering::SeqCst) as *mut Mutator<MockVM>) }
+}
+
+pub fn mutator_tls() -> OpaquePointer {
+    OpaquePointer::from_address(unsafe { Address::from_usize(BYTES_IN_WORD) })
+}
+
+/// Create the MMTk instance with the options (e.g. `&[("plan", "SemiSpace")]`), enable
+/// collection, and bind the test thread as the mutator.
+pub fn init(options: &[(&str, &str)], heap_size: usize) {
+    let mut builder = OptionsBuilder::new();
+    for (name, value) in options {
+        builder = builder.set(name, value).unwrap();
+    }
+    let mmtk: &'static mut MMTK<MockVM> =
+        Box::leak(Box::new(MMTK::with_options(builder.build().unwrap())));
+    MMTK_INSTANCE.store(mmtk as *mut _ as usize, Ordering::SeqCst);
+    memory_manager::gc_init(mmtk, heap_size);
+    let mmtk = self::mmtk();
+    memory_manager::enable_collection(mmtk, mutator_tls());
+    let mutator = Box::leak(memory_manager::bind_mutator(mmtk, mutator_tls()));
+    MUTATOR.store(mutator as *mut _ as usize, Ordering::SeqCst);
+}
+
+/// Allocate an object with `n_refs` null reference fields.
+pub fn alloc(n_refs: usize) -> ObjectReference {
+    alloc_with(n_refs, 0, AllocationSemantics::Default)
+}
+
+/// Allocate a reference object with the given referent, and register it with MMTk as a
+/// soft/weak/phantom reference with `add`, e.g. `memory_manager::add_soft_candidate`.
+pub fn alloc_reference(
+    referent: ObjectReference,
+    add: fn(&MMTK<MockVM>, ObjectReference, ObjectReference),
+) -> ObjectReference {
+    let reference = alloc_with(1, REFERENCE_FLAG, AllocationSemantics::Default);
+    unsafe { field(reference, 0).store(referent) };
+    add(mmtk(), reference, referent);
+    reference
+}
+
+pub fn alloc_with(n_refs: usize, flags: usize, semantics: AllocationSemantics) -> ObjectReference {
+    let bytes = (HEADER_WORDS + n_refs) * BYTES_IN_WORD;
+    let start = memory_manager::alloc(mutator(), bytes, BYTES_IN_WORD, 0, semantics);
+    assert!(!start.is_zero());
+    let object = init_object_with(start, n_refs, flags);
+    memory_manager::post_alloc(mutator(), object, null(), bytes, semantics);
+    object
+}
+
+/// Lay out an object with `n_refs` null reference fields at `start`, for the unit tests that
+/// place objects in memory that they have mapped.
+pub fn init_object(start: Address, n_refs: usize) -> ObjectReference {
+    init_object_with(start, n_refs, 0)
+}
+
+fn init_object_with(start: Address, n_refs: usize, flags: usize) -> ObjectReference {
+    unsafe {
+        start.store(0usize);
+        (start + BYTES_IN_WORD).store(n_refs | flags);
+        for i in 0..n_refs {
+            (start + (HEADER_WORDS + i) * BYTES_IN_WORD).store(0usize);
+        }
+        start.to_object_reference()
+    }
+}
+
+/// Allocate an object with `n_refs` null reference fields outside of MMTk, for the unit tests
+/// that need objects but no MMTk instance. The memory is never freed.
+pub fn alloc_object(n_refs: usize) -> ObjectReference {
+    let words = vec![0usize; HEADER_WORDS + n_refs].into_boxed_slice();
+    let start = Box::leak(words).as_mut_ptr();
+    unsafe {
+        *start.add(1) = n_refs;
+        Address::from_mut_ptr(start).to_object_reference()
+    }
+}
+
+pub fn null() -> ObjectReference {
+    unsafe { Address::ZERO.to_object_reference() }
+}
+
+pub fn num_refs(object: ObjectReference) -> usize {
+    unsafe { (object.to_address() + BYTES_IN_WORD).load::<usize>() & NUM_REFS_MASK }
+}
+
+fn is_reference(object: ObjectReference) -> bool {
+    unsafe { (object.to_address() + BYTES_IN_WORD).load::<usize>() & REFERENCE_FLAG != 0 }
+}
+
+fn size(object: ObjectReference) -> usize {
+    (HEADER_WORDS + num_refs(object)) * BYTES_IN_WORD
+}
+
+/// The slot of the `i`-th reference field of `object`.
+pub fn field(object: ObjectReference, i: usize) -> Address {
+    assert!(i < num_refs(object));
+    object.to_address() + (HEADER_WORDS + i) * BYTES_IN_WORD
+}
+
+pub fn read_field(object: ObjectReference, i: usize) -> ObjectReference {
+    unsafe { field(object, i).load() }
+}
+
+/// Write a reference to a field of `src` with the barriers, as a binding would.
+pub fn write_field(src: ObjectReference, i: usize, value: ObjectReference) {
+    let slot = field(src, i);
+    if mmtk().plan.constraints().needs_pre_write_barrier {
+        let old = unsafe { slot.load() };
+        mutator().record_pre_write(src, slot, old, value);
+    }
+    unsafe { slot.store(value) };
+    mutator().record_modified_node(src);
+}
+
+/// Copy the first `len` reference fields of `src` to `dst` (as in an array copy), with the array
+/// copy barriers.
+pub fn copy_fields(src: ObjectReference, dst: ObjectReference, len: usize) {
+    if mmtk().plan.constraints().needs_pre_write_barrier {
+        mutator().record_pre_array_copy(dst, field(dst, 0), len);
+    }
+    for i in 0..len {
+        unsafe { field(dst, i).store(read_field(src, i)) };
+    }
+    mutator().record_array_copy(field(src, 0), field(dst, 0), len);
+}
+
+/// The address of the `i`-th root slot.
+pub fn root(i: usize) -> Address {
+    Address::from_ref(&ROOTS[i])
+}
+
+pub fn get_root(i: usize) -> ObjectReference {
+    unsafe { root(i).load() }
+}
+
+pub fn set_root(i: usize, object: ObjectReference) {
+    unsafe { root(i).store(object) }
+}
+
+/// Trigger a GC and wait for it to finish.
+pub fn gc() {
+    memory_manager::handle_user_collection_request(mmtk(), mutator_tls());
+}
+
+/// The number of GCs that have finished.
+pub fn gc_count() -> usize {
+    MUTATOR_STATE.0.lock().unwrap().gc_count
+}
+
+/// Let a GC that is waiting for the mutator (e.g. the final pause of a concurrent GC) run, and
+/// wait for it to finish. Returns false if no GC is waiting.
+pub fn safepoint() -> bool {
+    let (lock, cvar) = &*MUTATOR_STATE;
+    let mut state = lock.lock().unwrap();
+    if !state.stopping {
+        return false;
+    }
+    let gc_count = state.gc_count;
+    state.blocked = true;
+    cvar.notify_all();
+    while state.gc_count == gc_count {
+        state = cvar.wait(state).unwrap();
+    }
+    state.blocked = false;
+    true
+}
+
+/// The reference objects that MMTk has enqueued (i.e. cleared).
+pub fn take_enqueued() -> Vec<ObjectReference> {
+    std::mem::take(&mut *ENQUEUED.lock().unwrap())
+}
+
+/// Allocate small and large objects, keep half of them alive across a GC (unless `collects` is
+/// false), allocate more, and check that `memory_manager::enumerate_objects()` finds each
+/// allocated object that is still alive exactly once. This needs the `alloc_bit` option.
+pub fn check_enumerate_objects(collects: bool) {
+    let mut objects: Vec<ObjectReference> = (0..NUM_ROOTS - 2).map(|i| alloc(i % 4)).collect();
+    objects.push(alloc_with(1, 0, AllocationSemantics::Los));
+    objects.push(alloc_with(1, 0, AllocationSemantics::Los));
+    for (i, &object) in objects.iter().enumerate().step_by(2) {
+        set_root(i / 2, object);
+    }
+    if collects {
+        gc();
+        objects = (0..NUM_ROOTS / 2).map(get_root).collect();
+    }
+    objects.extend((0..16).map(alloc));
+    objects.push(alloc_with(2, 0, AllocationSemantics::Los));
+
+    let mut found = vec![];
+    memory_manager::enumerate_objects(mmtk(), |object| found.push(object)).unwrap();
+    for object in &objects {
+        assert_eq!(found.iter().filter(|&o| o == object).count(), 1);
+    }
+    if !collects {
+        assert_eq!(found.len(), objects.len());
+    }
+}
+
+pub struct MockObjectModel;
+
+impl ObjectModel<MockVM> for MockObjectModel {
+    const GC_BYTE_OFFSET: usize = 56;
+
+    fn get_gc_byte(object: ObjectReference) -> &'static AtomicU8 {
+        unsafe { &*(object.to_address() + (Self::GC_BYTE_OFFSET >> 3)).to_ptr::<AtomicU8>() }
+    }
+
+    fn copy(
+        from: ObjectReference,
+        semantics: AllocationSemantics,
+        copy_context: &mut impl CopyContext,
+    ) -> ObjectReference {
+        let bytes = size(from);
+        let dst = copy_context.alloc_copy(from, bytes, BYTES_IN_WORD, 0, semantics);
+        unsafe {
+            std::ptr::copy_nonoverlapping::<u8>(
+                from.to_address().to_ptr(),
+                dst.to_mut_ptr(),
+                bytes,
+            );
+        }
+        let to = unsafe { dst.to_object_reference() };
+        copy_context.post_copy(to, Address::ZERO, bytes, semantics);
+        to
+    }
+
+    fn copy_to(from: ObjectReference, to: ObjectReference, _region: Address) -> Address {
+        let bytes = size(from);
+        if from != to {
+            unsafe {
+                std::ptr::copy::<u8>(
+                    from.to_address().to_ptr(),
+                    to.to_address().to_mut_ptr(),
+                    bytes,
+                );
+            }
+        }
+        to.to_address() + bytes
+    }
+
+    fn get_reference_when_copied_to(_from: ObjectReference, to: Address) -> ObjectReference {
+        unsafe { to.to_object_reference() }
+    }
+
+    fn get_size_when_copied(object: ObjectReference) -> usize {
+        size(object)
+    }
+
+    fn get_align_when_copied(_object: ObjectReference) -> usize {
+        BYTES_IN_WORD
+    }
+
+    fn get_align_offset_when_copied(_object: ObjectReference) -> isize {
+        0
+    }
+
+    fn get_current_size(object: ObjectReference) -> usize {
+        size(object)
+    }
+
+    fn get_next_object(object: ObjectReference) -> ObjectReference {
+        // Objects are word aligned, so the next object starts where this one ends
+        unsafe { Self::get_object_from_start_address(Self::get_object_end_address(object)) }
+    }
+
+    unsafe fn get_object_from_start_address(start: Address) -> ObjectReference {
+        start.to_object_reference()
+    }
+
+    fn get_object_end_address(object: ObjectReference) -> Address {
+        object.to_address() + size(object)
+    }
+
+    fn get_type_descriptor(_reference: ObjectReference) -> &'static [i8] {
+        // All the objects have the same type
+        &[b'M' as i8, b'o' as i8, b'c' as i8, b'k' as i8]
+    }
+
+    fn is_array(_object: ObjectReference) -> bool {
+        false
+    }
+
+    fn is_primitive_array(_object: ObjectReference) -> bool {
+        false
+    }
+
+    fn get_array_length(_object: ObjectReference) -> usize {
+        unimplemented!()
+    }
+
+    fn attempt_available_bits(object: ObjectReference, old: usize, new: usize) -> bool {
+        status_word(object)
+            .compare_exchange(old, new, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    }
+
+    fn prepare_available_bits(object: ObjectReference) -> usize {
+        status_word(object).load(Ordering::SeqCst)
+    }
+
+    fn write_available_byte(object: ObjectReference, val: u8) {
+        Self::get_gc_byte(object).store(val, Ordering::SeqCst)
+    }
+
+    fn read_available_byte(object: ObjectReference) -> u8 {
+        Self::get_gc_byte(object).load(Ordering::SeqCst)
+    }
+
+    fn write_available_bits_word(object: ObjectReference, val: usize) {
+        status_word(object).store(val, Ordering::SeqCst)
+    }
+
+    fn read_available_bits_word(object: ObjectReference) -> usize {
+        status_word(object).load(Ordering::SeqCst)
+    }
+
+    fn gc_header_offset() -> isize {
+        0
+    }
+
+    fn object_start_ref(object: ObjectReference) -> Address {
+        object.to_address()
+    }
+
+    fn ref_to_address(object: ObjectReference) -> Address {
+        object.to_address()
+    }
+
+    fn is_acyclic(_typeref: ObjectReference) -> bool {
+        false
+    }
+
+    fn dump_object(object: ObjectReference) {
+        println!("{} ({} refs)", object, num_refs(object));
+    }
+
+    fn get_array_base_offset() -> isize {
+        unimplemented!()
+    }
+
+    fn array_base_offset_trapdoor<T>(_o: T) -> isize {
+        unimplemented!()
+    }
+
+    fn get_array_length_offset() -> isize {
+        unimplemented!()
+    }
+}
+
+fn status_word(object: ObjectReference) -> &'static AtomicUsize {
+    unsafe { &*object.to_address().to_ptr::<AtomicUsize>() }
+}
+
+/// The slots of the traced fields of `object` (i.e. not the referent of a reference object).
+fn traced_fields(object: ObjectReference) -> impl Iterator<Item = Address> {
+    let first = if is_reference(object) { 1 } else { 0 };
+    (first..num_refs(object)).map(move |i| field(object, i))
+}
+
+fn process_edges<W: ProcessEdgesWork<VM = MockVM>>(edges: Vec<Address>, roots: bool) {
+    if !edges.is_empty() {
+        mmtk().scheduler.closure_stage.add(W::new(edges, roots));
+    }
+}
+
+pub struct MockScanning;
+
+impl Scanning<MockVM> for MockScanning {
+    fn scan_object<T: TransitiveClosure>(
+        trace: &mut T,
+        object: ObjectReference,
+        _tls: OpaquePointer,
+    ) {
+        for slot in traced_fields(object) {
+            trace.process_edge(slot);
+        }
+    }
+
+    fn reset_thread_counter() {}
+
+    fn notify_initial_thread_scan_complete(_partial_scan: bool, _tls: OpaquePointer) {}
+
+    fn scan_objects<W: ProcessEdgesWork<VM = MockVM>>(objects: &[ObjectReference]) {
+        let edges = objects.iter().flat_map(|&o| traced_fields(o)).collect();
+        process_edges::<W>(edges, false);
+    }
+
+    fn scan_thread_roots<W: ProcessEdgesWork<VM = MockVM>>() {
+        let edges = (0..NUM_ROOTS)
+            .map(root)
+            .filter(|slot| unsafe { !slot.load::<ObjectReference>().is_null() })
+            .collect();
+        process_edges::<W>(edges, true);
+    }
+
+    fn scan_thread_root<W: ProcessEdgesWork<VM = MockVM>>(
+        _mutator: &'static mut Mutator<MockVM>,
+        _tls: OpaquePointer,
+    ) {
+        Self::scan_thread_roots::<W>();
+    }
+
+    fn scan_vm_specific_roots<W: ProcessEdgesWork<VM = MockVM>>() {}
+
+    fn supports_return_barrier() -> bool {
+        false
+    }
+}
+
+pub struct MockCollection;
+
+impl Collection<MockVM> for MockCollection {
+    fn stop_all_mutators<E: ProcessEdgesWork<VM = MockVM>>(_tls: OpaquePointer) {
+        let (lock, cvar) = &*MUTATOR_STATE;
+        let mut state = lock.lock().unwrap();
+        state.stopping = true;
+        while !state.blocked {
+            state = cvar.wait(state).unwrap();
+        }
+    }
+
+    fn resume_mutators(_tls: OpaquePointer) {
+        let (lock, cvar) = &*MUTATOR_STATE;
+        let mut state = lock.lock().unwrap();
+        state.stopping = false;
+        state.gc_count += 1;
+        cvar.notify_all();
+    }
+
+    fn block_for_gc(_tls: OpaquePointer) {
+        let (lock, cvar) = &*MUTATOR_STATE;
+        let mut state = lock.lock().unwrap();
+        let gc_count = state.gc_count;
+        state.blocked = true;
+        cvar.notify_all();
+        while state.gc_count == gc_count {
+            state = cvar.wait(state).unwrap();
+        }
+        state.blocked = false;
+    }
+
+    fn spawn_worker_thread(_tls: OpaquePointer, ctx: Option<&Worker<MMTK<MockVM>>>) {
+        match ctx {
+            None => {
+                std::thread::spawn(|| {
+                    memory_manager::start_control_collector(mmtk(), OpaquePointer::UNINITIALIZED)
+                });
+            }
+            Some(worker) => {
+                let worker = worker as *const GCWorker<MockVM> as usize;
+                std::thread::spawn(move || {
+                    let worker = unsafe { &mut *(worker as *mut GCWorker<MockVM>) };
+                    memory_manager::start_worker(OpaquePointer::UNINITIALIZED, worker, mmtk())
+                });
+            }
+        }
+    }
+
+    fn prepare_mutator<T: MutatorContext<MockVM>>(_tls: OpaquePointer, _m: &T) {}
+}
+
+pub struct MockActivePlan;
+
+impl ActivePlan<MockVM> for MockActivePlan {
+    fn global() -> &'static dyn Plan<VM = MockVM> {
+        &*mmtk().plan
+    }
+
+    fn worker(_tls: OpaquePointer) -> &'static mut GCWorker<MockVM> {
+        unimplemented!()
+    }
+
+    unsafe fn is_mutator(tls: OpaquePointer) -> bool {
+        tls == mutator_tls()
+    }
+
+    unsafe fn mutator(_tls: OpaquePointer) -> &'static mut Mutator<MockVM> {
+        mutator()
+    }
+
+    fn collector_count() -> usize {
+        mmtk().options.threads
+    }
+
+    fn reset_mutator_iterator() {
+        MUTATOR_ITERATED.store(false, Ordering::SeqCst);
+    }
+
+    fn get_next_mutator() -> Option<&'static mut Mutator<MockVM>> {
+        if MUTATOR_ITERATED.swap(true, Ordering::SeqCst) {
+            None
+        } else {
+            Some(mutator())
+        }
+    }
+
+    fn number_of_mutators() -> usize {
+        1
+    }
+}
+
+pub struct MockReferenceGlue;
+
+impl ReferenceGlue<MockVM> for MockReferenceGlue {
+    fn get_referent(object: ObjectReference) -> ObjectReference {
+        read_field(object, 0)
+    }
+
+    fn set_referent(reff: ObjectReference, referent: ObjectReference) {
+        unsafe { field(reff, 0).store(referent) }
+    }
+
+    fn enqueue_references(references: &[ObjectReference], _tls: OpaquePointer) {
+        ENQUEUED.lock().unwrap().extend_from_slice(references);
+    }
+}
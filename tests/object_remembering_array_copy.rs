@@ -0,0 +1,31 @@
+mod mock_vm;
+
+use mmtk::MutatorContext;
+use mock_vm::*;
+
+// With the object-remembering barrier, a nursery GC traces the slots that are written by an array
+// copy into a mature object.
+#[test]
+pub fn object_remembering_array_copy() {
+    init(&[("plan", "GenCopy")], 64 << 20);
+
+    set_root(0, alloc(4));
+    // Copy the destination to the mature space
+    gc();
+    let young = alloc(4);
+    for i in 0..4 {
+        unsafe { field(young, i).store(alloc(i + 1)) };
+    }
+    set_root(1, young);
+    let (src, dst) = (field(get_root(1), 0), field(get_root(0), 0));
+    for i in 0..4 {
+        unsafe { field(get_root(0), i).store(read_field(get_root(1), i)) };
+    }
+    mutator().record_array_copy(src, dst, 4);
+    // Only the destination keeps the copied objects alive
+    set_root(1, null());
+    gc();
+    for i in 0..4 {
+        assert_eq!(num_refs(read_field(get_root(0), i)), i + 1);
+    }
+}
@@ -0,0 +1,23 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mmtk::util::options::PlanSelector;
+use mock_vm::*;
+
+#[test]
+pub fn process_plan_option_after_creation() {
+    init(&[("plan", "SemiSpace")], 64 << 20);
+
+    // The plan is already created, so it cannot be changed
+    assert!(!memory_manager::process(mmtk(), "plan", "MarkSweep"));
+    assert_eq!(mmtk().options.plan, PlanSelector::SemiSpace);
+    // Other options can still be set
+    assert!(memory_manager::process(mmtk(), "stress_factor", "1024"));
+    assert_eq!(mmtk().options.stress_factor, 1024);
+
+    // The instance still works with the plan it was created with
+    let object = alloc(1);
+    set_root(0, object);
+    gc();
+    assert_ne!(get_root(0), object, "SemiSpace moves the live objects");
+}
@@ -0,0 +1,52 @@
+mod mock_vm;
+
+use mock_vm::*;
+
+/// Allocate garbage until an RC pause (not a backup trace) is triggered.
+fn rc_pause() {
+    let count = gc_count();
+    while gc_count() == count {
+        alloc(0);
+    }
+    assert!(mmtk().plan.in_nursery());
+}
+
+// The RC barrier logs the destination object of an array copy before the copy, so the overwritten
+// referent is freed in the next pause, and the copied referent is kept.
+#[test]
+pub fn rc_array_copy() {
+    init(
+        &[
+            ("plan", "ReferenceCounting"),
+            ("min_nursery", "1048576"),
+            ("max_nursery", "1048576"),
+        ],
+        32 << 20,
+    );
+
+    let a = alloc(2);
+    let b = alloc(2);
+    write_field(a, 1, b);
+    set_root(0, a);
+    // a and b get their counts, and a is unlogged
+    rc_pause();
+    assert_eq!(read_field(a, 1), b);
+
+    let c = alloc(2);
+    write_field(c, 1, c);
+    let src = alloc(2);
+    write_field(src, 1, c);
+    copy_fields(src, a, 2);
+    // b gets a decrement from the snapshot of a, and c gets an increment
+    rc_pause();
+    let mut b_reused = false;
+    for _ in 0..10000 {
+        if alloc(2) == b {
+            b_reused = true;
+            break;
+        }
+    }
+    assert!(b_reused);
+    assert_eq!(read_field(a, 1), c);
+    assert_eq!(read_field(c, 1), c);
+}
@@ -0,0 +1,53 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mock_vm::*;
+
+/// Allocate garbage until an RC pause (not a backup trace) is triggered.
+fn rc_pause() {
+    let count = gc_count();
+    while gc_count() == count {
+        alloc(0);
+    }
+    assert!(mmtk().plan.in_nursery());
+}
+
+// RC pauses process weak references: a reference is cleared and enqueued when its referent is a
+// new object that gets no increment, or an old object that is freed by a decrement.
+#[test]
+pub fn rc_weak_references() {
+    init(
+        &[
+            ("plan", "ReferenceCounting"),
+            ("min_nursery", "1048576"),
+            ("max_nursery", "1048576"),
+        ],
+        32 << 20,
+    );
+
+    let holder = alloc(1);
+    let old = alloc(0);
+    let live = alloc(0);
+    write_field(holder, 0, old);
+    set_root(0, holder);
+    set_root(1, live);
+    let to_old = alloc_reference(old, memory_manager::add_weak_candidate::<MockVM>);
+    let to_new = alloc_reference(alloc(0), memory_manager::add_weak_candidate::<MockVM>);
+    let to_live = alloc_reference(live, memory_manager::add_weak_candidate::<MockVM>);
+    set_root(2, to_old);
+    set_root(3, to_new);
+    set_root(4, to_live);
+
+    // The new referent without references dies, and old becomes an old object
+    rc_pause();
+    assert_eq!(take_enqueued(), vec![to_new]);
+    assert!(read_field(to_new, 0).is_null());
+    assert_eq!(read_field(to_old, 0), old);
+
+    // old gets a decrement from the snapshot of holder
+    write_field(holder, 0, null());
+    rc_pause();
+    assert_eq!(take_enqueued(), vec![to_old]);
+    assert!(read_field(to_old, 0).is_null());
+    assert_eq!(read_field(to_live, 0), live);
+}
@@ -0,0 +1,50 @@
+mod mock_vm;
+
+use mmtk::memory_manager::is_mmtk_object;
+use mock_vm::*;
+
+const LIST_LENGTH: usize = 50000;
+
+// The SATB barrier logs the old values of the slots overwritten by an array copy during marking.
+// An object that is reachable when marking starts is marked, even if its only reference is
+// overwritten this way, and it is unreachable (floating garbage) when marking ends.
+#[test]
+pub fn satb_array_copy() {
+    init(
+        &[
+            ("plan", "ConcurrentMarkSweep"),
+            ("concurrent_trigger", "5"),
+            ("alloc_bit", "true"),
+        ],
+        64 << 20,
+    );
+
+    let a = alloc(2);
+    let b = alloc(5);
+    write_field(a, 1, b);
+    // Reach a through a long list, so that marking does not reach a before the array copy
+    let mut list = a;
+    for _ in 0..LIST_LENGTH {
+        let node = alloc(1);
+        write_field(node, 0, list);
+        list = node;
+    }
+    set_root(0, list);
+
+    // Allocate until the used heap reaches the concurrent trigger, which starts concurrent marking
+    // with the initial mark pause
+    while gc_count() == 0 {
+        alloc(0);
+    }
+
+    // Overwrite the only reference to b with an array copy of null fields
+    copy_fields(alloc(2), a, 2);
+    assert!(read_field(a, 1).is_null());
+
+    // Wait for the final mark pause
+    while gc_count() < 2 {
+        safepoint();
+    }
+    // b is not swept
+    assert!(is_mmtk_object(b.to_address()));
+}
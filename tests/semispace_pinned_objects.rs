@@ -0,0 +1,54 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mock_vm::*;
+
+const GARBAGE_OBJECTS: usize = 128;
+const LIVE_OBJECTS: usize = 16;
+// 8KB objects
+const OBJECT_REFS: usize = 1022;
+
+// A pinned object keeps its page in the from-space, and the other pages are released. The
+// to-space allocation skips the kept page when the space is the to-space again.
+#[test]
+pub fn semispace_pinned_objects() {
+    init(
+        &[
+            ("plan", "SemiSpace"),
+            ("alloc_bit", "true"),
+            ("pin_bit", "true"),
+        ],
+        8 << 20,
+    );
+
+    let pinned = alloc(1);
+    write_field(pinned, 0, pinned);
+    assert!(memory_manager::pin_object(pinned));
+    set_root(0, pinned);
+    for _ in 0..GARBAGE_OBJECTS {
+        alloc(OBJECT_REFS);
+    }
+    let used_before = memory_manager::used_bytes(mmtk());
+    gc();
+    assert_eq!(get_root(0), pinned);
+    assert_eq!(read_field(pinned, 0), pinned);
+    assert!(memory_manager::used_bytes(mmtk()) + GARBAGE_OBJECTS / 2 * 8192 < used_before);
+
+    // The live objects are copied into the space of the pinned object in the next GC
+    let holder = alloc(LIVE_OBJECTS);
+    set_root(1, holder);
+    for i in 0..LIVE_OBJECTS {
+        let object = alloc(OBJECT_REFS);
+        write_field(object, 0, object);
+        write_field(get_root(1), i, object);
+    }
+    gc();
+    gc();
+    assert_eq!(get_root(0), pinned);
+    assert_eq!(read_field(pinned, 0), pinned);
+    for i in 0..LIVE_OBJECTS {
+        let object = read_field(get_root(1), i);
+        assert_eq!(read_field(object, 0), object);
+    }
+    memory_manager::unpin_object(pinned);
+}
@@ -0,0 +1,39 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mock_vm::*;
+
+// A soft reference that is only reachable through the referent of another soft reference must be
+// processed after the referent is retained and traced.
+#[test]
+pub fn soft_reference_reachable_from_retained_referent() {
+    init(&[("plan", "SemiSpace")], 64 << 20);
+
+    // root -> s1 ~> a -> s2 ~> b, where ~> is a soft reference
+    let b = alloc(0);
+    let s2 = alloc_reference(b, memory_manager::add_soft_candidate::<MockVM>);
+    let a = alloc(1);
+    write_field(a, 0, s2);
+    let s1 = alloc_reference(a, memory_manager::add_soft_candidate::<MockVM>);
+    set_root(0, s1);
+
+    gc();
+
+    let s1 = get_root(0);
+    let a = read_field(s1, 0);
+    assert!(
+        !a.is_null(),
+        "the referent of a reachable soft reference is cleared"
+    );
+    let s2 = read_field(a, 0);
+    // s2 is found reachable only after a is traced. Its referent may be cleared, but then s2 is
+    // enqueued.
+    let enqueued = take_enqueued();
+    assert!(!enqueued.contains(&s1));
+    if read_field(s2, 0).is_null() {
+        assert!(
+            enqueued.contains(&s2),
+            "a soft reference is cleared but not enqueued"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+mod mock_vm;
+
+use mmtk::memory_manager::is_mmtk_object;
+use mock_vm::*;
+
+// Sticky mark bits: a nursery GC keeps the young objects that the old objects refer to, and
+// frees the dead young objects, but not the dead old objects, which a full heap GC frees.
+#[test]
+pub fn sticky_mark_sweep() {
+    init(
+        &[
+            ("plan", "StickyMarkSweep"),
+            ("min_nursery", "1048576"),
+            ("max_nursery", "1048576"),
+            ("alloc_bit", "true"),
+        ],
+        32 << 20,
+    );
+
+    let old = alloc(1);
+    let old_garbage = alloc(0);
+    set_root(0, old);
+    set_root(1, old_garbage);
+    // A user-triggered GC is a full heap GC, after which the objects are old
+    gc();
+    assert!(!mmtk().plan.in_nursery());
+    set_root(1, null());
+
+    // The only reference to the young object is in an old object
+    let young = alloc(5);
+    write_field(young, 0, young);
+    write_field(old, 0, young);
+    let young_garbage = alloc(5);
+    while gc_count() == 1 {
+        alloc(0);
+    }
+    assert!(mmtk().plan.in_nursery());
+    assert_eq!(read_field(old, 0), young);
+    assert_eq!(read_field(young, 0), young);
+    assert!(is_mmtk_object(young.to_address()));
+    assert!(!is_mmtk_object(young_garbage.to_address()));
+    assert!(is_mmtk_object(old_garbage.to_address()));
+
+    gc();
+    assert!(!mmtk().plan.in_nursery());
+    assert!(is_mmtk_object(young.to_address()));
+    assert!(!is_mmtk_object(old_garbage.to_address()));
+}
@@ -0,0 +1,47 @@
+mod mock_vm;
+
+use mmtk::memory_manager;
+use mock_vm::*;
+
+// A weak or phantom reference is cleared and enqueued if its referent dies, and it is kept (and
+// updated to the moved referent) if its referent survives.
+#[test]
+pub fn weak_phantom_references() {
+    init(&[("plan", "SemiSpace")], 64 << 20);
+
+    let live = alloc(0);
+    let dead = alloc(0);
+    set_root(0, live);
+    let references = [
+        alloc_reference(live, memory_manager::add_weak_candidate::<MockVM>),
+        alloc_reference(dead, memory_manager::add_weak_candidate::<MockVM>),
+        alloc_reference(live, memory_manager::add_phantom_candidate::<MockVM>),
+        alloc_reference(dead, memory_manager::add_phantom_candidate::<MockVM>),
+    ];
+    for (i, &reference) in references.iter().enumerate() {
+        set_root(i + 1, reference);
+    }
+
+    gc();
+
+    let live = get_root(0);
+    let enqueued = take_enqueued();
+    for (weak_or_phantom, i) in [("weak", 1), ("phantom", 3)].iter() {
+        let kept = get_root(*i);
+        assert_eq!(
+            read_field(kept, 0),
+            live,
+            "the {} reference to a live object is not updated",
+            weak_or_phantom
+        );
+        assert!(!enqueued.contains(&kept));
+        let cleared = get_root(*i + 1);
+        assert!(
+            read_field(cleared, 0).is_null(),
+            "the {} reference to a dead object is not cleared",
+            weak_or_phantom
+        );
+        assert!(enqueued.contains(&cleared));
+    }
+    assert_eq!(enqueued.len(), 2);
+}
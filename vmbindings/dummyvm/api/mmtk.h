@@ -74,6 +74,9 @@ extern void add_weak_candidate(void* ref, void* referent);
 extern void add_soft_candidate(void* ref, void* referent);
 extern void add_phantom_candidate(void* ref, void* referent);
 
+extern void add_finalizer(void* obj);
+extern void* get_finalized_object();
+
 extern void harness_begin(void *tls);
 extern void harness_end();
 
@@ -1,4 +1,4 @@
-use mmtk::{Plan, SelectedPlan};
+use mmtk::{Plan, Mutator};
 use mmtk::vm::ActivePlan;
 use mmtk::util::OpaquePointer;
 use mmtk::scheduler::*;
@@ -8,8 +8,8 @@ use SINGLETON;
 pub struct VMActivePlan<> {}
 
 impl ActivePlan<DummyVM> for VMActivePlan {
-    fn global() -> &'static SelectedPlan<DummyVM> {
-        &SINGLETON.plan
+    fn global() -> &'static dyn Plan<VM=DummyVM> {
+        &*SINGLETON.plan
     }
 
     fn worker(_tls: OpaquePointer) -> &'static mut GCWorker<DummyVM> {
@@ -25,7 +25,7 @@ impl ActivePlan<DummyVM> for VMActivePlan {
         true
     }
 
-    unsafe fn mutator(_tls: OpaquePointer) -> &'static mut <SelectedPlan<DummyVM> as Plan>::Mutator {
+    unsafe fn mutator(_tls: OpaquePointer) -> &'static mut Mutator<DummyVM> {
         unimplemented!()
     }
 
@@ -37,7 +37,7 @@ impl ActivePlan<DummyVM> for VMActivePlan {
         unimplemented!()
     }
 
-    fn get_next_mutator() -> Option<&'static mut <SelectedPlan<DummyVM> as Plan>::Mutator> {
+    fn get_next_mutator() -> Option<&'static mut Mutator<DummyVM>> {
         unimplemented!()
     }
 }
\ No newline at end of file
@@ -6,7 +6,6 @@ use std::ffi::CStr;
 use mmtk::memory_manager;
 use mmtk::AllocationSemantics;
 use mmtk::util::{ObjectReference, OpaquePointer, Address};
-use mmtk::SelectedPlan;
 use mmtk::scheduler::GCWorker;
 use mmtk::Mutator;
 use mmtk::MMTK;
@@ -28,23 +27,23 @@ pub extern "C" fn start_control_collector(tls: OpaquePointer) {
 }
 
 #[no_mangle]
-pub extern "C" fn bind_mutator(tls: OpaquePointer) -> *mut Mutator<SelectedPlan<DummyVM>> {
+pub extern "C" fn bind_mutator(tls: OpaquePointer) -> *mut Mutator<DummyVM> {
     Box::into_raw(memory_manager::bind_mutator(&SINGLETON, tls))
 }
 
 #[no_mangle]
-pub extern "C" fn destroy_mutator(mutator: *mut Mutator<SelectedPlan<DummyVM>>) {
+pub extern "C" fn destroy_mutator(mutator: *mut Mutator<DummyVM>) {
     memory_manager::destroy_mutator(unsafe { Box::from_raw(mutator) })
 }
 
 #[no_mangle]
-pub extern "C" fn alloc(mutator: *mut Mutator<SelectedPlan<DummyVM>>, size: usize,
+pub extern "C" fn alloc(mutator: *mut Mutator<DummyVM>, size: usize,
                     align: usize, offset: isize, semantics: AllocationSemantics) -> Address {
     memory_manager::alloc::<DummyVM>(unsafe { &mut *mutator }, size, align, offset, semantics)
 }
 
 #[no_mangle]
-pub extern "C" fn post_alloc(mutator: *mut Mutator<SelectedPlan<DummyVM>>, refer: ObjectReference, type_refer: ObjectReference,
+pub extern "C" fn post_alloc(mutator: *mut Mutator<DummyVM>, refer: ObjectReference, type_refer: ObjectReference,
                                         bytes: usize, semantics: AllocationSemantics) {
     memory_manager::post_alloc::<DummyVM>(unsafe { &mut *mutator }, refer, type_refer, bytes, semantics)
 }
@@ -125,6 +124,19 @@ pub extern "C" fn add_phantom_candidate(reff: ObjectReference, referent: ObjectR
     memory_manager::add_phantom_candidate(&SINGLETON, reff, referent)
 }
 
+#[no_mangle]
+pub extern "C" fn add_finalizer(object: ObjectReference) {
+    memory_manager::add_finalizer(&SINGLETON, object)
+}
+
+#[no_mangle]
+pub extern "C" fn get_finalized_object() -> ObjectReference {
+    match memory_manager::get_finalized_object(&SINGLETON) {
+        Some(object) => object,
+        None => unsafe { Address::ZERO.to_object_reference() },
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn harness_begin(tls: OpaquePointer) {
     memory_manager::harness_begin(&SINGLETON, tls)
@@ -1,6 +1,5 @@
 use mmtk::vm::ReferenceGlue;
 use mmtk::util::ObjectReference;
-use mmtk::TraceLocal;
 use mmtk::util::OpaquePointer;
 use DummyVM;
 
@@ -13,7 +12,7 @@ impl ReferenceGlue<DummyVM> for VMReferenceGlue {
     fn get_referent(_object: ObjectReference) -> ObjectReference {
         unimplemented!()
     }
-    fn process_reference<T: TraceLocal>(_trace: &mut T, _reference: ObjectReference, _tls: OpaquePointer) -> ObjectReference {
+    fn enqueue_references(_references: &[ObjectReference], _tls: OpaquePointer) {
         unimplemented!()
     }
 }
\ No newline at end of file
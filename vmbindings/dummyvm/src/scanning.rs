@@ -1,5 +1,5 @@
 use mmtk::vm::Scanning;
-use mmtk::{TransitiveClosure, SelectedPlan, Mutator};
+use mmtk::{TransitiveClosure, Mutator};
 use mmtk::util::{ObjectReference, SynchronizedCounter};
 use mmtk::util::OpaquePointer;
 use mmtk::scheduler::gc_works::*;
@@ -16,7 +16,7 @@ impl Scanning<DummyVM> for VMScanning {
     fn scan_thread_roots<W: ProcessEdgesWork<VM=DummyVM>>() {
         unimplemented!()
     }
-    fn scan_thread_root<W: ProcessEdgesWork<VM=DummyVM>>(_mutator: &'static mut Mutator<SelectedPlan<DummyVM>>, _tls: OpaquePointer) {
+    fn scan_thread_root<W: ProcessEdgesWork<VM=DummyVM>>(_mutator: &'static mut Mutator<DummyVM>, _tls: OpaquePointer) {
         unimplemented!()
     }
     fn scan_vm_specific_roots<W: ProcessEdgesWork<VM=DummyVM>>() {